@@ -0,0 +1,250 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// The UAC execution levels a Windows manifest may request.
+const EXECUTION_LEVELS: [&str; 3] = ["asInvoker", "highestAvailable", "requireAdministrator"];
+
+/// The Windows versions a manifest may declare supported, and their GUIDs. Windows 11 is `10`.
+const SUPPORTED_OS: [(&str, &str); 5] = [
+    ("vista", "{e2011457-1546-43c5-a5fe-008deee3d3f0}"),
+    ("7", "{35138b9a-5d96-4fbd-8e2d-a2440225f93a}"),
+    ("8", "{4a2f28e3-53b9-4441-ba9c-d69d4a4a6e38}"),
+    ("8.1", "{1f676c76-80e1-4239-95bb-83d0f6d0da78}"),
+    ("10", "{8e0f7a12-bfb3-4fe8-b9a5-48fd50a15a9a}"),
+];
+
+/// The keys of the app metadata, each also set by the environment variable `WHY_APP_<KEY>`.
+const METADATA_KEYS: [&str; 6] = ["name", "version", "company", "description", "copyright", "icon"];
+
+fn main() {
+    embed_config();
+    embed_manifest();
+    embed_resources();
+}
+
+/// Builds the config file named by `WHY_EMBEDDED_CONFIG`, relative to the crate, into the launcher,
+/// so that it can be shipped as a single file. A `launcher.ini` beside it still overrides its keys.
+/// Without it, the config built in is empty.
+fn embed_config() {
+    println!("cargo:rerun-if-env-changed=WHY_EMBEDDED_CONFIG");
+    let config = match env::var("WHY_EMBEDDED_CONFIG") {
+        Ok(path) if !path.is_empty() => {
+            println!("cargo:rerun-if-changed={}", path);
+            fs::read_to_string(&path)
+                .unwrap_or_else(|e| panic!("Failed to read WHY_EMBEDDED_CONFIG {}: {}", path, e))
+        }
+        _ => String::new(),
+    };
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    fs::write(out_dir.join("embedded_config.ini"), config).unwrap();
+}
+
+/// Embeds a manifest with the options of the environment variables when building for Windows with MSVC:
+/// - `WHY_EXECUTION_LEVEL`: the execution level requested, such as `requireAdministrator` for tools that always
+///   need to run as an administrator, which Windows then asks the user for before the launcher starts.
+/// - `WHY_LONG_PATH_AWARE`: `true` to use paths longer than `MAX_PATH` where Windows allows them.
+/// - `WHY_SUPPORTED_OS`: the Windows versions declared supported, of [`SUPPORTED_OS`] or their GUIDs,
+///   separated by `,`, so that Windows does not run the launcher as it would for older apps.
+fn embed_manifest() {
+    println!("cargo:rerun-if-env-changed=WHY_EXECUTION_LEVEL");
+    println!("cargo:rerun-if-env-changed=WHY_LONG_PATH_AWARE");
+    println!("cargo:rerun-if-env-changed=WHY_SUPPORTED_OS");
+    let option = |variable: &str| env::var(variable).ok().filter(|v| !v.trim().is_empty());
+    let level = option("WHY_EXECUTION_LEVEL");
+    if let Some(level) = &level {
+        if !EXECUTION_LEVELS.contains(&level.as_str()) {
+            panic!("WHY_EXECUTION_LEVEL must be one of {:?}, not {}", EXECUTION_LEVELS, level);
+        }
+    }
+    let long_path_aware = match option("WHY_LONG_PATH_AWARE").as_deref().map(str::trim) {
+        None | Some("false") => false,
+        Some("true") => true,
+        Some(other) => panic!("WHY_LONG_PATH_AWARE must be true or false, not {}", other),
+    };
+    let supported_os: Vec<String> = option("WHY_SUPPORTED_OS").unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|os| !os.is_empty())
+        .map(|os| supported_os_id(os).to_string())
+        .collect();
+    if level.is_none() && !long_path_aware && supported_os.is_empty() {
+        return;
+    }
+
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let target_env = env::var("CARGO_CFG_TARGET_ENV").unwrap_or_default();
+    if target_os != "windows" {
+        return;
+    }
+    if target_env != "msvc" {
+        println!("cargo:warning=WHY_EXECUTION_LEVEL, WHY_LONG_PATH_AWARE and WHY_SUPPORTED_OS are only supported with the MSVC linker");
+        return;
+    }
+    println!("cargo:rustc-link-arg-bins=/MANIFEST:EMBED");
+    if let Some(level) = &level {
+        println!("cargo:rustc-link-arg-bins=/MANIFESTUAC:level='{}'", level);
+    }
+    if !long_path_aware && supported_os.is_empty() {
+        return;
+    }
+
+    // The linker merges it with the manifest it makes, which has the execution level
+    let mut manifest = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+        <assembly xmlns=\"urn:schemas-microsoft-com:asm.v1\" manifestVersion=\"1.0\">\n");
+    if !supported_os.is_empty() {
+        manifest += "  <compatibility xmlns=\"urn:schemas-microsoft-com:compatibility.v1\">\n    <application>\n";
+        for id in &supported_os {
+            manifest += &format!("      <supportedOS Id=\"{}\"/>\n", id);
+        }
+        manifest += "    </application>\n  </compatibility>\n";
+    }
+    if long_path_aware {
+        manifest += "  <application xmlns=\"urn:schemas-microsoft-com:asm.v3\">\n\
+            \x20   <windowsSettings xmlns:ws2=\"http://schemas.microsoft.com/SMI/2016/WindowsSettings\">\n\
+            \x20     <ws2:longPathAware>true</ws2:longPathAware>\n\
+            \x20   </windowsSettings>\n\
+            \x20 </application>\n";
+    }
+    manifest += "</assembly>\n";
+    let file = PathBuf::from(env::var("OUT_DIR").unwrap()).join("launcher.manifest");
+    fs::write(&file, manifest).unwrap();
+    println!("cargo:rustc-link-arg-bins=/MANIFESTINPUT:{}", file.display());
+}
+
+/// The GUID of the Windows version for `WHY_SUPPORTED_OS`, a GUID as it is.
+fn supported_os_id(os: &str) -> &str {
+    if os.starts_with('{') {
+        return os;
+    }
+    SUPPORTED_OS.iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(os))
+        .map(|(_, id)| *id)
+        .unwrap_or_else(|| panic!("WHY_SUPPORTED_OS must be of {:?} or GUIDs, not {}",
+                                  SUPPORTED_OS.iter().map(|(n, _)| *n).collect::<Vec<_>>(), os))
+}
+
+/// The app metadata, from the `key = value` lines of the file named by `WHY_APP_METADATA`, with the keys of
+/// [`METADATA_KEYS`], and from the environment variables `WHY_APP_<KEY>` over those of the file.
+fn read_metadata() -> Vec<(&'static str, String)> {
+    println!("cargo:rerun-if-env-changed=WHY_APP_METADATA");
+    let file = match env::var("WHY_APP_METADATA") {
+        Ok(path) if !path.is_empty() => {
+            println!("cargo:rerun-if-changed={}", path);
+            fs::read_to_string(&path)
+                .unwrap_or_else(|e| panic!("Failed to read WHY_APP_METADATA {}: {}", path, e))
+        }
+        _ => String::new(),
+    };
+    METADATA_KEYS.iter()
+        .filter_map(|key| {
+            let variable = format!("WHY_APP_{}", key.to_uppercase());
+            println!("cargo:rerun-if-env-changed={}", variable);
+            let from_file = file.lines()
+                .filter_map(|line| line.split_once('='))
+                .find(|(k, _)| k.trim() == *key)
+                .map(|(_, v)| v.trim().to_string());
+            env::var(&variable).ok().or(from_file)
+                .filter(|v| !v.is_empty())
+                .map(|v| (*key, v))
+        })
+        .collect()
+}
+
+/// Embeds the version info and icon of the app metadata, see [`read_metadata`], as Windows resources,
+/// so that Explorer and the launcher's dialogs show the app's name, version and icon.
+/// The resource script is compiled with `rc` for MSVC, or `windres` for GNU, or the compiler set with `RC`.
+fn embed_resources() {
+    let metadata = read_metadata();
+    if metadata.is_empty() || env::var("CARGO_CFG_TARGET_OS").unwrap_or_default() != "windows" {
+        return;
+    }
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let script = out_dir.join("resources.rc");
+    fs::write(&script, resource_script(&metadata)).unwrap();
+
+    let msvc = env::var("CARGO_CFG_TARGET_ENV").unwrap_or_default() == "msvc";
+    println!("cargo:rerun-if-env-changed=RC");
+    let (compiler, compiled) = match env::var("RC") {
+        Ok(rc) if !rc.is_empty() => (rc, out_dir.join(if msvc { "resources.res" } else { "resources.o" })),
+        _ if msvc => (find_rc(), out_dir.join("resources.res")),
+        _ => ("windres".to_string(), out_dir.join("resources.o")),
+    };
+    let mut command = Command::new(&compiler);
+    if msvc {
+        command.arg("/nologo").arg("/fo").arg(&compiled).arg(&script);
+    } else {
+        command.arg(&script).arg("-O").arg("coff").arg("-o").arg(&compiled);
+    }
+    match command.status() {
+        Ok(status) if status.success() => {
+            println!("cargo:rustc-link-arg-bins={}", compiled.display());
+        }
+        result => {
+            println!("cargo:warning=The app metadata was not embedded, {} failed: {:?}", compiler, result);
+        }
+    }
+}
+
+/// The resource script of the icon and version info. The icon is the first, which the launcher's dialogs use.
+fn resource_script(metadata: &[(&str, String)]) -> String {
+    let value = |key: &str| metadata.iter().find(|(k, _)| *k == key).map(|(_, v)| v.as_str());
+    let quote = |text: &str| format!("\"{}\"", text.replace('"', "\"\""));
+    let mut script = String::new();
+    if let Some(icon) = value("icon") {
+        println!("cargo:rerun-if-changed={}", icon);
+        let icon = fs::canonicalize(icon).unwrap_or_else(|e| panic!("Failed to read the icon {}: {}", icon, e));
+        // Forward slashes, as backslashes are escapes
+        let icon = icon.display().to_string().trim_start_matches(r"\\?\").replace('\\', "/");
+        script += &format!("1 ICON {}\n", quote(&icon));
+    }
+
+    // Four numbers, the missing ones 0
+    let version = value("version");
+    let mut numbers: Vec<u16> = version.unwrap_or_default().split(|c| c == '.' || c == '-' || c == '+')
+        .map_while(|n| n.parse().ok())
+        .take(4)
+        .collect();
+    numbers.resize(4, 0);
+    let numbers: Vec<String> = numbers.iter().map(u16::to_string).collect();
+    let numbers = numbers.join(",");
+
+    let name = value("name");
+    let strings = [
+        ("CompanyName", value("company")),
+        ("FileDescription", value("description").or(name)),
+        ("FileVersion", version),
+        ("LegalCopyright", value("copyright")),
+        ("ProductName", name),
+        ("ProductVersion", version),
+    ];
+    script += &format!("1 VERSIONINFO\nFILEVERSION {0}\nPRODUCTVERSION {0}\nFILEOS 0x40004\nFILETYPE 0x1\n", numbers);
+    script += "BEGIN\n  BLOCK \"StringFileInfo\"\n  BEGIN\n    BLOCK \"040904b0\"\n    BEGIN\n";
+    for (key, value) in strings.iter().filter_map(|(k, v)| v.map(|v| (k, v))) {
+        script += &format!("      VALUE \"{}\", {}\n", key, quote(value));
+    }
+    script += "    END\n  END\n  BLOCK \"VarFileInfo\"\n  BEGIN\n    VALUE \"Translation\", 0x409, 1200\n  END\nEND\n";
+    script
+}
+
+/// The resource compiler of the newest Windows SDK for the host, as it is only on the `PATH` of a
+/// developer command prompt, else `rc.exe` to look for on the `PATH`.
+fn find_rc() -> String {
+    let arch = match env::var("PROCESSOR_ARCHITECTURE").as_deref() {
+        Ok("ARM64") => "arm64",
+        Ok("x86") => "x86",
+        _ => "x64",
+    };
+    let kits = env::var("ProgramFiles(x86)").map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(r"C:\Program Files (x86)"))
+        .join("Windows Kits").join("10").join("bin");
+    let mut versions: Vec<PathBuf> = fs::read_dir(&kits).into_iter().flatten()
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.join(arch).join("rc.exe").is_file())
+        .collect();
+    versions.sort();
+    versions.last()
+        .map(|v| v.join(arch).join("rc.exe").display().to_string())
+        .unwrap_or_else(|| "rc.exe".to_string())
+}
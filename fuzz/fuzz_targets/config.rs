@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use why::LauncherConfig;
+
+// Any input is either a config or an error, never a panic
+fuzz_target!(|data: &[u8]| {
+    let _ = LauncherConfig::parse_config_from(data);
+});
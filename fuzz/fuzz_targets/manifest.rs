@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use why::parse_manifest_bytes;
+
+// Any input is either a manifest or rejected, never a panic
+fuzz_target!(|data: &[u8]| {
+    let _ = parse_manifest_bytes(data);
+});
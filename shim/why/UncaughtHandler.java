@@ -0,0 +1,39 @@
+package why;
+
+import java.io.PrintWriter;
+import java.io.StringWriter;
+
+/**
+ * Default uncaught exception handler installed by the launcher when {@code install_exception_handler}
+ * is enabled. The report is forwarded to the launcher through a native method registered at runtime.
+ * <p>
+ * The launcher embeds the compiled class, rebuild it with
+ * {@code javac --release 8 -d shim shim/why/UncaughtHandler.java} after making changes.
+ */
+final class UncaughtHandler implements Thread.UncaughtExceptionHandler {
+    private UncaughtHandler() {
+    }
+
+    /**
+     * Called by the launcher before the main class is run.
+     */
+    static void install() {
+        Thread.setDefaultUncaughtExceptionHandler(new UncaughtHandler());
+    }
+
+    @Override
+    public void uncaughtException(Thread t, Throwable e) {
+        StringWriter trace = new StringWriter();
+        e.printStackTrace(new PrintWriter(trace));
+        String details = "Exception in thread \"" + t.getName() + "\" " + trace;
+
+        try {
+            report(details);
+        } catch (Throwable ignored) {
+            // The launcher could not be reached, keep the behavior of the default handler
+            System.err.print(details);
+        }
+    }
+
+    private static native void report(String details);
+}
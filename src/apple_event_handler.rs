@@ -0,0 +1,230 @@
+use std::env;
+use std::ffi::c_void;
+use std::os::raw::c_long;
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long the launcher waits at most for the open events sent right after it was started,
+/// such as for the files double-clicked in Finder.
+const EARLY_EVENT_WAIT: Duration = Duration::from_millis(500);
+
+/// Four character codes of the Apple Event Manager.
+const CORE_EVENT_CLASS: u32 = fourcc(b"aevt");
+const OPEN_APPLICATION_EVENT: u32 = fourcc(b"oapp");
+const OPEN_DOCUMENTS_EVENT: u32 = fourcc(b"odoc");
+const INTERNET_EVENT_CLASS: u32 = fourcc(b"GURL");
+const GET_URL_EVENT: u32 = fourcc(b"GURL");
+const DIRECT_OBJECT_KEY: u32 = fourcc(b"----");
+const LIST_TYPE: u32 = fourcc(b"list");
+const FILE_URL_TYPE: u32 = fourcc(b"furl");
+const UTF8_TEXT_TYPE: u32 = fourcc(b"utf8");
+
+const fn fourcc(code: &[u8; 4]) -> u32 {
+    u32::from_be_bytes(*code)
+}
+
+/// An Apple Event, or a value in one.
+#[repr(C)]
+struct AEDesc {
+    descriptor_type: u32,
+    data_handle: *mut c_void,
+}
+
+type AEEventHandler = extern "C" fn(event: *const AEDesc, reply: *mut AEDesc, refcon: isize) -> i16;
+type CFMachPortCallBack = extern "C" fn(port: *mut c_void, msg: *mut c_void, size: c_long, info: *mut c_void);
+
+#[link(name = "CoreServices", kind = "framework")]
+extern "C" {
+    fn AEInstallEventHandler(event_class: u32, event_id: u32, handler: AEEventHandler, refcon: isize,
+                             is_sys_handler: u8) -> i16;
+    fn AERemoveEventHandler(event_class: u32, event_id: u32, handler: AEEventHandler, is_sys_handler: u8) -> i16;
+    fn AEGetParamDesc(event: *const AEDesc, keyword: u32, desired_type: u32, result: *mut AEDesc) -> i16;
+    fn AEGetParamPtr(event: *const AEDesc, keyword: u32, desired_type: u32, actual_type: *mut u32,
+                     data: *mut c_void, max_size: c_long, actual_size: *mut c_long) -> i16;
+    fn AECountItems(list: *const AEDesc, count: *mut c_long) -> i16;
+    fn AEGetNthPtr(list: *const AEDesc, index: c_long, desired_type: u32, keyword: *mut u32,
+                   actual_type: *mut u32, data: *mut c_void, max_size: c_long, actual_size: *mut c_long) -> i16;
+    fn AEDisposeDesc(desc: *mut AEDesc) -> i16;
+    fn AEGetRegisteredMachPort() -> u32;
+    fn AEProcessMessage(header: *mut c_void) -> i32;
+}
+
+#[link(name = "CoreFoundation", kind = "framework")]
+extern "C" {
+    static kCFRunLoopDefaultMode: *const c_void;
+    fn CFRelease(cf: *const c_void);
+    fn CFMachPortCreateWithPort(allocator: *const c_void, port: u32, callback: CFMachPortCallBack,
+                                context: *const c_void, should_free_info: *mut u8) -> *mut c_void;
+    fn CFMachPortCreateRunLoopSource(allocator: *const c_void, port: *mut c_void, order: c_long) -> *mut c_void;
+    fn CFRunLoopGetCurrent() -> *mut c_void;
+    fn CFRunLoopAddSource(run_loop: *mut c_void, source: *mut c_void, mode: *const c_void);
+    fn CFRunLoopRemoveSource(run_loop: *mut c_void, source: *mut c_void, mode: *const c_void);
+    fn CFRunLoopRunInMode(mode: *const c_void, seconds: f64, return_after_source_handled: u8) -> i32;
+}
+
+/// `CFRunLoopRunInMode` returns this once the run loop has no sources left to wait for.
+const RUN_LOOP_FINISHED: i32 = 1;
+
+/// The files and URLs the app was asked to open, in the order the events arrived.
+static OPENED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Set once the first event the app was started with arrived, which is the open application event
+/// when there is nothing to open.
+static EVENT_RECEIVED: AtomicBool = AtomicBool::new(false);
+
+/// Collect the files and URLs sent to the app with Apple Events when it was started, such as
+/// files double-clicked in Finder or links with the app's URL scheme, so that they can be given
+/// to the app as program arguments. Without this they are lost, as Java only handles such events
+/// once the app registers its `java.awt.Desktop` handlers.<br>
+/// Only waits for events when the launcher runs from an app bundle, as only then are they sent, and
+/// only until the first event, of which there is one even when there is nothing to open.
+/// Events after the wait are left for the app to handle.
+pub fn collect_open_events() -> Vec<String> {
+    let in_bundle = env::current_exe()
+        .map(|e| e.to_string_lossy().contains(".app/Contents/MacOS/"))
+        .unwrap_or(false);
+    if !in_bundle {
+        return vec![];
+    }
+
+    let handlers: [(u32, u32, AEEventHandler); 3] = [
+        (CORE_EVENT_CLASS, OPEN_APPLICATION_EVENT, on_open_application),
+        (CORE_EVENT_CLASS, OPEN_DOCUMENTS_EVENT, on_open_documents),
+        (INTERNET_EVENT_CLASS, GET_URL_EVENT, on_get_url),
+    ];
+    unsafe {
+        let installed = handlers.iter()
+            .all(|(class, id, handler)| AEInstallEventHandler(*class, *id, *handler, 0, 0) == 0);
+        if installed {
+            wait_for_first_event();
+        } else {
+            info!("launcher", "Failed to install the Apple Event handlers");
+        }
+        // Later events are for the app's own handlers
+        for (class, id, handler) in handlers {
+            AERemoveEventHandler(class, id, handler, 0);
+        }
+    }
+
+    let opened = OPENED.lock().map(|mut o| o.drain(..).collect()).unwrap_or_default();
+    if !opened.is_empty() {
+        info!("launcher", "Asked to open {:?}", opened);
+    }
+    opened
+}
+
+/// Dispatch the Apple Events until the first arrives, or [`EARLY_EVENT_WAIT`] passes.<br>
+/// Apple Events arrive on a Mach port, which a process without an AppKit event loop has to dispatch itself.
+/// The port's run loop source is removed again, so that the app's event loop receives the later events.
+unsafe fn wait_for_first_event() {
+    let port = CFMachPortCreateWithPort(null_mut(), AEGetRegisteredMachPort(), on_message, null_mut(), null_mut());
+    if port.is_null() {
+        return;
+    }
+    let source = CFMachPortCreateRunLoopSource(null_mut(), port, 0);
+    if source.is_null() {
+        CFRelease(port);
+        return;
+    }
+    let run_loop = CFRunLoopGetCurrent();
+    CFRunLoopAddSource(run_loop, source, kCFRunLoopDefaultMode);
+
+    let deadline = Instant::now() + EARLY_EVENT_WAIT;
+    while !EVENT_RECEIVED.load(Ordering::SeqCst) {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() || CFRunLoopRunInMode(kCFRunLoopDefaultMode, left.as_secs_f64(), 1) == RUN_LOOP_FINISHED {
+            break;
+        }
+    }
+
+    CFRunLoopRemoveSource(run_loop, source, kCFRunLoopDefaultMode);
+    CFRelease(source);
+    CFRelease(port);
+}
+
+extern "C" fn on_message(_: *mut c_void, msg: *mut c_void, _: c_long, _: *mut c_void) {
+    unsafe {
+        AEProcessMessage(msg);
+    }
+}
+
+/// Ends the wait without anything to open, the app was started on its own.
+extern "C" fn on_open_application(_: *const AEDesc, _: *mut AEDesc, _: isize) -> i16 {
+    EVENT_RECEIVED.store(true, Ordering::SeqCst);
+    0
+}
+
+/// Queues the paths of the files to open, which the event has as a list of file URLs.
+extern "C" fn on_open_documents(event: *const AEDesc, _: *mut AEDesc, _: isize) -> i16 {
+    EVENT_RECEIVED.store(true, Ordering::SeqCst);
+    unsafe {
+        let mut list = AEDesc { descriptor_type: 0, data_handle: null_mut() };
+        if AEGetParamDesc(event, DIRECT_OBJECT_KEY, LIST_TYPE, &mut list) != 0 {
+            return 0;
+        }
+        let mut count: c_long = 0;
+        AECountItems(&list, &mut count);
+        for i in 1..=count {
+            let mut url = [0u8; 4096];
+            let (mut keyword, mut actual_type, mut size) = (0, 0, 0);
+            if AEGetNthPtr(&list, i, FILE_URL_TYPE, &mut keyword, &mut actual_type,
+                           url.as_mut_ptr() as *mut c_void, url.len() as c_long, &mut size) == 0 {
+                let url = String::from_utf8_lossy(&url[..(size as usize).min(url.len())]);
+                if let Some(path) = file_url_to_path(&url) {
+                    queue(path);
+                }
+            }
+        }
+        AEDisposeDesc(&mut list);
+    }
+    0
+}
+
+/// Queues the URL the app was opened with, the text of the event.
+extern "C" fn on_get_url(event: *const AEDesc, _: *mut AEDesc, _: isize) -> i16 {
+    EVENT_RECEIVED.store(true, Ordering::SeqCst);
+    let mut url = [0u8; 4096];
+    let (mut actual_type, mut size): (u32, c_long) = (0, 0);
+    let read = unsafe {
+        AEGetParamPtr(event, DIRECT_OBJECT_KEY, UTF8_TEXT_TYPE, &mut actual_type,
+                      url.as_mut_ptr() as *mut c_void, url.len() as c_long, &mut size)
+    };
+    if read == 0 {
+        queue(String::from_utf8_lossy(&url[..(size as usize).min(url.len())]).to_string());
+    }
+    0
+}
+
+fn queue(opened: String) {
+    if let Ok(mut queued) = OPENED.lock() {
+        queued.push(opened);
+    }
+}
+
+/// The path of a `file://` URL, with its percent-escapes decoded.
+fn file_url_to_path(url: &str) -> Option<String> {
+    let path = url.strip_prefix("file://")?;
+    // Skips the host, normally `localhost` or empty
+    let path = &path[path.find('/')?..];
+    let bytes = path.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes.get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
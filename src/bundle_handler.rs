@@ -0,0 +1,298 @@
+use std::{env, fs, process};
+use std::path::PathBuf;
+
+use crate::display_handler::{ask, Choice, is_headless};
+use crate::file_handler::{get_app_name, resolve_path};
+use crate::launch_config::LauncherConfig;
+use crate::locale_handler::text;
+
+const DOCK_NAME_OPT: &str = "-Xdock:name=";
+const DOCK_ICON_OPT: &str = "-Xdock:icon=";
+
+/// The `Contents` dir of the app bundle the launcher is in, `None` if it is not in one.
+pub fn get_bundle_contents() -> Option<PathBuf> {
+    let exe = env::current_exe().ok()?;
+    let contents = exe.parent().filter(|p| p.ends_with("MacOS"))?.parent()?;
+    let bundle = contents.parent()?;
+    let is_bundle = contents.ends_with("Contents") && bundle.extension().map_or(false, |e| e == "app");
+    is_bundle.then(|| contents.to_path_buf())
+}
+
+/// The name of the app bundle, without `.app`.
+fn bundle_name() -> Option<String> {
+    let contents = get_bundle_contents()?;
+    Some(contents.parent()?.file_stem()?.to_string_lossy().to_string())
+}
+
+/// The options showing the app's name and icon in the Dock and menu bar instead of the main class,
+/// unless the existing options set them.<br>
+/// The name is `app_name`, or that of the bundle. The icon is `icon` if it is an `.icns` file,
+/// or else the bundle's icon in `Contents/Resources`, named after the bundle or else the first found.
+pub fn dock_opts(config: &LauncherConfig, existing: &[&String]) -> Vec<String> {
+    let mut opts: Vec<String> = vec![];
+    if !existing.iter().any(|o| o.starts_with(DOCK_NAME_OPT)) {
+        let name = config.app_name.clone().or_else(bundle_name).unwrap_or_else(get_app_name);
+        opts.push(format!("{}{}", DOCK_NAME_OPT, name));
+    }
+    if !existing.iter().any(|o| o.starts_with(DOCK_ICON_OPT)) {
+        if let Some(icon) = dock_icon(config) {
+            opts.push(format!("{}{}", DOCK_ICON_OPT, icon.display()));
+        }
+    }
+    opts
+}
+
+fn dock_icon(config: &LauncherConfig) -> Option<PathBuf> {
+    let configured = config.icon.as_ref()
+        .map(|i| resolve_path(i))
+        .filter(|i| i.extension().map_or(false, |e| e == "icns") && i.is_file());
+    if configured.is_some() {
+        return configured;
+    }
+
+    let resources = get_bundle_contents()?.join("Resources");
+    let named = bundle_name().map(|n| resources.join(format!("{}.icns", n))).filter(|i| i.is_file());
+    if named.is_some() {
+        return named;
+    }
+    let mut icons: Vec<PathBuf> = fs::read_dir(&resources).ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().map_or(false, |e| e == "icns"))
+        .collect();
+    icons.sort();
+    icons.into_iter().next()
+}
+
+/// Whether the option is a `-Xdock:` option, which only the `java` executable understands, the JVM fails
+/// to start with them. Their name and icon are applied as the executable does, through the environment
+/// variables that AWT reads them from.
+pub fn apply_dock_opt(opt: &str) -> bool {
+    let (var, value) = if let Some(name) = opt.strip_prefix(DOCK_NAME_OPT) {
+        ("APP_NAME", name)
+    } else if let Some(icon) = opt.strip_prefix(DOCK_ICON_OPT) {
+        ("APP_ICON", icon)
+    } else {
+        return opt.starts_with("-Xdock:");
+    };
+    env::set_var(format!("{}_{}", var, process::id()), value);
+    true
+}
+
+/// What an app bundle made with appbundler configures in its `Info.plist`.
+#[derive(Debug, Default)]
+pub struct BundleConfig {
+    /// `JVMMainClassName`
+    pub main_class: Option<String>,
+    /// `JVMOptions`
+    pub jvm_options: Vec<String>,
+    /// The home of the Java bundled in `Contents/PlugIns` named by `JVMRuntime`.
+    pub runtime: Option<PathBuf>,
+    /// `JVMClassPath`, or else the jars in `Contents/Java`.
+    pub classpath: Vec<PathBuf>,
+    /// Whether the class path was configured with `JVMClassPath`, rather than found.
+    pub explicit_classpath: bool,
+}
+
+/// Read the appbundler keys of the bundle's `Info.plist`, `None` if the launcher is not in a bundle
+/// or the `Info.plist` has none of them. `$APP_ROOT` in their values is the bundle.
+pub fn read_bundle_config() -> Option<BundleConfig> {
+    let contents = get_bundle_contents()?;
+    let app_root = contents.parent()?.display().to_string();
+    let entries = parse_plist(&read_plist_xml(&contents.join("Info.plist"))?);
+    let text = |key: &str| entries.iter().find(|(k, _)| k == key).and_then(|(_, v)| match v {
+        PlistValue::Text(t) => Some(t.replace("$APP_ROOT", &app_root)),
+        _ => None,
+    });
+    let list = |key: &str| entries.iter().find(|(k, _)| k == key).and_then(|(_, v)| match v {
+        PlistValue::List(l) => Some(l.iter().map(|t| t.replace("$APP_ROOT", &app_root)).collect::<Vec<String>>()),
+        _ => None,
+    });
+
+    let main_class = text("JVMMainClassName");
+    let jvm_options = list("JVMOptions").unwrap_or_default();
+    let runtime = text("JVMRuntime").map(|name| {
+        let plugin = contents.join("PlugIns").join(name);
+        let home = plugin.join("Contents").join("Home");
+        if home.is_dir() { home } else { plugin }
+    });
+    if main_class.is_none() && jvm_options.is_empty() && runtime.is_none() {
+        return None;
+    }
+
+    let explicit_classpath = text("JVMClassPath");
+    let classpath = match &explicit_classpath {
+        Some(classpath) => classpath.split(':').filter(|p| !p.is_empty()).map(PathBuf::from).collect(),
+        None => {
+            let mut jars: Vec<PathBuf> = fs::read_dir(contents.join("Java")).into_iter()
+                .flatten()
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.extension().map_or(false, |e| e == "jar"))
+                .collect();
+            jars.sort();
+            jars
+        }
+    };
+    Some(BundleConfig { main_class, jvm_options, runtime, classpath, explicit_classpath: explicit_classpath.is_some() })
+}
+
+/// The `Info.plist` as XML, converted with `plutil` if it is binary.
+fn read_plist_xml(path: &PathBuf) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    if bytes.starts_with(b"bplist") {
+        let converted = process::Command::new("plutil")
+            .args(["-convert", "xml1", "-o", "-"])
+            .arg(path)
+            .output().ok()
+            .filter(|o| o.status.success())?;
+        return String::from_utf8(converted.stdout).ok();
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// A value of a property list, only as far as the launcher needs them.
+#[derive(Debug, PartialEq)]
+enum PlistValue {
+    Text(String),
+    /// An array, of its strings.
+    List(Vec<String>),
+    /// Anything else, such as a number or a dictionary.
+    Other,
+}
+
+/// The entries of the top dictionary of an XML property list.
+fn parse_plist(xml: &str) -> Vec<(String, PlistValue)> {
+    let mut entries = vec![];
+    let dict = match xml.find("<dict").and_then(|i| next_element(&xml[i..])) {
+        Some(("dict", content, _)) => content,
+        _ => return entries,
+    };
+    let mut rest = dict;
+    while let Some((tag, key, after_key)) = next_element(rest) {
+        rest = after_key;
+        if tag != "key" {
+            continue;
+        }
+        let (tag, content, after_value) = match next_element(rest) {
+            Some(value) => value,
+            None => break,
+        };
+        rest = after_value;
+        let value = match tag {
+            "string" => PlistValue::Text(unescape(content)),
+            "array" => {
+                let mut strings = vec![];
+                let mut items = content;
+                while let Some((tag, item, after)) = next_element(items) {
+                    if tag == "string" {
+                        strings.push(unescape(item));
+                    }
+                    items = after;
+                }
+                PlistValue::List(strings)
+            }
+            _ => PlistValue::Other,
+        };
+        entries.push((unescape(key), value));
+    }
+    entries
+}
+
+/// The tag, content and what follows of the element the text starts with, skipping comments.
+fn next_element(text: &str) -> Option<(&str, &str, &str)> {
+    let mut text = text.trim_start();
+    while text.starts_with("<!--") {
+        text = text[text.find("-->")? + 3..].trim_start();
+    }
+    let open = text.strip_prefix('<')?;
+    let end = open.find('>')?;
+    if open.starts_with('/') {
+        return None;
+    }
+    let tag_end = open.find(|c: char| c.is_whitespace() || c == '/' || c == '>')?;
+    let tag = &open[..tag_end];
+    if open[..end].ends_with('/') {
+        return Some((tag, "", &open[end + 1..]));
+    }
+
+    // Elements of the same tag may be nested, such as arrays of arrays
+    let body = &open[end + 1..];
+    let (opening, closing) = (format!("<{}", tag), format!("</{}>", tag));
+    let mut depth = 1;
+    let mut i = 0;
+    while depth > 0 {
+        let next_close = body[i..].find(&closing)? + i;
+        match body[i..].find(&opening).map(|o| o + i).filter(|o| *o < next_close) {
+            Some(o) => {
+                let nested = &body[o + opening.len()..];
+                if nested.starts_with(|c: char| c == '>' || c.is_whitespace()) {
+                    let nested_end = nested.find('>')?;
+                    if !nested[..nested_end].ends_with('/') {
+                        depth += 1;
+                    }
+                }
+                i = o + opening.len();
+            }
+            None => {
+                depth -= 1;
+                i = next_close + closing.len();
+            }
+        }
+    }
+    Some((tag, &body[..i - closing.len()], &body[i..]))
+}
+
+fn unescape(text: &str) -> String {
+    text.trim().replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// The extended attribute macOS marks downloaded files with until the user first opens them.
+const QUARANTINE_ATTRIBUTE: &str = "com.apple.quarantine";
+
+/// Whether the bundle was translocated by Gatekeeper, run from a randomized read-only copy because
+/// it was opened from where it was downloaded, such as the Downloads folder or a disk image. Paths
+/// next to the bundle then do not exist and nothing can be written into it.
+fn is_translocated(contents: &PathBuf) -> bool {
+    contents.components().any(|c| c.as_os_str() == "AppTranslocation")
+}
+
+/// Whether the bundle still carries the quarantine mark of a download.
+fn is_quarantined(bundle: &PathBuf) -> bool {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    let (path, name) = match (CString::new(bundle.as_os_str().as_bytes()), CString::new(QUARANTINE_ATTRIBUTE)) {
+        (Ok(path), Ok(name)) => (path, name),
+        _ => return false,
+    };
+    unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0, 0, 0) >= 0 }
+}
+
+/// Log whether the bundle is quarantined or translocated, and when translocated, ask the user to move
+/// the app to the Applications folder first, as it may not work properly from the copy.<br>
+/// Returns whether to go on launching it.
+pub fn check_translocation() -> bool {
+    let contents = match get_bundle_contents() {
+        Some(contents) => contents,
+        None => return true,
+    };
+    let bundle = contents.parent().map(|b| b.to_path_buf()).unwrap_or_default();
+    if is_quarantined(&bundle) {
+        info!("launcher", "The app bundle {:?} is quarantined", bundle);
+    }
+    if !is_translocated(&contents) {
+        return true;
+    }
+    info!("launcher", "The app bundle is translocated to {:?}", bundle);
+    if is_headless() {
+        return true;
+    }
+    match ask(&text("bundle.translocated"), &[Choice::Continue, Choice::Quit]) {
+        Choice::Continue => true,
+        _ => {
+            info!("launcher", "Launch from the translocated bundle cancelled by the user");
+            false
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::file_handler::{ClassVersion, get_app_name, get_user_data_dir, read_release_value};
+
+/// The first line of the index file, changed when its format does.
+const INDEX_HEADER: &str = "why-class-index 1";
+
+/// The first line of the file remembering the Java of the last launch, changed when its format does.
+const LAST_JVM_HEADER: &str = "why-last-jvm 2";
+
+/// The file remembering where the main class was found, and its version.
+fn get_index_file() -> Option<PathBuf> {
+    Some(get_user_data_dir()?.join(get_app_name() + ".index"))
+}
+
+/// What identifies the state of a classpath entry, its size and modification time,
+/// `None` if it does not exist.
+fn entry_stamp(entry: &Path) -> Option<String> {
+    let metadata = fs::metadata(entry).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some(format!("{}:{}", metadata.len(), modified.as_nanos()))
+}
+
+/// What identifies the state of a classpath entry for where the class is loaded from: a jar's stamp, or
+/// for a directory of classes, which is not stamped by changes to the classes in it, that of the class file.
+fn class_entry_stamp(entry: &Path, class_name: &str) -> Option<String> {
+    if entry.is_dir() {
+        entry_stamp(&entry.join(class_name.replace('.', "/") + ".class"))
+    } else {
+        entry_stamp(entry)
+    }
+}
+
+/// The version of the main class found on the classpath by an earlier launch, if none of
+/// the classpath's jars, nor the class in its directories, changed since, so that they need not be read again.
+pub fn read_cached_class_version(class_name: &str, entries: &[&Path]) -> Option<ClassVersion> {
+    let index = fs::read_to_string(get_index_file()?).ok()?;
+    let mut lines = index.lines();
+    if lines.next()? != INDEX_HEADER || lines.next()? != class_name {
+        return None;
+    }
+
+    let mut version = lines.next()?.split(' ');
+    let java: u16 = version.next()?.parse().ok()?;
+    let preview: bool = version.next()?.parse().ok()?;
+
+    // Any change to the classpath may change where the class is loaded from
+    let cached: Vec<(&str, &str)> = lines.map(|l| l.split_once(' ')).collect::<Option<_>>()?;
+    if cached.len() != entries.len() {
+        return None;
+    }
+    for ((stamp, path), entry) in cached.iter().zip(entries) {
+        if Path::new(path) != *entry || class_entry_stamp(entry, class_name).as_deref().unwrap_or("-") != *stamp {
+            return None;
+        }
+    }
+
+    debug!("discovery", "Using the cached version of {}", class_name);
+    Some(ClassVersion { java, preview })
+}
+
+/// Remember the version of the main class found on the classpath for the next launch,
+/// see [`read_cached_class_version`].
+pub fn cache_class_version(class_name: &str, entries: &[&Path], version: ClassVersion) {
+    let index_file = match get_index_file() {
+        Some(index_file) => index_file,
+        None => return,
+    };
+
+    let mut index = format!("{}\n{}\n{} {}\n", INDEX_HEADER, class_name, version.java, version.preview);
+    for entry in entries {
+        index += &format!("{} {}\n", class_entry_stamp(entry, class_name).as_deref().unwrap_or("-"), entry.display());
+    }
+
+    let written = index_file.parent().map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&index_file, index));
+    if let Err(e) = written {
+        info!("discovery", "Failed to write the class index {:?}: {:?}", index_file, e);
+    }
+}
+
+/// The file remembering the Java the app was last launched with.
+fn get_last_jvm_file() -> Option<PathBuf> {
+    Some(get_user_data_dir()?.join(get_app_name() + ".lastjvm"))
+}
+
+/// The Java the app was last launched with, see [`read_last_jvm`].
+pub struct LastJvm {
+    /// The Java dynamic library, as it was found.
+    pub library: PathBuf,
+    /// `JAVA_VERSION` from the installation's `release` file.
+    pub version: String,
+    /// Where it was found, `JAVA_HOME` or `lookup` for the common install locations.
+    pub source: String,
+    /// The config's `jvm_install` at the time, as a different one is to be looked in instead.
+    pub jvm_path: Option<String>,
+}
+
+/// The Java the app was last launched with, if it is still there as it was, so that it need not be
+/// looked for again. Whether the app can still run with it is up to the caller.
+pub fn read_last_jvm() -> Option<LastJvm> {
+    let cached = fs::read_to_string(get_last_jvm_file()?).ok()?;
+    let mut lines = cached.lines();
+    if lines.next()? != LAST_JVM_HEADER {
+        return None;
+    }
+    let (stamp, library) = lines.next()?.split_once(' ')?;
+    let version = lines.next()?.to_string();
+    let source = lines.next()?.to_string();
+    let jvm_path = lines.next().filter(|p| !p.is_empty()).map(str::to_string);
+
+    // Updated in place, or removed
+    let library = PathBuf::from(library);
+    if entry_stamp(&library).as_deref() != Some(stamp)
+        || read_release_value(&library, "JAVA_VERSION").as_deref() != Some(version.as_str()) {
+        debug!("discovery", "The Java of the last launch changed since: {:?}", library);
+        return None;
+    }
+    Some(LastJvm { library, version, source, jvm_path })
+}
+
+/// Remember the Java the app started with for the next launch, found at the source with the config's
+/// `jvm_install`, see [`read_last_jvm`]. `None` for the app's own Java, which is looked for first anyway,
+/// forgetting the Java of earlier launches.
+pub fn cache_last_jvm(library: &Path, source: Option<&str>, jvm_path: Option<&str>) {
+    let last_jvm_file = match get_last_jvm_file() {
+        Some(last_jvm_file) => last_jvm_file,
+        None => return,
+    };
+    let source = match source {
+        Some(source) => source,
+        None => {
+            forget_last_jvm();
+            return;
+        }
+    };
+    let (stamp, version) = match (entry_stamp(library), read_release_value(&library.to_path_buf(), "JAVA_VERSION")) {
+        (Some(stamp), Some(version)) => (stamp, version),
+        _ => return,
+    };
+
+    let cached = format!("{}\n{} {}\n{}\n{}\n{}\n", LAST_JVM_HEADER, stamp, library.display(), version, source,
+                         jvm_path.unwrap_or_default());
+    let written = last_jvm_file.parent().map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&last_jvm_file, cached));
+    if let Err(e) = written {
+        info!("discovery", "Failed to remember the Java of the launch in {:?}: {:?}", last_jvm_file, e);
+    }
+}
+
+/// Forget the Java of the last launch, so that it is looked for again, see [`read_last_jvm`].
+pub fn forget_last_jvm() {
+    if let Some(last_jvm_file) = get_last_jvm_file() {
+        let _ = fs::remove_file(last_jvm_file);
+    }
+}
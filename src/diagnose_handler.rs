@@ -0,0 +1,146 @@
+use std::{env, fs};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::display_handler::{is_headless, message};
+use crate::exit_handler::FAILURE_EXIT_CODE;
+use crate::file_handler::{get_jvm_candidates, get_user_data_dir, JvmCandidate};
+use crate::locale_handler::text_with;
+use crate::log_handler::{level, log_file_path};
+use crate::LaunchOpts;
+
+/// The environment variables that change how the launcher or Java behave.
+const VARS_OF_INTEREST: &'static [&str] = &[
+    "JAVA_HOME", "JDK_JAVA_OPTIONS", "JAVA_TOOL_OPTIONS", "_JAVA_OPTIONS", "CLASSPATH", "PATH",
+    "LD_LIBRARY_PATH", "DYLD_LIBRARY_PATH", "WHY_LOG", "DISPLAY", "WAYLAND_DISPLAY", "LANG", "LC_ALL",
+];
+
+/// Write what the launcher found out about the system and the app, instead of launching it,
+/// as asked for with `--why-diagnose`. A summary for people and a JSON file for tools are put in
+/// the user data dir, and the user is told where. Returns the exit status of the launcher.
+pub fn write_diagnosis(launch_opts: &LaunchOpts) -> i32 {
+    let candidates = get_jvm_candidates(&launch_opts.config);
+    let vars: Vec<(&str, String)> = VARS_OF_INTEREST.iter()
+        .filter_map(|v| Some((*v, env::var(v).ok()?)))
+        .collect();
+    let summary = summary(launch_opts, &candidates, &vars);
+    let json = json(launch_opts, &candidates, &vars);
+    println!("{}", summary);
+
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let written = get_user_data_dir().and_then(|dir| {
+        let summary_path = dir.join(format!("diagnosis-{}.txt", time));
+        let json_path = dir.join(format!("diagnosis-{}.json", time));
+        fs::create_dir_all(&dir).ok()?;
+        fs::write(&summary_path, &summary).ok()?;
+        fs::write(&json_path, &json).ok()?;
+        Some((summary_path, json_path))
+    });
+
+    match written {
+        Some((summary_path, json_path)) => {
+            info!("launcher", "Wrote the diagnosis to {:?} and {:?}", summary_path, json_path);
+            if !is_headless() {
+                message(&text_with("diagnosis.written", &[&summary_path.display().to_string(),
+                    &json_path.display().to_string()]));
+            }
+            0
+        }
+        None => {
+            info!("launcher", "Failed to write the diagnosis to the user data dir");
+            FAILURE_EXIT_CODE
+        }
+    }
+}
+
+/// The diagnosis for people, such as for a bug report.
+fn summary(launch_opts: &LaunchOpts, candidates: &[JvmCandidate], vars: &[(&str, String)]) -> String {
+    let mut out = format!("Launcher version: {}\nOS: {} {}\nLauncher: {}\nWorking directory: {}\n\
+                           Headless: {}\nLog level: {:?}\nLauncher log: {}\n",
+                          env!("CARGO_PKG_VERSION"), env::consts::OS, env::consts::ARCH,
+                          display_opt(env::current_exe().ok()), display_opt(env::current_dir().ok()),
+                          is_headless(), level(), display_opt(log_file_path()));
+
+    out += "\nJava installations, in the order they are tried:\n";
+    for candidate in candidates {
+        out += &format!("  {} ({}): ", candidate.location, candidate.source);
+        if let Some(library) = &candidate.library {
+            out += &format!("{}, Java {}, {}, ", library.display(),
+                            candidate.version.as_deref().unwrap_or("?"),
+                            candidate.arch.as_deref().unwrap_or("?"));
+        }
+        out += &match &candidate.rejection {
+            Some(rejection) => format!("not used, {}\n", rejection),
+            None => "usable\n".to_string(),
+        };
+    }
+
+    out += "\nEnvironment:\n";
+    for (var, value) in vars {
+        out += &format!("  {}={}\n", var, value);
+    }
+
+    out += &format!("\nJVM options:\n  {}\nApp arguments:\n  {}\n\nConfig:\n{:#?}\n",
+                    launch_opts.jvm_opts.iter().chain(&launch_opts.user_jvm_opts)
+                        .cloned().collect::<Vec<String>>().join("\n  "),
+                    launch_opts.program_opts.join("\n  "), launch_opts.config);
+    out
+}
+
+fn display_opt(path: Option<PathBuf>) -> String {
+    path.map(|p| p.display().to_string()).unwrap_or_else(|| "-".to_string())
+}
+
+/// The diagnosis for tools, a JSON object.
+fn json(launch_opts: &LaunchOpts, candidates: &[JvmCandidate], vars: &[(&str, String)]) -> String {
+    let path = |path: Option<PathBuf>| json_opt(path.map(|p| p.display().to_string()).as_deref());
+    let candidates: Vec<String> = candidates.iter().map(|c| {
+        format!("    {{\"source\": {}, \"location\": {}, \"library\": {}, \"version\": {}, \"arch\": {}, \
+                 \"usable\": {}, \"rejection\": {}}}",
+                json_string(c.source), json_string(&c.location), path(c.library.clone()),
+                json_opt(c.version.as_deref()), json_opt(c.arch.as_deref()),
+                c.rejection.is_none(), json_opt(c.rejection.as_deref()))
+    }).collect();
+    let vars: Vec<String> = vars.iter()
+        .map(|(var, value)| format!("    {}: {}", json_string(var), json_string(value)))
+        .collect();
+
+    format!("{{\n  \"launcher_version\": {},\n  \"os\": {},\n  \"arch\": {},\n  \"launcher\": {},\n  \
+             \"working_dir\": {},\n  \"headless\": {},\n  \"log\": {},\n  \"jvm_candidates\": [\n{}\n  ],\n  \
+             \"environment\": {{\n{}\n  }},\n  \"jvm_options\": {},\n  \"app_arguments\": {},\n  \"config\": {}\n}}\n",
+            json_string(env!("CARGO_PKG_VERSION")), json_string(env::consts::OS), json_string(env::consts::ARCH),
+            path(env::current_exe().ok()), path(env::current_dir().ok()), is_headless(),
+            path(log_file_path()), candidates.join(",\n"), vars.join(",\n"),
+            json_strings(launch_opts.jvm_opts.iter().chain(&launch_opts.user_jvm_opts)),
+            json_strings(launch_opts.program_opts.iter()),
+            json_string(&format!("{:?}", launch_opts.config)))
+}
+
+/// The strings as a JSON array.
+fn json_strings<'a, I: Iterator<Item=&'a String>>(values: I) -> String {
+    format!("[{}]", values.map(|v| json_string(v)).collect::<Vec<String>>().join(", "))
+}
+
+/// The text as a JSON string, `null` if there is none.
+fn json_opt(text: Option<&str>) -> String {
+    text.map_or("null".to_string(), json_string)
+}
+
+/// The text as a JSON string, quoted and escaped.
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            '\r' => out += "\\r",
+            '\t' => out += "\\t",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -2,19 +2,186 @@
 extern crate winapi;
 
 use std::io::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
 
+use crate::file_handler::get_app_name;
+use crate::locale_handler::{text, text_with};
+use crate::log_handler::{log_file_path, report_to_system_log};
+
+/// The most lines of details shown in a dialog.
+const MAX_DETAIL_LINES: usize = 20;
+
+/// The most lines of details shown in the expandable pane of a dialog.
+#[cfg(all(windows, feature = "gui"))]
+const MAX_EXPANDED_DETAIL_LINES: usize = 40;
+
+/// Whether dialogs are replaced by printing to stderr, see [`set_headless`].
+static HEADLESS: AtomicBool = AtomicBool::new(false);
+
+/// Set once a message reporting a problem was printed instead of shown while headless.
+static ERROR_PRINTED: AtomicBool = AtomicBool::new(false);
+
+/// Print messages to stderr instead of showing dialogs, so that servers and CI are never left
+/// waiting on a dialog nobody can see. Questions are answered with [`Answer::Cancel`].
+pub fn set_headless(headless: bool) {
+    HEADLESS.store(headless, Ordering::SeqCst);
+}
+
+/// Whether dialogs are replaced by printing to stderr, always so when built without the `gui` feature,
+/// which leaves out the dialogs.
+pub fn is_headless() -> bool {
+    !cfg!(feature = "gui") || HEADLESS.load(Ordering::SeqCst)
+}
+
+/// Whether a [`message`] or [`detailed_message`], which report a problem, was printed while headless,
+/// so that the launcher should exit with a failure status. Questions do not count.
+pub fn printed_headless_error() -> bool {
+    ERROR_PRINTED.load(Ordering::SeqCst)
+}
+
+/// Whether there is no session to show dialogs in, such as when run as a service
+/// or by the task scheduler without a logged on user.
 #[cfg(windows)]
+pub fn detect_headless() -> bool {
+    use std::mem::{size_of, zeroed};
+    use std::ptr::null_mut;
+    use winapi::shared::minwindef::DWORD;
+    use winapi::um::processthreadsapi::{GetCurrentProcessId, ProcessIdToSessionId};
+    use winapi::um::winuser::{GetProcessWindowStation, GetUserObjectInformationW, UOI_FLAGS,
+                              USEROBJECTFLAGS, WSF_VISIBLE};
+    unsafe {
+        // Services run in session 0, which has no desktop
+        let mut session: DWORD = 0;
+        if ProcessIdToSessionId(GetCurrentProcessId(), &mut session) != 0 && session == 0 {
+            return true;
+        }
+
+        let station = GetProcessWindowStation();
+        if station.is_null() {
+            return false;
+        }
+        let mut flags: USEROBJECTFLAGS = zeroed();
+        let read = GetUserObjectInformationW(station as _, UOI_FLAGS as _,
+                                             &mut flags as *mut USEROBJECTFLAGS as _,
+                                             size_of::<USEROBJECTFLAGS>() as DWORD, null_mut());
+        read != 0 && flags.dwFlags & WSF_VISIBLE == 0
+    }
+}
+
+/// Whether there is no session to show dialogs in, such as over SSH without X forwarding,
+/// or with a display left set from a session that ended.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn detect_headless() -> bool {
+    !has_x11_display() && !has_wayland_display()
+}
+
+/// Whether `DISPLAY` names an X server, for a local one only if its socket exists.
+/// Remote ones, such as forwarded over SSH, are assumed to be reachable.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn has_x11_display() -> bool {
+    use std::env;
+    let display = match env::var("DISPLAY") {
+        Ok(display) if !display.is_empty() => display,
+        _ => return false,
+    };
+    let (host, number) = match display.rsplit_once(':') {
+        Some(split) => split,
+        None => return false,
+    };
+    if !host.is_empty() && host != "unix" {
+        return true;
+    }
+    let number = number.split('.').next().unwrap_or_default();
+    Path::new("/tmp/.X11-unix").join(format!("X{}", number)).exists()
+}
+
+/// Whether `WAYLAND_DISPLAY` names a compositor socket that exists.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn has_wayland_display() -> bool {
+    use std::env;
+    let display = match env::var_os("WAYLAND_DISPLAY") {
+        Some(display) if !display.is_empty() => PathBuf::from(display),
+        _ => return false,
+    };
+    if display.is_absolute() {
+        return display.exists();
+    }
+    env::var_os("XDG_RUNTIME_DIR").map_or(false, |dir| Path::new(&dir).join(display).exists())
+}
+
+/// Whether there is no session to show dialogs in, such as over SSH or from a launch daemon,
+/// where the process's security session has no access to the window server.
+#[cfg(target_os = "macos")]
+pub fn detect_headless() -> bool {
+    // Security framework, `SessionGetInfo` with `callerSecuritySession`
+    const CALLER_SECURITY_SESSION: u32 = u32::MAX;
+    const SESSION_HAS_GRAPHIC_ACCESS: u32 = 0x0010;
+    #[link(name = "Security", kind = "framework")]
+    extern "C" {
+        fn SessionGetInfo(session: u32, session_id: *mut u32, attributes: *mut u32) -> i32;
+    }
+
+    let mut session_id = 0;
+    let mut attributes = 0;
+    let status = unsafe { SessionGetInfo(CALLER_SECURITY_SESSION, &mut session_id, &mut attributes) };
+    status == 0 && attributes & SESSION_HAS_GRAPHIC_ACCESS == 0
+}
+
+/// The title and icon of dialogs, see [`set_branding`].
+static BRANDING: Mutex<Option<(String, Option<PathBuf>)>> = Mutex::new(None);
+
+/// Title dialogs with the app's name and show its icon, such as an `.ico` file on Windows or a
+/// `.png` on Linux, so that they are recognizably from the app. Until set, dialogs are titled
+/// with the name of the launcher executable.
+pub fn set_branding(name: Option<String>, icon: Option<PathBuf>) {
+    if let Ok(mut branding) = BRANDING.lock() {
+        *branding = Some((name.unwrap_or_else(get_app_name), icon));
+    }
+}
+
+#[cfg(feature = "gui")]
+fn dialog_title() -> String {
+    BRANDING.lock().ok()
+        .and_then(|b| b.as_ref().map(|(name, _)| name.clone()))
+        .unwrap_or_else(get_app_name)
+}
+
+#[cfg(all(not(target_os = "macos"), feature = "gui"))]
+fn dialog_icon() -> Option<PathBuf> {
+    BRANDING.lock().ok()
+        .and_then(|b| b.as_ref().and_then(|(_, icon)| icon.clone()))
+        .filter(|icon| icon.is_file())
+}
+
+/// Print a message meant for a dialog to stderr.
+fn print_headless(msg: &str) {
+    eprintln!("{}", msg);
+}
+
+/// Print a message reporting a problem to stderr, see [`printed_headless_error`].
+fn print_headless_error(msg: &str) {
+    ERROR_PRINTED.store(true, Ordering::SeqCst);
+    print_headless(msg);
+}
+
+#[cfg(all(windows, feature = "gui"))]
 fn print_message(msg: &str) -> Result<i32, Error> {
     use std::ffi::OsStr;
     use std::iter::once;
     use std::os::windows::ffi::OsStrExt;
     use std::ptr::null_mut;
-    use winapi::um::winuser::{MessageBoxW, MB_OK};
+    use winapi::um::winuser::{IDOK, MessageBoxW, MB_OK};
     let wide: Vec<u16> = OsStr::new(msg).encode_wide().chain(once(0)).collect();
-    let title: Vec<u16> = OsStr::new("Launcher").encode_wide().chain(once(0)).collect();
+    let title: Vec<u16> = OsStr::new(&dialog_title()).encode_wide().chain(once(0)).collect();
     let ret = unsafe {
         use winapi::um::winuser::{MessageBeep, SPI_GETBEEP};
         MessageBeep(SPI_GETBEEP);
+        // Shows the app's icon where available
+        if task_dialog(msg, &[Choice::Ok], None).is_some() {
+            return Ok(IDOK);
+        }
         MessageBoxW(null_mut(), wide.as_ptr(), title.as_ptr(), MB_OK)
     };
     if ret == 0 {
@@ -24,16 +191,1099 @@ fn print_message(msg: &str) -> Result<i32, Error> {
     }
 }
 
-#[cfg(not(windows))]
+#[cfg(all(unix, not(target_os = "macos"), feature = "gui"))]
+fn print_message(msg: &str) -> Result<(), Error> {
+    let shown = |code: Option<i32>, shown_codes: &[i32]| code.map_or(false, |c| shown_codes.contains(&c));
+
+    // Closing the dialog counts as acknowledging it
+    let text_arg = format!("--text={}", escape_markup(msg));
+    if run_zenity(&["--info", "--width=400", text_arg.as_str()]).is_some() {
+        return Ok(());
+    }
+    if let Some(out) = run_dialog_tool("kdialog", &["--msgbox", msg]) {
+        if shown(out.status.code(), &[0, 1]) {
+            return Ok(());
+        }
+    }
+    // Exits with 1 when it cannot show the window
+    if let Some(out) = run_dialog_tool("xmessage", &["-center", "-buttons", "OK:100", msg]) {
+        if shown(out.status.code(), &[0, 100]) {
+            return Ok(());
+        }
+    }
+
+    eprintln!("{}", msg);
+    Ok(())
+}
+
+#[cfg(all(target_os = "macos", feature = "gui"))]
 fn print_message(msg: &str) -> Result<(), Error> {
     println!("{}", msg);
     Ok(())
 }
 
-/// Display a native message dialog.<br>
+/// Built without the `gui` feature the launcher is always headless, so there are no dialogs to show.
+#[cfg(not(feature = "gui"))]
+fn print_message(_: &str) -> Result<(), Error> {
+    Err(Error::from(std::io::ErrorKind::Unsupported))
+}
+
+/// Run a dialog tool, such as `zenity`, with the app's title and icon,
+/// returning `None` if it is not installed.
+#[cfg(all(unix, not(target_os = "macos"), feature = "gui"))]
+fn run_dialog_tool(tool: &str, args: &[&str]) -> Option<std::process::Output> {
+    use std::process::Stdio;
+    dialog_tool_command(tool).args(args).stdin(Stdio::null()).output().ok()
+}
+
+/// Run `zenity`, returning `None` if it is not installed or could not show the dialog, so that the next
+/// tool is tried. Otherwise it exited with 0 for OK, or with 1 when cancelled or closed.
+#[cfg(all(unix, not(target_os = "macos"), feature = "gui"))]
+fn run_zenity(args: &[&str]) -> Option<std::process::Output> {
+    let out = run_dialog_tool("zenity", args)?;
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    if zenity_failed(out.status.code(), &stderr) {
+        debug!("display", "zenity failed with {:?}: {}", out.status.code(), stderr.trim());
+        return None;
+    }
+    Some(out)
+}
+
+/// Whether `zenity` exited without showing the dialog. It also exits with 1, as when cancelled,
+/// if it cannot open the display.
+#[cfg(all(unix, not(target_os = "macos"), feature = "gui"))]
+fn zenity_failed(code: Option<i32>, stderr: &str) -> bool {
+    match code {
+        Some(0) => false,
+        Some(1) => stderr.to_lowercase().contains("open display"),
+        _ => true,
+    }
+}
+
+/// The command running a dialog tool with the app's title and icon.
+#[cfg(all(unix, not(target_os = "macos"), feature = "gui"))]
+fn dialog_tool_command(tool: &str) -> std::process::Command {
+    use std::process::Command;
+    let title = dialog_title();
+    let icon = dialog_icon();
+    let mut command = Command::new(tool);
+    match tool {
+        "zenity" => {
+            command.arg(format!("--title={}", title));
+            if let Some(icon) = &icon {
+                command.arg(format!("--window-icon={}", icon.display()));
+            }
+        }
+        "kdialog" => {
+            command.arg("--title").arg(&title);
+            if let Some(icon) = &icon {
+                command.arg("--icon").arg(icon);
+            }
+        }
+        _ => {
+            command.arg("-title").arg(&title);
+        }
+    }
+    command
+}
+
+/// Escape the text for tools that read it as Pango markup.
+#[cfg(all(unix, not(target_os = "macos"), feature = "gui"))]
+fn escape_markup(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// The button the user chose in a [`question`] dialog.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Answer {
+    Yes,
+    No,
+    Cancel,
+}
+
+/// A button of an [`ask`] dialog, and the user's choice.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Choice {
+    Ok,
+    Cancel,
+    Yes,
+    No,
+    Retry,
+    GetJava,
+    CopyDetails,
+    Continue,
+    Quit,
+    #[cfg(feature = "updater")]
+    OpenReleasePage,
+    #[cfg(feature = "updater")]
+    Later,
+}
+
+impl Choice {
+    /// The text of the button, in the user's language.
+    pub fn label(&self) -> String {
+        text(match self {
+            Choice::Ok => "button.ok",
+            Choice::Cancel => "button.cancel",
+            Choice::Yes => "button.yes",
+            Choice::No => "button.no",
+            Choice::Retry => "button.retry",
+            Choice::GetJava => "button.get_java",
+            Choice::CopyDetails => "button.copy_details",
+            Choice::Continue => "button.continue",
+            Choice::Quit => "button.quit",
+            #[cfg(feature = "updater")]
+            Choice::OpenReleasePage => "button.open_release_page",
+            #[cfg(feature = "updater")]
+            Choice::Later => "button.later",
+        })
+    }
+}
+
+/// The ID of the first button of a task dialog, those after it count up.
+#[cfg(all(windows, feature = "gui"))]
+const FIRST_BUTTON_ID: i32 = 100;
+
+/// The ID of the button of a task dialog copying its details.
+#[cfg(all(windows, feature = "gui"))]
+const COPY_BUTTON_ID: i32 = 99;
+
+#[cfg(all(windows, feature = "gui"))]
+fn print_choice(msg: &str, buttons: &[Choice]) -> Result<Choice, Error> {
+    match task_dialog(msg, buttons, None) {
+        Some(choice) => Ok(choice),
+        // Older Windows, or no Common Controls 6 for the launcher
+        None => message_box_choice(msg, buttons),
+    }
+}
+
+/// Show a task dialog with the buttons, `None` if not available.
+/// Details are shown in a pane the user can expand, with a button copying them.
+#[cfg(all(windows, feature = "gui"))]
+fn task_dialog(msg: &str, buttons: &[Choice], details: Option<&str>) -> Option<Choice> {
+    let labels: Vec<String> = buttons.iter().map(|b| b.label()).collect();
+    let index = show_task_dialog(msg, &labels, details, false)?;
+    // Closing the dialog picks the last button
+    index.and_then(|i| buttons.get(i)).or(buttons.last()).copied()
+}
+
+/// Show a task dialog with buttons of the labels, or with command links of them and a Cancel button,
+/// returning the index of the one pressed, `None` if the dialog was closed, and `None` in all
+/// if it is not available.<br>
+/// `TaskDialogIndirect` is only in version 6 of the Common Controls, which the launcher only gets
+/// if its manifest asks for it, so it is looked up when needed rather than linked.
+#[cfg(all(windows, feature = "gui"))]
+fn show_task_dialog(msg: &str, buttons: &[String], details: Option<&str>,
+                    command_links: bool) -> Option<Option<usize>> {
+    use std::ffi::OsStr;
+    use std::iter::once;
+    use std::mem::{size_of, zeroed};
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr::null_mut;
+    use winapi::ctypes::c_int;
+    use winapi::shared::basetsd::LONG_PTR;
+    use winapi::shared::minwindef::{BOOL, LPARAM, UINT, WPARAM};
+    use winapi::shared::windef::HWND;
+    use winapi::shared::winerror::{HRESULT, S_FALSE, S_OK, SUCCEEDED};
+    use winapi::um::commctrl::{TASKDIALOG_BUTTON, TASKDIALOGCONFIG, TASKDIALOGCONFIG_u1, TDCBF_CANCEL_BUTTON,
+                               TDF_ALLOW_DIALOG_CANCELLATION, TDF_USE_COMMAND_LINKS, TDF_USE_HICON_MAIN,
+                               TDN_BUTTON_CLICKED};
+    use winapi::um::winuser::DestroyIcon;
+    type TaskDialogIndirect = unsafe extern "system" fn(*const TASKDIALOGCONFIG, *mut c_int,
+                                                        *mut c_int, *mut BOOL) -> HRESULT;
+
+    /// Copies the details, keeping the dialog open.
+    unsafe extern "system" fn on_event(_: HWND, event: UINT, button: WPARAM, _: LPARAM,
+                                       data: LONG_PTR) -> HRESULT {
+        if event == TDN_BUTTON_CLICKED && button as c_int == COPY_BUTTON_ID && data != 0 {
+            copy_to_clipboard(&*(data as *const String));
+            return S_FALSE;
+        }
+        S_OK
+    }
+
+    let wide: Vec<u16> = OsStr::new(msg).encode_wide().chain(once(0)).collect();
+    let title: Vec<u16> = OsStr::new(&dialog_title()).encode_wide().chain(once(0)).collect();
+    let mut labels: Vec<Vec<u16>> = buttons.iter()
+        .map(|b| OsStr::new(b).encode_wide().chain(once(0)).collect())
+        .collect();
+    let mut ids: Vec<c_int> = (0..buttons.len()).map(|i| FIRST_BUTTON_ID + i as c_int).collect();
+    if details.is_some() {
+        labels.insert(0, OsStr::new(&Choice::CopyDetails.label()).encode_wide().chain(once(0)).collect());
+        ids.insert(0, COPY_BUTTON_ID);
+    }
+    let task_buttons: Vec<TASKDIALOG_BUTTON> = labels.iter().zip(&ids)
+        .map(|(label, id)| TASKDIALOG_BUTTON {
+            nButtonID: *id,
+            pszButtonText: label.as_ptr(),
+        })
+        .collect();
+
+    // Long details still make the dialog grow past the screen, all of them are copied
+    let copied: Option<String> = details.map(String::from);
+    let expanded: Option<Vec<u16>> = details.map(|d| {
+        let mut lines: Vec<&str> = d.lines().take(MAX_EXPANDED_DETAIL_LINES).collect();
+        if d.lines().count() > MAX_EXPANDED_DETAIL_LINES {
+            lines.push("...");
+        }
+        OsStr::new(&lines.join("\n")).encode_wide().chain(once(0)).collect()
+    });
+    let show_details: Vec<u16> = OsStr::new(&text("dialog.show_details")).encode_wide().chain(once(0)).collect();
+    let hide_details: Vec<u16> = OsStr::new(&text("dialog.hide_details")).encode_wide().chain(once(0)).collect();
+
+    unsafe {
+        let comctl = libloading::Library::new("comctl32.dll").ok()?;
+        let task_dialog: libloading::Symbol<TaskDialogIndirect> = comctl.get(b"TaskDialogIndirect\0").ok()?;
+
+        let mut config: TASKDIALOGCONFIG = zeroed();
+        config.cbSize = size_of::<TASKDIALOGCONFIG>() as UINT;
+        config.dwFlags = TDF_ALLOW_DIALOG_CANCELLATION;
+        config.pszWindowTitle = title.as_ptr();
+        config.pszContent = wide.as_ptr();
+        config.cButtons = task_buttons.len() as UINT;
+        config.pButtons = task_buttons.as_ptr();
+        config.nDefaultButton = FIRST_BUTTON_ID;
+        if command_links {
+            config.dwFlags |= TDF_USE_COMMAND_LINKS;
+            config.dwCommonButtons = TDCBF_CANCEL_BUTTON;
+        }
+        let icon = load_dialog_icon();
+        if !icon.is_null() {
+            config.dwFlags |= TDF_USE_HICON_MAIN;
+            // Set as a whole, as the struct is packed
+            let mut main_icon: TASKDIALOGCONFIG_u1 = zeroed();
+            *main_icon.hMainIcon_mut() = icon;
+            config.u1 = main_icon;
+        }
+        if let (Some(expanded), Some(copied)) = (&expanded, &copied) {
+            config.pszExpandedInformation = expanded.as_ptr();
+            config.pszCollapsedControlText = show_details.as_ptr();
+            config.pszExpandedControlText = hide_details.as_ptr();
+            config.pfCallback = Some(on_event);
+            config.lpCallbackData = copied as *const String as LONG_PTR;
+        }
+
+        let mut pressed: c_int = 0;
+        let result = task_dialog(&config, &mut pressed, null_mut(), null_mut());
+        if !icon.is_null() {
+            DestroyIcon(icon);
+        }
+        if !SUCCEEDED(result) {
+            return None;
+        }
+        Some(usize::try_from(pressed - FIRST_BUTTON_ID).ok().filter(|i| *i < buttons.len()))
+    }
+}
+
+/// Load the icon set with [`set_branding`], or else the launcher's own, null if there is none.
+/// The caller destroys it.
+#[cfg(all(windows, feature = "gui"))]
+fn load_dialog_icon() -> winapi::shared::windef::HICON {
+    use std::iter::once;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr::null_mut;
+    use winapi::um::libloaderapi::GetModuleHandleW;
+    use winapi::um::winuser::{CopyIcon, IMAGE_ICON, LoadIconW, LoadImageW, LR_DEFAULTSIZE,
+                              LR_LOADFROMFILE, MAKEINTRESOURCEW};
+    unsafe {
+        match dialog_icon() {
+            Some(icon) => {
+                let wide: Vec<u16> = icon.as_os_str().encode_wide().chain(once(0)).collect();
+                LoadImageW(null_mut(), wide.as_ptr(), IMAGE_ICON, 0, 0,
+                           LR_LOADFROMFILE | LR_DEFAULTSIZE) as _
+            }
+            // The first icon resource, as resource compilers number them, copied as shared icons
+            // must not be destroyed
+            None => {
+                let icon = LoadIconW(GetModuleHandleW(null_mut()), MAKEINTRESOURCEW(1));
+                if icon.is_null() { icon } else { CopyIcon(icon) }
+            }
+        }
+    }
+}
+
+/// Show the buttons with a message box, which only has fixed buttons.
+/// Up to three are shown, explained in the message unless they are the box's own.
+#[cfg(all(windows, feature = "gui"))]
+fn message_box_choice(msg: &str, buttons: &[Choice]) -> Result<Choice, Error> {
+    use std::ffi::OsStr;
+    use std::iter::once;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr::null_mut;
+    use winapi::um::winuser::{IDCANCEL, IDNO, IDOK, IDYES, MB_ICONWARNING, MB_OK, MB_YESNO,
+                              MB_YESNOCANCEL, MessageBoxW};
+    let shown: Vec<Choice> = if buttons.len() > 3 {
+        vec![buttons[0], buttons[1], buttons[buttons.len() - 1]]
+    } else {
+        buttons.to_vec()
+    };
+    let (style, native, ids) = match shown.len() {
+        1 => (MB_OK, vec![Choice::Ok], vec![IDOK]),
+        2 => (MB_YESNO, vec![Choice::Yes, Choice::No], vec![IDYES, IDNO]),
+        _ => (MB_YESNOCANCEL, vec![Choice::Yes, Choice::No, Choice::Cancel], vec![IDYES, IDNO, IDCANCEL]),
+    };
+
+    let mut shown_msg = msg.to_string();
+    if shown != native {
+        shown_msg += "\n";
+        for (n, b) in native.iter().zip(&shown) {
+            shown_msg += &format!("\n{} - {}", n.label(), b.label());
+        }
+    }
+
+    let wide: Vec<u16> = OsStr::new(&shown_msg).encode_wide().chain(once(0)).collect();
+    let title: Vec<u16> = OsStr::new(&dialog_title()).encode_wide().chain(once(0)).collect();
+    let ret = unsafe {
+        MessageBoxW(null_mut(), wide.as_ptr(), title.as_ptr(), style | MB_ICONWARNING)
+    };
+    if ret == 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(ids.iter().position(|id| *id == ret)
+        .and_then(|i| shown.get(i))
+        .or(shown.last())
+        .copied()
+        .unwrap_or(Choice::Ok))
+}
+
+#[cfg(all(unix, not(target_os = "macos"), feature = "gui"))]
+fn print_choice(msg: &str, buttons: &[Choice]) -> Result<Choice, Error> {
+    use std::io::ErrorKind;
+    let first = buttons[0];
+    let last = buttons[buttons.len() - 1];
+    let middle = &buttons[1..buttons.len() - 1];
+    let labels: Vec<String> = buttons.iter().map(|b| b.label()).collect();
+    let first_label = labels[0].as_str();
+    let last_label = labels[labels.len() - 1].as_str();
+
+    // The extra buttons print their label, the others only set the exit status
+    let text_arg = format!("--text={}", escape_markup(msg));
+    let mut zenity: Vec<String> = vec!["--question".to_string(), "--width=400".to_string(), text_arg,
+                                       format!("--ok-label={}", first_label)];
+    if buttons.len() > 1 {
+        zenity.push(format!("--cancel-label={}", last_label));
+    }
+    zenity.extend(middle.iter().map(|b| format!("--extra-button={}", b.label())));
+    let zenity: Vec<&str> = zenity.iter().map(|a| a.as_str()).collect();
+    if let Some(out) = run_zenity(&zenity) {
+        if out.status.success() {
+            return Ok(first);
+        }
+        // Cancelled or closed, unless one of the extra buttons was pressed
+        let printed = String::from_utf8_lossy(&out.stdout).trim().to_string();
+        return Ok(*middle.iter().find(|b| b.label() == printed).unwrap_or(&last));
+    }
+
+    // At most three buttons
+    let kdialog: Option<Vec<&str>> = match buttons.len() {
+        1 => Some(vec!["--msgbox", msg, "--ok-label", first_label]),
+        2 => Some(vec!["--yesno", msg, "--yes-label", first_label, "--no-label", last_label]),
+        3 => Some(vec!["--yesnocancel", msg, "--yes-label", first_label,
+                       "--no-label", labels[1].as_str(), "--cancel-label", last_label]),
+        _ => None,
+    };
+    if let Some(kdialog) = kdialog {
+        if let Some(out) = run_dialog_tool("kdialog", &kdialog) {
+            match out.status.code().and_then(|c| usize::try_from(c).ok()) {
+                Some(i) if i < buttons.len() => return Ok(buttons[i]),
+                _ => {}
+            }
+        }
+    }
+
+    // Exits with the value of the button, 0 when closed and 1 when it cannot show the window
+    let xmessage_buttons: Vec<String> = labels.iter().enumerate()
+        .map(|(i, label)| format!("{}:{}", label.replace(|c| c == ',' || c == ':', " "), 100 + i))
+        .collect();
+    let xmessage_buttons = xmessage_buttons.join(",");
+    let xmessage = ["-center", "-buttons", xmessage_buttons.as_str(), msg];
+    if let Some(out) = run_dialog_tool("xmessage", &xmessage) {
+        match out.status.code() {
+            Some(0) => return Ok(last),
+            Some(c) if c >= 100 && ((c - 100) as usize) < buttons.len() => return Ok(buttons[(c - 100) as usize]),
+            _ => {}
+        }
+    }
+
+    eprintln!("{}", msg);
+    Err(Error::new(ErrorKind::NotFound, "No dialog tool could show the dialog"))
+}
+
+#[cfg(all(target_os = "macos", feature = "gui"))]
+fn print_choice(msg: &str, _buttons: &[Choice]) -> Result<Choice, Error> {
+    use std::io::ErrorKind;
+    println!("{}", msg);
+    Err(Error::new(ErrorKind::Unsupported, "Dialogs with buttons are not supported"))
+}
+
+/// Built without the `gui` feature the launcher is always headless, so there are no dialogs to show.
+#[cfg(not(feature = "gui"))]
+fn print_choice(_: &str, _: &[Choice]) -> Result<Choice, Error> {
+    Err(Error::from(std::io::ErrorKind::Unsupported))
+}
+
+/// Display a native dialog with the buttons, the message should say what each does,
+/// returning the one the user chose.<br>
+/// The last button should be the one that gives up, such as [`Choice::Cancel`] or
+/// [`Choice::Quit`]: it is returned when the dialog is closed or could not be shown,
+/// and while headless, when the message is printed instead.
+pub fn ask(msg: &str, buttons: &[Choice]) -> Choice {
+    let fallback = buttons.last().copied().unwrap_or(Choice::Ok);
+    if buttons.is_empty() {
+        message(msg);
+        return fallback;
+    }
+    if is_headless() {
+        print_headless(msg);
+        return fallback;
+    }
+    print_choice(msg, buttons).unwrap_or(fallback)
+}
+
+/// Let the user choose one of the items from a native list dialog, or from the items numbered on
+/// the console while headless or if the dialog could not be shown, returning the index of the one
+/// chosen, `None` if none was.
+pub fn choose(msg: &str, items: &[String]) -> Option<usize> {
+    if items.is_empty() {
+        return None;
+    }
+    if is_headless() {
+        return prompt_choice(msg, items);
+    }
+    print_list(msg, items).unwrap_or_else(|| prompt_choice(msg, items))
+}
+
+/// Print the items numbered and read the number of the one chosen from stdin.<br>
+/// Not a message of the launcher, so it does not count as one printed while headless.
+fn prompt_choice(msg: &str, items: &[String]) -> Option<usize> {
+    use std::io::{BufRead, Write};
+    eprintln!("{}", msg);
+    for (i, item) in items.iter().enumerate() {
+        eprintln!("  {}) {}", i + 1, item);
+    }
+    eprint!("> ");
+    let _ = std::io::stderr().flush();
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line).ok()?;
+    parse_choice(&line, items.len())
+}
+
+/// The index of the item numbered from 1 in the text, such as typed or printed by a dialog tool.
+fn parse_choice(text: &str, count: usize) -> Option<usize> {
+    text.trim().parse::<usize>().ok().filter(|n| (1..=count).contains(n)).map(|n| n - 1)
+}
+
+/// Show the items as command links, `None` if the dialog could not be shown.
+#[cfg(all(windows, feature = "gui"))]
+fn print_list(msg: &str, items: &[String]) -> Option<Option<usize>> {
+    show_task_dialog(msg, items, None, true)
+}
+
+/// Show the items in a list, `None` if no dialog tool could show it.
+#[cfg(all(unix, not(target_os = "macos"), feature = "gui"))]
+fn print_list(msg: &str, items: &[String]) -> Option<Option<usize>> {
+    // Each item after its number, which the tools print for the chosen one
+    let rows: Vec<String> = items.iter().enumerate()
+        .flat_map(|(i, item)| [(i + 1).to_string(), item.clone()])
+        .collect();
+    let chosen = |out: &std::process::Output| parse_choice(&String::from_utf8_lossy(&out.stdout), items.len());
+
+    let text_arg = format!("--text={}", escape_markup(msg));
+    let mut zenity = vec!["--list", "--width=600", "--height=400", text_arg.as_str(), "--hide-header",
+                          "--column=", "--column=", "--hide-column=1", "--print-column=1"];
+    zenity.extend(rows.iter().map(|r| r.as_str()));
+    if let Some(out) = run_zenity(&zenity) {
+        // Cancelled or closed with anything but 0
+        return Some(if out.status.success() { chosen(&out) } else { None });
+    }
+
+    let mut kdialog = vec!["--menu", msg];
+    kdialog.extend(rows.iter().map(|r| r.as_str()));
+    if let Some(out) = run_dialog_tool("kdialog", &kdialog) {
+        match out.status.code() {
+            Some(0) => return Some(chosen(&out)),
+            Some(1) => return Some(None),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Show the items in a list with AppleScript, `None` if it could not be shown.
+#[cfg(all(target_os = "macos", feature = "gui"))]
+fn print_list(msg: &str, items: &[String]) -> Option<Option<usize>> {
+    use std::process::{Command, Stdio};
+    let list: Vec<String> = items.iter().map(|i| apple_script_string(i)).collect();
+    let script = format!("choose from list {{{}}} with prompt {} with title {}", list.join(", "),
+                         apple_script_string(msg), apple_script_string(&dialog_title()));
+    let out = Command::new("osascript").arg("-e").arg(script)
+        .stdin(Stdio::null()).stderr(Stdio::null())
+        .output().ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    // The chosen item, or `false` when cancelled
+    let printed = String::from_utf8_lossy(&out.stdout);
+    let printed = printed.trim_end_matches('\n');
+    Some(items.iter().position(|i| i == printed))
+}
+
+/// Built without the `gui` feature the launcher is always headless, so there are no dialogs to show.
+#[cfg(not(feature = "gui"))]
+fn print_list(_: &str, _: &[String]) -> Option<Option<usize>> {
+    None
+}
+
+/// The text as a string literal of AppleScript.
+#[cfg(all(target_os = "macos", feature = "gui"))]
+fn apple_script_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Display a native Yes/No/Cancel dialog, the message should explain what each button does.<br>
+/// Returns [`Answer::Yes`] if the dialog could not be shown.
+/// While headless the message is printed and [`Answer::Cancel`] returned.
+pub fn question(msg: &str) -> Answer {
+    if is_headless() {
+        print_headless(msg);
+        return Answer::Cancel;
+    }
+    match print_choice(msg, &[Choice::Yes, Choice::No, Choice::Cancel]) {
+        Ok(Choice::No) => Answer::No,
+        Ok(Choice::Cancel) => Answer::Cancel,
+        _ => Answer::Yes,
+    }
+}
+
+/// The message, ending with where the launcher log is if it is open,
+/// for messages about something that went wrong.
+fn with_log_note(msg: &str) -> String {
+    match log_file_path() {
+        Some(log) => format!("{}\n\n{}", msg, text_with("log.written", &[&log.display().to_string()])),
+        None => msg.to_string(),
+    }
+}
+
+/// Display a native message dialog, or print it to stderr while headless.
+/// Messages report something that went wrong, so they end with where the launcher log is.<br>
 /// Eats any errors that occur.
 pub fn message(msg: &str) {
+    info!("display", "{}", msg);
+    report_to_system_log(msg);
+    let msg = &with_log_note(msg);
+    if is_headless() {
+        print_headless_error(msg);
+        return;
+    }
     if let Ok(_) = print_message(msg) {
         // no-op
     }
 }
+
+
+/// Display a native message dialog with technical details such as a stack trace,
+/// offering to copy them for a bug report. On Windows they are in a pane the user can expand,
+/// elsewhere long details are cut short so that the dialog still fits on screen.<br>
+/// While headless all the details are printed. Like [`message`], the message ends with where the
+/// launcher log is.<br>
+/// Eats any errors that occur.
+pub fn detailed_message(msg: &str, details: &str) {
+    info!("display", "{}\n{}", msg, details);
+    report_to_system_log(&format!("{}\n\n{}", msg, details));
+    let msg = &with_log_note(msg);
+    if is_headless() {
+        print_headless_error(&format!("{}\n\n{}\n{}", msg, text("dialog.details"), details));
+        return;
+    }
+
+    #[cfg(all(windows, feature = "gui"))]
+    if task_dialog(msg, &[Choice::Ok], Some(details)).is_some() {
+        return;
+    }
+
+    let mut lines: Vec<&str> = details.lines().take(MAX_DETAIL_LINES).collect();
+    if details.lines().count() > MAX_DETAIL_LINES {
+        lines.push("...");
+    }
+    let shown = format!("{}\n\n{}\n{}", msg, text("dialog.details"), lines.join("\n"));
+    if ask(&shown, &[Choice::CopyDetails, Choice::Ok]) == Choice::CopyDetails {
+        copy_to_clipboard(details);
+    }
+}
+
+/// How long a notification is shown in the notification area on Windows.
+#[cfg(all(windows, feature = "gui"))]
+const NOTIFICATION_DURATION: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Show a notification that does not wait for the user, for something worth knowing that does
+/// not stop the app from working, such as falling back to another Java installation.<br>
+/// The message is logged either way.
+pub fn notify(msg: &str) {
+    info!("display", "{}", msg);
+    if !is_headless() && !show_notification(msg) {
+        debug!("display", "Failed to show the notification");
+    }
+}
+
+/// Show a balloon from an icon in the notification area, removed again after a while
+/// by a thread of its own. Returns once the balloon is shown.
+#[cfg(all(windows, feature = "gui"))]
+fn show_notification(msg: &str) -> bool {
+    use std::ffi::OsStr;
+    use std::iter::once;
+    use std::mem::{size_of, zeroed};
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr::null_mut;
+    use winapi::um::shellapi::{NIF_ICON, NIF_INFO, NIF_TIP, NIIF_INFO, NIM_ADD, NIM_DELETE,
+                               NOTIFYICONDATAW, Shell_NotifyIconW};
+    use winapi::um::winuser::{CreateWindowExW, DestroyIcon, DestroyWindow, HWND_MESSAGE};
+
+    /// Copy as much of the text as fits, leaving the terminating null.
+    fn copy_wide(dst: &mut [u16], text: &str) {
+        for (d, c) in dst.iter_mut().take(dst.len() - 1).zip(OsStr::new(text).encode_wide()) {
+            *d = c;
+        }
+    }
+
+    let title = dialog_title();
+    let msg = msg.to_string();
+    let (sender, shown) = std::sync::mpsc::channel();
+    std::thread::spawn(move || unsafe {
+        // The icon needs a window, one that only exists to receive messages
+        let class: Vec<u16> = OsStr::new("STATIC").encode_wide().chain(once(0)).collect();
+        let window = CreateWindowExW(0, class.as_ptr(), null_mut(), 0, 0, 0, 0, 0,
+                                     HWND_MESSAGE, null_mut(), null_mut(), null_mut());
+        if window.is_null() {
+            let _ = sender.send(false);
+            return;
+        }
+        let icon = load_dialog_icon();
+
+        let mut data: NOTIFYICONDATAW = zeroed();
+        data.cbSize = size_of::<NOTIFYICONDATAW>() as u32;
+        data.hWnd = window;
+        data.uID = 1;
+        data.uFlags = NIF_INFO | NIF_TIP | if icon.is_null() { 0 } else { NIF_ICON };
+        data.hIcon = icon;
+        data.dwInfoFlags = NIIF_INFO;
+        copy_wide(&mut data.szTip, &title);
+        copy_wide(&mut data.szInfoTitle, &title);
+        copy_wide(&mut data.szInfo, &msg);
+
+        let added = Shell_NotifyIconW(NIM_ADD, &mut data) != 0;
+        let _ = sender.send(added);
+        if added {
+            std::thread::sleep(NOTIFICATION_DURATION);
+            Shell_NotifyIconW(NIM_DELETE, &mut data);
+        }
+        if !icon.is_null() {
+            DestroyIcon(icon);
+        }
+        DestroyWindow(window);
+    });
+    shown.recv().unwrap_or(false)
+}
+
+/// Show a notification with `notify-send`, from libnotify.
+#[cfg(all(unix, not(target_os = "macos"), feature = "gui"))]
+fn show_notification(msg: &str) -> bool {
+    use std::process::{Command, Stdio};
+    let mut command = Command::new("notify-send");
+    command.arg(format!("--app-name={}", dialog_title()));
+    if let Some(icon) = dialog_icon() {
+        command.arg(format!("--icon={}", icon.display()));
+    }
+    command.arg(dialog_title()).arg(msg)
+        .stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null())
+        .status().map(|s| s.success()).unwrap_or(false)
+}
+
+/// Show a notification through AppleScript, which posts it from the script runner.
+#[cfg(all(target_os = "macos", feature = "gui"))]
+fn show_notification(msg: &str) -> bool {
+    use std::process::{Command, Stdio};
+    let script = format!("display notification {} with title {}", apple_script_string(msg),
+                         apple_script_string(&dialog_title()));
+    Command::new("osascript").arg("-e").arg(script)
+        .stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null())
+        .status().map(|s| s.success()).unwrap_or(false)
+}
+
+/// Built without the `gui` feature the launcher is always headless, so there are no dialogs to show.
+#[cfg(not(feature = "gui"))]
+fn show_notification(_: &str) -> bool {
+    false
+}
+
+/// Put the text on the clipboard, returning whether it worked.
+#[cfg(windows)]
+pub fn copy_to_clipboard(text: &str) -> bool {
+    use std::ffi::OsStr;
+    use std::iter::once;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr::{copy_nonoverlapping, null_mut};
+    use winapi::um::winbase::{GlobalAlloc, GlobalFree, GlobalLock, GlobalUnlock, GMEM_MOVEABLE};
+    use winapi::um::winuser::{CF_UNICODETEXT, CloseClipboard, EmptyClipboard, OpenClipboard, SetClipboardData};
+    let wide: Vec<u16> = OsStr::new(text).encode_wide().chain(once(0)).collect();
+    unsafe {
+        if OpenClipboard(null_mut()) == 0 {
+            return false;
+        }
+        EmptyClipboard();
+        let mem = GlobalAlloc(GMEM_MOVEABLE, wide.len() * 2);
+        let mut ok = false;
+        if !mem.is_null() {
+            let dst = GlobalLock(mem) as *mut u16;
+            if !dst.is_null() {
+                copy_nonoverlapping(wide.as_ptr(), dst, wide.len());
+                GlobalUnlock(mem);
+                // The clipboard owns the memory once set
+                ok = !SetClipboardData(CF_UNICODETEXT, mem).is_null();
+            }
+            if !ok {
+                GlobalFree(mem);
+            }
+        }
+        CloseClipboard();
+        ok
+    }
+}
+
+/// Put the text on the clipboard, returning whether it worked.
+#[cfg(not(windows))]
+pub fn copy_to_clipboard(text: &str) -> bool {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    let tools: &[(&str, &[&str])] = &[("pbcopy", &[]), ("wl-copy", &[]),
+        ("xclip", &["-selection", "clipboard"]), ("xsel", &["--clipboard", "--input"])];
+    for (tool, args) in tools {
+        if let Ok(mut child) = Command::new(tool).args(*args).stdin(Stdio::piped()).spawn() {
+            if let Some(mut stdin) = child.stdin.take() {
+                let _ = stdin.write_all(text.as_bytes());
+            }
+            if child.wait().map(|s| s.success()).unwrap_or(false) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Show the file in the platform's file manager.
+pub fn show_in_folder(path: &Path) {
+    use std::process::Command;
+    #[cfg(windows)]
+    let result = Command::new("explorer").arg(format!("/select,{}", path.display())).spawn();
+    #[cfg(target_os = "macos")]
+    let result = Command::new("open").arg("-R").arg(path).spawn();
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let result = Command::new("xdg-open").arg(path.parent().unwrap_or(path)).spawn();
+    if let Err(e) = result {
+        info!("display", "Failed to open the folder of {:?}: {:?}", path, e);
+    }
+}
+
+/// Open the web page in the default browser.
+pub fn open_url(url: &str) {
+    use std::process::Command;
+    #[cfg(windows)]
+    let result = Command::new("explorer").arg(url).spawn();
+    #[cfg(target_os = "macos")]
+    let result = Command::new("open").arg(url).spawn();
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let result = Command::new("xdg-open").arg(url).spawn();
+    if let Err(e) = result {
+        info!("display", "Failed to open {}: {:?}", url, e);
+    }
+}
+
+/// What a progress window shows, shared with the thread showing it on Windows.
+struct ProgressState {
+    title: String,
+    /// `None` while the progress is unknown, shown as a bar that keeps moving.
+    percent: Option<u8>,
+    text: String,
+    /// Whether the window is out of date.
+    #[cfg(all(windows, feature = "gui"))]
+    changed: bool,
+    /// Set once the window should close.
+    #[cfg(all(windows, feature = "gui"))]
+    closed: bool,
+    /// Set if the user closed the window.
+    cancelled: bool,
+}
+
+/// How a progress window is shown.
+enum ProgressWindow {
+    #[cfg(all(windows, feature = "gui"))]
+    TaskDialog(std::thread::JoinHandle<()>),
+    #[cfg(all(unix, not(target_os = "macos"), feature = "gui"))]
+    Zenity(std::process::Child),
+    /// Printed to stdout when headless or no window could be shown.
+    Printed,
+}
+
+/// A window showing the progress of a long operation, such as a download, see [`progress`].
+/// The window is closed when the handle is dropped.
+pub struct ProgressHandle {
+    state: std::sync::Arc<Mutex<ProgressState>>,
+    window: ProgressWindow,
+}
+
+/// Show a window with a progress bar for the operation described by the title, so that the user
+/// knows the launcher is still busy. The window has a button cancelling the operation, which
+/// the operation should check with [`ProgressHandle::is_cancelled`].<br>
+/// While headless, or if no window can be shown, the progress is printed instead.
+pub fn progress(title: &str) -> ProgressHandle {
+    use std::sync::Arc;
+    let state = Arc::new(Mutex::new(ProgressState {
+        title: title.to_string(),
+        percent: None,
+        text: String::new(),
+        #[cfg(all(windows, feature = "gui"))]
+        changed: true,
+        #[cfg(all(windows, feature = "gui"))]
+        closed: false,
+        cancelled: false,
+    }));
+    let window = if is_headless() {
+        None
+    } else {
+        show_progress(title, &state)
+    };
+    if window.is_none() {
+        println!("{}", title);
+    }
+    ProgressHandle {
+        state,
+        window: window.unwrap_or(ProgressWindow::Printed),
+    }
+}
+
+impl ProgressHandle {
+    /// Show how far along the operation is, `None` if that is not known, and what it is doing.
+    pub fn update(&mut self, percent: Option<u8>, text: &str) {
+        let percent = percent.map(|p| p.min(100));
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        if state.percent == percent && state.text == text {
+            return;
+        }
+        let text_changed = state.text != text;
+        state.percent = percent;
+        state.text = text.to_string();
+        #[cfg(all(windows, feature = "gui"))]
+        {
+            state.changed = true;
+        }
+
+        match &mut self.window {
+            #[cfg(all(windows, feature = "gui"))]
+            ProgressWindow::TaskDialog(_) => {}
+            #[cfg(all(unix, not(target_os = "macos"), feature = "gui"))]
+            ProgressWindow::Zenity(child) => {
+                use std::io::Write;
+                if let Some(stdin) = &mut child.stdin {
+                    let line = match percent {
+                        Some(percent) => format!("{}\n# {}\n", percent, escape_markup(&state.text)),
+                        None => format!("# {}\n", escape_markup(&state.text)),
+                    };
+                    let _ = stdin.write_all(line.as_bytes()).and_then(|_| stdin.flush());
+                }
+            }
+            // Only as often as there is something new to say
+            ProgressWindow::Printed => if text_changed {
+                match percent {
+                    Some(percent) => println!("{}: {} ({}%)", state.title, state.text, percent),
+                    None => println!("{}: {}", state.title, state.text),
+                }
+            }
+        }
+    }
+
+    /// Whether the user closed the window, the operation should then be stopped.
+    pub fn is_cancelled(&mut self) -> bool {
+        #[cfg(all(unix, not(target_os = "macos"), feature = "gui"))]
+        if let ProgressWindow::Zenity(child) = &mut self.window {
+            // Exits with a failure status when cancelled, only OK once at 100%
+            if let Ok(Some(status)) = child.try_wait() {
+                if let Ok(mut state) = self.state.lock() {
+                    state.cancelled |= !status.success();
+                }
+            }
+        }
+        self.state.lock().map(|s| s.cancelled).unwrap_or(false)
+    }
+
+    /// Close the window, as when the handle is dropped.
+    pub fn close(self) {}
+}
+
+impl Drop for ProgressHandle {
+    fn drop(&mut self) {
+        #[cfg(all(windows, feature = "gui"))]
+        if let Ok(mut state) = self.state.lock() {
+            state.closed = true;
+        }
+        match std::mem::replace(&mut self.window, ProgressWindow::Printed) {
+            #[cfg(all(windows, feature = "gui"))]
+            ProgressWindow::TaskDialog(thread) => {
+                let _ = thread.join();
+            }
+            #[cfg(all(unix, not(target_os = "macos"), feature = "gui"))]
+            ProgressWindow::Zenity(mut child) => {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+            ProgressWindow::Printed => {}
+        }
+    }
+}
+
+/// Show the progress with `zenity`, `None` if it is not installed.
+#[cfg(all(unix, not(target_os = "macos"), feature = "gui"))]
+fn show_progress(title: &str, _: &std::sync::Arc<Mutex<ProgressState>>) -> Option<ProgressWindow> {
+    use std::process::Stdio;
+    let text_arg = format!("--text={}", escape_markup(title));
+    let child = dialog_tool_command("zenity")
+        .args(["--progress", "--width=400", text_arg.as_str()])
+        .stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::null())
+        .spawn().ok()?;
+    Some(ProgressWindow::Zenity(child))
+}
+
+#[cfg(any(target_os = "macos", not(feature = "gui")))]
+fn show_progress(_: &str, _: &std::sync::Arc<Mutex<ProgressState>>) -> Option<ProgressWindow> {
+    None
+}
+
+/// Show the progress in a task dialog on a thread of its own, `None` if task dialogs are not
+/// available, see [`task_dialog`]. The dialog checks for changes to the progress on a timer.
+#[cfg(all(windows, feature = "gui"))]
+fn show_progress(title: &str, state: &std::sync::Arc<Mutex<ProgressState>>) -> Option<ProgressWindow> {
+    use std::ffi::OsStr;
+    use std::iter::once;
+    use std::mem::{size_of, zeroed};
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr::null_mut;
+    use std::sync::Arc;
+    use winapi::ctypes::c_int;
+    use winapi::shared::basetsd::LONG_PTR;
+    use winapi::shared::minwindef::{BOOL, LPARAM, UINT, WPARAM};
+    use winapi::shared::windef::HWND;
+    use winapi::shared::winerror::{HRESULT, S_OK};
+    use winapi::um::commctrl::{TASKDIALOGCONFIG, TASKDIALOGCONFIG_u1, TDCBF_CANCEL_BUTTON,
+                               TDE_CONTENT, TDF_ALLOW_DIALOG_CANCELLATION, TDF_CALLBACK_TIMER,
+                               TDF_SHOW_MARQUEE_PROGRESS_BAR, TDF_SHOW_PROGRESS_BAR,
+                               TDF_USE_HICON_MAIN, TDM_CLICK_BUTTON, TDM_SET_ELEMENT_TEXT,
+                               TDM_SET_MARQUEE_PROGRESS_BAR, TDM_SET_PROGRESS_BAR_MARQUEE,
+                               TDM_SET_PROGRESS_BAR_POS, TDN_BUTTON_CLICKED, TDN_TIMER};
+    use winapi::um::winuser::{DestroyIcon, IDCANCEL, SendMessageW};
+    type TaskDialogIndirect = unsafe extern "system" fn(*const TASKDIALOGCONFIG, *mut c_int,
+                                                        *mut c_int, *mut BOOL) -> HRESULT;
+
+    /// Brings the dialog up to date with the progress, closing it once the handle is dropped.
+    unsafe extern "system" fn on_event(window: HWND, event: UINT, button: WPARAM, _: LPARAM,
+                                       data: LONG_PTR) -> HRESULT {
+        let state = &*(data as *const Mutex<ProgressState>);
+        match event {
+            TDN_TIMER => {
+                // Not locked while sending, the dialog may call back on this thread
+                let (closed, update) = match state.lock() {
+                    Ok(mut state) => {
+                        let update = state.changed.then(|| (state.percent, state.text.clone()));
+                        state.changed = false;
+                        (state.closed, update)
+                    }
+                    Err(_) => (true, None),
+                };
+                if closed {
+                    SendMessageW(window, TDM_CLICK_BUTTON, IDCANCEL as WPARAM, 0);
+                } else if let Some((percent, text)) = update {
+                    match percent {
+                        Some(percent) => {
+                            SendMessageW(window, TDM_SET_MARQUEE_PROGRESS_BAR, 0, 0);
+                            SendMessageW(window, TDM_SET_PROGRESS_BAR_POS, percent as WPARAM, 0);
+                        }
+                        None => {
+                            SendMessageW(window, TDM_SET_MARQUEE_PROGRESS_BAR, 1, 0);
+                            SendMessageW(window, TDM_SET_PROGRESS_BAR_MARQUEE, 1, 0);
+                        }
+                    }
+                    let wide: Vec<u16> = OsStr::new(&text).encode_wide().chain(once(0)).collect();
+                    SendMessageW(window, TDM_SET_ELEMENT_TEXT, TDE_CONTENT as WPARAM, wide.as_ptr() as LPARAM);
+                }
+            }
+            TDN_BUTTON_CLICKED if button as c_int == IDCANCEL => {
+                if let Ok(mut state) = state.lock() {
+                    state.cancelled |= !state.closed;
+                }
+            }
+            _ => {}
+        }
+        S_OK
+    }
+
+    // Checked before showing it, so the progress can be printed instead
+    unsafe {
+        let comctl = libloading::Library::new("comctl32.dll").ok()?;
+        comctl.get::<TaskDialogIndirect>(b"TaskDialogIndirect\0").ok()?;
+    }
+
+    let title: Vec<u16> = OsStr::new(title).encode_wide().chain(once(0)).collect();
+    let window_title: Vec<u16> = OsStr::new(&dialog_title()).encode_wide().chain(once(0)).collect();
+    let state = Arc::clone(state);
+    let thread = std::thread::spawn(move || unsafe {
+        let comctl = match libloading::Library::new("comctl32.dll") {
+            Ok(comctl) => comctl,
+            Err(_) => return,
+        };
+        let task_dialog: libloading::Symbol<TaskDialogIndirect> = match comctl.get(b"TaskDialogIndirect\0") {
+            Ok(task_dialog) => task_dialog,
+            Err(_) => return,
+        };
+
+        // There must be content for it to be changed later
+        let content: Vec<u16> = OsStr::new(" ").encode_wide().chain(once(0)).collect();
+        let mut config: TASKDIALOGCONFIG = zeroed();
+        config.cbSize = size_of::<TASKDIALOGCONFIG>() as UINT;
+        config.dwFlags = TDF_ALLOW_DIALOG_CANCELLATION | TDF_CALLBACK_TIMER
+            | TDF_SHOW_PROGRESS_BAR | TDF_SHOW_MARQUEE_PROGRESS_BAR;
+        config.dwCommonButtons = TDCBF_CANCEL_BUTTON;
+        config.pszWindowTitle = window_title.as_ptr();
+        config.pszMainInstruction = title.as_ptr();
+        config.pszContent = content.as_ptr();
+        config.pfCallback = Some(on_event);
+        config.lpCallbackData = Arc::as_ptr(&state) as LONG_PTR;
+        let icon = load_dialog_icon();
+        if !icon.is_null() {
+            config.dwFlags |= TDF_USE_HICON_MAIN;
+            let mut main_icon: TASKDIALOGCONFIG_u1 = zeroed();
+            *main_icon.hMainIcon_mut() = icon;
+            config.u1 = main_icon;
+        }
+
+        task_dialog(&config, null_mut(), null_mut(), null_mut());
+        if !icon.is_null() {
+            DestroyIcon(icon);
+        }
+    });
+    Some(ProgressWindow::TaskDialog(thread))
+}
+
+#[cfg(all(test, unix, not(target_os = "macos"), feature = "gui"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zenity_cancel_is_not_a_failure() {
+        assert!(!zenity_failed(Some(0), ""));
+        assert!(!zenity_failed(Some(1), ""));
+        assert!(!zenity_failed(Some(1), "Gtk-Message: GtkDialog mapped without a transient parent"));
+        assert!(zenity_failed(Some(1), "(zenity:4242): Gtk-WARNING **: cannot open display: :0"));
+        assert!(zenity_failed(Some(255), ""));
+        assert!(zenity_failed(None, ""));
+    }
+}
@@ -0,0 +1,72 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// How long fetching a small document, such as the update feed, may take.
+const FETCH_TIMEOUT_SECS: &str = "15";
+
+/// How long downloading a file, such as an update package, may take.
+const DOWNLOAD_TIMEOUT_SECS: &str = "1800";
+
+/// Keeps curl from fetching anything, or following a redirect, over a protocol other than https.
+const HTTPS_ONLY: [&str; 4] = ["--proto", "=https", "--proto-redir", "=https"];
+
+/// Whether the URL is an https one, the only ones the launcher downloads from.
+pub fn is_https(url: &str) -> bool {
+    url.get(..8).map_or(false, |scheme| scheme.eq_ignore_ascii_case("https://"))
+}
+
+/// Fetch the URL with `curl`, which Windows 10 and later, macOS and most Linux distributions have.
+pub fn fetch(url: &str) -> Option<String> {
+    let output = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--location", "--max-time", FETCH_TIMEOUT_SECS])
+        .args(HTTPS_ONLY)
+        .arg(url)
+        .stdin(Stdio::null())
+        .output();
+    match output {
+        Ok(output) if output.status.success() => String::from_utf8(output.stdout).ok(),
+        Ok(output) => {
+            debug!("launcher", "curl failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+            None
+        }
+        Err(e) => {
+            debug!("launcher", "Failed to run curl: {}", e);
+            None
+        }
+    }
+}
+
+/// Download the URL to the file with `curl`, creating its folder if needed.
+pub fn download_to(url: &str, file: &Path) -> Result<(), String> {
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let output = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--location", "--max-time", DOWNLOAD_TIMEOUT_SECS])
+        .args(HTTPS_ONLY)
+        .arg("--output")
+        .arg(file)
+        .arg(url)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("failed to run curl: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!("curl failed: {}", String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_https_urls_are_used() {
+        assert!(is_https("https://example.com/feed.json"));
+        assert!(is_https("HTTPS://example.com/feed.json"));
+        assert!(!is_https("http://example.com/feed.json"));
+        assert!(!is_https("file:///tmp/feed.json"));
+    }
+}
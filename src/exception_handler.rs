@@ -0,0 +1,159 @@
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use jni::{JNIEnv, NativeMethod};
+use jni::objects::{JClass, JObject, JString};
+
+use crate::detailed_message;
+use crate::locale_handler::text;
+
+/// How many nested causes to follow when describing a Java exception.
+const MAX_CAUSE_DEPTH: usize = 8;
+
+/// The compiled `why.UncaughtHandler`, see `shim/why/UncaughtHandler.java`.
+const UNCAUGHT_HANDLER_CLASS: &[u8] = include_bytes!("../shim/why/UncaughtHandler.class");
+
+/// Whether uncaught exceptions reported by the handler should be shown in a dialog.
+static SHOW_CRASH_DIALOG: AtomicBool = AtomicBool::new(true);
+
+/// Define the `why.UncaughtHandler` shim in the system class loader and install it as the
+/// default uncaught exception handler.<br>
+/// Uncaught exceptions are then printed to the launcher's output, and optionally shown in a dialog.
+pub fn install_uncaught_handler(env: &JNIEnv, show_dialog: bool) -> jni::errors::Result<()> {
+    SHOW_CRASH_DIALOG.store(show_dialog, Ordering::Relaxed);
+
+    let loader = env.call_static_method("java/lang/ClassLoader", "getSystemClassLoader",
+                                        "()Ljava/lang/ClassLoader;", &[])?.l()?;
+    let class = env.define_class("why/UncaughtHandler", loader, UNCAUGHT_HANDLER_CLASS)?;
+    env.register_native_methods(class, &[NativeMethod {
+        name: "report".into(),
+        sig: "(Ljava/lang/String;)V".into(),
+        fn_ptr: report_uncaught as *mut c_void,
+    }])?;
+    env.call_static_method(class, "install", "()V", &[])?;
+
+    Ok(())
+}
+
+/// Native half of `why.UncaughtHandler.report`.
+extern "system" fn report_uncaught(env: JNIEnv, _class: JClass, details: JString) {
+    let details: String = match env.get_string(details) {
+        Ok(s) => s.into(),
+        Err(_) => return,
+    };
+
+    info!("jni", "{}", details);
+
+    if SHOW_CRASH_DIALOG.load(Ordering::Relaxed) {
+        detailed_message(&text("app.unexpected_error"), details.as_str());
+    }
+}
+
+/// Take the pending Java exception, if there is one, and describe its class,
+/// message and stack trace in the same form as `Throwable.printStackTrace`.<br>
+/// The exception is cleared so that the JVM can still be used afterwards.
+pub fn take_exception(env: &JNIEnv) -> Option<String> {
+    if !env.exception_check().unwrap_or(false) {
+        return None;
+    }
+
+    let throwable = env.exception_occurred().ok()?;
+
+    // Also prints to stderr, which JNI does not do by itself
+    let _ = env.exception_describe();
+    let _ = env.exception_clear();
+
+    Some(describe_throwable(env, JObject::from(throwable)))
+}
+
+/// Describe a throwable and its causes.
+fn describe_throwable(env: &JNIEnv, throwable: JObject) -> String {
+    let mut out = String::new();
+    let mut current = throwable;
+
+    for depth in 0..MAX_CAUSE_DEPTH {
+        if current.is_null() {
+            break;
+        }
+
+        if depth > 0 {
+            out.push_str("Caused by: ");
+        }
+
+        // Class and message
+        let class_name = env.call_method(current, "getClass", "()Ljava/lang/Class;", &[])
+            .and_then(|c| c.l())
+            .ok()
+            .and_then(|c| call_string_method(env, c, "getName"))
+            .unwrap_or_else(|| "<unknown exception>".to_string());
+        out.push_str(class_name.as_str());
+        if let Some(msg) = call_string_method(env, current, "getMessage") {
+            out.push_str(": ");
+            out.push_str(msg.as_str());
+        }
+        out.push('\n');
+
+        // Stack trace
+        for element in get_stack_trace(env, current) {
+            out.push_str("\tat ");
+            out.push_str(element.as_str());
+            out.push('\n');
+        }
+
+        // Follow the cause, stopping on self-referencing causes
+        let cause = env.call_method(current, "getCause", "()Ljava/lang/Throwable;", &[])
+            .and_then(|c| c.l());
+        match cause {
+            Ok(cause) if !env.is_same_object(cause, current).unwrap_or(true) => current = cause,
+            _ => {
+                let _ = env.exception_clear();
+                break;
+            }
+        }
+    }
+
+    out
+}
+
+/// Get each `StackTraceElement` of a throwable as a string.
+fn get_stack_trace(env: &JNIEnv, throwable: JObject) -> Vec<String> {
+    let mut out: Vec<String> = vec![];
+
+    let trace = env.call_method(throwable, "getStackTrace",
+                                "()[Ljava/lang/StackTraceElement;", &[])
+        .and_then(|t| t.l());
+    if let Ok(trace) = trace {
+        if trace.is_null() {
+            return out;
+        }
+        let len = env.get_array_length(*trace).unwrap_or(0);
+        for i in 0..len {
+            if let Ok(element) = env.get_object_array_element(*trace, i) {
+                if let Some(s) = call_string_method(env, element, "toString") {
+                    out.push(s);
+                }
+                let _ = env.delete_local_ref(element);
+            }
+        }
+    } else {
+        let _ = env.exception_clear();
+    }
+
+    out
+}
+
+/// Call a no-arg method returning a `String`, returning `None` for `null` or if it throws.
+fn call_string_method(env: &JNIEnv, obj: JObject, method: &str) -> Option<String> {
+    let value = env.call_method(obj, method, "()Ljava/lang/String;", &[])
+        .and_then(|v| v.l());
+    match value {
+        Ok(value) if !value.is_null() => {
+            env.get_string(JString::from(value)).ok().map(|s| s.into())
+        }
+        Ok(_) => None,
+        Err(_) => {
+            let _ = env.exception_clear();
+            None
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use std::{env, fs, panic, process};
+use std::backtrace::Backtrace;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use jni::sys::jint;
+
+use crate::display_handler::{Answer, copy_to_clipboard, printed_headless_error, question, show_in_folder};
+use crate::file_handler::get_log_dir;
+use crate::locale_handler::{text, text_with};
+#[cfg(windows)]
+use crate::output_handler::wait_for_console_close;
+use crate::process_handler::remove_pid_file;
+#[cfg(feature = "updater")]
+use crate::update_handler::roll_back_failed_update;
+use crate::{detailed_message, message};
+
+/// How old a JVM crash log may be and still be reported when the launcher starts.
+const CRASH_LOG_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Exit status used when the launcher failed for a reason without an [`ExitCode`] of its own,
+/// such as a failed setup command. Matches the `java` launcher.
+pub const FAILURE_EXIT_CODE: i32 = 1;
+
+/// Exit statuses of the launcher failing to start the app, so that installers and wrapper scripts
+/// can tell why. They are from 64 on, where `sysexits.h` starts, as apps rarely exit with those,
+/// and stay the same between versions of the launcher.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    /// `launcher.ini` is missing, names no main class or module and no class or module path,
+    /// or asks for a newer `min_java` than its `max_java`.
+    ConfigInvalid = 64,
+    /// No Java installation the app can run on was found.
+    NoJava = 65,
+    /// Java was found, but the JVM could not be created or the launcher could not attach to it.
+    JvmFailed = 66,
+    /// The app's main method threw.
+    MainThrew = 67,
+    /// The main class or module was not found, or has no main method the launcher can call.
+    MainNotFound = 68,
+    /// The `preflight_class` threw, a `pre-launch` or `first-run` hook failed, or the user chose not to run
+    /// the app translocated on macOS, so the app was not started.
+    PreflightFailed = 69,
+    /// The launcher itself crashed, `EX_SOFTWARE` of `sysexits.h`, so that it can be told apart
+    /// from the app failing.
+    LauncherCrashed = 70,
+    /// The app was refused by the signature, integrity or permission check.
+    Refused = 71,
+    /// The user gave up on the launch, such as while Java was slow to start or an update was applied.
+    Cancelled = 72,
+}
+
+impl ExitCode {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+}
+
+/// The exit status that makes the launcher start the app again, see [`set_restart_exit_code`].
+static RESTART_EXIT_CODE: Mutex<Option<i32>> = Mutex::new(None);
+
+/// Set the exit status that makes the launcher start the app again once it has exited,
+/// `None` disables restarting.
+pub fn set_restart_exit_code(code: Option<i32>) {
+    if let Ok(mut c) = RESTART_EXIT_CODE.lock() {
+        *c = code;
+    }
+}
+
+/// Passed to the JVM as its `exit` hook, the JVM calls this with the status it exits with when
+/// the app calls `System.exit` or `Runtime.halt`, instead of exiting the process itself.<br>
+/// See <https://docs.oracle.com/en/java/javase/17/docs/specs/jni/invocation.html#jni_createjavavm>
+pub extern "system" fn jvm_exit_hook(code: jint) {
+    exit(code)
+}
+
+/// Exit the launcher with the exit status of the app.<br>
+/// If the status matches the restart exit code, a new instance of the launcher is started first
+/// with the same arguments, which reads the config and looks for Java again, and the launcher exits
+/// with its status once it has exited.
+pub fn exit(code: i32) -> ! {
+    let restart_code = RESTART_EXIT_CODE.lock().ok().and_then(|c| *c);
+    let code = if restart_code == Some(code) {
+        info!("launcher", "App requested a restart");
+        restart().unwrap_or(code)
+    } else if code == 0 && printed_headless_error() {
+        // Headless launches report problems through the exit status, as nobody saw a dialog
+        FAILURE_EXIT_CODE
+    } else {
+        code
+    };
+
+    // The previous version is started next time
+    #[cfg(feature = "updater")]
+    if code != 0 {
+        roll_back_failed_update();
+    }
+
+    remove_pid_file();
+    info!("launcher", "Launcher exiting with code {}", code);
+    #[cfg(windows)]
+    wait_for_console_close();
+    process::exit(code)
+}
+
+/// Report a panic of the launcher instead of exiting silently, as a GUI launch has nowhere to
+/// print it: its backtrace is logged, the user is told the launcher crashed and where the log is,
+/// and the launcher exits with [`ExitCode::LauncherCrashed`]. Release builds abort on panics,
+/// the hook still runs first.
+pub fn install_panic_hook() {
+    panic::set_hook(Box::new(|info| {
+        let details = format!("{}\n\n{}", info, Backtrace::force_capture());
+        info!("launcher", "Launcher crashed: {}", details);
+        detailed_message(&text("launcher.crashed"), &details);
+        process::exit(ExitCode::LauncherCrashed.code());
+    }));
+}
+
+/// Start a new instance of the launcher, forwarding the launch args, and wait for it to exit, so that
+/// whoever started the launcher gets the status of the restarted app rather than the restart exit code.<br>
+/// Returns its exit status, `None` if it could not be started.
+fn restart() -> Option<i32> {
+    let status = env::current_exe()
+        .and_then(|exe| Command::new(exe).args(env::args_os().skip(1)).status());
+    match status {
+        // Killed by a signal
+        Ok(status) => Some(status.code().unwrap_or(FAILURE_EXIT_CODE)),
+        Err(e) => {
+            info!("launcher", "{:?}", e);
+            message(&text("app.restart_failed"));
+            None
+        }
+    }
+}
+
+/// The directory the JVM writes its crash logs to, `crashes` in the log directory, set with
+/// [`crash_log_opt`]. Only the app's JVMs write to it, unlike the working and temporary directories
+/// the JVM uses by default, so the crash logs in it are those of the app.
+fn crash_log_dir() -> Option<PathBuf> {
+    Some(get_log_dir()?.join("crashes"))
+}
+
+/// The JVM option making the JVM write its crash log, `hs_err_pid<pid>.log`, to the [`crash_log_dir`],
+/// which is created for it.
+pub fn crash_log_opt() -> Option<String> {
+    let dir = crash_log_dir()?;
+    if let Err(e) = fs::create_dir_all(&dir) {
+        info!("launcher", "Failed to create the crash log dir {:?}: {}", dir, e);
+        return None;
+    }
+    Some(format!("-XX:ErrorFile={}", dir.join("hs_err_pid%p.log").display()))
+}
+
+/// Report the crash log of the JVM with the process id, if it wrote one, moving it to the log directory
+/// and telling the user about it.<br>
+/// When the JVM runs in the launcher's process a crash takes the launcher with it,
+/// so this is done on the next start instead, see [`report_recent_crash_logs`].
+pub fn report_crash_logs(pid: u32) {
+    if let Some(dir) = crash_log_dir() {
+        let crash_log = dir.join(format!("hs_err_pid{}.log", pid));
+        if crash_log.is_file() {
+            show_crash_log(&move_to_log_dir(crash_log));
+        }
+    }
+}
+
+/// Report the crash logs recent launches left behind in the [`crash_log_dir`], see [`report_crash_logs`].
+/// Older ones are moved to the log directory without telling the user.
+pub fn report_recent_crash_logs() {
+    let entries = match crash_log_dir().and_then(|dir| fs::read_dir(dir).ok()) {
+        Some(entries) => entries,
+        None => return,
+    };
+    let since = SystemTime::now() - CRASH_LOG_MAX_AGE;
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !(name.starts_with("hs_err_pid") && name.ends_with(".log")) {
+            continue;
+        }
+        let recent = entry.metadata().and_then(|m| m.modified()).map_or(false, |m| m >= since);
+        let crash_log = move_to_log_dir(entry.path());
+        if recent {
+            show_crash_log(&crash_log);
+        }
+    }
+}
+
+/// Move the file into the log directory, returning where it ended up.
+fn move_to_log_dir(path: PathBuf) -> PathBuf {
+    if let (Some(log_dir), Some(name)) = (get_log_dir(), path.file_name()) {
+        let target = log_dir.join(name);
+        if fs::create_dir_all(&log_dir).and_then(|_| fs::rename(&path, &target)).is_ok() {
+            return target;
+        }
+        // Renaming fails across drives
+        if fs::copy(&path, &target).is_ok() {
+            let _ = fs::remove_file(&path);
+            return target;
+        }
+    }
+    path
+}
+
+/// Tell the user where the crash log is, offering to show it.
+fn show_crash_log(crash_log: &PathBuf) {
+    info!("launcher", "Found JVM crash log {:?}", crash_log);
+    let answer = question(&text_with("app.crashed", &[&crash_log.display().to_string()]));
+    match answer {
+        Answer::Yes => show_in_folder(crash_log),
+        Answer::No => {
+            copy_to_clipboard(&crash_log.display().to_string());
+        }
+        Answer::Cancel => {}
+    }
+}
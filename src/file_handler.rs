@@ -1,14 +1,24 @@
 use core::option::Option;
 use core::option::Option::{None, Some};
 use std::env;
+use std::ffi::OsString;
 use std::fmt::Error;
 use std::fs::{File};
 use std::io::{Read};
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 use config::{Config, FileFormat};
 use walkdir::{DirEntry, WalkDir};
 use zip::ZipArchive;
 use crate::{LauncherConfig, LaunchOpts};
+use crate::cache_handler::{cache_class_version, cache_last_jvm, forget_last_jvm, LastJvm, read_cached_class_version,
+                           read_last_jvm};
+use crate::install_handler::{data_dir_for, install_scope};
+use crate::manifest_handler::read_jar_manifest;
+use crate::permission_handler::check_runtime_permissions;
+use crate::zip_handler::{open_zip, read_nested_zip_entry, read_zip_entry, zip_has_entry};
 
 /// The fallback locations to look for a Java installation, drawn from common install locations.
 const JVM_LOC_QUERIES: &'static [&str] = &[
@@ -23,65 +33,354 @@ const JVM_LOC_QUERIES: &'static [&str] = &[
     "C:/Program Files/Eclipse Adoptium/JDK",
     "C:/Program Files/Eclipse Adoptium/JRE",
     "C:/Program Files/Azul Systems/Zulu",
+    "C:/Program Files/Zulu",
+    "C:/Program Files/Microsoft",
+    "C:/Program Files/BellSoft",
+    "C:/Program Files/Amazon Corretto",
 ];
 
+/// The modules JavaFX consists of, without the `javafx.` prefix.
+const JAVAFX_MODULES: &'static [&str] = &["base", "controls", "fxml", "graphics", "media", "swing", "web"];
+
 #[cfg(windows)]
 /// Name of the dynamic Java library file.
 const DYN_JAVA_LIB: &str = "jvm.dll";
-#[cfg(macos)]
+#[cfg(target_os = "macos")]
 /// Name of the dynamic Java library file.
 const DYN_JAVA_LIB: &str = "libjvm.dylib";
-#[cfg(linux)]
+#[cfg(all(unix, not(target_os = "macos")))]
 /// Name of the dynamic Java library file.
 const DYN_JAVA_LIB: &str = "libjvm.so";
 
+#[cfg(windows)]
+/// The environment variable the OS searches for dynamic libraries.
+pub const LIBRARY_PATH_VAR: &str = "PATH";
+#[cfg(target_os = "macos")]
+/// The environment variable the OS searches for dynamic libraries.
+pub const LIBRARY_PATH_VAR: &str = "DYLD_LIBRARY_PATH";
+#[cfg(all(unix, not(target_os = "macos")))]
+/// The environment variable the OS searches for dynamic libraries.
+pub const LIBRARY_PATH_VAR: &str = "LD_LIBRARY_PATH";
+
+#[cfg(windows)]
+/// The separator of the paths in the class path and module path Java takes.
+pub const PATH_SEPARATOR: &str = ";";
+#[cfg(not(windows))]
+/// The separator of the paths in the class path and module path Java takes.
+pub const PATH_SEPARATOR: &str = ":";
+
+/// The name of the app, taken from the name of the launcher executable.
+pub fn get_app_name() -> String {
+    env::current_exe().ok()
+        .and_then(|p| p.file_stem().map(|s| s.to_string_lossy().to_string()))
+        .unwrap_or_else(|| "JavaLauncher".to_string())
+}
+
+/// The directory set with [`set_user_data_dir`].
+static USER_DATA_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Keep the launcher's files in the given directory instead of the one for the install scope,
+/// such as for tests, which should not leave files in the user's profile.
+pub fn set_user_data_dir(data_dir: Option<PathBuf>) {
+    if let Ok(mut d) = USER_DATA_DIR.lock() {
+        *d = data_dir;
+    }
+}
+
+/// The directory the launcher keeps its files in, named after the app, per-user unless
+/// the app is installed per-machine, see [`data_dir_for`], or as set with [`set_user_data_dir`].<br>
+/// The directory is not created.
+pub fn get_user_data_dir() -> Option<PathBuf> {
+    if let Some(data_dir) = USER_DATA_DIR.lock().ok().and_then(|d| d.clone()) {
+        return Some(data_dir);
+    }
+    data_dir_for(install_scope())
+}
+
+/// The working directory the launcher was started in, see [`set_launch_dir`].
+static LAUNCH_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Remember the working directory the launcher was started in, before it changes to its own directory.
+pub fn set_launch_dir(launch_dir: Option<PathBuf>) {
+    if let Ok(mut d) = LAUNCH_DIR.lock() {
+        *d = launch_dir;
+    }
+}
+
+/// The working directory the launcher was started in, if it was set with [`set_launch_dir`].
+pub fn get_launch_dir() -> Option<PathBuf> {
+    LAUNCH_DIR.lock().ok().and_then(|d| d.clone())
+}
+
+/// The working directory the app runs in, the one the launcher was started in for `preserve_cwd`, or `None`
+/// for the launcher's directory. The launcher only changes to it once the Java is chosen, as the config's
+/// relative paths, such as `jvm_allowed_paths` and `launch_options`, are in the launcher's directory.
+pub fn app_working_dir(config: &LauncherConfig) -> Option<PathBuf> {
+    get_launch_dir().filter(|_| config.preserve_cwd)
+}
+
+/// The directory set with `log_dir`, see [`set_log_dir`].
+static LOG_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Use the given directory for logs instead of the one in the user data dir.
+pub fn set_log_dir(log_dir: Option<PathBuf>) {
+    if let Ok(mut d) = LOG_DIR.lock() {
+        *d = log_dir;
+    }
+}
+
+/// The directory the launcher keeps logs in, within the user data dir unless set otherwise.<br>
+/// The directory is not created.
+pub fn get_log_dir() -> Option<PathBuf> {
+    if let Some(log_dir) = LOG_DIR.lock().ok().and_then(|d| d.clone()) {
+        return Some(log_dir);
+    }
+    Some(get_user_data_dir()?.join("logs"))
+}
+
+/// The file the app may write extra JVM options to, which are used from the next launch on.
+/// Combined with `restart_exit_code` this lets the app change its own JVM options,
+/// such as the maximum heap size.
+pub fn get_relaunch_file() -> Option<PathBuf> {
+    Some(get_user_data_dir()?.join(get_app_name() + ".relaunch"))
+}
+
 /// Try and find the main class from the given classpath (without resolving it)
-/// and return its required Java version.
-pub fn get_java_version_of_main(launch_cfg: &LauncherConfig) -> Option<u16> {
+/// and return its required Java version.<br>
+/// For multi-release jars this is the lowest Java version able to run the variant of the
+/// class it would load.
+pub fn get_java_version_of_main(launch_cfg: &LauncherConfig) -> Option<ClassVersion> {
     // Not enough info provided
     if !launch_cfg.validate() {
         return None
     }
+    let main_class = launch_cfg.main_class.as_ref()?;
+    let class_path = main_class.replace(".", "/") + ".class";
 
     // Go over the classpath
-    return if let Some(classpath) = &launch_cfg.classpath {
-        let jars: Vec<&str> = classpath.split(";").collect();
-        for jar_str in jars {
-            // Open the jar
-            let jar_path = Path::new(jar_str);
-
-            if let Ok(jar) = File::open(jar_path) {
-                if jar_path.is_dir() {
-                    if let Ok(mut zip_jar) = ZipArchive::new(jar) {
-                        // Find main class
-                        if let Ok(class) = zip_jar.by_name((launch_cfg.main_class
-                            .as_ref().unwrap().to_string()
-                            .replace(".", "/") + ".class").as_str()) {
-                            // Found main class, get the version
-                            return read_class_version_to_java(class)
-                        }
+    let classpath = launch_cfg.classpath.as_ref()?;
+    let entries: Vec<&Path> = classpath.split(";").map(Path::new).collect();
+
+    // Reading the jars again is only needed when they changed
+    if let Some(version) = read_cached_class_version(main_class, &entries) {
+        return Some(version)
+    }
+    let version = find_version_of_main(&entries, main_class, &class_path)?;
+    cache_class_version(main_class, &entries, version);
+    Some(version)
+}
+
+/// The required Java version of the main class, found on the classpath.
+fn find_version_of_main(entries: &[&Path], main_class: &str, class_path: &str) -> Option<ClassVersion> {
+    let jar_path = entries[find_class(entries, class_path, main_class)?];
+
+    if jar_path.is_dir() {
+        // Exploded classes, Java only loads versioned classes from jars
+        let class_file = File::open(jar_path.join(class_path)).ok()?;
+        return read_class_version_to_java(class_file)
+    }
+
+    // Found main class, get the version
+    let base = read_class_version_to_java(read_zip_entry(jar_path, class_path)?.as_slice());
+    let manifest = read_jar_manifest(jar_path);
+    let multi_release = manifest.as_ref().map(|m| m.is_multi_release()).unwrap_or(false);
+    // Only listing the versioned classes needs the whole jar
+    let version = match multi_release.then(|| open_zip(jar_path)).flatten() {
+        Some(mut zip_jar) => versioned_class_requirement(&mut zip_jar, class_path, base),
+        None => base,
+    };
+
+    // Spring Boot jars start their own launcher, which then loads the app's classes
+    match manifest.as_ref().and_then(|m| m.get("Start-Class")) {
+        Some(start_class) => {
+            let start = spring_boot_class_version(jar_path, start_class.trim());
+            match (version, start) {
+                (Some(v), Some(s)) => Some(ClassVersion {
+                    java: v.java.max(s.java),
+                    preview: v.preview || s.preview,
+                }),
+                (v, s) => v.or(s),
+            }
+        }
+        None => version,
+    }
+}
+
+/// Find the first classpath entry, a jar or a directory of classes, with the class, as Java would
+/// load it from. Jars are searched concurrently, those named like the class's package first,
+/// as opening each in turn is slow for long classpaths.
+fn find_class(entries: &[&Path], class_path: &str, class_name: &str) -> Option<usize> {
+    // The most specific part of the package, such as `ij` of `ij.ImageJ`
+    let hint = class_name.rsplit('.')
+        .skip(1)
+        .find(|p| p.len() >= 2 && !["com", "org", "net", "app", "main"].contains(p))
+        .unwrap_or(class_name)
+        .to_lowercase();
+    let mut order: Vec<usize> = (0..entries.len()).collect();
+    order.sort_by_key(|&i| {
+        let name = entries[i].file_stem().map(|n| n.to_string_lossy().to_lowercase());
+        !name.map_or(false, |n| n.contains(&hint))
+    });
+
+    let has_class = |entry: &Path| if entry.is_dir() {
+        entry.join(class_path).is_file()
+    } else {
+        zip_has_entry(entry, class_path)
+    };
+
+    let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+        .min(MAX_SCAN_THREADS)
+        .min(entries.len());
+    let next = AtomicUsize::new(0);
+    let found = AtomicUsize::new(usize::MAX);
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                while let Some(&i) = order.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    // An earlier entry has it, which is the one Java would load
+                    if i > found.load(Ordering::Relaxed) {
+                        continue;
                     }
-                } else {
-                    if let Some(class) = find_file_with_path(jar_path, (launch_cfg.main_class
-                        .as_ref().unwrap().to_string()
-                        .replace(".", "/") + ".class").as_str()) {
-                        if let Ok(class_file) = File::open(class) {
-                            // Found main class, get the version
-                            return read_class_version_to_java(class_file)
-                        }
+                    if has_class(entries[i]) {
+                        found.fetch_min(i, Ordering::Relaxed);
                     }
                 }
+            });
+        }
+    });
+
+    match found.into_inner() {
+        usize::MAX => None,
+        i => Some(i),
+    }
+}
+
+/// The version needed for a class of a Spring Boot executable jar, which keeps the app's classes
+/// in `BOOT-INF/classes/` and its libraries as nested jars in `BOOT-INF/lib/`.
+fn spring_boot_class_version(jar_path: &Path, class_name: &str) -> Option<ClassVersion> {
+    let class_path = class_name.replace(".", "/") + ".class";
+    if let Some(class) = read_zip_entry(jar_path, &(SPRING_BOOT_CLASSES.to_string() + &class_path)) {
+        return read_class_version_to_java(class.as_slice());
+    }
+
+    // Only listing the entries needs the whole jar
+    let libs: Vec<String> = open_zip(jar_path)?.file_names()
+        .filter(|n| n.starts_with(SPRING_BOOT_LIB) && n.ends_with(".jar"))
+        .map(String::from)
+        .collect();
+    let class = libs.iter().find_map(|lib| read_nested_zip_entry(jar_path, lib, &class_path))?;
+    read_class_version_to_java(class.as_slice())
+}
+
+/// The version needed for the main class of a multi-release jar. On Java N the class under
+/// `META-INF/versions/<highest version <= N>/` is loaded, falling back to the base class,
+/// so this is the lowest N whose loaded class was compiled for at most N.
+fn versioned_class_requirement<R: Read + std::io::Seek>(jar: &mut ZipArchive<R>, class_path: &str,
+                                                        base: Option<ClassVersion>) -> Option<ClassVersion> {
+    // The release each variant is for, and the version it was compiled for
+    let mut variants: Vec<(u16, ClassVersion)> = vec![];
+    let names: Vec<String> = jar.file_names().map(String::from).collect();
+    for name in names {
+        let release = name.strip_prefix("META-INF/versions/")
+            .and_then(|rest| rest.strip_suffix(class_path))
+            .and_then(|release| release.strip_suffix('/'))
+            .and_then(|release| release.parse::<u16>().ok());
+        if let Some(release) = release {
+            if let Some(version) = jar.by_name(&name).ok().and_then(read_class_version_to_java) {
+                variants.push((release, version));
+            }
+        }
+    }
+    if variants.is_empty() {
+        return base;
+    }
+    variants.sort_by_key(|(release, _)| *release);
+
+    let highest = variants.iter().map(|(r, v)| (*r).max(v.java))
+        .chain(base.map(|b| b.java))
+        .max()?;
+    for java in 1..=highest {
+        let loaded = variants.iter().rev()
+            .find(|(release, _)| *release <= java)
+            .map(|(_, v)| *v)
+            .or(base);
+        if let Some(loaded) = loaded {
+            if loaded.java <= java {
+                return Some(ClassVersion { java, preview: loaded.preview });
             }
         }
-        None
-    } else {
-        None
     }
+
+    variants.last().map(|(_, v)| *v)
+}
+
+/// The Java agents in the agents dir, `agents_dir` or else the `agents` folder next to the
+/// launcher: every jar with a `Premain-Class` that is not listed in `disabled_agents`,
+/// sorted by file name.
+pub fn find_agents(launch_cfg: &LauncherConfig) -> Vec<PathBuf> {
+    let agents_dir = match &launch_cfg.agents_dir {
+        Some(agents_dir) => resolve_path(agents_dir),
+        None => match get_app_dir() {
+            Some(app_dir) => app_dir.join("agents"),
+            None => return vec![],
+        },
+    };
+    let disabled: Vec<&str> = launch_cfg.disabled_agents.iter()
+        .flat_map(|d| d.split(";"))
+        .map(|d| d.trim())
+        .collect();
+
+    let mut agents: Vec<PathBuf> = agents_dir.read_dir()
+        .map(|d| d.flatten().map(|e| e.path()).collect())
+        .unwrap_or_default();
+    agents.retain(|agent| {
+        let name = agent.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        if !name.ends_with(".jar") || !agent.is_file() {
+            return false;
+        }
+        if disabled.contains(&name.as_str()) {
+            debug!("config", "Agent {} is disabled", name);
+            return false;
+        }
+        let is_agent = read_jar_manifest(agent).map_or(false, |m| m.get("Premain-Class").is_some());
+        if !is_agent {
+            info!("config", "Skipping {:?}, it has no Premain-Class", agent);
+        }
+        is_agent
+    });
+    agents.sort_by_key(|a| a.file_name().map(|n| n.to_os_string()));
+    agents
+}
+
+/// The JavaFX modules of a jar, or of the jars in a directory such as the `lib` folder of the
+/// JavaFX SDK. Both the SDK's `javafx.controls.jar` and Maven's `javafx-controls-17-win.jar`
+/// naming is recognized.
+pub fn find_javafx_modules(path: &Path) -> Vec<String> {
+    let names: Vec<String> = if path.is_dir() {
+        path.read_dir().map(|entries| entries.flatten()
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .collect())
+            .unwrap_or_default()
+    } else {
+        path.file_name().map(|n| vec![n.to_string_lossy().to_string()]).unwrap_or_default()
+    };
+
+    names.iter().filter_map(|name| {
+        let name = name.strip_suffix(".jar")?;
+        let module = name.strip_prefix("javafx-").or_else(|| name.strip_prefix("javafx."))?;
+        let module = module.split(|c| c == '-' || c == '.').next()?;
+        if JAVAFX_MODULES.contains(&module) {
+            Some(format!("javafx.{}", module))
+        } else {
+            None
+        }
+    }).collect()
 }
 
 /// Get all valid paths to [`DYN_JAVA_LIB`],
 /// skipping hidden paths.<br>
-/// If [`Config::jvm_path`] is `None`, search the current working directory.
+/// If [`Config::jvm_path`] is `None`, search the launcher's directory, the working directory
+/// unless the app keeps the one it was started in with `preserve_cwd`.
 /// If `Some`, search the given path.<br>
 /// If [`Config::allows_java_location_lookup`] is `true`,
 /// will search [`JVM_LOC_QUERIES`] for a valid path.<br>
@@ -90,14 +389,14 @@ pub fn get_jvm_paths(launch_opts: &LaunchOpts) -> Vec<Box<dyn FnOnce(&LaunchOpts
     let mut jvm_paths: Vec<Box<dyn FnOnce(&LaunchOpts) -> Option<PathBuf>>> = Vec::new();
 
     match &launch_opts.config.jvm_path {
-        // Search current directory
+        // Search the launcher's directory
         None => {
             jvm_paths.push(Box::new(|opts: &LaunchOpts| {
                 let min_java_ver = opts.config.min_java.unwrap_or(0) as i32;
-                if let Ok(c_dir) = env::current_dir() {
-                    let p = valid_path(find_file(c_dir.to_str().unwrap_or(""), DYN_JAVA_LIB));
+                if let Some(c_dir) = get_app_dir() {
+                    let p = valid_path(find_jvm(&c_dir));
                     if let Some(valid_path) = p {
-                        if let Some(compatible) = compatible_java_version(&valid_path, min_java_ver) {
+                        if let Some(compatible) = compatible_java_version(&valid_path, min_java_ver, opts.config.max_java) {
                             if compatible {
                                 return Some(valid_path);
                             }
@@ -112,9 +411,9 @@ pub fn get_jvm_paths(launch_opts: &LaunchOpts) -> Vec<Box<dyn FnOnce(&LaunchOpts
             jvm_paths.push(Box::new(|opts: &LaunchOpts| {
                 let min_java_ver = (&opts.config.min_java).unwrap_or(0) as i32;
                 if let Some(path) = &opts.config.jvm_path {
-                    let p = valid_path(find_file(path, DYN_JAVA_LIB));
+                    let p = valid_path(find_jvm(path));
                     if let Some(valid_path) = p {
-                        if let Some(compatible) = compatible_java_version(&valid_path, min_java_ver) {
+                        if let Some(compatible) = compatible_java_version(&valid_path, min_java_ver, opts.config.max_java) {
                             if compatible {
                                 use dunce::canonicalize;
                                 if let Ok(resolved_path) = canonicalize(&*valid_path) {
@@ -138,7 +437,7 @@ pub fn get_jvm_paths(launch_opts: &LaunchOpts) -> Vec<Box<dyn FnOnce(&LaunchOpts
                 Ok(path) if !path.is_empty() => {
                     let pb = PathBuf::from(path);
                     let min_java_ver = opts.config.min_java.unwrap_or(0) as i32;
-                    if let Some(compatible) = compatible_java_version(&pb, min_java_ver) {
+                    if let Some(compatible) = compatible_java_version(&pb, min_java_ver, opts.config.max_java) {
                         if compatible {
                             return Some(pb);
                         }
@@ -153,13 +452,13 @@ pub fn get_jvm_paths(launch_opts: &LaunchOpts) -> Vec<Box<dyn FnOnce(&LaunchOpts
 
     // Search fallback locations
     if launch_opts.config.allows_java_location_lookup && !jvm_paths.len() > 4 {
-        // Search current directory if we don't have a path
+        // Search the launcher's directory if we don't have a path
         jvm_paths.push(Box::new(|opts: &LaunchOpts| {
-            if let Ok(c_dir) = env::current_dir() {
-                let p = valid_path(find_file(c_dir.to_str().unwrap_or(""), DYN_JAVA_LIB));
+            if let Some(c_dir) = get_app_dir() {
+                let p = valid_path(find_jvm(&c_dir));
                 if let Some(valid_path) = p {
                     let min_java_ver = opts.config.min_java.unwrap_or(0) as i32;
-                    if let Some(compatible) = compatible_java_version(&valid_path, min_java_ver) {
+                    if let Some(compatible) = compatible_java_version(&valid_path, min_java_ver, opts.config.max_java) {
                         if compatible {
                             return Some(valid_path);
                         }
@@ -172,10 +471,10 @@ pub fn get_jvm_paths(launch_opts: &LaunchOpts) -> Vec<Box<dyn FnOnce(&LaunchOpts
         // Search common install locations
         for loc in JVM_LOC_QUERIES.iter() {
             jvm_paths.push(Box::new(|opts: &LaunchOpts| {
-                let p = valid_path(find_file(process_path(loc).as_str(), DYN_JAVA_LIB));
+                let p = valid_path(find_jvm(resolve_path(loc)));
                 if let Some(valid_path) = p {
                     let min_java_ver = opts.config.min_java.unwrap_or(0) as i32;
-                    if let Some(compatible) = compatible_java_version(&valid_path, min_java_ver) {
+                    if let Some(compatible) = compatible_java_version(&valid_path, min_java_ver, opts.config.max_java) {
                         if compatible {
                             return Some(valid_path);
                         }
@@ -188,14 +487,203 @@ pub fn get_jvm_paths(launch_opts: &LaunchOpts) -> Vec<Box<dyn FnOnce(&LaunchOpts
         }
     }
 
-    jvm_paths
+    // The Java of the last launch before looking further, but after the app's own Java,
+    // which may have been installed since
+    if let Some(last) = usable_last_jvm(&launch_opts.config) {
+        jvm_paths.insert(1, Box::new(move |opts: &LaunchOpts| last_jvm(&last, opts)));
+    }
+
+    // Wherever Java was found, it must be under the allowed paths, and safe from other users
+    jvm_paths.into_iter()
+        .map(|find| -> Box<dyn FnOnce(&LaunchOpts) -> Option<PathBuf>> {
+            Box::new(move |opts: &LaunchOpts| find(opts).filter(|path| {
+                if !is_allowed_jvm(&opts.config, path) {
+                    info!("discovery", "Refusing {:?}, it is not under jvm_allowed_paths", path);
+                    return false;
+                }
+                check_runtime_permissions(&opts.config, path)
+            }))
+        })
+        .collect()
+}
+
+/// The Java of the last launch, see [`read_last_jvm`], unless the config now looks for Java elsewhere:
+/// in another `jvm_install`, or no longer where it was found. One found at `JAVA_HOME` is forgotten once
+/// `JAVA_HOME` no longer points at it, as the user chose another Java.
+fn usable_last_jvm(config: &LauncherConfig) -> Option<LastJvm> {
+    let last = read_last_jvm()?;
+    if last.source == "JAVA_HOME" && !under_java_home(&last.library) {
+        debug!("discovery", "Forgetting the Java of the last launch {:?}, JAVA_HOME changed", last.library);
+        forget_last_jvm();
+        return None;
+    }
+    let allowed = match last.source.as_str() {
+        "JAVA_HOME" => config.allows_system_java,
+        _ => config.allows_java_location_lookup,
+    };
+    if last.jvm_path != config.jvm_path || !allowed {
+        debug!("discovery", "Not using the Java of the last launch {:?}, Java is looked for elsewhere now",
+               last.library);
+        return None;
+    }
+    Some(last)
+}
+
+/// Remember the Java the app started with, found in the `index`th place Java is looked for, to try it
+/// before searching on the next launch, see [`cache_last_jvm`].
+pub fn remember_launched_jvm(config: &LauncherConfig, library: &Path, index: usize) {
+    let source = match index {
+        0 => None,
+        _ if under_java_home(library) => Some("JAVA_HOME"),
+        _ => Some("lookup"),
+    };
+    cache_last_jvm(library, source, config.jvm_path.as_deref());
+}
+
+/// Whether the Java library is in the installation the `JAVA_HOME` environment variable points at.
+fn under_java_home(library: &Path) -> bool {
+    env::var("JAVA_HOME").ok().filter(|h| !h.is_empty()).map_or(false, |home| library.starts_with(home))
+}
+
+/// The Java of the last launch, if the app can still run with it, see [`read_last_jvm`].
+fn last_jvm(last: &LastJvm, opts: &LaunchOpts) -> Option<PathBuf> {
+    let min_java_ver = opts.config.min_java.unwrap_or(0) as i32;
+    if compatible_java_version(&last.library, min_java_ver, opts.config.max_java) != Some(true) {
+        info!("discovery", "Not using the Java {} of the last launch, the app needs another", last.version);
+        return None;
+    }
+    debug!("discovery", "Using the Java of the last launch {:?}", last.library);
+    Some(last.library.clone())
+}
+
+/// Whether Java at the path may be loaded, which is only if it is under one of the `jvm_allowed_paths`
+/// when they are set. Links are resolved first, so that none lead out of them.
+pub fn is_allowed_jvm(config: &LauncherConfig, jvm_path: &Path) -> bool {
+    let roots = match &config.jvm_allowed_paths {
+        Some(roots) if !roots.trim().is_empty() => roots,
+        _ => return true,
+    };
+    let jvm_path = match dunce::canonicalize(jvm_path) {
+        Ok(jvm_path) => jvm_path,
+        Err(_) => return false,
+    };
+    roots.split(';')
+        .map(str::trim)
+        .filter(|root| !root.is_empty())
+        .filter_map(|root| dunce::canonicalize(resolve_path(root)).ok())
+        .any(|root| jvm_path.starts_with(root))
 }
 
 /// This checks the path of the Java dynamic library for a `release` file,
 /// reading the first integer of the `.` separated value of `JAVA_VERSION` as the Java version,
-/// returns `Some(found_ver >= req_ver)` or `None` if the `release` could not be found,
-/// or another error occurs.
-fn compatible_java_version(jvm_path: &PathBuf, req_ver: i32) -> Option<bool> {
+/// returns `Some(found_ver >= req_ver)`, also requiring `found_ver <= max_ver` if given,
+/// or `None` if the `release` could not be found, or another error occurs.
+fn compatible_java_version(jvm_path: &PathBuf, req_ver: i32, max_ver: Option<i64>) -> Option<bool> {
+    // Try and get the Java version of the installation
+    let ver_str = read_release_value(jvm_path, "JAVA_VERSION")?;
+    let parts: Vec<&str> = ver_str.split(".").collect();
+    let ver = parts.first()?.parse::<i32>().ok()?;
+
+    return Some(ver >= req_ver && max_ver.map_or(true, |max| ver as i64 <= max));
+}
+
+/// A place [`get_jvm_paths`] looks for Java, and what it found there, for diagnostics.
+#[derive(Debug)]
+pub struct JvmCandidate {
+    /// Why the place is searched, such as `jvm_install` or `JAVA_HOME`.
+    pub source: &'static str,
+    pub location: String,
+    /// The Java dynamic library found there.
+    pub library: Option<PathBuf>,
+    /// `JAVA_VERSION` and `OS_ARCH` from the installation's `release` file.
+    pub version: Option<String>,
+    pub arch: Option<String>,
+    /// Why the installation would not be used, `None` if it would be.
+    pub rejection: Option<String>,
+}
+
+/// Everywhere [`get_jvm_paths`] looks for Java with the config, in the same order,
+/// and whether what it finds there would be used.
+pub fn get_jvm_candidates(config: &LauncherConfig) -> Vec<JvmCandidate> {
+    let launcher_dir = get_app_dir().map(|d| d.display().to_string()).unwrap_or_default();
+    let mut places: Vec<(&'static str, String)> = vec![];
+    match &config.jvm_path {
+        None => places.push(("launcher directory", launcher_dir.clone())),
+        Some(path) => places.push(("jvm_install", path.clone())),
+    }
+    if config.allows_system_java {
+        places.push(("JAVA_HOME", env::var("JAVA_HOME").unwrap_or_default()));
+    }
+    if config.allows_java_location_lookup {
+        places.push(("launcher directory", launcher_dir));
+        places.extend(JVM_LOC_QUERIES.iter().map(|loc| ("install location", process_path(loc))));
+    }
+    if let Some(last) = usable_last_jvm(config) {
+        places.insert(1, ("last launch", last.library.display().to_string()));
+    }
+
+    places.into_iter().map(|(source, location)| {
+        let library = if location.is_empty() { None } else { valid_path(find_file(&location, DYN_JAVA_LIB)) };
+        let version = library.as_ref().and_then(|l| read_release_value(l, "JAVA_VERSION"));
+        let arch = library.as_ref().and_then(|l| read_release_value(l, "OS_ARCH"));
+        let rejection = jvm_rejection(config, library.as_ref(), version.as_deref(), arch.as_deref());
+        JvmCandidate { source, location, library, version, arch, rejection }
+    }).collect()
+}
+
+/// Why the Java installation would not be used, `None` if it would be.
+fn jvm_rejection(config: &LauncherConfig, library: Option<&PathBuf>, version: Option<&str>,
+                 arch: Option<&str>) -> Option<String> {
+    let library = match library {
+        Some(library) => library,
+        None => return Some(format!("no {} found", DYN_JAVA_LIB)),
+    };
+    let version = match version {
+        Some(version) => version,
+        None => return Some("no release file with its JAVA_VERSION".to_string()),
+    };
+    // Read as `compatible_java_version` does
+    let major = match version.split('.').next().map(|v| v.parse::<i32>()) {
+        Some(Ok(major)) => major,
+        _ => return Some(format!("unreadable JAVA_VERSION {}", version)),
+    };
+    let min_java = config.min_java.unwrap_or(0) as i32;
+    if compatible_java_version(library, min_java, config.max_java) != Some(true) {
+        return Some(match config.max_java {
+            Some(max) if major as i64 > max => format!("Java {} is newer than max_java {}", major, max),
+            _ => format!("Java {} is older than min_java {}", major, min_java),
+        });
+    }
+    if !is_allowed_jvm(config, library) {
+        return Some("not under jvm_allowed_paths".to_string());
+    }
+    if !is_native_library(library) {
+        return Some(format!("built for another architecture than the launcher, {}", env::consts::ARCH));
+    }
+    let normalize = |arch: &str| match arch {
+        "amd64" | "x64" => "x86_64".to_string(),
+        "i386" | "i586" | "i686" => "x86".to_string(),
+        "arm64" => "aarch64".to_string(),
+        other => other.to_string(),
+    };
+    match arch {
+        Some(arch) if normalize(arch) != env::consts::ARCH => {
+            Some(format!("built for {}, the launcher is {}", arch, env::consts::ARCH))
+        }
+        _ => None,
+    }
+}
+
+/// Get the modules of the Java installation from the `MODULES` value of its `release` file.<br>
+/// Empty if they are not listed, such as for Java 8.
+pub fn get_runtime_modules(jvm_path: &PathBuf) -> Vec<String> {
+    read_release_value(jvm_path, "MODULES")
+        .map(|m| m.split_whitespace().map(String::from).collect())
+        .unwrap_or_default()
+}
+
+/// Read a value from the `release` file of the Java installation the dynamic library belongs to.
+pub fn read_release_value(jvm_path: &PathBuf, key: &str) -> Option<String> {
     // First we go up 3 levels from jvm.dll path to get runtime info
     let mut java_folder = jvm_path.to_path_buf();
     for _ in 0..3 {
@@ -204,26 +692,144 @@ fn compatible_java_version(jvm_path: &PathBuf, req_ver: i32) -> Option<bool> {
         }
     }
 
-    // Try and get the Java version of the installation
-    if let Some(release_path) = valid_path(find_file(java_folder.to_str()?, "release")) {
-        let release_info = Config::builder()
-            .add_source(config::File::from(release_path).format(FileFormat::Ini))
-            .build().ok()?;
-        let ver_str = release_info.get_string("JAVA_VERSION").ok()?;
-        let parts: Vec<&str> = ver_str.split(".").collect();
-        let ver = parts.first()?.parse::<i32>().unwrap();
+    let release_path = valid_path(find_file(&java_folder, "release"))?;
+    let release_info = Config::builder()
+        .add_source(config::File::from(release_path).format(FileFormat::Ini))
+        .build().ok()?;
+    release_info.get_string(key).ok()
+}
+
+/// Replace tokens with their real values, see [`resolve_path`] for a path to use as one.
+pub fn process_path(path: &str) -> String {
+    resolve_path(path).to_string_lossy().to_string()
+}
+
+/// Replace tokens with their real values, keeping the dirs as the OS gives them,
+/// even when they are not valid Unicode.
+pub fn resolve_path(path: &str) -> PathBuf {
+    let tokens = [("$USER$", dirs::home_dir()), ("$APPDIR$", get_app_dir()), ("$USERDATA$", get_user_data_dir())];
+    let mut resolved = OsString::new();
+    let mut rest = path;
+    while let Some((i, token, dir)) = tokens.iter()
+        .filter_map(|(token, dir)| rest.find(token).map(|i| (i, token, dir)))
+        .min_by_key(|(i, _, _)| *i) {
+        resolved.push(&rest[..i]);
+        if let Some(dir) = dir {
+            resolved.push(dir);
+        }
+        rest = &rest[i + token.len()..];
+    }
+    resolved.push(rest);
+    PathBuf::from(resolved)
+}
+
+/// The longest path the OS's own functions take without the `\\?\` prefix, as for a directory, which must
+/// leave room for a file name in it.
+#[cfg(windows)]
+const MAX_UNPREFIXED_PATH: usize = 248;
+
+/// The path as given to the OS's own functions, such as to load a library, which fail for paths longer
+/// than `MAX_PATH` unless they have the `\\?\` prefix, see [`verbatim_path`]. Shorter paths are left as
+/// they are, as some tools do not understand the prefix.<br>
+/// Rust's own file functions already add the prefix where needed.
+#[cfg(windows)]
+pub fn long_path(path: &Path) -> PathBuf {
+    let absolute = match env::current_dir() {
+        Ok(dir) if path.is_relative() => dir.join(path),
+        _ => path.to_path_buf(),
+    };
+    if absolute.as_os_str().len() < MAX_UNPREFIXED_PATH {
+        return path.to_path_buf();
+    }
+    match absolute.to_str() {
+        Some(absolute) => PathBuf::from(verbatim_path(absolute)),
+        None => absolute,
+    }
+}
+
+/// Paths are not limited in length elsewhere.
+#[cfg(not(windows))]
+pub fn long_path(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
 
-        return Some(ver >= req_ver);
+/// The absolute Windows path with the `\\?\` prefix, `\\?\UNC\` for network shares such as
+/// `\\server\share\App`. As the OS then takes the path as it is, `/` are turned into `\` and `.` and
+/// `..` are resolved. Relative and already prefixed paths are returned as they are.
+#[cfg(any(windows, test))]
+fn verbatim_path(path: &str) -> String {
+    if path.starts_with(r"\\?\") || path.starts_with(r"\\.\") {
+        return path.to_string();
     }
+    let path = path.replace('/', "\\");
+    let (prefix, rest) = if let Some(share) = path.strip_prefix(r"\\") {
+        (r"\\?\UNC\", share)
+    } else if path.len() >= 3 && path.as_bytes()[1] == b':' && path.as_bytes()[2] == b'\\' {
+        (r"\\?\", path.as_str())
+    } else {
+        return path;
+    };
 
-    return None;
+    // The root, the drive or the server and share, is never left by `..`
+    let root_len = if prefix.ends_with(r"UNC\") { 2 } else { 1 };
+    let mut parts: Vec<&str> = vec![];
+    for (i, part) in rest.split('\\').enumerate() {
+        match part {
+            "" | "." if i >= root_len => {}
+            ".." if i >= root_len => {
+                if parts.len() > root_len {
+                    parts.pop();
+                }
+            }
+            part => parts.push(part),
+        }
+    }
+    // A drive's root keeps its separator, `C:` alone is its current directory
+    let separator = if parts.len() == root_len && root_len == 1 { "\\" } else { "" };
+    format!("{}{}{}", prefix, parts.join("\\"), separator)
 }
 
-/// Replace tokens with their real values
-fn process_path(path: &str) -> String {
-    let user_path = dirs::home_dir().unwrap_or_default();
-    let user = user_path.to_str().unwrap_or("");
-    path.replace("$USER$", user)
+/// Split a list of paths from the config, separated by `;` or `:` so that a config written on one platform
+/// also works on the others. A `:` after a drive letter, as in `C:\App`, is part of the path.
+pub fn split_path_list(list: &str) -> Vec<&str> {
+    let bytes = list.as_bytes();
+    let mut paths = vec![];
+    let mut start = 0;
+    for (i, c) in list.char_indices() {
+        let is_drive = c == ':' && i == start + 1 && bytes[start].is_ascii_alphabetic()
+            && matches!(bytes.get(i + 1), Some(b'\\') | Some(b'/'));
+        if c == ';' || (c == ':' && !is_drive) {
+            paths.push(&list[start..i]);
+            start = i + 1;
+        }
+    }
+    paths.push(&list[start..]);
+    paths.into_iter().filter(|p| !p.is_empty()).collect()
+}
+
+/// The list of paths from the config as the launcher keeps it, separated by `;`, see [`split_path_list`].
+pub fn normalize_path_list(list: &str) -> String {
+    split_path_list(list).join(";")
+}
+
+/// The directory of the launcher executable.
+pub fn get_app_dir() -> Option<PathBuf> {
+    Some(env::current_exe().ok()?.parent()?.to_path_buf())
+}
+
+/// The directories the app's bundled native libraries are in, from `native_libs`,
+/// or else the `lib` and `natives` folders next to the launcher if they exist.
+pub fn get_native_lib_dirs(launch_cfg: &LauncherConfig) -> Vec<PathBuf> {
+    match &launch_cfg.native_libs {
+        Some(native_libs) => native_libs.split(";")
+            .filter(|p| !p.is_empty())
+            .map(|p| resolve_path(p))
+            .collect(),
+        None => ["lib", "natives"].iter()
+            .filter_map(|d| Some(get_app_dir()?.join(d)))
+            .filter(|d| d.is_dir())
+            .collect(),
+    }
 }
 
 /// Checks if the path points to an existing file
@@ -238,53 +844,121 @@ fn valid_path(path: Option<PathBuf>) -> Option<PathBuf> {
 
 /// Locates a file in a given path at max depth 5
 /// Skips hidden files
-fn find_file_with_path<P: AsRef<Path>>(root: P, file: &str) -> Option<PathBuf> {
-    return find_file(root.as_ref().to_str()?, file)
+fn find_file<P: AsRef<Path>>(root: P, file: &str) -> Option<PathBuf> {
+    find_file_matching(root, file, |_| true)
 }
 
-/// Locates a file in a given path at max depth 5
+/// Locates [`DYN_JAVA_LIB`] in a given path, as [`find_file`], skipping those built for another
+/// architecture than the launcher, which it cannot load, such as an x64 Java on Windows on ARM.
+fn find_jvm<P: AsRef<Path>>(root: P) -> Option<PathBuf> {
+    find_file_matching(root, DYN_JAVA_LIB, |p| {
+        let native = is_native_library(p);
+        if !native {
+            debug!("discovery", "Skipping {:?}, it is built for another architecture", p);
+        }
+        native
+    })
+}
+
+/// Locates a file that `accept` accepts in a given path at max depth 5
 /// Skips hidden files
-fn find_file(root: &str, file: &str) -> Option<PathBuf> {
-    let walker = WalkDir::new(root)
+fn find_file_matching<P: AsRef<Path>, F: Fn(&Path) -> bool>(root: P, file: &str, accept: F) -> Option<PathBuf> {
+    let walker = WalkDir::new(&root)
         .max_depth(5)
         .into_iter();
-    let mut path = Path::new(root).to_path_buf();
+    let mut path = root.as_ref().to_path_buf();
 
     if path.ends_with(file) {
-        return Some(path);
+        return if accept(&path) { Some(path) } else { None };
     }
 
     let mut has_path: bool = false;
     for entry in walker.filter_entry(|e| !is_hidden(e)) {
         if let Ok(e) = entry {
-            match e.file_name().to_str() {
-                None => {}
-                Some(name) => {
-                    if name == file {
-                        path = e.into_path();
-                        has_path = true;
-                        // Don't break here in case of multiple installs in one folder
-                    }
-                }
+            if e.file_name() == file && accept(e.path()) {
+                path = e.into_path();
+                has_path = true;
+                // Don't break here in case of multiple installs in one folder
             }
         }
     }
     if has_path { Some(path) } else { None }
 }
 
+/// The PE machine type of the launcher's architecture, see
+/// <https://docs.microsoft.com/en-us/windows/win32/debug/pe-format#machine-types>
+#[cfg(windows)]
+const NATIVE_MACHINE: u16 = if cfg!(target_arch = "aarch64") {
+    0xAA64
+} else if cfg!(target_arch = "x86") {
+    0x14C
+} else {
+    0x8664
+};
+
+/// Whether the dll is built for the launcher's architecture, from the machine type in its PE header.
+/// Dlls that cannot be read are taken to be, the loader then tells.
+#[cfg(windows)]
+fn is_native_library(path: &Path) -> bool {
+    let mut header = Vec::with_capacity(4096);
+    let read = File::open(path).and_then(|f| f.take(4096).read_to_end(&mut header));
+    match read.ok().and_then(|_| pe_machine(&header)) {
+        Some(machine) => machine == NATIVE_MACHINE,
+        None => true,
+    }
+}
+
+/// The loader refuses libraries of another architecture elsewhere, so they are tried.
+#[cfg(not(windows))]
+fn is_native_library(_path: &Path) -> bool {
+    true
+}
+
+/// The machine type of the PE file header, found through the offset at `0x3C` of the DOS header.
+#[cfg(any(windows, test))]
+fn pe_machine(header: &[u8]) -> Option<u16> {
+    if !header.starts_with(b"MZ") {
+        return None;
+    }
+    let offset = u32::from_le_bytes(header.get(0x3C..0x40)?.try_into().ok()?) as usize;
+    if header.get(offset..offset + 4)? != b"PE\0\0" {
+        return None;
+    }
+    Some(u16::from_le_bytes(header.get(offset + 4..offset + 6)?.try_into().ok()?))
+}
+
 /// Used to skip hidden files
 fn is_hidden(entry: &DirEntry) -> bool {
     entry.file_name()
-        .to_str()
-        .map(|s| s.starts_with("."))
-        .unwrap_or(false)
+        .to_string_lossy()
+        .starts_with(".")
+}
+
+/// The minor version of class files that use preview features of their Java version.
+const PREVIEW_MINOR_VERSION: u16 = 0xFFFF;
+
+/// The most threads searching the classpath for the main class.
+const MAX_SCAN_THREADS: usize = 8;
+
+/// Where Spring Boot executable jars keep the app's classes and libraries.
+const SPRING_BOOT_CLASSES: &str = "BOOT-INF/classes/";
+const SPRING_BOOT_LIB: &str = "BOOT-INF/lib/";
+
+/// The Java version a class file was compiled for.
+#[derive(Debug, Clone, Copy)]
+pub struct ClassVersion {
+    /// The Java major version, such as 17
+    pub java: u16,
+    /// Whether the class was compiled with `--enable-preview`,
+    /// it then only runs on exactly this Java version with preview features enabled
+    pub preview: bool,
 }
 
 /// Reads in the first few bytes of a file to determine if it is a class file,
 /// and if so what Java version it was compiled for.<br>
 /// Returns the Java version a class needs.<br>
 /// See <https://docs.oracle.com/javase/specs/jvms/se17/html/jvms-4.html>
-fn read_class_version_to_java<R: Read>(mut reader: R) -> Option<u16> {
+fn read_class_version_to_java<R: Read>(mut reader: R) -> Option<ClassVersion> {
     let mut buffer: [u8; 8] = [0; 8];
 
     // Read file into buffer
@@ -299,15 +973,18 @@ fn read_class_version_to_java<R: Read>(mut reader: R) -> Option<u16> {
 
     // Is Java class?
     if magic_number == 0xCAFEBABE {
-        /*let minor_version =
-            u16::from_be_bytes((buffer[4..6]).try_into().ok().unwrap_or_default());*/
+        let minor_version =
+            u16::from_be_bytes((buffer[4..6]).try_into().ok().unwrap_or_default());
         let major_version =
             u16::from_be_bytes((buffer[6..8]).try_into().ok().unwrap_or_default());
 
         // If smaller than 45, it likely isn't a Java class
         if major_version >= 45 {
             // Convert to Java major version
-            return Some(major_version - 44);
+            return Some(ClassVersion {
+                java: major_version - 44,
+                preview: minor_version == PREVIEW_MINOR_VERSION,
+            });
         } else {
             None
         }
@@ -315,3 +992,72 @@ fn read_class_version_to_java<R: Read>(mut reader: R) -> Option<u16> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_drive_path_is_prefixed() {
+        let dir = format!(r"C:\Users\user\{}\App", "a".repeat(260));
+        let path = format!(r"{}\jre\bin\server\jvm.dll", dir);
+        assert!(path.len() > 260);
+        assert_eq!(verbatim_path(&path), format!(r"\\?\{}", path));
+    }
+
+    #[test]
+    fn unc_path_is_prefixed() {
+        assert_eq!(verbatim_path(r"\\server\share\App\jre\bin"), r"\\?\UNC\server\share\App\jre\bin");
+
+        let long = format!(r"\\server\share\{}\jre\bin\server\jvm.dll", "b".repeat(260));
+        assert_eq!(verbatim_path(&long), format!(r"\\?\UNC\{}", &long[2..]));
+    }
+
+    #[test]
+    fn verbatim_path_is_normalized() {
+        assert_eq!(verbatim_path("C:/App/./jre//bin/../bin"), r"\\?\C:\App\jre\bin");
+        assert_eq!(verbatim_path(r"C:\App\..\..\jre"), r"\\?\C:\jre");
+        assert_eq!(verbatim_path(r"\\server\share\..\..\App"), r"\\?\UNC\server\share\App");
+        assert_eq!(verbatim_path(r"C:\"), r"\\?\C:\");
+    }
+
+    #[test]
+    fn verbatim_path_keeps_prefixed_and_relative_paths() {
+        assert_eq!(verbatim_path(r"\\?\C:\App"), r"\\?\C:\App");
+        assert_eq!(verbatim_path(r"\\?\UNC\server\share"), r"\\?\UNC\server\share");
+        assert_eq!(verbatim_path(r"jre\bin"), r"jre\bin");
+    }
+
+    #[test]
+    fn tokens_are_resolved_to_dirs() {
+        let app_dir = get_app_dir().unwrap().into_os_string();
+        let mut expected = app_dir.clone();
+        expected.push("/jre;");
+        expected.push(&app_dir);
+        assert_eq!(resolve_path("$APPDIR$/jre;$APPDIR$").into_os_string(), expected);
+        assert_eq!(resolve_path("jre/bin"), PathBuf::from("jre/bin"));
+    }
+
+    #[test]
+    fn pe_machine_is_read() {
+        let mut header = vec![0u8; 0x100];
+        header[..2].copy_from_slice(b"MZ");
+        header[0x3C..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+        header[0x80..0x84].copy_from_slice(b"PE\0\0");
+        header[0x84..0x86].copy_from_slice(&0xAA64u16.to_le_bytes());
+        assert_eq!(pe_machine(&header), Some(0xAA64));
+
+        header[0x80] = b'X';
+        assert_eq!(pe_machine(&header), None);
+        assert_eq!(pe_machine(b"MZ"), None);
+        assert_eq!(pe_machine(b"\x7fELF"), None);
+    }
+
+    #[test]
+    fn path_lists_take_either_separator() {
+        assert_eq!(split_path_list("app.jar;lib/a.jar:lib/b.jar"), vec!["app.jar", "lib/a.jar", "lib/b.jar"]);
+        assert_eq!(split_path_list(r"C:\App\app.jar;D:/lib;;"), vec![r"C:\App\app.jar", "D:/lib"]);
+        assert_eq!(split_path_list("/opt/app.jar:a:b"), vec!["/opt/app.jar", "a", "b"]);
+        assert_eq!(normalize_path_list(r"lib:C:\lib"), r"lib;C:\lib");
+    }
+}
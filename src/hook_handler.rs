@@ -0,0 +1,147 @@
+use std::process::Command;
+
+use crate::display_handler::message;
+use crate::file_handler::process_path;
+use crate::locale_handler::text_with;
+
+/// What the launcher does when a hook command fails, or cannot be run.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HookFailure {
+    /// The app is not started, and the user is told which command failed
+    Abort,
+    /// The app is started anyway
+    Continue,
+}
+
+impl HookFailure {
+    /// Parse the value of a `-failure` key of `[Hooks]`, unknown values fall back to [`HookFailure::Abort`].
+    pub fn from_key(key: &str) -> Self {
+        match key.trim().to_lowercase().as_str() {
+            "continue" => HookFailure::Continue,
+            _ => HookFailure::Abort,
+        }
+    }
+}
+
+/// How the commands of a hook ran, see [`run_hooks`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HookResult {
+    /// All of them succeeded
+    Succeeded,
+    /// One failed, and the launch goes on as the hook's failure policy is [`HookFailure::Continue`]
+    Failed,
+    /// One failed, and the app is not started
+    Aborted,
+}
+
+/// The commands of the `[Hooks]` section of launcher.ini, run by the launcher at points of the launch
+/// and waited for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hooks {
+    /// `pre-launch`: run before Java is started, such as to check a native prerequisite,
+    /// refresh a license or prepare the environment.
+    pub pre_launch: Vec<String>,
+    /// `pre-launch-failure`: `abort` or `continue`.
+    pub pre_launch_failure: HookFailure,
+    /// `first-run`: run after the `pre-launch` commands on the first launch of the app's version,
+    /// such as for one-time migrations, see [`crate::install_handler::is_first_run`].
+    pub first_run: Vec<String>,
+    /// `first-run-failure`: `abort` or `continue`. A first run is run again on the next launch unless all its
+    /// commands succeeded and the app started.
+    pub first_run_failure: HookFailure,
+}
+
+impl Default for Hooks {
+    fn default() -> Self {
+        Hooks {
+            pre_launch: vec![],
+            pre_launch_failure: HookFailure::Abort,
+            first_run: vec![],
+            first_run_failure: HookFailure::Abort,
+        }
+    }
+}
+
+/// Run the `pre-launch` commands one after another, see [`run_hooks`].
+pub fn run_pre_launch_hooks(hooks: &Hooks) -> HookResult {
+    run_hooks("pre-launch", &hooks.pre_launch, hooks.pre_launch_failure)
+}
+
+/// Run the `first-run` commands one after another, see [`run_hooks`].
+pub fn run_first_run_hooks(hooks: &Hooks) -> HookResult {
+    run_hooks("first-run", &hooks.first_run, hooks.first_run_failure)
+}
+
+/// Run the hook's commands one after another, waiting for each. A command failing, by exiting with
+/// a failure status or not being found, stops the rest from running.<br>
+/// Returns whether they all succeeded, or else whether to go on with the launch, which is not done
+/// if the hook's failure policy is [`HookFailure::Abort`].
+pub fn run_hooks(hook: &str, commands: &[String], failure: HookFailure) -> HookResult {
+    for command in commands {
+        let args: Vec<String> = split_command(command).iter().map(|a| process_path(a)).collect();
+        let (program, args) = match args.split_first() {
+            Some(split) => split,
+            None => continue,
+        };
+
+        info!("launcher", "Running the {} hook {:?}", hook, command);
+        let mut process = Command::new(program);
+        process.args(args);
+        #[cfg(windows)]
+        {
+            // The launcher has no console of its own, without this each hook would open one
+            use std::os::windows::process::CommandExt;
+            process.creation_flags(winapi::um::winbase::CREATE_NO_WINDOW);
+        }
+        let error = match process.status() {
+            Ok(status) if status.success() => continue,
+            Ok(status) => status.to_string(),
+            Err(e) => e.to_string(),
+        };
+        info!("launcher", "The {} hook {:?} failed: {}", hook, command, error);
+        if failure == HookFailure::Continue {
+            return HookResult::Failed;
+        }
+        message(&text_with("hook.failed", &[command, &error]));
+        return HookResult::Aborted;
+    }
+    HookResult::Succeeded
+}
+
+/// Split a command into its program and arguments on spaces, keeping those in `"` quotes together.
+pub fn split_command(command: &str) -> Vec<String> {
+    let mut args: Vec<String> = vec![];
+    let mut arg: Option<String> = None;
+    let mut quoted = false;
+    for c in command.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                arg.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !quoted => args.extend(arg.take()),
+            c => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(arg);
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_split_outside_quotes() {
+        assert_eq!(split_command(r#"  "$APPDIR$/check prerequisites.sh" --quiet "" x"#),
+                   vec!["$APPDIR$/check prerequisites.sh", "--quiet", "", "x"]);
+        assert!(split_command(" ").is_empty());
+    }
+
+    #[test]
+    fn continued_failure_is_not_success() {
+        assert_eq!(run_hooks("test", &[], HookFailure::Abort), HookResult::Succeeded);
+        assert_eq!(run_hooks("test", &["why-no-such-command".to_string()], HookFailure::Continue),
+                   HookResult::Failed);
+    }
+}
@@ -0,0 +1,225 @@
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::file_handler::{get_app_dir, get_app_name, get_user_data_dir};
+use crate::launch_config::LauncherConfig;
+
+/// Who the app was installed for, found from where the app image is.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum InstallScope {
+    /// In a location the user can write to, such as the user's own programs or home folder.
+    User,
+    /// In a protected system location, such as Program Files or `/opt`, shared by all users.
+    Machine,
+    /// Run from wherever it was copied to, such as a USB stick, keeping its data beside the executable,
+    /// see [`set_portable`].
+    Portable,
+}
+
+impl fmt::Display for InstallScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            InstallScope::User => "user",
+            InstallScope::Machine => "machine",
+            InstallScope::Portable => "portable",
+        })
+    }
+}
+
+/// The scope once found, see [`install_scope`].
+static INSTALL_SCOPE: Mutex<Option<InstallScope>> = Mutex::new(None);
+
+/// The marker file beside the executable which makes the app portable.
+const PORTABLE_MARKER: &str = "portable";
+
+/// The scope of the app's install. It is portable if set with `portable` or marked by a `portable` file
+/// beside the executable, per-machine if the app image is in one of the system's program locations or
+/// the user cannot write to it, and per-user otherwise.
+pub fn install_scope() -> InstallScope {
+    if let Some(scope) = INSTALL_SCOPE.lock().ok().and_then(|s| *s) {
+        return scope;
+    }
+    let is_marked = get_app_dir().map_or(false, |d| d.join(PORTABLE_MARKER).is_file());
+    let scope = match get_app_image() {
+        _ if is_marked => InstallScope::Portable,
+        Some(image) if is_system_location(&image) || !is_writable(&image) => InstallScope::Machine,
+        _ => InstallScope::User,
+    };
+    debug!("launcher", "Install scope: {}", scope);
+    if let Ok(mut s) = INSTALL_SCOPE.lock() {
+        *s = Some(scope);
+    }
+    scope
+}
+
+/// Make the app portable if set with `portable`, otherwise the scope is found from where the app is.
+pub fn set_portable(portable: bool) {
+    if !portable {
+        return;
+    }
+    if let Ok(mut s) = INSTALL_SCOPE.lock() {
+        *s = Some(InstallScope::Portable);
+    }
+}
+
+/// The directory of the app image, the bundle on macOS.
+pub fn get_app_image() -> Option<PathBuf> {
+    let dir = get_app_dir()?;
+    #[cfg(target_os = "macos")]
+    if let Some(contents) = crate::bundle_handler::get_bundle_contents() {
+        return contents.parent().map(|b| b.to_path_buf());
+    }
+    Some(dir)
+}
+
+/// Whether the user can create files in the directory, found by creating one.
+fn is_writable(dir: &Path) -> bool {
+    let probe = dir.join(format!(".{}-{}.tmp", get_app_name(), process::id()));
+    match OpenOptions::new().write(true).create_new(true).open(&probe) {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(windows)]
+fn is_system_location(dir: &Path) -> bool {
+    ["ProgramFiles", "ProgramFiles(x86)", "ProgramW6432", "ProgramData", "SystemRoot"].iter()
+        .filter_map(std::env::var_os)
+        .any(|root| starts_with_ignoring_case(dir, Path::new(&root)))
+}
+
+/// Paths on Windows are compared without case.
+#[cfg(windows)]
+fn starts_with_ignoring_case(path: &Path, root: &Path) -> bool {
+    let lower = |p: &Path| PathBuf::from(p.to_string_lossy().to_lowercase());
+    !root.as_os_str().is_empty() && lower(path).starts_with(lower(root))
+}
+
+#[cfg(target_os = "macos")]
+fn is_system_location(dir: &Path) -> bool {
+    ["/Applications", "/Library", "/System", "/usr", "/opt"].iter().any(|root| dir.starts_with(root))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn is_system_location(dir: &Path) -> bool {
+    ["/usr", "/opt", "/snap", "/var/lib/flatpak", "/nix/store", "/app"].iter().any(|root| dir.starts_with(root))
+}
+
+/// The directory the launcher keeps its logs, caches and the app's data in, named after the app.<br>
+/// This is per-user, as nobody may write to a per-machine install, except for an administrator
+/// of a per-machine install, such as a service, which uses the machine's data dir shared by all users.
+/// A portable app keeps it in a `data` folder beside the executable.
+pub fn data_dir_for(scope: InstallScope) -> Option<PathBuf> {
+    if scope == InstallScope::Portable {
+        return Some(get_app_dir()?.join("data"));
+    }
+    if scope == InstallScope::Machine && is_administrator() {
+        if let Some(dir) = machine_data_dir() {
+            return Some(dir.join(get_app_name()));
+        }
+    }
+    Some(dirs::data_local_dir()?.join(get_app_name()))
+}
+
+/// Whether the launcher runs as an administrator, elevated on Windows and as root elsewhere.
+#[cfg(windows)]
+pub fn is_administrator() -> bool {
+    unsafe { winapi::um::shlobj::IsUserAnAdmin() != 0 }
+}
+
+/// Whether the launcher runs as an administrator, elevated on Windows and as root elsewhere.
+#[cfg(unix)]
+pub fn is_administrator() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(windows)]
+fn machine_data_dir() -> Option<PathBuf> {
+    std::env::var_os("ProgramData").map(PathBuf::from)
+}
+
+#[cfg(target_os = "macos")]
+fn machine_data_dir() -> Option<PathBuf> {
+    Some(PathBuf::from("/Library/Application Support"))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn machine_data_dir() -> Option<PathBuf> {
+    Some(PathBuf::from("/var/lib"))
+}
+
+/// The file listing the versions of the app launched for the user, one per line, see [`is_first_run`].
+fn get_launched_file() -> Option<PathBuf> {
+    Some(get_user_data_dir()?.join(get_app_name() + ".launched"))
+}
+
+/// The app's version as listed in the launched file.
+fn launched_version(config: &LauncherConfig) -> &str {
+    config.app_version.as_deref().map(str::trim).unwrap_or("unversioned")
+}
+
+/// Whether this is the first launch of the app's `app_version` for the user, the version not being
+/// listed by [`mark_first_run_done`] yet. Without `app_version` it is the first launch of the app at all.
+/// Never the first without a data dir to list the versions in, as every launch would be.
+pub fn is_first_run(config: &LauncherConfig) -> bool {
+    let launched_file = match get_launched_file() {
+        Some(launched_file) => launched_file,
+        None => return false,
+    };
+    let launched = fs::read_to_string(launched_file).unwrap_or_default();
+    let first_run = !launched.lines().any(|v| v == launched_version(config));
+    if first_run {
+        info!("launcher", "First launch of version {}", launched_version(config));
+    }
+    first_run
+}
+
+/// Set once the `first-run` commands all succeeded, see [`set_first_run_succeeded`].
+static FIRST_RUN_SUCCEEDED: AtomicBool = AtomicBool::new(false);
+
+/// Remember that this is the first run and its `first-run` commands all succeeded, so that
+/// [`mark_first_run_done`] lists the version once the app has started.
+pub fn set_first_run_succeeded() {
+    FIRST_RUN_SUCCEEDED.store(true, Ordering::SeqCst);
+}
+
+/// List the app's version as launched once the app has started, so that its next launches are not the first,
+/// see [`is_first_run`]. Only if [`set_first_run_succeeded`] was, so that a first run whose `first-run`
+/// commands failed, or whose app did not start, is run again on the next launch.
+pub fn mark_first_run_done(config: &LauncherConfig) {
+    if !FIRST_RUN_SUCCEEDED.swap(false, Ordering::SeqCst) {
+        return;
+    }
+    let launched_file = match get_launched_file() {
+        Some(launched_file) => launched_file,
+        None => return,
+    };
+    let written = launched_file.parent().map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| OpenOptions::new().create(true).append(true).open(&launched_file))
+        .and_then(|mut f| writeln!(f, "{}", launched_version(config)));
+    if let Err(e) = written {
+        info!("launcher", "Failed to list the version as launched in {:?}: {:?}", launched_file, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn portable_data_dir_is_beside_the_executable() {
+        // Without setting the install scope, which the other tests of the binary see
+        assert!(LauncherConfig::is_portable(&["", "portable=true\n"]));
+        let app_dir = get_app_dir().unwrap();
+        let data_dir = data_dir_for(InstallScope::Portable).unwrap();
+        assert_eq!(data_dir, app_dir.join("data"));
+    }
+}
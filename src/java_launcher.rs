@@ -1,148 +1,719 @@
-use std::iter::once;
-use std::path::{PathBuf};
+use std::env;
+use std::ffi::{c_void, CString, NulError, OsString};
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicPtr, Ordering};
 
-use jni::{InitArgs, InitArgsBuilder, JavaVM, JNIVersion, JvmError, sys};
-use jni::objects::{JObject, JValue};
-use jni::sys::{jint, JNI_OK, JNIInvokeInterface_, jsize};
-use crate::file_handler::get_jvm_paths;
+use jni::{JavaVM, JNIEnv, sys};
+use jni::objects::{JClass, JObject, JString, JValue};
+use jni::sys::{jint, JavaVMInitArgs, jobjectArray, JavaVMOption, JNI_EEXIST, JNI_EINVAL, JNI_ENOMEM, JNI_ERR,
+               JNI_EVERSION, JNI_FALSE, JNI_OK, JNI_TRUE, JNIInvokeInterface_, jsize};
+#[cfg(target_os = "macos")]
+use crate::bundle_handler::apply_dock_opt;
+use crate::exception_handler::{install_uncaught_handler, take_exception};
+use crate::exit_handler::{ExitCode, jvm_exit_hook};
+use crate::display_handler::{ask, Choice, notify, open_url};
+use crate::file_handler::{app_working_dir, get_jvm_paths, get_native_lib_dirs, get_runtime_modules, LIBRARY_PATH_VAR,
+                          long_path, PATH_SEPARATOR, remember_launched_jvm, resolve_path};
 
-use crate::launch_config::LauncherConfig;
-use crate::message;
+use crate::install_handler::mark_first_run_done;
+use crate::launch_config::{CONFIG_FILE, is_suspect_opt, LauncherConfig};
+use crate::locale_handler::{text, text_with};
+#[cfg(unix)]
+use crate::process_handler::forward_termination_signals;
+use crate::process_launcher::try_run_java_process;
+use crate::session_handler::{toolkit_opts, wm_class_for_launch};
+use crate::timing_handler::{log_timings, Phase, time, timing_properties};
+#[cfg(feature = "updater")]
+use crate::update_handler::{confirm_update, mark_update_tried};
+use crate::watchdog::{set_stage, startup_complete, stop_watchdog};
+use crate::{detailed_message, message};
 
 /// The launcher options, such as JVM args and where the JVM is located.
 #[derive(Debug)]
 pub struct LaunchOpts {
     pub config: LauncherConfig,
     pub jvm_opts: Vec<String>,
+    /// JVM options the user left in the Launch4j ini and the app asked for through the relaunch file,
+    /// used after `jvm_opts`
+    pub user_jvm_opts: Vec<String>,
     pub program_opts: Vec<String>,
 }
 
+impl LaunchOpts {
+    /// The JVM options, then those the user and the app asked for.
+    pub fn all_jvm_opts(&self) -> Vec<&String> {
+        self.jvm_opts.iter().chain(self.user_jvm_opts.iter()).collect()
+    }
+}
+
+/// Builds [`LaunchOpts`] for launchers of their own, such as `LaunchOptsBuilder::new().main_class("a.Main")
+/// .classpath("app.jar").min_java(17).build()`, with the JVM options for the class and module path that
+/// the launcher adds itself when it reads `launcher.ini`.
+#[derive(Debug, Default)]
+pub struct LaunchOptsBuilder {
+    config: LauncherConfig,
+    jvm_opts: Vec<String>,
+    program_opts: Vec<String>,
+}
+
+impl LaunchOptsBuilder {
+    /// Start from the defaults `launcher.ini` has.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Start from a config, such as one read with [`LauncherConfig::parse_config_from`].
+    pub fn from_config(config: LauncherConfig) -> Self {
+        LaunchOptsBuilder { config, ..Default::default() }
+    }
+
+    pub fn main_class(mut self, main_class: &str) -> Self {
+        self.config.main_class = Some(main_class.to_string());
+        self
+    }
+
+    /// The main module, as `module` or `module/class`.
+    pub fn main_module(mut self, main_module: &str) -> Self {
+        self.config.main_module = Some(main_module.to_string());
+        self
+    }
+
+    /// A jar whose manifest has the main class and class path, where they are not set.
+    pub fn main_jar(mut self, main_jar: &str) -> Self {
+        self.config.main_jar = Some(main_jar.to_string());
+        self
+    }
+
+    /// Add an entry to the class path.
+    pub fn classpath(mut self, entry: &str) -> Self {
+        self.config.classpath = Some(join_entry(self.config.classpath.take(), entry));
+        self
+    }
+
+    /// Add an entry to the module path.
+    pub fn module_path(mut self, entry: &str) -> Self {
+        self.config.module_path = Some(join_entry(self.config.module_path.take(), entry));
+        self
+    }
+
+    /// Add a JVM option, after those of the config.
+    pub fn jvm_opt(mut self, opt: &str) -> Self {
+        self.jvm_opts.push(opt.to_string());
+        self
+    }
+
+    /// Add an argument for the app's `main` method.
+    pub fn program_arg(mut self, arg: &str) -> Self {
+        self.program_opts.push(arg.to_string());
+        self
+    }
+
+    /// The Java runtime to look for Java in first, as `jvm_install`.
+    pub fn runtime(mut self, path: &Path) -> Self {
+        self.config.jvm_path = Some(path.display().to_string());
+        self
+    }
+
+    pub fn min_java(mut self, version: i64) -> Self {
+        self.config.min_java = Some(version);
+        self
+    }
+
+    pub fn max_java(mut self, version: i64) -> Self {
+        self.config.max_java = Some(version);
+        self
+    }
+
+    /// Check the options and build them as [`LauncherConfig::read_file`] and the launcher do: the main class
+    /// and class path are taken from the main jar, the required Java from the main class if `check_main_class`
+    /// is set, and the class and module path are passed to Java with the JVM options.<br>
+    /// Fails if there is nothing to launch, or the Java versions contradict each other.
+    pub fn build(self) -> Result<LaunchOpts, String> {
+        let mut config = self.config;
+        config.apply_main_jar();
+        if !config.validate() {
+            return Err(format!("Nothing to launch, the config needs {}", config.missing_keys().join(", and ")));
+        }
+        // As raised for the main class
+        config.ensure_correct_java();
+        if let Some((min, max)) = config.java_conflict() {
+            return Err(format!("min_java {} is newer than max_java {}", min, max));
+        }
+
+        let module_path: Vec<PathBuf> = config.module_path.iter()
+            .flat_map(|p| p.split(";"))
+            .filter(|p| !p.is_empty())
+            .map(resolve_path)
+            .collect();
+        let mut jvm_opts = config.read_launch_opts();
+        let user_jvm_opts = config.read_user_launch_opts();
+        jvm_opts.append(&mut path_opts(config.classpath.as_deref(), &module_path, &[], config.main_module.as_deref()));
+        if config.enable_preview && !jvm_opts.iter().chain(self.jvm_opts.iter()).any(|o| o == "--enable-preview") {
+            jvm_opts.push("--enable-preview".to_string());
+        }
+        jvm_opts.extend(self.jvm_opts);
+
+        Ok(LaunchOpts { config, jvm_opts, user_jvm_opts, program_opts: self.program_opts })
+    }
+}
+
+fn join_entry(path: Option<String>, entry: &str) -> String {
+    match path {
+        Some(path) if !path.is_empty() => format!("{};{}", path, entry),
+        _ => entry.to_string(),
+    }
+}
+
+/// The JVM options for the class path and module path, the main module being resolved as a root module
+/// at startup, and the modules to add as such.
+pub fn path_opts(classpath: Option<&str>, module_path: &[PathBuf], add_modules: &[String],
+                 main_module: Option<&str>) -> Vec<String> {
+    let mut opts: Vec<String> = vec![];
+    if let Some(classpath) = classpath {
+        let entries: Vec<&str> = classpath.split(';').filter(|p| !p.is_empty()).collect();
+        opts.push(format!("-Djava.class.path={}", entries.join(PATH_SEPARATOR)));
+    }
+    if !module_path.is_empty() {
+        if let Ok(joined) = env::join_paths(module_path) {
+            opts.push(format!("--module-path={}", joined.to_string_lossy()));
+        }
+    }
+    if !add_modules.is_empty() {
+        opts.push(format!("--add-modules={}", add_modules.join(",")));
+    }
+    if let Some(main_module) = main_module {
+        let module = main_module.split('/').next().unwrap_or_default();
+        opts.push(format!("-Djdk.module.main={}", module));
+    }
+    opts
+}
+
+/// The Java version offered for download when the app does not say which it needs,
+/// a long-term support release.
+const DEFAULT_DOWNLOAD_VERSION: i64 = 21;
+
+/// The JVM created by the launcher, so that other threads can reach it.
+static CREATED_JVM: AtomicPtr<sys::JavaVM> = AtomicPtr::new(ptr::null_mut());
+
+/// The Java the JVM was created from and the place it was found in, see [`launched_with`].
+static LAUNCHED_FROM: Mutex<Option<(PathBuf, usize)>> = Mutex::new(None);
+
+/// The options choosing the AWT toolkit the JVM was last created with, see [`toolkit_opts`].
+static LAUNCH_TOOLKIT_OPTS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
 /// Create the JVM, attach to it, and run the `main` method of the given `launch_opts`.<br>
-/// Blocks until the JVM has shut down.
-pub fn create_and_run_jvm(launch_opts: &LaunchOpts) {
+/// Blocks until the JVM has shut down, and returns the exit status of the app.
+/// If the app calls `System.exit` the launcher exits from [`jvm_exit_hook`] instead.
+pub fn create_and_run_jvm(launch_opts: &LaunchOpts) -> i32 {
     // Not enough information provided in the launcher config
     if !launch_opts.config.validate() {
-        message("Invalid launcher config.\n\
-        Please contact the developers.");
-        return;
-    }
-
-    // The launch attempt
-    if let Some(jvm) = try_launch_jvm(launch_opts) {
-        // Attach the current thread to call into Java
-        // This method returns the guard that will detach the current thread when dropped,
-        // also freeing any local references created in it
-        let maybe_env = jvm.attach_current_thread_as_daemon();
-
-        // Starting the app
-        match maybe_env {
-            Ok(env) => {
-                // Convert program args for forwarding
-                let opts: Vec<JValue> = launch_opts.program_opts.iter()
-                    .map(|s| env.new_string(s)) // Convert to JString (maybe)
-                    .filter(|m| m.is_ok()).map(|m| m.unwrap())// Remove invalid JStrings
-                    .map(|s| JValue::Object(*s)).collect(); // Convert to something usable
-
-                // Make array for main method, passing a slice of jstrings does not work
-                let arg_array = env.new_object_array(opts.len() as i32, "java/lang/String", env.new_string("").unwrap());
-                let args = arg_array.unwrap();
-                let mut i = 0;
-                for o in opts {
-                    let _ = env.set_object_array_element(args, i, o.l().unwrap());
-                    i = i + 1;
-                }
+        show_invalid_config(&launch_opts.config);
+        return ExitCode::ConfigInvalid.code();
+    }
 
-                // Ensure correct format of main class
-                let main_class = launch_opts.config.main_class.as_ref().unwrap().replace(".", "/");
+    // The launch attempt, again if the user installed Java
+    let launched = loop {
+        match try_launch_jvm(launch_opts) {
+            Err(None) if show_missing_java(launch_opts) => continue,
+            launched => break launched,
+        }
+    };
+    match launched {
+        Ok(jvm) => {
+            CREATED_JVM.store(jvm.get_java_vm_pointer(), Ordering::SeqCst);
+            #[cfg(unix)]
+            forward_termination_signals();
 
-                // Call main method
-                let v = env.call_static_method(main_class, "main", "([Ljava/lang/String;)V", &[JValue::from(JObject::from(args)),]);
+            // Attach the current thread to call into Java
+            // This method returns the guard that will detach the current thread when dropped,
+            // also freeing any local references created in it
+            let maybe_env = jvm.attach_current_thread_as_daemon();
 
-                // Launch failed
-                if let Err(e) = v {
-                    println!("{:?}", e);
-                    message("Failed to start the app, the classname was invalid or \
-                    not on the classpath, or the main method could not be found.\n\
-                    Please contact the developers.");
-                    return;
+            // Starting the app
+            let status = match maybe_env {
+                Ok(env) => {
+                    // Convert program args for forwarding
+                    let opts: Vec<JValue> = launch_opts.program_opts.iter()
+                        .map(|s| env.new_string(s)) // Convert to JString (maybe)
+                        .filter(|m| m.is_ok()).map(|m| m.unwrap())// Remove invalid JStrings
+                        .map(|s| JValue::Object(*s)).collect(); // Convert to something usable
+
+                    // Make array for main method, passing a slice of jstrings does not work
+                    let arg_array = env.new_object_array(opts.len() as i32, "java/lang/String", env.new_string("").unwrap());
+                    let args = arg_array.unwrap();
+                    let mut i = 0;
+                    for o in opts {
+                        let _ = env.set_object_array_element(args, i, o.l().unwrap());
+                        i = i + 1;
+                    }
+
+                    // Report exceptions of threads that have no handler of their own
+                    if launch_opts.config.install_exception_handler {
+                        if let Err(e) = install_uncaught_handler(&env, launch_opts.config.show_crash_dialog) {
+                            info!("jni", "Failed to install the uncaught exception handler: {:?}", e);
+                            let _ = take_exception(&env);
+                        }
+                    }
+
+                    // Before the app creates any windows
+                    let toolkit_opts = LAUNCH_TOOLKIT_OPTS.lock().map(|o| o.clone()).unwrap_or_default();
+                    let mut jvm_opts = launch_opts.all_jvm_opts();
+                    jvm_opts.extend(toolkit_opts.iter());
+                    if let Some(wm_class) = wm_class_for_launch(&launch_opts.config, &jvm_opts) {
+                        set_wm_class(&env, &wm_class);
+                    }
+
+                    // Run the preflight class, such as an updater, only continuing if it succeeds
+                    if let Some(preflight_class) = &launch_opts.config.preflight_class {
+                        if run_main(&env, preflight_class, args).is_err() {
+                            return ExitCode::PreflightFailed.code();
+                        }
+                    }
+
+                    // Launch failed
+                    let class = time(Phase::LoadMain, || match &launch_opts.config.main_module {
+                        Some(main_module) => find_module_main_class(&env, main_module),
+                        None => find_main_class(&env, launch_opts.config.main_class.as_ref().unwrap()),
+                    });
+                    let class = match class {
+                        Some(class) => class,
+                        None => return ExitCode::MainNotFound.code(),
+                    };
+                    startup_complete();
+                    #[cfg(feature = "updater")]
+                    confirm_update();
+                    if let Ok(Some((library, index))) = LAUNCHED_FROM.lock().map(|l| l.clone()) {
+                        remember_launched_jvm(&launch_opts.config, &library, index);
+                    }
+                    mark_first_run_done(&launch_opts.config);
+                    log_timings();
+                    set_system_properties(&env, &timing_properties());
+                    if let Err(code) = call_main(&env, class, args) {
+                        return code.code();
+                    }
+
+                    // This hangs and waits for all Java threads to close before shutting down
+                    // Also keeps the JVM open, without this we immediately shut down
+                    if let Ok(j) = env.get_java_vm() { // This gets around ownership issues
+                        close_jvm(j);
+                    }
+                    0
+                }
+                Err(e) => {
+                    info!("jni", "{:?}", e);
+                    stop_watchdog();
+                    message(&text("jvm.attach_failed"));
+                    ExitCode::JvmFailed.code()
                 }
+            };
 
-                // This hangs and waits for all Java threads to close before shutting down
-                // Also keeps the JVM open, without this we immediately shut down
-                if let Ok(j) = env.get_java_vm() { // This gets around ownership issues
-                    close_jvm(j);
+            // Ensure the JVM is closed
+            close_jvm(jvm);
+            status
+        }
+        Err(Some(e)) => {
+            stop_watchdog();
+            // Java was found but could not be started, its java executable may still manage
+            if launch_opts.config.process_fallback && e.process_may_start() {
+                info!("jni", "Failed to create the JVM in process, falling back to a separate Java process");
+                if let Some(status) = try_run_java_process(launch_opts) {
+                    info!("jni", "Launched the app in a separate Java process");
+                    return status;
                 }
             }
-            Err(e) => {
-                println!("{:?}", e);
-                message("Java successfully started, but failed to attach to it and therefore cannot proceed.\n\
-                Please contact the developers.")
-            }
+
+            message(e.user_message().as_str());
+            ExitCode::JvmFailed.code()
         }
+        Err(None) => ExitCode::NoJava.code(),
+    }
+}
 
-        // Ensure the JVM is closed
-        close_jvm(jvm)
+/// Tell the user the config does not say what to launch, which file it was read from and what it lacks.
+pub fn show_invalid_config(config: &LauncherConfig) {
+    let msg = match &config.config_file {
+        Some(file) => {
+            let missing: Vec<String> = config.missing_keys().iter().map(|k| format!("- {}", k)).collect();
+            text_with("config.incomplete", &[&file.display().to_string(), &missing.join("\n")])
+        }
+        None => {
+            let expected = env::current_dir().map(|d| d.join(CONFIG_FILE)).unwrap_or_else(|_| CONFIG_FILE.into());
+            text_with("config.not_found", &[&expected.display().to_string()])
+        }
+    };
+    stop_watchdog();
+    message(&msg);
+}
+
+/// Tell the user that no compatible Java installation could be found, offering the download page,
+/// returning whether they want the launcher to look again.
+pub fn show_missing_java(launch_opts: &LaunchOpts) -> bool {
+    let version = launch_opts.config.min_java.unwrap_or(0);
+    let inst = if version > 0 {
+        text_with("java.min_version", &[&version.to_string()])
     } else {
-        // Error messages
-        // String formatting? What's that?
-        let version = launch_opts.config.min_java.unwrap_or(0);
-        let mut inst = "any Java.".to_owned();
-        if version > 0 {
-            let mut x = "Java ".to_owned();
-            x.push_str(version.to_string().as_str());
-            x.push_str(" or newer.");
-            inst = x.clone();
+        text("java.any")
+    };
+    stop_watchdog();
+    loop {
+        let choice = ask(&text_with("java.missing", &[&inst]),
+                         &[Choice::GetJava, Choice::Retry, Choice::Quit]);
+        match choice {
+            Choice::GetJava => open_url(&java_download_url(&launch_opts.config)),
+            choice => return choice == Choice::Retry,
+        }
+    }
+}
+
+/// The page to download the Java the app needs for this OS and architecture from,
+/// see `java_download_url`.
+fn java_download_url(config: &LauncherConfig) -> String {
+    let version = config.min_java.filter(|v| *v > 0).or(config.max_java)
+        .unwrap_or(DEFAULT_DOWNLOAD_VERSION);
+    let os = if cfg!(windows) {
+        "windows"
+    } else if cfg!(target_os = "macos") {
+        "mac"
+    } else {
+        "linux"
+    };
+    let arch = match env::consts::ARCH {
+        "x86_64" => "x64",
+        "x86" => "x32",
+        arch => arch,
+    };
+    config.java_download_url
+        .replace("$VERSION$", &version.to_string())
+        .replace("$OS$", os)
+        .replace("$ARCH$", arch)
+}
+
+/// Set the `WM_CLASS` AWT gives the app's windows, which it otherwise takes from the class at the bottom of
+/// the stack that first uses AWT, often `java-lang-Thread`. Java has no option for it, and the X11 toolkit names
+/// the app when it is created, so the toolkit is created first and its name then set through JNI, which is not bound
+/// by module access.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn set_wm_class(env: &JNIEnv, wm_class: &str) {
+    let set = || -> jni::errors::Result<bool> {
+        let toolkit = env.call_static_method("java/awt/Toolkit", "getDefaultToolkit", "()Ljava/awt/Toolkit;", &[])?
+            .l()?;
+        let class = env.find_class("sun/awt/X11/XToolkit")?;
+        if !env.is_instance_of(toolkit, class)? {
+            return Ok(false);
+        }
+        let field = env.get_static_field_id(class, "awtAppClassName", "Ljava/lang/String;")?;
+        let value = env.new_string(wm_class)?;
+        unsafe {
+            let raw = env.get_native_interface();
+            if let Some(set_field) = (**raw).SetStaticObjectField {
+                set_field(raw, class.into_inner(), field.into_inner(), value.into_inner());
+            }
+        }
+        Ok(true)
+    };
+    match set() {
+        Ok(true) => debug!("display", "Set WM_CLASS to {}", wm_class),
+        Ok(false) => debug!("display", "Not setting WM_CLASS, the app does not use the X11 toolkit"),
+        // Such as without a toolkit
+        Err(e) => {
+            debug!("display", "Failed to set WM_CLASS: {:?}", e);
+            let _ = take_exception(env);
+        }
+    }
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+fn set_wm_class(_env: &JNIEnv, _wm_class: &str) {}
+
+/// Set the system properties in the JVM, for those only known once it was created.
+fn set_system_properties(env: &JNIEnv, properties: &[(String, String)]) {
+    for (key, value) in properties {
+        let set = env.new_string(key).and_then(|key| Ok((key, env.new_string(value)?)))
+            .and_then(|(key, value)| env.call_static_method("java/lang/System", "setProperty",
+                                                           "(Ljava/lang/String;Ljava/lang/String;)Ljava/lang/String;",
+                                                           &[JValue::Object(*key), JValue::Object(*value)]));
+        if let Err(e) = set {
+            info!("jni", "Failed to set the system property {}: {:?}", key, e);
+            let _ = take_exception(env);
+        }
+    }
+}
+
+/// Call the `main` method of the given class, returning why it did not complete normally.<br>
+/// Tells the user if the class or method could not be found, or if it threw.
+fn run_main(env: &JNIEnv, main_class: &str, args: jobjectArray) -> Result<(), ExitCode> {
+    match find_main_class(env, main_class) {
+        Some(class) => call_main(env, class, args),
+        None => Err(ExitCode::MainNotFound),
+    }
+}
+
+/// Find the given class, telling the user if it could not be found.
+fn find_main_class<'a>(env: &JNIEnv<'a>, main_class: &str) -> Option<JClass<'a>> {
+    set_stage(format!("loading {}", main_class));
+
+    // Ensure correct format of main class
+    let main_class = main_class.replace(".", "/");
+
+    // Find the main class, this also runs its static initializers
+    match env.find_class(main_class) {
+        Ok(class) => Some(class),
+        Err(e) => {
+            info!("jni", "{:?}", e);
+            show_launch_failure(env, &text("app.main_not_found"));
+            None
+        }
+    }
+}
+
+/// Find the main class of a module in the boot layer, given as `module` or `module/class`,
+/// the same way the `java` launcher does for `-m`.<br>
+/// Without a class the main class of the module descriptor is used.
+/// Tells the user if the module or class could not be found.
+fn find_module_main_class<'a>(env: &JNIEnv<'a>, main_module: &str) -> Option<JClass<'a>> {
+    set_stage(format!("loading module {}", main_module));
+
+    let class = lookup_module_main_class(env, main_module).unwrap_or_else(|e| {
+        info!("jni", "{:?}", e);
+        JObject::null()
+    });
+    if class.is_null() {
+        show_launch_failure(env, &text("app.module_not_found"));
+        return None;
+    }
+
+    Some(JClass::from(class))
+}
+
+/// Resolve the main class of a module over the `java.lang.module` API,
+/// returning `null` if the module or class does not exist.
+fn lookup_module_main_class<'a>(env: &JNIEnv<'a>, main_module: &str) -> jni::errors::Result<JObject<'a>> {
+    let (module_name, class_name) = match main_module.split_once('/') {
+        Some((module, class)) => (module, Some(class.to_string())),
+        None => (main_module, None),
+    };
+
+    // ModuleLayer.boot().findModule(name).orElse(null)
+    let layer = env.call_static_method("java/lang/ModuleLayer", "boot",
+                                       "()Ljava/lang/ModuleLayer;", &[])?.l()?;
+    let module = env.call_method(layer, "findModule", "(Ljava/lang/String;)Ljava/util/Optional;",
+                                 &[JValue::Object(*env.new_string(module_name)?)])?.l()?;
+    let module = env.call_method(module, "orElse", "(Ljava/lang/Object;)Ljava/lang/Object;",
+                                 &[JValue::Object(JObject::null())])?.l()?;
+    if module.is_null() {
+        return Ok(JObject::null());
+    }
+
+    // module.getDescriptor().mainClass().orElse(null)
+    let class_name = match class_name {
+        Some(class_name) => class_name,
+        None => {
+            let descriptor = env.call_method(module, "getDescriptor",
+                                             "()Ljava/lang/module/ModuleDescriptor;", &[])?.l()?;
+            let main_class = env.call_method(descriptor, "mainClass", "()Ljava/util/Optional;", &[])?.l()?;
+            let main_class = env.call_method(main_class, "orElse", "(Ljava/lang/Object;)Ljava/lang/Object;",
+                                             &[JValue::Object(JObject::null())])?.l()?;
+            if main_class.is_null() {
+                return Ok(JObject::null());
+            }
+            env.get_string(JString::from(main_class))?.into()
+        }
+    };
+
+    // Class.forName(module, name), which also returns null if the class does not exist
+    let class = env.call_static_method("java/lang/Class", "forName",
+                                       "(Ljava/lang/Module;Ljava/lang/String;)Ljava/lang/Class;",
+                                       &[JValue::Object(module), JValue::Object(*env.new_string(class_name)?)])?.l()?;
+
+    Ok(class)
+}
+
+/// Call the `main` method of the given class, returning why it did not complete normally.<br>
+/// Tells the user if the method could not be found, or if it threw.
+fn call_main(env: &JNIEnv, class: JClass, args: jobjectArray) -> Result<(), ExitCode> {
+    // Call main method
+    let v = env.call_static_method(class, "main", "([Ljava/lang/String;)V", &[JValue::from(JObject::from(args)),]);
+
+    if let Err(e) = v {
+        info!("jni", "{:?}", e);
+        if let jni::errors::Error::MethodNotFound { .. } = e {
+            show_launch_failure(env, &text("app.main_not_found"));
+            return Err(ExitCode::MainNotFound);
         }
-        message(&("A missing or older Java installation was found.\n\
-                    Please install ".to_owned() + inst.as_str()))
+        show_launch_failure(env, &text("app.fatal_error"));
+        return Err(ExitCode::MainThrew);
     }
+
+    Ok(())
 }
 
-/// Create the JVM if possible
-fn try_launch_jvm(launch_opts: &LaunchOpts) -> Option<JavaVM> {
+/// Ask the JVM to exit with the given status through `System.exit`,
+/// so that the app's shutdown hooks run.<br>
+/// Returns `false` if there is no JVM to exit, otherwise the launcher exits from [`jvm_exit_hook`].
+pub fn request_jvm_exit(code: i32) -> bool {
+    let vm_ptr = CREATED_JVM.load(Ordering::SeqCst);
+    if vm_ptr.is_null() {
+        return false;
+    }
+
+    if let Ok(jvm) = unsafe { JavaVM::from_raw(vm_ptr) } {
+        if let Ok(env) = jvm.attach_current_thread_as_daemon() {
+            return env.call_static_method("java/lang/System", "exit", "(I)V", &[JValue::Int(code)])
+                .is_ok();
+        }
+    }
+
+    false
+}
+
+/// Show a failure message, including the details of the pending Java exception if there is one.
+fn show_launch_failure(env: &JNIEnv, msg: &str) {
+    stop_watchdog();
+    if let Some(details) = take_exception(env) {
+        info!("jni", "{}", details);
+        detailed_message(msg, details.as_str());
+    } else {
+        message(msg);
+    }
+}
+
+/// Create the JVM if possible.<br>
+/// Returns the reason the last Java installation found failed to start,
+/// or `None` if no compatible installation was found.
+fn try_launch_jvm(launch_opts: &LaunchOpts) -> Result<JavaVM, Option<CreateJvmError>> {
+    set_stage("looking for Java".to_string());
     let path_getters = get_jvm_paths(launch_opts);
-    let count = path_getters.len();
-    for jvm_path_f in path_getters {
-        if let Some(jvm_path) = (jvm_path_f)(launch_opts) {
-            // Make sure the system can find the needed dynamic libraries
-            set_dynamic_library_lookup_loc(&jvm_path);
+    let mut last_error: Option<CreateJvmError> = None;
+    for (i, jvm_path_f) in path_getters.into_iter().enumerate() {
+        // The first candidate is the app's own Java, if it has one
+        let own_java_skipped = i > 0 && launch_opts.config.jvm_path.is_some();
+        if let Some(jvm_path) = time(Phase::JvmDiscovery, || (jvm_path_f)(launch_opts)) {
+            debug!("discovery", "Found Java at {:?}", jvm_path);
 
-            // This is needed for the lookup passed to with_libjvm
-            let path_getter = || {
-                Ok(jvm_path.as_path())
-            };
+            // Make sure the system can find the needed dynamic libraries
+            set_dynamic_library_lookup_loc(&jvm_path, launch_opts);
 
-            // Create JVM arguments
-            let args = make_jvm_args(launch_opts);
+            // Create JVM arguments, with those depending on the Java
+            let toolkit_opts = toolkit_opts(&jvm_path, &launch_opts.config, &launch_opts.all_jvm_opts());
+            remember_toolkit_opts(&toolkit_opts);
+            let args = make_jvm_args(launch_opts.jvm_opts.iter()
+                .chain(launch_opts.user_jvm_opts.iter())
+                .chain(toolkit_opts.iter()));
             if args.is_err() {
-                message("Failed to create JVM arguments.\n\
-                Please contact the developers or undo any changes to the configuration.");
-                return None;
+                return Err(Some(CreateJvmError::InvalidOption));
             }
 
             if launch_opts.config.use_previous_jvm {
                 if let Some(old_jvm) = get_prev_made_jvm(&jvm_path) {
-                    return Some(old_jvm)
+                    return Ok(launched_with(old_jvm, &jvm_path, i, own_java_skipped))
                 }
             }
 
             // Create a new VM
-            let maybe_jvm = JavaVM::with_libjvm(args.unwrap(), path_getter);
-            match maybe_jvm {
-                Ok(vm) => { return Some(vm) }
+            set_stage(format!("creating the JVM from {}", jvm_path.display()));
+            #[cfg(feature = "updater")]
+            mark_update_tried();
+            match time(Phase::CreateJvm, || create_jvm(&jvm_path, &args.unwrap(), &launch_opts.config)) {
+                Ok(vm) => { return Ok(launched_with(vm, &jvm_path, i, own_java_skipped)) }
                 Err(e) => {
-                    println!("{:?}", e);
+                    info!("discovery", "Failed to create a JVM from {:?}: {:?}", jvm_path, e);
+
+                    // A single bad option should not make the app unlaunchable
+                    match retry_without_suspect_opts(launch_opts, &jvm_path) {
+                        Some(Ok(vm)) => { return Ok(launched_with(vm, &jvm_path, i, own_java_skipped)) }
+                        Some(Err(retry_e)) => last_error = Some(retry_e),
+                        None => last_error = Some(e),
+                    }
                     continue
                 }
             }
         }
     }
-    if count > 0 {
-        message("A valid Java installation was found, failed to start.\n\
-                Please check the launch arguments as they may be invalid.\n\
-                Please contact the developers.")
+    Err(last_error)
+}
+
+/// Keep the Java the JVM was created from, found in the `index`th place Java is looked for, to remember
+/// it for the next launch once the app has started. Let the user know the app runs with another Java
+/// than its own, which it may not work as well with, such as an older one.
+fn launched_with(jvm: JavaVM, jvm_path: &Path, index: usize, own_java_skipped: bool) -> JavaVM {
+    if let Ok(mut launched_from) = LAUNCHED_FROM.lock() {
+        *launched_from = Some((jvm_path.to_path_buf(), index));
+    }
+    if own_java_skipped {
+        notify(&text("notify.system_java"));
+    }
+    jvm
+}
+
+/// Keep the options choosing the AWT toolkit the JVM is created with, as they decide whether it has a `WM_CLASS`.
+fn remember_toolkit_opts(toolkit_opts: &[String]) {
+    if let Ok(mut opts) = LAUNCH_TOOLKIT_OPTS.lock() {
+        *opts = toolkit_opts.to_vec();
+    }
+}
+
+/// Try to create the JVM once more, leaving out the options that likely caused the failure:
+/// options from the Launch4j ini and the relaunch file, and malformed options, see [`is_suspect_opt`].<br>
+/// Returns `None` if there was nothing to leave out.
+fn retry_without_suspect_opts(launch_opts: &LaunchOpts, jvm_path: &PathBuf) -> Option<Result<JavaVM, CreateJvmError>> {
+    let runtime_modules = get_runtime_modules(jvm_path);
+    let (mut dropped, kept): (Vec<&String>, Vec<&String>) = launch_opts.jvm_opts.iter()
+        .partition(|o| is_suspect_opt(o, &runtime_modules));
+    dropped.extend(launch_opts.user_jvm_opts.iter());
+
+    if dropped.is_empty() {
+        return None;
+    }
+
+    info!("jni", "Retrying without the launch options {:?}", dropped);
+    let toolkit_opts = toolkit_opts(jvm_path, &launch_opts.config, &kept);
+    remember_toolkit_opts(&toolkit_opts);
+    let args = make_jvm_args(kept.into_iter().chain(toolkit_opts.iter())).ok()?;
+    let result = time(Phase::CreateJvm, || create_jvm(jvm_path, &args, &launch_opts.config));
+    match &result {
+        Ok(_) => {
+            info!("jni", "Started Java after dropping the launch options {:?}", dropped);
+            let dropped: Vec<&str> = dropped.iter().map(|o| o.as_str()).collect();
+            notify(&text_with("notify.dropped_options", &[&dropped.join(" ")]));
+        }
+        Err(e) => info!("discovery", "Failed to create a JVM from {:?}: {:?}", jvm_path, e),
+    }
+    Some(result)
+}
+
+/// The library search path of the OS before the launcher changed it,
+/// the outer `None` meaning it has not been read yet.
+static ORIGINAL_LIBRARY_PATH: Mutex<Option<Option<OsString>>> = Mutex::new(None);
+
+/// Make sure the dynamic libraries of the Java runtime and of the app can be found,
+/// including those loaded by other native libraries rather than by the JVM.<br>
+/// The app's native directories and the runtime's `bin` and `lib` folders are put in front of
+/// the OS library search path. On Linux and macOS the loader only reads it when a process starts,
+/// so there it applies to Java processes started by the launcher or the app.
+/// Subsequent calls replace the paths of the previous call.
+pub fn set_dynamic_library_lookup_loc(jvm_path: &PathBuf, launch_opts: &LaunchOpts) {
+    let original = match ORIGINAL_LIBRARY_PATH.lock() {
+        Ok(mut original) => original.get_or_insert_with(|| env::var_os(LIBRARY_PATH_VAR)).clone(),
+        Err(_) => return,
+    };
+
+    let mut paths = get_native_lib_dirs(&launch_opts.config);
+    if let Some(home) = jvm_path.parent().and_then(|p| p.parent()).and_then(|p| p.parent()) {
+        paths.push(home.join("bin"));
+        paths.push(home.join("lib"));
+    }
+    if let Some(original) = &original {
+        paths.extend(env::split_paths(original));
+    }
+    if let Ok(joined) = env::join_paths(paths) {
+        env::set_var(LIBRARY_PATH_VAR, joined);
     }
-    None
+
+    #[cfg(windows)]
+    set_dll_directory(jvm_path);
 }
 
 /// Sets the DLL path to the bin folder of the Java runtime,
@@ -151,24 +722,20 @@ fn try_launch_jvm(launch_opts: &LaunchOpts) -> Option<JavaVM> {
 ///
 /// see: https://docs.microsoft.com/en-us/windows/win32/api/winbase/nf-winbase-setdlldirectoryw
 #[cfg(windows)]
-fn set_dynamic_library_lookup_loc(jvm_path: &PathBuf) {
+fn set_dll_directory(jvm_path: &PathBuf) {
+    use std::iter::once;
     use winapi::um::winbase::{SetDllDirectoryW};
     use std::os::windows::ffi::OsStrExt;
     if let Some(jvm_dll_folder) = jvm_path.parent() {
         if let Some(bin) = jvm_dll_folder.parent() {
-            let bin_as_lpcwstr: Vec<u16> = bin.as_os_str().encode_wide().chain(once(0)).collect();
-            unsafe {
-                SetDllDirectoryW(bin_as_lpcwstr.as_ptr());
+            let bin_as_lpcwstr: Vec<u16> = long_path(bin).as_os_str().encode_wide().chain(once(0)).collect();
+            if unsafe { SetDllDirectoryW(bin_as_lpcwstr.as_ptr()) } == 0 {
+                info!("jni", "Failed to set the DLL directory to {:?}", bin);
             }
         }
     }
 }
 
-#[cfg(not(windows))]
-fn set_dynamic_library_lookup_loc(jvm_path: &PathBuf) {
-    // NO-OP at this time
-}
-
 /// Calls `DestroyJavaVM` of JNI - it blocks until all Java threads are closed <br>
 /// See <https://docs.oracle.com/en/java/javase/17/docs/specs/jni/invocation.html#unloading-the-vm>
 fn close_jvm(jvm: JavaVM) {
@@ -189,7 +756,7 @@ fn get_prev_made_jvm(jvm_path: &PathBuf) -> Option<JavaVM> {
     let mut jvm_buf: *mut sys::JavaVM = std::ptr::null_mut();
     let jvm_buf_ptr: *mut *mut sys::JavaVM = &mut jvm_buf;
     unsafe {
-        let lib = libloading::Library::new(jvm_path).ok()?;
+        let lib = libloading::Library::new(long_path(jvm_path)).ok()?;
         let f: libloading::
         Symbol<unsafe extern fn(vm_buf: *mut *mut sys::JavaVM, buf_len: jsize, n_vms: *mut jsize) -> jint> =
             lib.get(b"JNI_GetCreatedJavaVMs").ok()?;
@@ -202,16 +769,244 @@ fn get_prev_made_jvm(jvm_path: &PathBuf) -> Option<JavaVM> {
     }
 }
 
-/// Convert string args to the proper format and add to the launch args.<br>
-/// Sets the JVM to ignore unrecognized `-X` args and to expect calls to JNI 2
-fn make_jvm_args(launch_opts: &LaunchOpts) -> Result<InitArgs, JvmError> {
-    let mut jvm_args = InitArgsBuilder::new()
-        .version(JNIVersion::V2)// No touchy or things breaky
-        .ignore_unrecognized(true);
+/// Convert string args to the proper format for the JVM.
+fn make_jvm_args<'a, I: IntoIterator<Item=&'a String>>(opts: I) -> Result<Vec<CString>, NulError> {
+    let opts = opts.into_iter();
+    #[cfg(target_os = "macos")]
+    let opts = opts.filter(|o| !apply_dock_opt(o));
+    opts
+        .map(|o| encode_jvm_opt(o))
+        .collect()
+}
+
+/// The option in the encoding the JVM reads its options in, which is UTF-8 except on Windows.
+#[cfg(not(windows))]
+fn encode_jvm_opt(opt: &str) -> Result<CString, NulError> {
+    CString::new(opt)
+}
 
-    for jvm_opt in &launch_opts.jvm_opts {
-        jvm_args = jvm_args.option(jvm_opt.as_str());
+/// The option in the encoding the JVM reads its options in, the system's ANSI code page on Windows.
+/// Paths with characters the code page lacks, such as a Cyrillic user name on a Western system,
+/// are given by their short names instead, which are ASCII where the drive has them.
+#[cfg(windows)]
+fn encode_jvm_opt(opt: &str) -> Result<CString, NulError> {
+    if opt.is_ascii() {
+        return CString::new(opt);
+    }
+    if let Some(encoded) = ansi::encode(opt) {
+        return CString::new(encoded);
+    }
+    if let Some(encoded) = ansi::short_path_opt(opt).as_deref().and_then(ansi::encode) {
+        debug!("launcher", "Passing {:?} with short paths", opt);
+        return CString::new(encoded);
+    }
+    info!("launcher", "The option {:?} has characters the system's code page lacks, the JVM may not read it", opt);
+    CString::new(ansi::encode_lossy(opt))
+}
+
+#[cfg(windows)]
+mod ansi {
+    use std::ffi::{OsStr, OsString};
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use std::path::Path;
+    use std::ptr::{null, null_mut};
+    use winapi::shared::minwindef::BOOL;
+    use winapi::um::fileapi::GetShortPathNameW;
+    use winapi::um::stringapiset::WideCharToMultiByte;
+    use winapi::um::winnls::{CP_ACP, CP_UTF8, GetACP, WC_NO_BEST_FIT_CHARS};
+
+    /// The text in the ANSI code page, `None` if it has characters the code page lacks.
+    pub fn encode(text: &str) -> Option<Vec<u8>> {
+        let (encoded, lossy) = convert(text);
+        if lossy { None } else { Some(encoded) }
     }
 
-    jvm_args.build()
-}
\ No newline at end of file
+    /// The text in the ANSI code page, with `?` for the characters it lacks.
+    pub fn encode_lossy(text: &str) -> Vec<u8> {
+        convert(text).0
+    }
+
+    /// The text in the ANSI code page, and whether characters had to be replaced.
+    fn convert(text: &str) -> (Vec<u8>, bool) {
+        // Systems set to use UTF-8 have it as their code page, which takes no flags
+        if unsafe { GetACP() } == CP_UTF8 {
+            return (text.as_bytes().to_vec(), false);
+        }
+        let wide: Vec<u16> = OsStr::new(text).encode_wide().collect();
+        if wide.is_empty() {
+            return (vec![], false);
+        }
+        unsafe {
+            let size = WideCharToMultiByte(CP_ACP, WC_NO_BEST_FIT_CHARS, wide.as_ptr(), wide.len() as i32,
+                                           null_mut(), 0, null(), null_mut());
+            if size <= 0 {
+                return (text.as_bytes().to_vec(), true);
+            }
+            let mut encoded = vec![0u8; size as usize];
+            let mut lossy: BOOL = 0;
+            WideCharToMultiByte(CP_ACP, WC_NO_BEST_FIT_CHARS, wide.as_ptr(), wide.len() as i32,
+                                encoded.as_mut_ptr() as *mut _, size, null(), &mut lossy);
+            (encoded, lossy != 0)
+        }
+    }
+
+    /// The option with the existing paths of its value, after its first `=` or `:`, such as those of
+    /// `-Djava.class.path=` or `-javaagent:`, replaced by their short names.
+    pub fn short_path_opt(opt: &str) -> Option<String> {
+        let start = opt.find(|c| c == '=' || c == ':')? + 1;
+        let paths: Vec<String> = opt[start..].split(';')
+            .map(|p| if p.is_ascii() { p.to_string() } else { short_path(p).unwrap_or_else(|| p.to_string()) })
+            .collect();
+        Some(format!("{}{}", &opt[..start], paths.join(";")))
+    }
+
+    /// The short name of the existing path, `None` if it has none.
+    fn short_path(path: &str) -> Option<String> {
+        if !Path::new(path).exists() {
+            return None;
+        }
+        let wide: Vec<u16> = OsStr::new(path).encode_wide().chain(Some(0)).collect();
+        unsafe {
+            let size = GetShortPathNameW(wide.as_ptr(), null_mut(), 0);
+            if size == 0 {
+                return None;
+            }
+            let mut short = vec![0u16; size as usize];
+            let len = GetShortPathNameW(wide.as_ptr(), short.as_mut_ptr(), size);
+            if len == 0 || len >= size {
+                return None;
+            }
+            short.truncate(len as usize);
+            OsString::from_wide(&short).into_string().ok()
+        }
+    }
+}
+
+/// Why a JVM could not be created.
+#[derive(Debug)]
+enum CreateJvmError {
+    /// A launch option could not be passed to the JVM
+    InvalidOption,
+    /// The dynamic Java library could not be loaded
+    Load(libloading::Error),
+    /// The dynamic Java library does not provide `JNI_CreateJavaVM`
+    MissingEntryPoint,
+    /// `JNI_CreateJavaVM` failed with the given JNI error code
+    Jni(jint),
+}
+
+impl CreateJvmError {
+    /// Whether starting the `java` executable might work where creating the JVM in process did not.
+    /// Not the case for bad options or a lack of memory, as `java` runs into the same issue.
+    fn process_may_start(&self) -> bool {
+        !matches!(self, CreateJvmError::InvalidOption | CreateJvmError::Jni(JNI_ENOMEM))
+    }
+
+    /// A message for the user describing what went wrong and what they can do about it.
+    fn user_message(&self) -> String {
+        match self {
+            CreateJvmError::InvalidOption => text("jvm.invalid_option"),
+            CreateJvmError::Load(_) => text("jvm.load_failed"),
+            CreateJvmError::MissingEntryPoint => text("jvm.missing_entry_point"),
+            CreateJvmError::Jni(JNI_ENOMEM) => text("jvm.no_memory"),
+            CreateJvmError::Jni(JNI_EVERSION) => text("jvm.too_old"),
+            CreateJvmError::Jni(JNI_EINVAL) => text("jvm.rejected_args"),
+            CreateJvmError::Jni(JNI_EEXIST) => text("jvm.already_started"),
+            CreateJvmError::Jni(code) => text_with("jvm.failed", &[&code.to_string()]),
+        }
+    }
+}
+
+/// Load the dynamic Java library at `jvm_path` and create a new JVM from it with the given args.<br>
+/// The JNI version and whether unrecognized `-X` args are ignored come from the `config`,
+/// and the JVM reports its exit status to [`jvm_exit_hook`].<br>
+/// See <https://docs.oracle.com/en/java/javase/17/docs/specs/jni/invocation.html#jni_createjavavm>
+fn create_jvm(jvm_path: &PathBuf, args: &[CString], config: &LauncherConfig) -> Result<JavaVM, CreateJvmError> {
+    let mut options: Vec<JavaVMOption> = args.iter()
+        .map(|a| JavaVMOption {
+            optionString: a.as_ptr() as *mut c_char,
+            extraInfo: ptr::null_mut(),
+        })
+        .collect();
+    options.push(JavaVMOption {
+        optionString: b"exit\0".as_ptr() as *mut c_char,
+        extraInfo: jvm_exit_hook as *mut c_void,
+    });
+
+    debug!("jni", "Creating the JVM from {:?} with the options {:?}", jvm_path, args);
+    let mut init_args = JavaVMInitArgs {
+        version: config.jni_version,
+        nOptions: options.len() as jint,
+        options: options.as_mut_ptr(),
+        ignoreUnrecognized: if config.strict_jvm_options { JNI_FALSE } else { JNI_TRUE },
+    };
+
+    // Loaded from its absolute path, as the JVM finds its runtime from it, before changing to the app's
+    // working directory, which the JVM takes as `user.dir`
+    let launcher_dir = env::current_dir().ok();
+    let library = launcher_dir.as_ref().map_or_else(|| jvm_path.clone(), |d| d.join(jvm_path));
+    let working_dir = app_working_dir(config);
+    if let Some(dir) = &working_dir {
+        match env::set_current_dir(dir) {
+            Ok(_) => info!("launcher", "Running the app in {:?}", dir),
+            Err(e) => info!("launcher", "Failed to change to {:?}: {}", dir, e),
+        }
+    }
+    let created = load_and_create_jvm(&library, &mut init_args);
+    // The next Java is looked for in the launcher's directory again
+    if let (Err(_), Some(_), Some(launcher_dir)) = (&created, &working_dir, &launcher_dir) {
+        let _ = env::set_current_dir(launcher_dir);
+    }
+    created
+}
+
+/// Load the Java dynamic library and create the JVM with its `JNI_CreateJavaVM`.
+fn load_and_create_jvm(jvm_path: &Path, init_args: &mut JavaVMInitArgs) -> Result<JavaVM, CreateJvmError> {
+    unsafe {
+        let lib = libloading::Library::new(long_path(jvm_path)).map_err(CreateJvmError::Load)?;
+        let result = {
+            let f: libloading::Symbol<unsafe extern "system" fn(pvm: *mut *mut sys::JavaVM,
+                                                                   penv: *mut *mut c_void,
+                                                                   args: *mut c_void) -> jint> =
+                lib.get(b"JNI_CreateJavaVM").map_err(|_| CreateJvmError::MissingEntryPoint)?;
+            let mut vm: *mut sys::JavaVM = ptr::null_mut();
+            let mut env: *mut c_void = ptr::null_mut();
+            let r = f(&mut vm, &mut env, init_args as *mut JavaVMInitArgs as *mut c_void);
+            if r == JNI_OK { Ok(vm) } else { Err(CreateJvmError::Jni(r)) }
+        };
+
+        // The library must stay loaded for as long as the process lives,
+        // even a failed creation may have started threads of the JVM
+        std::mem::forget(lib);
+
+        JavaVM::from_raw(result?).map_err(|_| CreateJvmError::Jni(JNI_ERR))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_adds_path_opts() {
+        let opts = LaunchOptsBuilder::new()
+            .main_class("a.Main")
+            .classpath("app.jar")
+            .classpath("lib/dep.jar")
+            .jvm_opt("-Xss4m")
+            .program_arg("--open")
+            .build()
+            .expect("options should build");
+        assert_eq!(opts.config.main_class.as_deref(), Some("a.Main"));
+        assert!(opts.jvm_opts.contains(&format!("-Djava.class.path=app.jar{}lib/dep.jar", PATH_SEPARATOR)));
+        assert_eq!(opts.jvm_opts.last().map(String::as_str), Some("-Xss4m"));
+        assert_eq!(opts.program_opts, vec!["--open".to_string()]);
+    }
+
+    #[test]
+    fn builder_requires_something_to_launch() {
+        assert!(LaunchOptsBuilder::new().classpath("app.jar").build().is_err());
+        assert!(LaunchOptsBuilder::new().main_class("a.Main").classpath("app.jar").min_java(21).max_java(17)
+            .build().is_err());
+    }
+}
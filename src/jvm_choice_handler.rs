@@ -0,0 +1,87 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::display_handler::choose;
+use crate::file_handler::{get_app_name, get_jvm_candidates, get_user_data_dir};
+use crate::launch_config::LauncherConfig;
+use crate::locale_handler::{text, text_with};
+
+/// The file remembering the Java dynamic library of the installation the user chose.
+fn get_choice_file() -> Option<PathBuf> {
+    Some(get_user_data_dir()?.join(get_app_name() + ".jvm"))
+}
+
+/// The installation's home, the directory with its `release` file, for showing it to the user.
+fn java_home(library: &Path) -> &Path {
+    library.ancestors().find(|d| d.join("release").is_file())
+        .or(library.parent())
+        .unwrap_or(library)
+}
+
+/// Let the user choose the installation the app is run with from those it could be, for
+/// `--why-choose-jvm`, setting the config's `jvm_install` to it and remembering it for the next launches,
+/// see [`use_chosen_jvm`].<br>
+/// Returns whether to launch, `false` if the user chose none. Without an installation to choose from,
+/// Java is looked for as usual, so that the user is told how to get it.
+pub fn choose_jvm(config: &mut LauncherConfig) -> bool {
+    // Places searched more than once find the same installation
+    let mut choices: Vec<(PathBuf, String)> = vec![];
+    for candidate in get_jvm_candidates(config) {
+        match (candidate.library, candidate.version) {
+            (Some(library), Some(version)) if candidate.rejection.is_none()
+                && !choices.iter().any(|(l, _)| *l == library) => choices.push((library, version)),
+            _ => {}
+        }
+    }
+    if choices.is_empty() {
+        info!("discovery", "No Java installation to choose from");
+        return true;
+    }
+
+    let items: Vec<String> = choices.iter()
+        .map(|(library, version)| text_with("jvm.choice", &[version, &java_home(library).display().to_string()]))
+        .collect();
+    let library = match choose(&text("jvm.choose"), &items) {
+        Some(i) => &choices[i].0,
+        None => {
+            info!("discovery", "No Java installation was chosen");
+            return false;
+        }
+    };
+    info!("discovery", "Chose {:?}", library);
+
+    if let Some(choice_file) = get_choice_file() {
+        let written = choice_file.parent().map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&choice_file, library.display().to_string()));
+        if let Err(e) = written {
+            info!("discovery", "Failed to remember the chosen Java in {:?}: {:?}", choice_file, e);
+        }
+    }
+    config.jvm_path = Some(library.display().to_string());
+    true
+}
+
+/// Set the config's `jvm_install` to the installation chosen with `--why-choose-jvm` on an earlier launch,
+/// unless it is gone or the app can no longer run with it, such as after an update raised `min_java`.
+pub fn use_chosen_jvm(config: &mut LauncherConfig) {
+    let library = match get_choice_file().and_then(|f| fs::read_to_string(f).ok()) {
+        Some(library) => PathBuf::from(library.trim()),
+        None => return,
+    };
+
+    // Checked as the launch would
+    let previous = config.jvm_path.replace(library.display().to_string());
+    let chosen = get_jvm_candidates(config).into_iter()
+        .find(|c| c.source == "jvm_install")
+        .filter(|c| c.library.as_deref() == Some(library.as_path()));
+    match chosen {
+        Some(candidate) if candidate.rejection.is_none() => {
+            debug!("discovery", "Using the chosen Java {:?}", library);
+        }
+        candidate => {
+            let reason = candidate.and_then(|c| c.rejection).unwrap_or_else(|| "it is gone".to_string());
+            info!("discovery", "Not using the chosen Java {:?}, {}", library, reason);
+            config.jvm_path = previous;
+        }
+    }
+}
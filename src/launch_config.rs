@@ -1,11 +1,37 @@
-use std::fs::File;
+use std::env;
+use std::fs::{self, File};
 use std::io;
-use std::io::BufRead;
-use std::path::Path;
+use std::io::{BufRead, Read};
+use std::path::{Path, PathBuf};
 
 use config::{Config, FileFormat};
+use jni::sys::{jint, JNI_VERSION_1_2};
 use sysinfo::{System, SystemExt};
+#[cfg(target_os = "macos")]
+use crate::bundle_handler::{get_bundle_contents, read_bundle_config};
+use crate::file_handler::{get_app_dir, get_app_name, get_launch_dir, get_relaunch_file, normalize_path_list, resolve_path};
+use crate::hook_handler::{HookFailure, Hooks, split_command};
+use crate::manifest_handler::{read_jar_manifest, resolve_class_path, resolve_class_path_transitively,
+                              set_strict_manifests};
 use crate::get_java_version_of_main;
+use crate::permission_handler::PermissionCheck;
+use crate::signature_handler::SignatureCheck;
+
+/// The config file, in the current working directory.
+pub const CONFIG_FILE: &str = "launcher.ini";
+
+/// The config built into the launcher with `WHY_EMBEDDED_CONFIG`, see `build.rs`, empty if none was.
+const EMBEDDED_CONFIG: &str = include_str!(concat!(env!("OUT_DIR"), "/embedded_config.ini"));
+
+/// The JNI version used by default, JNI 2 works with any Java to date.
+const DEFAULT_JNI_VERSION: jint = JNI_VERSION_1_2;
+
+/// Where users are sent to get Java when none is suitable, see `java_download_url`.
+const DEFAULT_JAVA_DOWNLOAD_URL: &str =
+    "https://adoptium.net/temurin/releases/?os=$OS$&arch=$ARCH$&package=jre&version=$VERSION$";
+
+/// How many levels of nested `Class-Path` manifests are followed for `transitive_class_path`.
+const MAX_CLASS_PATH_DEPTH: usize = 8;
 
 /// These module paths must be in the form of opt=value
 const MODULE_OPTS: &'static [&str] = &["--add-reads", "--add-exports", "--add-opens",
@@ -17,6 +43,76 @@ const MODULE_OPTS: &'static [&str] = &["--add-reads", "--add-exports", "--add-op
 /// These options are assumed to contain numbers
 const STANDARD_OPTS: &'static [&str] = &["-Xmx", "-Xms"];
 
+/// How the launcher starts the app.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LaunchMode {
+    /// Create the JVM in the launcher's process through JNI
+    Jni,
+    /// Start a separate process with the `java` executable of the Java installation.
+    /// Needed for features that only the real `java` launcher supports, such as `-splash`
+    Process,
+}
+
+impl LaunchMode {
+    /// Parse the value of the `launch_mode` key, unknown values fall back to [`LaunchMode::Jni`].
+    fn from_key(key: &str) -> Self {
+        match key.trim().to_lowercase().as_str() {
+            "process" => LaunchMode::Process,
+            _ => LaunchMode::Jni,
+        }
+    }
+}
+
+/// Which AWT toolkit the app uses in a Wayland session on Linux.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum WaylandToolkit {
+    /// X11 through XWayland, the only one most Java versions have.
+    XWayland,
+    /// Wayland itself, with the `WLToolkit` of Java versions that have it, else as [`WaylandToolkit::XWayland`].
+    Native,
+    /// Whatever the Java version uses by default.
+    Off,
+}
+
+impl WaylandToolkit {
+    /// Parse the value of the `wayland_toolkit` key, unknown values fall back to [`WaylandToolkit::XWayland`].
+    fn from_key(key: &str) -> Self {
+        match key.trim().to_lowercase().as_str() {
+            "native" => WaylandToolkit::Native,
+            "off" => WaylandToolkit::Off,
+            _ => WaylandToolkit::XWayland,
+        }
+    }
+}
+
+/// A file type the app opens, an entry of the `[FileAssociations]` section of launcher.ini
+/// as `<extension> = <MIME type>; <description>; <icon>`, the description and icon being optional.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileAssociation {
+    /// Without the leading dot, such as `fits`.
+    pub extension: String,
+    pub mime_type: String,
+    /// Shown for the type in file managers, such as `FITS image`.
+    pub description: Option<String>,
+    /// The icon shown for files of the type, a path that can be relative.
+    pub icon: Option<String>,
+}
+
+impl FileAssociation {
+    /// Parse an entry of the `[FileAssociations]` section, `None` without a MIME type.
+    fn from_entry(extension: &str, value: &str) -> Option<Self> {
+        let mut fields = value.split(';').map(str::trim);
+        let mime_type = fields.next().filter(|m| m.contains('/'))?.to_string();
+        let mut field = || fields.next().filter(|f| !f.is_empty()).map(str::to_string);
+        Some(FileAssociation {
+            extension: extension.trim().trim_start_matches('.').to_lowercase(),
+            mime_type,
+            description: field(),
+            icon: field(),
+        })
+    }
+}
+
 /// These are read in from launcher.ini from the current working directory
 #[derive(Debug)]
 pub struct LauncherConfig {
@@ -32,6 +128,10 @@ pub struct LauncherConfig {
     /// key: launch_options; format: String (path), can be relative by preceding with './';
     /// what it does: the Launch4J-style config to read JVM options from
     pub launch_options_file: Option<String>,
+    /// key: read_l4j_ini; format: bool;
+    /// what it does: whether JVM options are also read from the Launch4j `<exe>.l4j.ini` beside the launcher,
+    /// after those of `launch_options`, for apps switching from Launch4j whose users edit it.
+    pub read_l4j_ini: bool,
     /// key: classpath; format: same as the launch argument - ';' separated paths;
     /// what it does: sets the classpath; If given a jar, it will respect the jar
     /// manifest's classpath entry
@@ -40,6 +140,12 @@ pub struct LauncherConfig {
     /// key: min_java; format: integer; what it does: only tries to run Java that is
     /// equal to or greater than this Java version
     pub min_java: Option<i64>,
+    /// key: max_java; format: integer; what it does: only tries to run Java that is
+    /// equal to or less than this Java version
+    pub max_java: Option<i64>,
+    /// key: enable_preview; format: boolean; what it does: enables the preview features of
+    /// the Java version, also turned on when the main class was compiled with them
+    pub enable_preview: bool,
     /// key: allow_system_java; format: boolean; what it does: whether the launcher
     /// should use the Java listed in JAVA_HOME
     pub allows_system_java: bool,
@@ -47,13 +153,21 @@ pub struct LauncherConfig {
     /// what it does: whether the launcher should check common Java
     /// installation directories for a Java install
     pub allows_java_location_lookup: bool,
+    /// key: jvm_allowed_paths; format: String (paths separated by `;`);
+    /// what it does: only load Java from under these directories, such as the bundled runtime,
+    /// refusing Java found anywhere else, such as through JAVA_HOME.
+    pub jvm_allowed_paths: Option<String>,
     /// key: maximum_heap_percentage; format: integer;
-    /// what it does: sets the -Xmx to this value if missing from the launch args.
+    /// what it does: sets the -Xmx to this percentage of the memory, or of the cgroup memory
+    /// limit if lower, if missing from the launch args.
     pub max_mem_percent: Option<i64>,
     /// key: check_main_class; format: boolean;
     /// what it does: whether the launcher should check check the main class' Java version
     /// requirement and use that as the min_java if the current min_java is not specified or
     /// less than the found main class requirement. Otherwise, use the specified min_java.
+    /// The version found is kept in `<app>.index` in the per-user data dir, and the jars are only
+    /// read again once one of the classpath's jars, or the main class in one of its directories,
+    /// changed size or modification time.
     pub check_main_class: bool,
     /// key: use_previous_jvm; format: boolean;
     /// what it does: whether the launcher should check if the selected JVM has a previous
@@ -61,6 +175,203 @@ pub struct LauncherConfig {
     /// On Linux and Windows, it is typical to open a new instance of the application for file
     /// associations, this allows that behavior to be overruled.
     pub use_previous_jvm: bool,
+    /// key: install_exception_handler; format: boolean;
+    /// what it does: whether the launcher should install a default uncaught exception handler
+    /// before the main class is run, so that exceptions of threads without a handler of their
+    /// own are reported by the launcher.
+    pub install_exception_handler: bool,
+    /// key: show_crash_dialog; format: boolean;
+    /// what it does: whether exceptions caught by the launcher's uncaught exception handler
+    /// should also be shown in a dialog.
+    pub show_crash_dialog: bool,
+    /// key: restart_exit_code; format: integer;
+    /// what it does: if the app exits with this status, the launcher starts it again,
+    /// reading the config and looking for Java anew, and exits with the status of the restarted app.
+    /// This allows apps to restart themselves, such as after an update.
+    pub restart_exit_code: Option<i64>,
+    /// key: launch_mode; format: String, either `jni` or `process`;
+    /// what it does: whether the JVM is created in the launcher's process,
+    /// or the app is run in a separate process with the `java` executable of the Java installation.
+    pub launch_mode: LaunchMode,
+    /// key: process_fallback; format: boolean;
+    /// what it does: whether the launcher should run the app in a separate Java process
+    /// if the JVM could not be created in the launcher's process.
+    pub process_fallback: bool,
+    /// key: jni_version; format: String, such as `1.2`, `1.8` or `10`;
+    /// what it does: the JNI version the JVM is asked to support, older Java that does not support
+    /// it will fail to start.
+    pub jni_version: jint,
+    /// key: strict_jvm_options; format: boolean;
+    /// what it does: whether the JVM should fail to start on unrecognized `-X` options,
+    /// rather than ignoring them. Useful during development to catch typos.
+    pub strict_jvm_options: bool,
+    /// key: preflight_class; format: String (as it would appear in a jar manifest);
+    /// what it does: a class whose main method is run before the main class, in the same JVM,
+    /// such as an updater or migration step. The main class is only run if it returns normally.
+    pub preflight_class: Option<String>,
+    /// key: startup_timeout; format: integer (seconds);
+    /// what it does: if the main class has not been loaded after this long, the user is asked
+    /// whether to keep waiting, save diagnostic information, or quit.
+    pub startup_timeout: Option<i64>,
+    /// key: kill_child_processes; format: boolean;
+    /// what it does: Windows only, whether processes started by the app are killed
+    /// when the launcher exits, rather than being left behind.
+    pub kill_child_processes: bool,
+    /// key: log_dir; format: path;
+    /// what it does: the directory logs and crash reports are kept in,
+    /// instead of the `logs` folder in the per-user data dir.
+    pub log_dir: Option<String>,
+    /// key: capture_output; format: boolean;
+    /// what it does: whether the output of the launcher, JVM and app is written to
+    /// `output.log` in the log dir when nothing else receives it.
+    pub capture_output: bool,
+    /// key: utf8_encoding; format: boolean;
+    /// what it does: whether `file.encoding` and `sun.jnu.encoding` are set to UTF-8,
+    /// rather than left to the legacy code page Windows may give the JVM.
+    pub utf8_encoding: bool,
+    /// key: use_os_locale; format: boolean;
+    /// what it does: whether `user.language` and `user.country` are set from the OS locale.
+    pub use_os_locale: bool,
+    /// key: native_libs; format: ';' separated paths;
+    /// what it does: the directories of the app's native libraries, which are added
+    /// to `java.library.path`. Defaults to the `lib` and `natives` folders next to the launcher.
+    pub native_libs: Option<String>,
+    /// key: tmpdir; format: path;
+    /// what it does: the directory Java keeps temporary files in, `java.io.tmpdir`.
+    /// It is created if needed.
+    pub tmpdir: Option<String>,
+    /// key: mainmodule; format: module name, optionally followed by '/' and the main class;
+    /// what it does: launches the app as a module, using the main class of its module
+    /// descriptor unless one is given. Takes the place of `mainclass`.
+    pub main_module: Option<String>,
+    /// key: module_path; format: ';' separated paths;
+    /// what it does: sets the module path, the same as `--module-path`.
+    pub module_path: Option<String>,
+    /// key: add_module_path_modules; format: boolean;
+    /// what it does: without `mainmodule`, whether all modules on the module path are added
+    /// with `--add-modules`, not only those of JavaFX.
+    pub add_module_path_modules: bool,
+    /// key: detect_javafx; format: boolean;
+    /// what it does: whether JavaFX jars on the classpath are moved to the module path,
+    /// and the JavaFX modules found there added with `--add-modules`.
+    pub detect_javafx: bool,
+    /// key: main_jar; format: path;
+    /// what it does: the app's jar, like `java -jar`, or the directory of an exploded jar. Its manifest gives
+    /// the main class and the jars of its `Class-Path`, unless `mainclass` and `classpath` are set.
+    pub main_jar: Option<String>,
+    /// key: transitive_class_path; format: boolean;
+    /// what it does: whether the `Class-Path` of the jars in `main_jar`'s `Class-Path`
+    /// is followed too, as `java -jar` does.
+    pub transitive_class_path: bool,
+    /// key: strict_manifest; format: boolean;
+    /// what it does: whether jar manifests that do not follow the JAR spec are rejected,
+    /// rather than read as well as possible.
+    pub strict_manifest: bool,
+    /// key: verify_signatures; format: false, warn or true;
+    /// what it does: whether the signature of the app's jar is checked before launching,
+    /// warning the user or refusing to launch if its contents were changed after signing.
+    pub verify_signatures: SignatureCheck,
+    /// key: trusted_signers; format: String (SHA-256 fingerprints separated by `;`);
+    /// what it does: the certificates the app's jars must be signed with for `verify_signatures`,
+    /// by their SHA-256 fingerprints as `keytool -printcert` prints them.
+    pub trusted_signers: Vec<String>,
+    /// key: verify_integrity; format: bool;
+    /// what it does: check the app's files against the SHA-256 hashes `integrity.sha256` in the app image lists
+    /// before launching, refusing to launch if any are missing or modified.
+    pub verify_integrity: bool,
+    /// key: check_permissions; format: false, warn or true;
+    /// what it does: check that other users cannot change the Java runtime, the app's jar or the config,
+    /// `warn` tells the user if they can, `true` also refuses to launch, or to use the runtime.
+    pub check_permissions: PermissionCheck,
+    /// key: agents_dir; format: path;
+    /// what it does: the directory of Java agent jars, each with a `Premain-Class` is added
+    /// with `-javaagent`. Defaults to the `agents` folder next to the launcher.
+    pub agents_dir: Option<String>,
+    /// key: disabled_agents; format: ';' separated file names;
+    /// what it does: agent jars in the agents dir that are not used.
+    pub disabled_agents: Option<String>,
+    /// key: java_download_url; format: URL;
+    /// what it does: the page offered to the user when no suitable Java is found,
+    /// with `$VERSION$`, `$OS$` and `$ARCH$` replaced to pick the download.
+    pub java_download_url: String,
+    /// key: app_name; format: string;
+    /// what it does: the name the launcher's dialogs are titled with,
+    /// defaults to the name of the launcher.
+    pub app_name: Option<String>,
+    /// key: icon; format: path;
+    /// what it does: the `.ico` (or `.png` on Linux) shown in the launcher's dialogs,
+    /// defaults to the launcher's own icon.
+    pub icon: Option<String>,
+    /// key: log_to_event_log; format: boolean;
+    /// what it does: whether launch failures are also reported to the Windows Event Log.
+    pub log_to_event_log: bool,
+    /// key: log_to_journal; format: boolean;
+    /// what it does: whether launch failures are also reported to the systemd journal on Linux.
+    pub log_to_journal: bool,
+    /// key: log_to_os_log; format: boolean;
+    /// what it does: whether launch failures are also reported to the unified log on macOS.
+    pub log_to_os_log: bool,
+    /// section: FileAssociations; format: `<extension> = <MIME type>; <description>; <icon>` per file type;
+    /// what it does: the file types registered for the app with `--why-register-filetypes`.
+    pub file_associations: Vec<FileAssociation>,
+    /// key: read_info_plist; format: bool;
+    /// what it does: on macOS, whether the main class, JVM options and Java are read from the app bundle's
+    /// `Info.plist`, as appbundler writes them, over those configured here.
+    /// Also done when there is no launcher.ini.
+    pub read_info_plist: bool,
+    /// key: wayland_toolkit; format: String, either `xwayland`, `native` or `off`;
+    /// what it does: which AWT toolkit the app uses in a Wayland session on Linux, unless its options set one.
+    pub wayland_toolkit: WaylandToolkit,
+    /// key: detect_ui_scale; format: bool;
+    /// what it does: whether the app is scaled as the desktop is on Linux, with `GDK_SCALE`, `GDK_DPI_SCALE`
+    /// or `Xft.dpi`, unless its options set `sun.java2d.uiScale`.
+    pub detect_ui_scale: bool,
+    /// key: wm_class; format: String;
+    /// what it does: the `WM_CLASS` of the app's windows on Linux, which desktops group them by,
+    /// instead of the app's name. Empty to leave it to Java, such as for command line apps.
+    pub wm_class: Option<String>,
+    /// key: app_version; format: String;
+    /// what it does: the version of the app, which update checks compare the newest version with.
+    pub app_version: Option<String>,
+    /// key: update_url; format: String;
+    /// what it does: the URL of a JSON file or Sparkle appcast describing the newest version of the app,
+    /// checked for a newer version than `app_version` when the app starts.
+    pub update_url: Option<String>,
+    /// key: update_check_hours; format: integer;
+    /// what it does: how many hours to wait after checking for updates before checking again.
+    pub update_check_hours: i64,
+    /// key: auto_update; format: bool;
+    /// what it does: download the update package of a newer version and install it the next time the app
+    /// starts, instead of offering to open its release page.
+    pub auto_update: bool,
+    /// key: update_public_key; format: String;
+    /// what it does: the Ed25519 public key, as base64, the update packages `auto_update` installs must be
+    /// signed with. A key built into the launcher with `WHY_UPDATE_PUBLIC_KEY` takes precedence.
+    pub update_public_key: Option<String>,
+    /// key: portable; format: bool;
+    /// what it does: whether the launcher keeps its logs, caches and the relaunch options in a `data` folder
+    /// beside the executable instead of the user's data dir, as does a `portable` file beside it.
+    pub portable: bool,
+    /// key: require_elevation; format: bool;
+    /// what it does: whether the launcher starts itself again as an administrator on Windows,
+    /// asking the user with a UAC prompt, for apps that need it.
+    pub require_elevation: bool,
+    /// key: pid_file; format: path;
+    /// what it does: where `--why-daemon` writes the launcher's process id, instead of `<app>.pid` in the data dir.
+    pub pid_file: Option<String>,
+    /// key: preserve_cwd; format: bool;
+    /// what it does: whether the app runs in the working directory the launcher was started in, instead of the
+    /// launcher's, for command line apps taking relative paths. It is passed as `user.launch.dir` too.
+    pub preserve_cwd: bool,
+    /// section: Hooks; format: `pre-launch = <command>; <command>`, each a program and its arguments,
+    /// quoted with `"` where they have spaces, the value in `'` quotes if it starts with one,
+    /// and `pre-launch-failure = abort` or `continue`, as for `first-run`;
+    /// what it does: commands the launcher runs and waits for, see [`Hooks`].
+    pub hooks: Hooks,
+    /// Not a key: the `JVMOptions` of the `Info.plist`, see `read_info_plist`.
+    pub bundle_jvm_options: Vec<String>,
+    /// Not a key: the file the config was read from, `None` if none was read and the defaults are used.
+    pub config_file: Option<PathBuf>,
 }
 
 /// Sets the defaults
@@ -71,12 +382,68 @@ impl Default for LauncherConfig {
             main_class: None,
             classpath: None,
             min_java: None,
+            max_java: None,
+            enable_preview: false,
             max_mem_percent: None,
             launch_options_file: None,
+            read_l4j_ini: false,
             allows_system_java: true,
             allows_java_location_lookup: true,
+            jvm_allowed_paths: None,
             check_main_class: true,
             use_previous_jvm: false,
+            install_exception_handler: false,
+            show_crash_dialog: true,
+            restart_exit_code: None,
+            launch_mode: LaunchMode::Jni,
+            process_fallback: true,
+            jni_version: DEFAULT_JNI_VERSION,
+            strict_jvm_options: false,
+            preflight_class: None,
+            startup_timeout: None,
+            kill_child_processes: false,
+            log_dir: None,
+            capture_output: true,
+            utf8_encoding: false,
+            use_os_locale: false,
+            native_libs: None,
+            tmpdir: None,
+            main_module: None,
+            module_path: None,
+            add_module_path_modules: false,
+            detect_javafx: true,
+            main_jar: None,
+            transitive_class_path: false,
+            strict_manifest: false,
+            verify_signatures: SignatureCheck::Off,
+            trusted_signers: vec![],
+            verify_integrity: false,
+            check_permissions: PermissionCheck::Off,
+            agents_dir: None,
+            disabled_agents: None,
+            java_download_url: DEFAULT_JAVA_DOWNLOAD_URL.to_string(),
+            app_name: None,
+            icon: None,
+            log_to_event_log: false,
+            log_to_journal: false,
+            log_to_os_log: false,
+            file_associations: vec![],
+            read_info_plist: false,
+            wayland_toolkit: WaylandToolkit::XWayland,
+            detect_ui_scale: true,
+            wm_class: None,
+            app_version: None,
+            update_url: None,
+            update_check_hours: 24,
+            auto_update: false,
+            update_public_key: None,
+            portable: false,
+            require_elevation: false,
+            pid_file: None,
+            preserve_cwd: false,
+            hooks: Hooks::default(),
+            bundle_jvm_options: vec![],
+            config_file: None,
         }
     }
 }
@@ -84,53 +451,274 @@ impl Default for LauncherConfig {
 impl LauncherConfig {
     /// Ensure that enough information is provided to actually start Java
     pub fn validate(&self) -> bool {
-        self.main_class.is_some() && self.classpath.is_some()
+        (self.main_class.is_some() || self.main_module.is_some())
+            && (self.classpath.is_some() || self.module_path.is_some())
+    }
+
+    /// The keys, or manifest attributes, of which one is needed to start Java but none is set,
+    /// empty if the config is valid, see [`LauncherConfig::validate`].
+    pub fn missing_keys(&self) -> Vec<String> {
+        let mut missing = vec![];
+        if self.main_class.is_none() && self.main_module.is_none() {
+            missing.push(match &self.main_jar {
+                Some(main_jar) => format!("mainclass, mainmodule or a Main-Class attribute in the manifest of {}",
+                                          resolve_path(main_jar).display()),
+                None => "mainclass, mainmodule or main_jar with a Main-Class attribute in its manifest".to_string(),
+            });
+        }
+        if self.classpath.is_none() && self.module_path.is_none() {
+            missing.push("classpath, module_path or main_jar".to_string());
+        }
+        missing
+    }
+
+    /// The `min_java` and `max_java` if no Java can match both, such as when the main class uses preview
+    /// features of an older Java than `min_java`, see [`LauncherConfig::ensure_correct_java`].
+    pub fn java_conflict(&self) -> Option<(i64, i64)> {
+        match (self.min_java, self.max_java) {
+            (Some(min), Some(max)) if min > max => Some((min, max)),
+            _ => None,
+        }
+    }
+
+    /// Whether launch failures are reported to the log of this OS,
+    /// see `log_to_event_log`, `log_to_journal` and `log_to_os_log`.
+    pub fn logs_to_system(&self) -> bool {
+        if cfg!(windows) {
+            self.log_to_event_log
+        } else if cfg!(target_os = "macos") {
+            self.log_to_os_log
+        } else {
+            self.log_to_journal
+        }
     }
 
     /// Read `launcher.ini` and setup the launcher config.<br>
     /// This will also ensure that the main class can be run by the minimum Java requirement,
     /// if enabled.
     pub fn read_file() -> Self {
-        let config_file = Config::builder()
-            .add_source(config::File::new("launcher.ini", FileFormat::Ini))
-            .build();
-        return if let Ok(c) = config_file {
-            let mut cfg = LauncherConfig {
-                main_class: c.get_string("mainclass").ok(),
-                classpath: c.get_string("classpath").ok(),
-                jvm_path: c.get_string("jvm_install").ok(),
-                min_java: c.get_int("min_java").ok(),
-                launch_options_file: c.get_string("launch_options").ok(),
-                allows_system_java: c.get_bool("allow_system_java").unwrap_or(true),
-                allows_java_location_lookup: c.get_bool("allow_java_location_lookup").unwrap_or(true),
-                max_mem_percent: c.get_int("maximum_heap_percentage").ok(),
-                check_main_class: c.get_bool("check_main_class").unwrap_or(true),
-                use_previous_jvm: c.get_bool("use_previous_jvm").unwrap_or(false),
-                ..Default::default()
+        // The config built into the launcher, if any, with the keys of launcher.ini over its own
+        let file_text = fs::read_to_string(CONFIG_FILE).ok();
+        let sources: Vec<&str> = [Some(EMBEDDED_CONFIG).filter(|c| !c.is_empty()), file_text.as_deref()]
+            .into_iter().flatten().collect();
+        let config_file = if sources.is_empty() {
+            Err(format!("No {} found", CONFIG_FILE))
+        } else {
+            Self::parse_config_sources(&sources)
+        };
+        return if let Ok(mut cfg) = config_file {
+            cfg.config_file = match file_text {
+                Some(_) => env::current_dir().ok().map(|d| d.join(CONFIG_FILE)),
+                None => {
+                    info!("config", "No launcher.ini read, using the config built into the launcher");
+                    env::current_exe().ok()
+                }
             };
+            #[cfg(target_os = "macos")]
+            if cfg.read_info_plist && !cfg.apply_info_plist() {
+                info!("config", "No appbundler keys found in the app bundle's Info.plist");
+            }
+            set_strict_manifests(cfg.strict_manifest);
+            cfg.apply_main_jar();
             cfg.ensure_correct_java();
+            debug!("config", "Read launcher.ini: {:?}", cfg);
             cfg
         } else {
-            Default::default()
+            let mut cfg: LauncherConfig = Default::default();
+            // Bundles made with appbundler have their config in the Info.plist
+            #[cfg(target_os = "macos")]
+            if cfg.apply_info_plist() {
+                info!("config", "No launcher.ini read, using the app bundle's Info.plist");
+                cfg.read_info_plist = true;
+                cfg.config_file = get_bundle_contents().map(|c| c.join("Info.plist"));
+                cfg.ensure_correct_java();
+                debug!("config", "Read Info.plist: {:?}", cfg);
+                return cfg;
+            }
+            info!("config", "No launcher.ini read, using the defaults");
+            cfg
         };
     }
 
-    /// Read `launch_options_file` into a series of launch options,
-    /// sanitizing and correcting where possible.
-    pub fn read_launch_opts(&self) -> Vec<String> {
-        let mut out: Vec<String> = vec![];
-        if self.launch_options_file.as_ref().is_some() {
-            if let Ok(lines) = read_lines(self.launch_options_file.as_ref().unwrap().as_str()) {
-                // Consumes the iterator, returns an (Optional) String
-                for line in lines {
-                    if let Ok(ip) = line {
-                        let sanitized_line = verify_line(ip);
-                        let mut opts = parse_line(sanitized_line).iter()
-                            .map(|o| verify_opt(o.to_owned())).collect();
-                        out.append(&mut opts)
-                    }
+    /// Parse a config in the format of `launcher.ini`, only taking its keys as they are, without
+    /// looking at the files they name or the main class, which [`LauncherConfig::read_file`] does.<br>
+    /// Fails if it is not valid INI.
+    pub fn parse_config_from<R: BufRead>(mut reader: R) -> Result<Self, String> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text).map_err(|e| e.to_string())?;
+        Self::parse_config_sources(&[&text])
+    }
+
+    /// Whether `portable` is set in `launcher.ini` or the config built into the launcher, read ahead of
+    /// the rest of the config, as reading that already uses the data dir.
+    pub fn read_portable() -> bool {
+        let file_text = fs::read_to_string(CONFIG_FILE).ok();
+        Self::is_portable(&[EMBEDDED_CONFIG, file_text.as_deref().unwrap_or_default()])
+    }
+
+    /// Whether the config sources set `portable`, the later ones over the earlier ones.
+    pub fn is_portable(sources: &[&str]) -> bool {
+        sources.iter()
+            .fold(Config::builder(), |builder, text| builder.add_source(config::File::from_str(text, FileFormat::Ini)))
+            .build()
+            .and_then(|c| c.get_bool("portable"))
+            .unwrap_or(false)
+    }
+
+    /// Parse configs in the format of `launcher.ini` as one, as [`LauncherConfig::parse_config_from`] does,
+    /// the keys of each taking precedence over those of the ones before it.
+    pub fn parse_config_sources(sources: &[&str]) -> Result<Self, String> {
+        let c = sources.iter()
+            .fold(Config::builder(), |builder, text| builder.add_source(config::File::from_str(text, FileFormat::Ini)))
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(LauncherConfig {
+            main_class: c.get_string("mainclass").ok(),
+            classpath: c.get_string("classpath").ok().map(|p| normalize_path_list(&p)),
+            jvm_path: c.get_string("jvm_install").ok(),
+            min_java: c.get_int("min_java").ok(),
+            max_java: c.get_int("max_java").ok(),
+            enable_preview: c.get_bool("enable_preview").unwrap_or(false),
+            launch_options_file: c.get_string("launch_options").ok(),
+            read_l4j_ini: c.get_bool("read_l4j_ini").unwrap_or(false),
+            allows_system_java: c.get_bool("allow_system_java").unwrap_or(true),
+            allows_java_location_lookup: c.get_bool("allow_java_location_lookup").unwrap_or(true),
+            jvm_allowed_paths: c.get_string("jvm_allowed_paths").ok().map(|p| normalize_path_list(&p)),
+            max_mem_percent: c.get_int("maximum_heap_percentage").ok(),
+            check_main_class: c.get_bool("check_main_class").unwrap_or(true),
+            use_previous_jvm: c.get_bool("use_previous_jvm").unwrap_or(false),
+            install_exception_handler: c.get_bool("install_exception_handler").unwrap_or(false),
+            show_crash_dialog: c.get_bool("show_crash_dialog").unwrap_or(true),
+            restart_exit_code: c.get_int("restart_exit_code").ok(),
+            launch_mode: c.get_string("launch_mode").map(|m| LaunchMode::from_key(&m))
+                .unwrap_or(LaunchMode::Jni),
+            process_fallback: c.get_bool("process_fallback").unwrap_or(true),
+            jni_version: c.get_string("jni_version").ok()
+                .map(|v| parse_jni_version(&v)).unwrap_or(DEFAULT_JNI_VERSION),
+            strict_jvm_options: c.get_bool("strict_jvm_options").unwrap_or(false),
+            preflight_class: c.get_string("preflight_class").ok(),
+            startup_timeout: c.get_int("startup_timeout").ok(),
+            kill_child_processes: c.get_bool("kill_child_processes").unwrap_or(false),
+            log_dir: c.get_string("log_dir").ok(),
+            capture_output: c.get_bool("capture_output").unwrap_or(true),
+            utf8_encoding: c.get_bool("utf8_encoding").unwrap_or(false),
+            use_os_locale: c.get_bool("use_os_locale").unwrap_or(false),
+            native_libs: c.get_string("native_libs").ok().map(|p| normalize_path_list(&p)),
+            tmpdir: c.get_string("tmpdir").ok(),
+            main_module: c.get_string("mainmodule").ok(),
+            module_path: c.get_string("module_path").ok().map(|p| normalize_path_list(&p)),
+            add_module_path_modules: c.get_bool("add_module_path_modules").unwrap_or(false),
+            detect_javafx: c.get_bool("detect_javafx").unwrap_or(true),
+            main_jar: c.get_string("main_jar").ok(),
+            transitive_class_path: c.get_bool("transitive_class_path").unwrap_or(false),
+            strict_manifest: c.get_bool("strict_manifest").unwrap_or(false),
+            verify_signatures: c.get_string("verify_signatures").map(|v| SignatureCheck::from_key(&v))
+                .unwrap_or(SignatureCheck::Off),
+            trusted_signers: c.get_string("trusted_signers").map(|s| s.split(';')
+                .map(|f| f.trim().to_string())
+                .filter(|f| !f.is_empty())
+                .collect()).unwrap_or_default(),
+            verify_integrity: c.get_bool("verify_integrity").unwrap_or(false),
+            check_permissions: c.get_string("check_permissions").map(|v| PermissionCheck::from_key(&v))
+                .unwrap_or(PermissionCheck::Off),
+            agents_dir: c.get_string("agents_dir").ok(),
+            disabled_agents: c.get_string("disabled_agents").ok(),
+            java_download_url: c.get_string("java_download_url")
+                .unwrap_or_else(|_| DEFAULT_JAVA_DOWNLOAD_URL.to_string()),
+            app_name: c.get_string("app_name").ok(),
+            icon: c.get_string("icon").ok(),
+            log_to_event_log: c.get_bool("log_to_event_log").unwrap_or(false),
+            log_to_journal: c.get_bool("log_to_journal").unwrap_or(false),
+            log_to_os_log: c.get_bool("log_to_os_log").unwrap_or(false),
+            file_associations: read_file_associations(&c),
+            read_info_plist: c.get_bool("read_info_plist").unwrap_or(false),
+            wayland_toolkit: c.get_string("wayland_toolkit").map(|t| WaylandToolkit::from_key(&t))
+                .unwrap_or(WaylandToolkit::XWayland),
+            detect_ui_scale: c.get_bool("detect_ui_scale").unwrap_or(true),
+            wm_class: c.get_string("wm_class").ok(),
+            app_version: c.get_string("app_version").ok(),
+            update_url: c.get_string("update_url").ok(),
+            update_check_hours: c.get_int("update_check_hours").unwrap_or(24),
+            auto_update: c.get_bool("auto_update").unwrap_or(false),
+            update_public_key: c.get_string("update_public_key").ok(),
+            portable: c.get_bool("portable").unwrap_or(false),
+            require_elevation: c.get_bool("require_elevation").unwrap_or(false),
+            pid_file: c.get_string("pid_file").ok(),
+            preserve_cwd: c.get_bool("preserve_cwd").unwrap_or(false),
+            hooks: read_hooks(&c),
+            ..Default::default()
+        })
+    }
+
+    /// Take the main class, JVM options, Java and class path from the app bundle's `Info.plist`, over those
+    /// configured, see [`read_bundle_config`]. The jars in `Contents/Java` are only the class path
+    /// if none is configured.<br>
+    /// Returns whether the `Info.plist` had any of them.
+    #[cfg(target_os = "macos")]
+    fn apply_info_plist(&mut self) -> bool {
+        let bundle = match read_bundle_config() {
+            Some(bundle) => bundle,
+            None => return false,
+        };
+        if bundle.main_class.is_some() {
+            self.main_class = bundle.main_class;
+        }
+        if let Some(runtime) = bundle.runtime {
+            self.jvm_path = Some(runtime.display().to_string());
+        }
+        if !bundle.classpath.is_empty() && (bundle.explicit_classpath || self.classpath.is_none()) {
+            let classpath: Vec<String> = bundle.classpath.iter().map(|p| p.display().to_string()).collect();
+            self.classpath = Some(classpath.join(";"));
+        }
+        self.bundle_jvm_options = bundle.jvm_options;
+        true
+    }
+
+    /// Take the main class and classpath from the manifest of `main_jar`, where not configured.
+    /// The `Class-Path` entries are resolved against the jar's directory.<br>
+    /// Spring Boot jars are started through the launcher their manifest names, even when their
+    /// `Start-Class` is configured as the main class.
+    pub fn apply_main_jar(&mut self) {
+        let main_jar = match &self.main_jar {
+            Some(main_jar) => resolve_path(main_jar),
+            None => return,
+        };
+        let manifest = read_jar_manifest(&main_jar);
+        if manifest.is_none() {
+            info!("config", "No manifest found in {:?}", main_jar);
+        }
+
+        let manifest_main = manifest.as_ref().and_then(|m| m.get("Main-Class")).map(|c| c.trim().to_string());
+        // Spring Boot jars need their launcher to find the app's classes
+        let start_class = manifest.as_ref().and_then(|m| m.get("Start-Class")).map(|c| c.trim());
+        if manifest_main.is_some() && start_class.is_some() && self.main_class.as_deref() == start_class {
+            info!("config", "{:?} is started by its launcher {:?}", start_class, manifest_main);
+            self.main_class = None;
+        }
+        if self.main_class.is_none() {
+            self.main_class = manifest_main;
+        }
+        if self.classpath.is_none() {
+            let mut classpath = vec![main_jar.clone()];
+            if let Some(manifest) = &manifest {
+                if self.transitive_class_path {
+                    classpath.append(&mut resolve_class_path_transitively(&main_jar, manifest,
+                                                                          MAX_CLASS_PATH_DEPTH));
+                } else {
+                    classpath.append(&mut resolve_class_path(&main_jar, manifest));
                 }
             }
+            let classpath: Vec<String> = classpath.iter().map(|p| p.display().to_string()).collect();
+            self.classpath = Some(classpath.join(";"));
+        }
+    }
+
+    /// Read `launch_options_file` into a series of launch options, after the `JVMOptions` of the
+    /// `Info.plist` if read, sanitizing and correcting where possible.
+    pub fn read_launch_opts(&self) -> Vec<String> {
+        let mut out: Vec<String> = self.bundle_jvm_options.clone();
+        if let Some(launch_options_file) = &self.launch_options_file {
+            out.append(&mut read_opts_file(launch_options_file));
         }
 
         if let Some(mem_per) = self.max_mem_percent {
@@ -143,10 +731,43 @@ impl LauncherConfig {
         return out;
     }
 
-    /// Make sure the minimum Java requirement is not less than that needed for the main class.
+    /// Read the options the user left in the Launch4j `<exe>.l4j.ini` if `read_l4j_ini` is set.<br>
+    /// These come after the app's options, and are left out if Java fails to start with them.
+    pub fn read_user_launch_opts(&self) -> Vec<String> {
+        if self.read_l4j_ini {
+            read_l4j_ini()
+        } else {
+            vec![]
+        }
+    }
+
+    /// Read the options the app left in the relaunch file, see [`get_relaunch_file`].<br>
+    /// These come after all other options, so that they take precedence.
+    pub fn read_relaunch_opts(&self) -> Vec<String> {
+        let mut out: Vec<String> = vec![];
+        if let Some(relaunch_file) = get_relaunch_file() {
+            if relaunch_file.exists() {
+                debug!("config", "Reading relaunch options from {:?}", relaunch_file);
+                out.append(&mut read_opts_file(relaunch_file));
+            }
+        }
+
+        return out;
+    }
+
+    /// Make sure the minimum Java requirement is not less than that needed for the main class.<br>
+    /// A main class using preview features needs exactly its Java version, with them enabled, within
+    /// a configured `max_java`, see [`LauncherConfig::java_conflict`].
     pub fn ensure_correct_java(&mut self) {
         if self.check_main_class {
-            let new_min = get_java_version_of_main(self);
+            let main_version = get_java_version_of_main(self);
+            if let Some(version) = main_version.filter(|v| v.preview) {
+                info!("config", "The main class uses preview features of Java {}", version.java);
+                self.enable_preview = true;
+                let preview_java = version.java as i64;
+                self.max_java = Some(self.max_java.map_or(preview_java, |max| max.min(preview_java)));
+            }
+            let new_min = main_version.map(|v| v.java);
             if let Some(new_min) = new_min {
                 if let Some(min_java) = self.min_java {
                     if new_min as i64 > min_java {
@@ -157,6 +778,149 @@ impl LauncherConfig {
                 }
             }
         }
+
+        // Modules were added in Java 9
+        if self.main_module.is_some() && self.min_java.unwrap_or(0) < 9 {
+            self.min_java = Some(9);
+        }
+    }
+}
+
+/// Read a Launch4J-style options file into a series of launch options,
+/// sanitizing and correcting where possible.
+fn read_opts_file<P: AsRef<Path>>(path: P) -> Vec<String> {
+    let mut out: Vec<String> = vec![];
+    if let Ok(lines) = read_lines(path) {
+        // Consumes the iterator, returns an (Optional) String
+        for line in lines {
+            if let Ok(ip) = line {
+                let sanitized_line = verify_line(ip);
+                let mut opts = parse_line(sanitized_line).iter()
+                    .filter(|o| !o.is_empty())
+                    .map(|o| verify_opt(o.to_owned())).collect();
+                out.append(&mut opts)
+            }
+        }
+    }
+
+    out
+}
+
+/// Read the Launch4j `<exe>.l4j.ini` beside the launcher into a series of launch options, see `read_l4j_ini`.
+/// Lines have options as on a command line, quoted with `"` where they have spaces, and `#` starts a comment.
+fn read_l4j_ini() -> Vec<String> {
+    let l4j_ini = match get_app_dir() {
+        Some(dir) => dir.join(get_app_name() + ".l4j.ini"),
+        None => return vec![],
+    };
+    let lines = match read_lines(&l4j_ini) {
+        Ok(lines) => lines,
+        Err(_) => return vec![],
+    };
+    debug!("config", "Reading Launch4j options from {:?}", l4j_ini);
+
+    let exe = env::current_exe().ok().map(|e| e.display().to_string());
+    let exe_dir = get_app_dir().map(|d| d.display().to_string());
+    let old_pwd = get_launch_dir().map(|d| d.display().to_string());
+    let lookup = |name: &str| match name {
+        "EXEFILE" => exe.clone(),
+        "EXEDIR" => exe_dir.clone(),
+        "OLDPWD" => old_pwd.clone(),
+        name => env::var(name).ok(),
+    };
+    lines.flatten()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.starts_with('#'))
+        .flat_map(|line| split_command(&verify_line(expand_l4j_vars(&line, lookup))))
+        .filter(|o| !o.is_empty())
+        .map(verify_opt)
+        .collect()
+}
+
+/// Replace the `%VAR%` of a Launch4j option with the variable's value, as Launch4j does,
+/// keeping those without one.
+fn expand_l4j_vars(line: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut expanded = String::new();
+    let mut rest = line;
+    while let Some(start) = rest.find('%') {
+        let end = match rest[start + 1..].find('%') {
+            Some(end) => start + 1 + end,
+            None => break,
+        };
+        expanded.push_str(&rest[..start]);
+        match lookup(&rest[start + 1..end]) {
+            Some(value) => {
+                expanded.push_str(&value);
+                rest = &rest[end + 1..];
+            }
+            // The closing `%` may open the next variable
+            None => {
+                expanded.push('%');
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Convert a JNI version, as it appears in the Java version it was introduced with,
+/// to its JNI constant. Unknown versions fall back to [`DEFAULT_JNI_VERSION`].
+fn parse_jni_version(version: &str) -> jint {
+    match version.trim() {
+        "1.1" => 0x00010001,
+        "1.2" => 0x00010002,
+        "1.4" => 0x00010004,
+        "1.6" => 0x00010006,
+        "1.8" => 0x00010008,
+        "9" => 0x00090000,
+        "10" => 0x000a0000,
+        "19" => 0x00130000,
+        "20" => 0x00140000,
+        "21" => 0x00150000,
+        v => {
+            info!("config", "Unknown JNI version {}, using the default", v);
+            DEFAULT_JNI_VERSION
+        }
+    }
+}
+
+/// Read the `[FileAssociations]` section, sorted by extension. Entries without a MIME type are skipped.
+fn read_file_associations(c: &Config) -> Vec<FileAssociation> {
+    // Older versions of the config crate lowercase the section names
+    let section = c.get_table("fileassociations").or_else(|_| c.get_table("FileAssociations"));
+    let mut associations: Vec<FileAssociation> = section.into_iter()
+        .flatten()
+        .filter_map(|(extension, value)| {
+            let association = value.into_string().ok()
+                .and_then(|v| FileAssociation::from_entry(&extension, &v));
+            if association.is_none() {
+                info!("config", "Ignoring file association {} without a MIME type", extension);
+            }
+            association
+        })
+        .collect();
+    associations.sort_by(|a, b| a.extension.cmp(&b.extension));
+    associations
+}
+
+/// Read the `[Hooks]` section, each hook's commands separated by `;`.
+fn read_hooks(c: &Config) -> Hooks {
+    let section = c.get_table("hooks").or_else(|_| c.get_table("Hooks")).unwrap_or_default();
+    let value = |key: &str| section.get(key).and_then(|v| v.clone().into_string().ok());
+    let commands = |key: &str| value(key).iter()
+        .flat_map(|v| v.split(';'))
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .map(String::from)
+        .collect();
+    Hooks {
+        pre_launch: commands("pre-launch"),
+        pre_launch_failure: value("pre-launch-failure").map(|f| HookFailure::from_key(&f))
+            .unwrap_or(HookFailure::Abort),
+        first_run: commands("first-run"),
+        first_run_failure: value("first-run-failure").map(|f| HookFailure::from_key(&f))
+            .unwrap_or(HookFailure::Abort),
     }
 }
 
@@ -190,11 +954,56 @@ fn verify_opt(input: String) -> String {
     input
 }
 
+/// Whether a launch option is likely to keep the JVM from starting: malformed `-XX` options,
+/// and module options that are malformed or name modules `runtime_modules` does not list.
+pub fn is_suspect_opt(opt: &str, runtime_modules: &[String]) -> bool {
+    if let Some(flag) = opt.strip_prefix("-XX:") {
+        let is_name = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        return if let Some(name) = flag.strip_prefix('+').or_else(|| flag.strip_prefix('-')) {
+            !is_name(name)
+        } else if let Some((name, _)) = flag.split_once('=') {
+            !is_name(name)
+        } else {
+            true
+        };
+    }
+
+    for module_opt in ["--add-opens", "--add-exports", "--add-reads"] {
+        if let Some(value) = opt.strip_prefix(module_opt) {
+            // In the form of opt=module/package=target,target or opt=module=target for reads
+            let value = match value.strip_prefix('=') {
+                Some(v) => v,
+                None => return true,
+            };
+            let (source, targets) = match value.split_once('=') {
+                Some(p) => p,
+                None => return true,
+            };
+            let module = if module_opt == "--add-reads" {
+                source
+            } else {
+                match source.split_once('/') {
+                    Some((m, p)) if !p.is_empty() => m,
+                    _ => return true,
+                }
+            };
+
+            // Only check for unknown modules if the runtime tells us which it has
+            let is_known = |m: &str| runtime_modules.is_empty() ||
+                runtime_modules.iter().any(|r| r == m);
+            return module.is_empty() || !is_known(module) ||
+                targets.split(',').any(|t| t.is_empty() || (!t.starts_with("ALL-") && !is_known(t)));
+        }
+    }
+
+    false
+}
+
 /// Handle verification of args with spaces
 fn verify_line(mut line: String) -> String {
     for opt in MODULE_OPTS {
         if line.contains((opt.to_string() + " ").as_str()) {
-            println!("Launcher corrected a module option ({})! They must be in the form of opt=val.", opt)
+            info!("config", "Launcher corrected a module option ({})! They must be in the form of opt=val.", opt)
         }
         line = line.replace((opt.to_string() + " ").as_str(), (opt.to_string() + "=").as_str());
     }
@@ -212,7 +1021,13 @@ fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
 fn get_max_heap(mem_per: i64) -> u64 {
     let mut sys = System::new_all();
     sys.refresh_all();
-    let max_mem = sys.total_memory();
+    let mut max_mem = sys.total_memory();
+
+    // A container may have far less memory than the host
+    if let Some(limit) = get_cgroup_memory_limit() {
+        max_mem = max_mem.min(limit / 1024);
+    }
+
     let mut mem_frac: f64 = (mem_per as f64) / 100f64;
     if mem_frac <= 0.01f64 || mem_frac >= 1f64 {
         mem_frac = 0.2f64;
@@ -220,3 +1035,126 @@ fn get_max_heap(mem_per: i64) -> u64 {
     ((max_mem as f64) * mem_frac) as u64
 }
 
+
+/// The memory limit of the cgroup (v2 or v1) the launcher runs in, in bytes,
+/// such as set by Docker or a systemd slice.<br>
+/// `None` if there is no limit.
+#[cfg(target_os = "linux")]
+fn get_cgroup_memory_limit() -> Option<u64> {
+    use std::fs;
+    let cgroups = fs::read_to_string("/proc/self/cgroup").ok()?;
+    for line in cgroups.lines() {
+        // Formatted as hierarchy-ID:controller-list:cgroup-path
+        let mut parts = line.splitn(3, ':');
+        let (_, controllers, path) = (parts.next()?, parts.next()?, parts.next()?);
+        let path = path.trim_start_matches('/');
+
+        let candidates = if controllers.is_empty() {
+            // v2, the unified hierarchy
+            vec![Path::new("/sys/fs/cgroup").join(path).join("memory.max"),
+                 PathBuf::from("/sys/fs/cgroup/memory.max")]
+        } else if controllers.split(',').any(|c| c == "memory") {
+            vec![Path::new("/sys/fs/cgroup/memory").join(path).join("memory.limit_in_bytes"),
+                 PathBuf::from("/sys/fs/cgroup/memory/memory.limit_in_bytes")]
+        } else {
+            continue;
+        };
+
+        for candidate in candidates {
+            if let Ok(limit) = fs::read_to_string(&candidate) {
+                // v2 uses "max" for no limit, v1 a value near the maximum page-aligned i64
+                return limit.trim().parse::<u64>().ok().filter(|l| *l < (1 << 62));
+            }
+        }
+    }
+
+    None
+}
+
+/// The memory limit of the cgroup the launcher runs in, cgroups only exist on Linux.
+#[cfg(not(target_os = "linux"))]
+fn get_cgroup_memory_limit() -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_is_parsed_from_reader() {
+        let ini = "mainclass=a.B\nclasspath=lib/app.jar;lib/dep.jar\nmin_java=17\nlaunch_mode=process\n";
+        let config = LauncherConfig::parse_config_from(ini.as_bytes()).expect("config should parse");
+        assert_eq!(config.main_class.as_deref(), Some("a.B"));
+        assert_eq!(config.classpath.as_deref(), Some("lib/app.jar;lib/dep.jar"));
+        assert_eq!(config.min_java, Some(17));
+        assert_eq!(config.launch_mode, LaunchMode::Process);
+        assert!(config.allows_system_java);
+    }
+
+    #[test]
+    fn missing_keys_are_named() {
+        let config = LauncherConfig::parse_config_from("main_jar=app.jar\n".as_bytes()).unwrap();
+        let missing = config.missing_keys();
+        assert_eq!(missing.len(), 2);
+        assert!(missing[0].contains("Main-Class attribute in the manifest of app.jar"));
+        assert_eq!(missing[1], "classpath, module_path or main_jar");
+
+        let config = LauncherConfig::parse_config_from("mainmodule=a\nmodule_path=mods\n".as_bytes()).unwrap();
+        assert!(config.missing_keys().is_empty());
+    }
+
+    #[test]
+    fn contradicting_java_bounds_are_a_conflict() {
+        let config = LauncherConfig::parse_config_from("min_java=21\nmax_java=17\n".as_bytes()).unwrap();
+        assert_eq!(config.java_conflict(), Some((21, 17)));
+        let config = LauncherConfig::parse_config_from("min_java=17\nmax_java=17\n".as_bytes()).unwrap();
+        assert_eq!(config.java_conflict(), None);
+    }
+
+    #[test]
+    fn hooks_are_read_from_their_section() {
+        let ini = "mainclass=a.B\n[Hooks]\npre-launch = $APPDIR$/check.sh; \"$APPDIR$/set up\" --quiet\n\
+            pre-launch-failure = continue\nfirst-run = migrate\n";
+        let hooks = LauncherConfig::parse_config_from(ini.as_bytes()).unwrap().hooks;
+        assert_eq!(hooks.pre_launch, vec!["$APPDIR$/check.sh", "\"$APPDIR$/set up\" --quiet"]);
+        assert_eq!(hooks.pre_launch_failure, HookFailure::Continue);
+        assert_eq!(hooks.first_run, vec!["migrate"]);
+        assert_eq!(hooks.first_run_failure, HookFailure::Abort);
+
+        let ini = "mainclass=a.B\n[Hooks]\npre-launch = '\"$APPDIR$/set up\" --quiet; check'\n";
+        let hooks = LauncherConfig::parse_config_from(ini.as_bytes()).unwrap().hooks;
+        assert_eq!(hooks.pre_launch, vec!["\"$APPDIR$/set up\" --quiet", "check"]);
+
+        let hooks = LauncherConfig::parse_config_from("mainclass=a.B\n".as_bytes()).unwrap().hooks;
+        assert_eq!(hooks, Hooks::default());
+    }
+
+    #[test]
+    fn launch4j_variables_are_expanded() {
+        let lookup = |name: &str| match name {
+            "EXEDIR" => Some("C:\\App".to_string()),
+            "APPDATA" => Some("C:\\Users\\a".to_string()),
+            _ => None,
+        };
+        assert_eq!(expand_l4j_vars("-Dlog=%EXEDIR%\\logs -Ddata=\"%APPDATA%\\My App\"", lookup),
+                   "-Dlog=C:\\App\\logs -Ddata=\"C:\\Users\\a\\My App\"");
+        assert_eq!(expand_l4j_vars("-Dp=50%%UNSET%%EXEDIR%", lookup), "-Dp=50%%UNSET%C:\\App");
+        assert_eq!(split_command(&expand_l4j_vars("-Xmx1g \"-Ddir=%EXEDIR%\"", lookup)),
+                   vec!["-Xmx1g", "-Ddir=C:\\App"]);
+    }
+
+    #[test]
+    fn later_configs_override_earlier_ones() {
+        let embedded = "mainclass=a.B\nmin_java=11\n[Hooks]\npre-launch=check\n";
+        let config = LauncherConfig::parse_config_sources(&[embedded, "min_java=17\n"]).unwrap();
+        assert_eq!(config.main_class.as_deref(), Some("a.B"));
+        assert_eq!(config.min_java, Some(17));
+        assert_eq!(config.hooks.pre_launch, vec!["check"]);
+    }
+
+    #[test]
+    fn invalid_config_is_an_error() {
+        assert!(LauncherConfig::parse_config_from("[unclosed\nmainclass=a.B".as_bytes()).is_err());
+    }
+}
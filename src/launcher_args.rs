@@ -0,0 +1,81 @@
+/// Prefix of the arguments meant for the launcher itself, these are not forwarded to the app.
+const LAUNCHER_ARG_PREFIX: &str = "--why-";
+
+/// Options given to the launcher on the command line, as `--why-<option>`.
+#[derive(Debug, Default)]
+pub struct LauncherArgs {
+    /// `--why-headless`: never show dialogs, see [`crate::display_handler::set_headless`].
+    pub headless: bool,
+    /// `--why-verbose`: log everything, see [`crate::log_handler::set_verbose`].
+    pub verbose: bool,
+    /// `--why-console`: open a console window showing the launcher's log and the app's output, on Windows,
+    /// see [`crate::output_handler::open_console`].
+    pub console: bool,
+    /// `--why-diagnose`: write a diagnosis instead of launching the app,
+    /// see [`crate::diagnose_handler::write_diagnosis`].
+    pub diagnose: bool,
+    /// `--why-register-protocol <scheme>`: register the launcher for links with the scheme instead of
+    /// launching the app, see [`crate::registration_handler::register_protocol`].
+    pub register_protocol: Option<String>,
+    /// `--why-unregister-protocol <scheme>`: undo `--why-register-protocol`.
+    pub unregister_protocol: Option<String>,
+    /// `--why-register-filetypes`: register the launcher for the file types of the config instead of
+    /// launching the app, see [`crate::registration_handler::register_file_types`].
+    pub register_file_types: bool,
+    /// `--why-unregister-filetypes`: undo `--why-register-filetypes`.
+    pub unregister_file_types: bool,
+    /// `--why-daemon`: run the app in the background, detached from the terminal,
+    /// see [`crate::process_handler::daemonize`].
+    pub daemon: bool,
+    /// `--why-choose-jvm`: let the user choose the Java the app is run with, now and on the next launches,
+    /// see [`crate::jvm_choice_handler::choose_jvm`].
+    pub choose_jvm: bool,
+}
+
+impl LauncherArgs {
+    /// Take the launcher's own arguments out of the program args, leaving those for the app.
+    pub fn take_from(program_opts: &mut Vec<String>) -> LauncherArgs {
+        let mut args = LauncherArgs::default();
+
+        let mut opts = program_opts.drain(..);
+        let mut app_opts: Vec<String> = vec![];
+        while let Some(opt) = opts.next() {
+            let name = match opt.strip_prefix(LAUNCHER_ARG_PREFIX) {
+                Some(name) => name,
+                None => {
+                    app_opts.push(opt);
+                    continue;
+                }
+            };
+            // Either `--why-option=value` or `--why-option value`
+            let (name, inline_value) = match name.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (name, None),
+            };
+            let mut value = || {
+                let value = inline_value.clone().or_else(|| opts.next());
+                if value.is_none() {
+                    info!("config", "Ignoring launcher argument {} without a value", opt);
+                }
+                value
+            };
+            match name {
+                "headless" => args.headless = true,
+                "verbose" => args.verbose = true,
+                "console" => args.console = true,
+                "diagnose" => args.diagnose = true,
+                "register-protocol" => args.register_protocol = value(),
+                "unregister-protocol" => args.unregister_protocol = value(),
+                "register-filetypes" => args.register_file_types = true,
+                "unregister-filetypes" => args.unregister_file_types = true,
+                "daemon" => args.daemon = true,
+                "choose-jvm" => args.choose_jvm = true,
+                _ => info!("config", "Ignoring unknown launcher argument {}", opt),
+            }
+        }
+        drop(opts);
+        *program_opts = app_opts;
+
+        args
+    }
+}
@@ -0,0 +1,472 @@
+//! The launcher of Why, which starts a Java app from the Java it finds, as configured by `launcher.ini`.<br>
+//! The `JavaLauncher` executable only calls [`run`]. Launchers of their own, such as with other branding,
+//! can call [`run`] or [`launch`] too, or use the parts of the launcher they need: [`LauncherConfig`] reads
+//! the config, [`get_jvm_paths`] and [`get_jvm_candidates`] find Java, and [`create_and_run_jvm`] starts
+//! the app with the [`LaunchOpts`] built from them.
+
+use std::{env, fs, process, thread};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[cfg(target_os = "macos")]
+use crate::apple_event_handler::collect_open_events;
+#[cfg(target_os = "macos")]
+use crate::bundle_handler::{check_translocation, dock_opts};
+use crate::diagnose_handler::write_diagnosis;
+#[cfg(feature = "download")]
+pub use crate::download_handler::{download_to, fetch, is_https};
+use crate::display_handler::{detailed_message, detect_headless, message, notify, set_branding, set_headless};
+pub use crate::exit_handler::ExitCode;
+use crate::exit_handler::{crash_log_opt, exit, FAILURE_EXIT_CODE, install_panic_hook, report_recent_crash_logs,
+                          set_restart_exit_code};
+pub use crate::file_handler::{get_jvm_candidates, get_jvm_paths, JvmCandidate, set_user_data_dir};
+use crate::file_handler::{find_agents, find_javafx_modules, get_java_version_of_main, get_launch_dir, get_native_lib_dirs, get_relaunch_file, get_user_data_dir, LIBRARY_PATH_VAR, PATH_SEPARATOR, resolve_path, set_launch_dir, set_log_dir};
+use crate::hook_handler::{HookResult, run_first_run_hooks, run_pre_launch_hooks};
+use crate::install_handler::{install_scope, is_first_run, set_first_run_succeeded, set_portable};
+pub use crate::java_launcher::{create_and_run_jvm, LaunchOpts, LaunchOptsBuilder};
+use crate::java_launcher::path_opts;
+use crate::jvm_choice_handler::{choose_jvm, use_chosen_jvm};
+pub use crate::launch_config::{LauncherConfig, LaunchMode};
+use crate::launcher_args::LauncherArgs;
+use crate::locale_handler::{encoding_and_locale_opts, text_with};
+use crate::log_handler::{open_log_file, set_system_log, set_verbose};
+pub use crate::manifest_handler::{Manifest, parse_manifest_bytes};
+use crate::output_handler::capture_output;
+use crate::permission_handler::check_app_permissions;
+#[cfg(windows)]
+use crate::output_handler::{attach_parent_console, console_key_held, open_console};
+#[cfg(windows)]
+use crate::process_handler::{handle_console_events, kill_children_with_launcher};
+use crate::process_handler::{daemonize, run_elevated};
+use crate::process_launcher::run_java_process;
+use crate::registration_handler::{register_file_types, register_protocol, unregister_file_types,
+                                  unregister_protocol};
+use crate::session_handler::{set_process_name, ui_scale_opts};
+use crate::signature_handler::{check_app_integrity, check_app_signature};
+use crate::timing_handler::{Phase, start_timing, time};
+#[cfg(feature = "updater")]
+use crate::update_handler::{apply_staged_update, start_update_check, wait_for_update};
+use crate::watchdog::start_watchdog;
+use crate::zip_handler::{close_zips, find_modules};
+
+// First, for its macros to be available to the other modules
+#[macro_use]
+mod log_handler;
+#[cfg(target_os = "macos")]
+mod apple_event_handler;
+#[cfg(target_os = "macos")]
+mod bundle_handler;
+mod cache_handler;
+mod diagnose_handler;
+mod display_handler;
+#[cfg(feature = "download")]
+mod download_handler;
+mod exception_handler;
+mod exit_handler;
+mod install_handler;
+mod java_launcher;
+mod jvm_choice_handler;
+mod launch_config;
+mod launcher_args;
+mod locale_handler;
+mod manifest_handler;
+mod file_handler;
+mod hook_handler;
+mod output_handler;
+mod permission_handler;
+mod pkcs7_handler;
+mod process_handler;
+mod process_launcher;
+mod registration_handler;
+mod session_handler;
+mod signature_handler;
+mod timing_handler;
+#[cfg(feature = "updater")]
+mod update_handler;
+mod watchdog;
+mod zip_handler;
+
+/// Run the launcher as the `JavaLauncher` executable does, from setting up its logging and working directory
+/// to exiting with the app's exit status.
+pub fn run() -> ! {
+    start_timing();
+    install_panic_hook();
+
+    // Before anything is printed, so it reaches the terminal
+    #[cfg(windows)]
+    attach_parent_console();
+
+    info!("launcher", "Launcher starting!");
+
+    set_launch_dir(env::current_dir().ok());
+    correct_directory();
+
+    #[cfg(windows)]
+    handle_console_events();
+
+    exit(launch());
+}
+
+/// Setup the environment and launch the application, returning its exit status
+pub fn launch() -> i32 {
+    // Forward launch args to the app, the first element is the launcher path, no need to pass it on
+    let mut program_opts: Vec<String> = env::args().skip(1).collect();
+
+    // Before the config is read, so that reading it is logged
+    let launcher_args = LauncherArgs::take_from(&mut program_opts);
+    if launcher_args.verbose {
+        set_verbose();
+    }
+
+    // Before the launcher logs more, and before the output may be captured, which a console prevents
+    #[cfg(windows)]
+    if launcher_args.console || console_key_held() {
+        open_console();
+    }
+
+    // Before anything uses the data dir, which waiting for an update and reading the config already do
+    set_portable(LauncherConfig::read_portable());
+
+    // Before anything may show a dialog
+    set_headless(launcher_args.headless || launcher_args.daemon || detect_headless());
+
+    // Before the config is read, as an update may change it
+    #[cfg(feature = "updater")]
+    if !wait_for_update() {
+        return ExitCode::Cancelled.code();
+    }
+    #[cfg(feature = "updater")]
+    let update_message = apply_staged_update();
+
+    // Files and URLs the app was opened with from Finder or another app, as arguments, in place of
+    // the process serial number older macOS versions pass
+    #[cfg(target_os = "macos")]
+    {
+        program_opts.retain(|o| !o.starts_with("-psn_"));
+        program_opts.extend(collect_open_events());
+    }
+
+    // Build launch opts
+    let mut m = LaunchOpts {
+        config: LauncherConfig {
+            ..time(Phase::Config, LauncherConfig::read_file)
+        },
+        jvm_opts: vec![],                    //this can be relative
+        user_jvm_opts: vec![],
+        program_opts,
+    };
+
+    set_branding(m.config.app_name.clone(), m.config.icon.as_ref().map(|i| resolve_path(i)));
+
+    set_restart_exit_code(m.config.restart_exit_code.map(|c| c as i32));
+    set_process_name(&m.config);
+
+    // Keep the output of GUI launches
+    set_log_dir(m.config.log_dir.as_ref().map(|d| resolve_path(d)));
+    open_log_file();
+    set_system_log(m.config.logs_to_system());
+
+    #[cfg(feature = "updater")]
+    if let Some(msg) = &update_message {
+        notify(msg);
+    }
+
+    // A translocated app runs from a read-only copy where its relative paths may not resolve
+    #[cfg(target_os = "macos")]
+    if !check_translocation() {
+        return ExitCode::PreflightFailed.code();
+    }
+
+    // Setup commands, such as for an installer, run instead of the app
+    if let Some(scheme) = &launcher_args.register_protocol {
+        return register_protocol(scheme, &m.config);
+    }
+    if let Some(scheme) = &launcher_args.unregister_protocol {
+        return unregister_protocol(scheme);
+    }
+    if launcher_args.register_file_types {
+        return register_file_types(&m.config);
+    }
+    if launcher_args.unregister_file_types {
+        return unregister_file_types(&m.config);
+    }
+
+    // Before anything the elevated launcher would do again
+    if m.config.require_elevation {
+        if let Some(code) = run_elevated() {
+            return code;
+        }
+    }
+
+    // Before the launcher starts any threads, which do not survive detaching
+    if launcher_args.daemon {
+        if let Err(e) = daemonize(&m.config) {
+            message(&e);
+            return FAILURE_EXIT_CODE;
+        }
+    } else {
+        capture_output(&m.config);
+    }
+
+    // In the background, while the app starts
+    #[cfg(feature = "updater")]
+    start_update_check(&m.config);
+
+    // A crash of the JVM takes the launcher with it, so check for one when starting instead
+    report_recent_crash_logs();
+
+    #[cfg(windows)]
+    if m.config.kill_child_processes {
+        kill_children_with_launcher();
+    }
+
+    let mut classpath = m.config.classpath.clone();
+    let mut module_path: Vec<PathBuf> = m.config.module_path.iter()
+        .flat_map(|p| p.split(";"))
+        .filter(|p| !p.is_empty())
+        .map(|p| resolve_path(p))
+        .collect();
+
+    // JavaFX only supports being loaded as modules, a modular app already requires what it needs
+    if m.config.detect_javafx && m.config.main_module.is_none() {
+        if let Some(cp) = classpath.take() {
+            let (fx_jars, rest): (Vec<&str>, Vec<&str>) = cp.split(";")
+                .partition(|p| !find_javafx_modules(Path::new(p)).is_empty() && Path::new(p).is_file());
+            if !fx_jars.is_empty() {
+                info!("config", "Moving JavaFX to the module path: {:?}", fx_jars);
+                module_path.extend(fx_jars.iter().map(PathBuf::from));
+            }
+            classpath = Some(rest.join(";"));
+        }
+    }
+
+    // Modules on the module path are only resolved if something requires them,
+    // which app code on the classpath cannot do
+    let mut add_modules: Vec<String> = vec![];
+    if m.config.main_module.is_none() && !module_path.is_empty() {
+        // Also reports modules with the same name
+        let modules = find_modules(&module_path);
+        if m.config.add_module_path_modules {
+            add_modules = modules.into_iter().map(|module| module.name).collect();
+        } else if m.config.detect_javafx {
+            for path in &module_path {
+                for module in find_javafx_modules(path) {
+                    if !add_modules.contains(&module) {
+                        add_modules.push(module);
+                    }
+                }
+            }
+        }
+    }
+
+    // Build classpath and module path
+    m.jvm_opts.append(&mut m.config.read_launch_opts());
+    m.user_jvm_opts.append(&mut m.config.read_user_launch_opts());
+    let mut paths = path_opts(classpath.as_deref(), &module_path, &add_modules, m.config.main_module.as_deref());
+    m.jvm_opts.append(&mut paths);
+
+    if m.config.enable_preview && !m.jvm_opts.iter().any(|o| o == "--enable-preview") {
+        m.jvm_opts.push("--enable-preview".to_string());
+    }
+
+    // Drop-in agents, such as for monitoring
+    for agent in find_agents(&m.config) {
+        m.jvm_opts.push(format!("-javaagent:{}", agent.display()));
+    }
+
+    // Let bundled natives be found with System.loadLibrary
+    add_native_lib_dirs(&mut m.jvm_opts, &get_native_lib_dirs(&m.config));
+
+    // Keep temporary files out of a temp dir Java cannot use
+    if let Some(tmpdir) = &m.config.tmpdir {
+        if !m.jvm_opts.iter().any(|o| o.starts_with("-Djava.io.tmpdir=")) {
+            let tmpdir = resolve_path(tmpdir);
+            match fs::create_dir_all(&tmpdir) {
+                Ok(_) => m.jvm_opts.push(format!("-Djava.io.tmpdir={}", tmpdir.display())),
+                Err(e) => info!("config", "Failed to create the temp dir {:?}: {:?}", tmpdir, e),
+            }
+        }
+    }
+
+    // Encoding and locale, unless the app sets them itself
+    let mut locale_opts = encoding_and_locale_opts(&m.config, &m.jvm_opts);
+    m.jvm_opts.append(&mut locale_opts);
+
+    // Let the app know where it can leave options for the next launch
+    if let Some(relaunch_file) = get_relaunch_file() {
+        m.jvm_opts.push(format!("-Dwhy.relaunch.file={}", relaunch_file.display()));
+    }
+    m.user_jvm_opts.append(&mut m.config.read_relaunch_opts());
+
+    // Show the app's name and icon in the Dock and menu bar instead of the main class
+    #[cfg(target_os = "macos")]
+    {
+        let mut dock_opts = dock_opts(&m.config, &m.all_jvm_opts());
+        m.jvm_opts.append(&mut dock_opts);
+    }
+
+    // Scale the app as the desktop is, which Java does not fully on Linux
+    let mut ui_scale_opts = ui_scale_opts(&m.config, &m.all_jvm_opts());
+    m.jvm_opts.append(&mut ui_scale_opts);
+
+    // Let the app know it was started by the launcher, and which one
+    m.jvm_opts.push(format!("-Dwhy.launcher.version={}", env!("CARGO_PKG_VERSION")));
+    if let Ok(exe) = env::current_exe() {
+        m.jvm_opts.push(format!("-Dwhy.launcher.path={}", exe.display()));
+    }
+    m.jvm_opts.push(format!("-Dwhy.launcher.pid={}", process::id()));
+    // Where only the app's crash logs are, to find them after a crash
+    m.jvm_opts.extend(crash_log_opt());
+    m.jvm_opts.push(format!("-Dwhy.install.scope={}", install_scope()));
+    if let Some(data_dir) = get_user_data_dir() {
+        m.jvm_opts.push(format!("-Dwhy.data.dir={}", data_dir.display()));
+    }
+    let first_run = is_first_run(&m.config);
+    m.jvm_opts.push(format!("-Dwhy.first-run={}", first_run));
+
+    // The Java the user chose, now or on an earlier launch
+    if launcher_args.choose_jvm {
+        if !choose_jvm(&mut m.config) {
+            return ExitCode::Cancelled.code();
+        }
+    } else {
+        use_chosen_jvm(&mut m.config);
+    }
+
+    // Once everything the launch would use is known
+    if launcher_args.diagnose {
+        close_zips();
+        return write_diagnosis(&m);
+    }
+
+    // No Java could match, such as when the main class needs an older Java than min_java for its preview features
+    if let Some((min, max)) = m.config.java_conflict() {
+        let config_file = m.config.config_file.as_ref().map(|f| f.display().to_string()).unwrap_or_default();
+        message(&text_with("config.java_conflict", &[&config_file, &min.to_string(), &max.to_string()]));
+        return ExitCode::ConfigInvalid.code();
+    }
+
+    // Refuse to run a tampered app
+    if !check_app_signature(&m.config) {
+        return ExitCode::Refused.code();
+    }
+    if !check_app_integrity(&m.config) {
+        return ExitCode::Refused.code();
+    }
+    if !check_app_permissions(&m.config) {
+        return ExitCode::Refused.code();
+    }
+
+    // Before Java is started, in the launcher's directory as the config's paths are
+    if run_pre_launch_hooks(&m.config.hooks) == HookResult::Aborted {
+        return ExitCode::PreflightFailed.code();
+    }
+    if first_run {
+        match run_first_run_hooks(&m.config.hooks) {
+            HookResult::Aborted => return ExitCode::PreflightFailed.code(),
+            // Listed as launched once the app has started
+            HookResult::Succeeded => set_first_run_succeeded(),
+            HookResult::Failed => {}
+        }
+    }
+
+    // Last, as the launcher takes relative paths from its own directory
+    if m.config.preserve_cwd {
+        pass_launch_dir(&mut m);
+    }
+
+    // The launcher is done reading the app's jars
+    close_zips();
+
+    // Run the app in a separate Java process
+    if m.config.launch_mode == LaunchMode::Process {
+        return run_java_process(&m);
+    }
+
+    // Watch for Java getting stuck while starting, until the launch ends however it does
+    let _watchdog = m.config.startup_timeout
+        .filter(|timeout| *timeout > 0)
+        .map(|timeout| start_watchdog(Duration::from_secs(timeout as u64)));
+
+    // Run the app
+    // Done on a separate thread per the note in:
+    // https://docs.oracle.com/en/java/javase/17/docs/specs/jni/invocation.html#creating-the-vm
+    let handle = thread::spawn(move || {
+        create_and_run_jvm(&m)
+    });
+
+    handle.join().unwrap_or(ExitCode::LauncherCrashed.code())
+}
+
+/// Append the directories to `java.library.path`, adding the option if the launch options do not
+/// set it. In that case the library path of the OS is kept in it too, as Java would have used.
+fn add_native_lib_dirs(jvm_opts: &mut Vec<String>, dirs: &[PathBuf]) {
+    if dirs.is_empty() {
+        return;
+    }
+
+    const PROPERTY: &str = "-Djava.library.path=";
+    let existing = jvm_opts.iter().position(|o| o.starts_with(PROPERTY));
+    let mut paths: Vec<PathBuf> = match existing {
+        Some(i) => env::split_paths(&jvm_opts.remove(i)[PROPERTY.len()..]).collect(),
+        None => vec![],
+    };
+    paths.extend(dirs.iter().cloned());
+    if existing.is_none() {
+        if let Some(os_paths) = env::var_os(LIBRARY_PATH_VAR) {
+            paths.extend(env::split_paths(&os_paths));
+        }
+    }
+
+    if let Ok(joined) = env::join_paths(paths) {
+        jvm_opts.push(format!("{}{}", PROPERTY, joined.to_string_lossy()));
+    }
+}
+
+/// Pass the working directory the launcher was started in to the app as `user.launch.dir`, for `preserve_cwd`,
+/// the app being run in it once its Java is chosen, see [`file_handler::app_working_dir`]. The paths of
+/// the launch the JVM resolves itself are made absolute, against the launcher's directory they are relative to.
+fn pass_launch_dir(m: &mut LaunchOpts) {
+    let (launch_dir, launcher_dir) = match (get_launch_dir(), env::current_dir()) {
+        (Some(launch_dir), Ok(launcher_dir)) => (launch_dir, launcher_dir),
+        _ => {
+            info!("launcher", "Not knowing the working directory the launcher was started in, the app runs in the launcher's");
+            return;
+        }
+    };
+    let absolute = |path: &str| launcher_dir.join(path).display().to_string();
+    let absolute_list = |list: &str, separator: &str| list.split(separator)
+        .map(|p| if p.is_empty() { p.to_string() } else { absolute(p) })
+        .collect::<Vec<String>>().join(separator);
+    m.config.jvm_path = m.config.jvm_path.as_deref().map(absolute);
+    for opt in m.jvm_opts.iter_mut() {
+        if let Some(classpath) = opt.strip_prefix("-Djava.class.path=") {
+            *opt = format!("-Djava.class.path={}", absolute_list(classpath, PATH_SEPARATOR));
+        } else if let Some(module_path) = opt.strip_prefix("--module-path=") {
+            *opt = format!("--module-path={}", absolute_list(module_path, PATH_SEPARATOR));
+        } else if let Some(library_path) = opt.strip_prefix("-Djava.library.path=") {
+            *opt = format!("-Djava.library.path={}", absolute_list(library_path, PATH_SEPARATOR));
+        } else if let Some(agent) = opt.strip_prefix("-javaagent:") {
+            let (jar, options) = agent.split_once('=').map_or((agent, None), |(j, o)| (j, Some(o)));
+            *opt = format!("-javaagent:{}{}", absolute(jar), options.map(|o| format!("={}", o)).unwrap_or_default());
+        }
+    }
+    m.jvm_opts.push(format!("-Duser.launch.dir={}", launch_dir.display()));
+}
+
+/// This makes sure the current working directory is the exe's home.<br>
+/// This can differ from the current working directory in cases where you are running the exe
+/// from command line or script from a different location.
+fn correct_directory() {
+    // This gets the location of the exe file, not its current working directory
+    // These can differ if say running the exe through command line when in a different folder
+    let exe_home = env::current_exe();
+    if let Ok(exe_home) = exe_home {
+        if let Some(exe_home) = exe_home.parent() {
+            // Fails for paths too long to be the working directory, paths are then taken from the exe
+            if let Err(e) = env::set_current_dir(exe_home) {
+                info!("launcher", "Failed to change to the launcher's dir {:?}: {}", exe_home, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,350 @@
+use std::fs;
+use std::sync::Mutex;
+
+use crate::file_handler::get_app_dir;
+use crate::LauncherConfig;
+
+/// The launcher's messages in English, by key. Apps can translate them, see [`text`].
+const DEFAULT_TEXTS: &'static [(&str, &str)] = &[
+    ("button.ok", "OK"),
+    ("button.cancel", "Cancel"),
+    ("button.yes", "Yes"),
+    ("button.no", "No"),
+    ("button.retry", "Retry"),
+    ("button.get_java", "Get Java"),
+    ("button.copy_details", "Copy details"),
+    ("button.continue", "Continue"),
+    ("button.quit", "Quit"),
+    ("button.open_release_page", "Open release page"),
+    ("button.later", "Later"),
+    ("dialog.details", "Details:"),
+    ("dialog.show_details", "Show details"),
+    ("dialog.hide_details", "Hide details"),
+    ("config.incomplete", "The app cannot be started, its launcher config {0} does not say what to launch. \
+        It needs:\n{1}\n\nPlease contact the developers."),
+    ("config.java_conflict", "The app cannot be started, its launcher config {0} asks for Java {1} or newer, \
+        but at most Java {2}, such as for the preview features its main class uses.\n\nPlease contact the developers."),
+    ("config.not_found", "The app cannot be started, its launcher config {0} was not found or could not be read.\n\n\
+        Please contact the developers."),
+    ("java.missing", "A missing or older Java installation was found.\nPlease install {0}\n\n\
+        Get Java - Open the download page\n\
+        Retry - Look for Java again once installed\n\
+        Quit - Close the app"),
+    ("java.any", "any Java."),
+    ("java.min_version", "Java {0} or newer."),
+    ("jvm.attach_failed", "Java successfully started, but failed to attach to it and therefore cannot proceed.\n\
+        Please contact the developers."),
+    ("jvm.invalid_option", "Failed to create JVM arguments.\n\
+        Please contact the developers or undo any changes to the configuration."),
+    ("jvm.load_failed", "A Java installation was found, but could not be loaded.\n\
+        It may be damaged, or made for a different type of computer (such as 32-bit Java on a 64-bit system).\n\
+        Please reinstall Java or contact the developers."),
+    ("jvm.missing_entry_point", "A Java installation was found, but it is not usable by the launcher.\n\
+        It may be damaged, please reinstall Java or contact the developers."),
+    ("jvm.no_memory", "Not enough memory is available for the requested heap size.\n\
+        Please close other programs, or lower the maximum heap size."),
+    ("jvm.too_old", "The Java installation found is too old to be started by the launcher.\n\
+        Please install a newer version of Java."),
+    ("jvm.rejected_args", "A valid Java installation was found, but it rejected the launch arguments.\n\
+        Please undo any changes to the configuration or contact the developers."),
+    ("jvm.already_started", "Java was already started by the launcher and cannot be started again.\n\
+        Please contact the developers."),
+    ("jvm.failed", "A valid Java installation was found, failed to start (error code {0}).\n\
+        Please check the launch arguments as they may be invalid.\n\
+        Please contact the developers."),
+    ("jvm.choose", "Choose the Java installation to run the app with, it is used from now on:"),
+    ("jvm.choice", "Java {0} in {1}"),
+    ("console.close", "The app has exited, press Enter to close this window."),
+    ("hook.failed", "The app cannot be started, a command it runs first failed:\n{0}\n({1})\n\n\
+        Please contact the developers."),
+    ("app.main_not_found", "Failed to start the app, the classname was invalid or \
+        not on the classpath, or the main method could not be found.\n\
+        Please contact the developers."),
+    ("app.module_not_found", "Failed to start the app, the module was not found on the \
+        module path or has no main class.\n\
+        Please contact the developers."),
+    ("app.fatal_error", "The app encountered an error it could not recover from.\n\
+        Please contact the developers."),
+    ("app.unexpected_error", "The app encountered an unexpected error.\n\
+        Please contact the developers."),
+    ("app.restart_failed", "Failed to restart the app.\nPlease start it again manually."),
+    ("app.crashed", "Java crashed while running the app. \
+        Please send the crash log to the developers, it was saved to:\n{0}\n\n\
+        Yes - Open the folder containing the crash log\n\
+        No - Copy the path of the crash log\n\
+        Cancel - Close"),
+    ("startup.slow", "The app is taking longer than expected to start.\n\n\
+        Yes - Keep waiting\n\
+        No - Save diagnostic information and keep waiting\n\
+        Cancel - Quit the app"),
+    ("startup.diagnostics_saved", "The diagnostic information was saved to:\n{0}"),
+    ("startup.diagnostics_failed", "Failed to save the diagnostic information:\n\n{0}"),
+    ("signature.refused", "The app was not started as its files are not as the developers signed them. \
+        Please reinstall the app."),
+    ("signature.warning", "The app's files are not as the developers signed them, \
+        it may have been tampered with. Consider reinstalling the app."),
+    ("integrity.failed", "The app was not started as some of its files are missing or damaged, \
+        such as by an incomplete download or antivirus software. Please reinstall the app."),
+    ("permissions.refused", "The app was not started as other users of this computer can change its files, \
+        which would let them run programs as you. Please ask your administrator to fix their permissions."),
+    ("permissions.warning", "Other users of this computer can change some of the app's files, \
+        which would let them run programs as you. Consider asking your administrator to fix their permissions."),
+    ("archive.unreadable", "The archive {0} is too large or corrupt for this launcher:\n{1}"),
+    ("notify.system_java", "The app's own Java could not be used, it runs with the Java installed on this computer."),
+    ("diagnosis.written", "The diagnosis was written to {0}, and for tools to {1}."),
+    ("launcher.crashed", "The launcher crashed, this is a bug in the launcher."),
+    ("log.written", "Details were written to {0}"),
+    ("notify.dropped_options", "Java did not start with the options {0}, the app was started without them."),
+    ("protocol.invalid", "{0} is not a valid URL scheme, it must start with a letter followed by letters, digits, \
+        '+', '-' or '.'."),
+    ("protocol.registered", "The app now opens {0}: links."),
+    ("protocol.register_failed", "Failed to make the app open {0}: links:\n\n{1}"),
+    ("protocol.unregistered", "The app no longer opens {0}: links."),
+    ("protocol.unregister_failed", "Failed to stop the app from opening {0}: links:\n\n{1}"),
+    ("protocol.undeclared", "The app's Info.plist does not declare the {0} scheme. Add it to CFBundleURLSchemes \
+        in CFBundleURLTypes, macOS only passes links to apps declaring their scheme."),
+    ("bundle.translocated", "macOS runs the app from a temporary read-only copy, as it was opened where it was \
+        downloaded or unpacked. It may not find its files or keep its settings.\n\n\
+        Quit the app, move it to the Applications folder with the Finder, and open it from there."),
+    ("update.available", "Version {0} of the app is available, you have version {1}."),
+    ("update.staged", "Version {0} of the app was downloaded, it will be installed the next time the app starts."),
+    ("update.rolled_back", "Version {0} of the app failed to start, the previous version was restored."),
+    ("update.in_progress", "The app is being updated, it can be started once the update is complete."),
+    ("update.applying", "Installing version {0} of the app"),
+    ("update.apply_failed", "Version {0} of the app could not be installed, the previous version is kept."),
+    ("daemon.running", "The app already runs in the background, as process {0}."),
+    ("daemon.failed", "Failed to run the app in the background:\n\n{0}"),
+    ("daemon.unsupported", "Running the app in the background with --why-daemon is only supported on Linux."),
+    ("elevation.declined", "The app needs to run as an administrator, and was not allowed to."),
+    ("elevation.failed", "Failed to start the app as an administrator:\n\n{0}"),
+    ("filetypes.none", "The launcher config has no [FileAssociations] to register."),
+    ("filetypes.registered", "The app now opens {0} files."),
+    ("filetypes.register_failed", "Failed to make the app open {0} files:\n\n{1}"),
+    ("filetypes.unregistered", "The app no longer opens {0} files."),
+    ("filetypes.unregister_failed", "Failed to stop the app from opening {0} files:\n\n{1}"),
+    ("filetypes.undeclared", "The app's Info.plist does not declare the {0} extension. Add it to \
+        CFBundleDocumentTypes, macOS only opens the files apps declare with them."),
+    ("filetypes.unregister_macos", "macOS stops opening the files with the app when their types are removed \
+        from the app's Info.plist, or the app is deleted."),
+    ("protocol.unregister_macos", "macOS stops opening {0}: links with the app when the scheme is removed from \
+        the app's Info.plist, or the app is deleted."),
+];
+
+/// The translated messages for the user's locale, read when first needed.
+static TRANSLATIONS: Mutex<Option<Vec<(String, String)>>> = Mutex::new(None);
+
+/// The JVM options for the encoding and locale enabled in the config, leaving out any property
+/// the existing options already set.
+pub fn encoding_and_locale_opts(config: &LauncherConfig, existing: &[String]) -> Vec<String> {
+    let mut opts: Vec<String> = vec![];
+    let mut add = |property: &str, value: &str| {
+        let prefix = format!("-D{}=", property);
+        if !existing.iter().any(|o| o.starts_with(&prefix)) {
+            opts.push(prefix + value);
+        }
+    };
+
+    if config.utf8_encoding {
+        add("file.encoding", "UTF-8");
+        add("sun.jnu.encoding", "UTF-8");
+    }
+
+    if config.use_os_locale {
+        if let Some((language, country)) = get_os_locale() {
+            add("user.language", &language);
+            if let Some(country) = country {
+                add("user.country", &country);
+            }
+        }
+    }
+
+    opts
+}
+
+/// The language and country of the user's locale, such as `en` and `US`.
+pub fn get_os_locale() -> Option<(String, Option<String>)> {
+    parse_locale(&read_os_locale()?)
+}
+
+/// Split a locale such as `en-US`, `en_US.UTF-8` or `de_DE@euro` into its language and country.
+fn parse_locale(locale: &str) -> Option<(String, Option<String>)> {
+    let locale = locale.split(|c| c == '.' || c == '@').next()?;
+    let mut parts = locale.split(|c| c == '_' || c == '-');
+
+    let language = parts.next()?.to_lowercase();
+    if language.is_empty() || language == "c" || language == "posix" {
+        return None;
+    }
+    let country = parts.next()
+        .filter(|c| c.len() == 2 && c.chars().all(|c| c.is_ascii_alphabetic()))
+        .map(|c| c.to_uppercase());
+
+    Some((language, country))
+}
+
+#[cfg(windows)]
+fn read_os_locale() -> Option<String> {
+    use winapi::um::winnls::GetUserDefaultLocaleName;
+    use winapi::um::winnt::LOCALE_NAME_MAX_LENGTH;
+    let mut buf = [0u16; LOCALE_NAME_MAX_LENGTH];
+    let len = unsafe { GetUserDefaultLocaleName(buf.as_mut_ptr(), buf.len() as i32) };
+    if len <= 1 {
+        return None;
+    }
+    // The length includes the terminating null
+    Some(String::from_utf16_lossy(&buf[..len as usize - 1]))
+}
+
+#[cfg(not(windows))]
+fn read_os_locale() -> Option<String> {
+    use std::env;
+    ["LC_ALL", "LC_MESSAGES", "LANG"].iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|v| !v.is_empty())
+}
+
+/// A message for the user, in their language if the app has a translation of it.<br>
+/// Translations are `.properties` files in the `i18n` folder next to the launcher, named after
+/// the language and country (`de_CH.properties`) or just the language (`de.properties`),
+/// with the keys of [`DEFAULT_TEXTS`]. Messages missing from them stay in English.
+pub fn text(key: &str) -> String {
+    text_with(key, &[])
+}
+
+/// A message for the user, see [`text`], with `{0}`, `{1}` and so on replaced by the arguments.
+pub fn text_with(key: &str, args: &[&str]) -> String {
+    let translated = TRANSLATIONS.lock().ok().and_then(|mut translations| {
+        translations.get_or_insert_with(read_translations).iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, t)| t.clone())
+    });
+    let template = translated
+        .or_else(|| DEFAULT_TEXTS.iter().find(|(k, _)| *k == key).map(|(_, t)| t.to_string()))
+        .unwrap_or_else(|| key.to_string());
+    fill_placeholders(&template, args)
+}
+
+/// Replace the `{n}` placeholders in one pass, so that arguments are never read as placeholders.
+fn fill_placeholders(template: &str, args: &[&str]) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let placeholder = rest[start + 1..].find('}')
+            .and_then(|end| Some((end, args.get(rest[start + 1..start + 1 + end].parse::<usize>().ok()?)?)));
+        match placeholder {
+            Some((end, arg)) => {
+                out.push_str(arg);
+                rest = &rest[start + end + 2..];
+            }
+            None => {
+                out.push('{');
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Read the translations for the user's locale, the country specific ones first.
+fn read_translations() -> Vec<(String, String)> {
+    let (language, country) = match get_os_locale() {
+        Some(locale) => locale,
+        None => return vec![],
+    };
+    let i18n_dir = match get_app_dir() {
+        Some(app_dir) => app_dir.join("i18n"),
+        None => return vec![],
+    };
+
+    let mut names: Vec<String> = vec![];
+    if let Some(country) = country {
+        names.push(format!("{}_{}.properties", language, country));
+    }
+    names.push(format!("{}.properties", language));
+
+    let mut translations: Vec<(String, String)> = vec![];
+    for name in names {
+        if let Ok(bytes) = fs::read(i18n_dir.join(&name)) {
+            debug!("config", "Using the translations in {}", name);
+            translations.append(&mut parse_properties(&String::from_utf8_lossy(&bytes)));
+        }
+    }
+    translations
+}
+
+/// Parse a `.properties` file as Java's `Properties.load` does, in UTF-8 as resource bundles are.<br>
+/// See <https://docs.oracle.com/en/java/javase/17/docs/api/java.base/java/util/Properties.html#load(java.io.Reader)>
+fn parse_properties(content: &str) -> Vec<(String, String)> {
+    let mut properties: Vec<(String, String)> = vec![];
+    let mut lines = content.lines();
+    while let Some(line) = lines.next() {
+        let mut line = line.trim_start().to_string();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+            continue;
+        }
+        // An odd number of trailing backslashes continues the line
+        while line.chars().rev().take_while(|c| *c == '\\').count() % 2 == 1 {
+            line.pop();
+            match lines.next() {
+                Some(next) => line.push_str(next.trim_start()),
+                None => break,
+            }
+        }
+
+        // The key ends at the first unescaped separator
+        let chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+        while i < chars.len() && !matches!(chars[i], '=' | ':' | ' ' | '\t' | '\x0C') {
+            i += if chars[i] == '\\' { 2 } else { 1 };
+        }
+        let key_end = i.min(chars.len());
+        while i < chars.len() && matches!(chars[i], ' ' | '\t' | '\x0C') {
+            i += 1;
+        }
+        if i < chars.len() && matches!(chars[i], '=' | ':') {
+            i += 1;
+        }
+        while i < chars.len() && matches!(chars[i], ' ' | '\t' | '\x0C') {
+            i += 1;
+        }
+
+        let key = unescape_property(&chars[..key_end]);
+        let value = unescape_property(&chars[i..]);
+        properties.push((key, value));
+    }
+    properties
+}
+
+fn unescape_property(chars: &[char]) -> String {
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] != '\\' || i + 1 == chars.len() {
+            out.push(chars[i]);
+            i += 1;
+            continue;
+        }
+        i += 1;
+        match chars[i] {
+            'n' => out.push('\n'),
+            't' => out.push('\t'),
+            'r' => out.push('\r'),
+            'f' => out.push('\x0C'),
+            'u' => {
+                let hex: String = chars[i + 1..chars.len().min(i + 5)].iter().collect();
+                match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                    Some(c) if hex.len() == 4 => {
+                        out.push(c);
+                        i += 4;
+                    }
+                    _ => out.push('u'),
+                }
+            }
+            c => out.push(c),
+        }
+        i += 1;
+    }
+    out
+}
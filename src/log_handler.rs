@@ -0,0 +1,283 @@
+use std::{env, mem, process};
+use std::fmt::Arguments;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::file_handler::{get_app_name, get_log_dir};
+use crate::output_handler::{log_path, open_log, output_captured};
+
+// Defined first, to be usable below
+/// Log what the launcher decided or what went wrong, with the category first.
+macro_rules! info {
+    ($category:expr, $($arg:tt)+) => {
+        $crate::log_handler::write($crate::log_handler::Level::Info, $category, format_args!($($arg)+))
+    };
+}
+
+/// Log the details of how the launcher decided, with the category first.
+macro_rules! debug {
+    ($category:expr, $($arg:tt)+) => {
+        $crate::log_handler::write($crate::log_handler::Level::Debug, $category, format_args!($($arg)+))
+    };
+}
+
+/// The environment variable setting how much the launcher logs, see [`Level`].
+pub const LOG_VAR: &str = "WHY_LOG";
+
+/// How much the launcher logs, each level including those before it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Nothing.
+    Off = 0,
+    /// What the launcher decided and what went wrong, the default.
+    Info = 1,
+    /// How it got there, such as each Java installation considered.
+    Debug = 2,
+}
+
+impl Level {
+    /// The level for the value of [`LOG_VAR`], `None` if unknown.
+    fn from_key(key: &str) -> Option<Level> {
+        match key.trim().to_ascii_lowercase().as_str() {
+            "off" => Some(Level::Off),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            _ => None,
+        }
+    }
+}
+
+/// The current level, [`UNSET`] until it is first needed or set.
+static LEVEL: AtomicU8 = AtomicU8::new(UNSET);
+
+const UNSET: u8 = u8::MAX;
+
+/// Whether the logged lines are also printed to stderr, only when asked for with `--why-verbose` or
+/// [`LOG_VAR`], so that they do not mix with the app's output, and not while it is captured.
+static ECHO: AtomicBool = AtomicBool::new(false);
+
+/// Log everything, as asked for with `--why-verbose`, whatever [`LOG_VAR`] says.
+pub fn set_verbose() {
+    LEVEL.store(Level::Debug as u8, Ordering::SeqCst);
+    ECHO.store(true, Ordering::SeqCst);
+}
+
+/// The level set with [`LOG_VAR`], or [`set_verbose`].
+pub fn level() -> Level {
+    match LEVEL.load(Ordering::SeqCst) {
+        0 => Level::Off,
+        1 => Level::Info,
+        2 => Level::Debug,
+        _ => {
+            let var = env::var(LOG_VAR).ok();
+            let set = var.as_deref().and_then(Level::from_key);
+            if set.is_some() {
+                ECHO.store(true, Ordering::SeqCst);
+            }
+            let level = set.unwrap_or(Level::Info);
+            // Unless `--why-verbose` was read in the meantime
+            let _ = LEVEL.compare_exchange(UNSET, level as u8, Ordering::SeqCst, Ordering::SeqCst);
+            if let Some(var) = var.filter(|v| Level::from_key(v).is_none()) {
+                write(Level::Info, "launcher", format_args!("Unknown {} level {}, using info", LOG_VAR, var));
+            }
+            level
+        }
+    }
+}
+
+/// Name of the log the launcher's own lines are kept in, in the log dir.
+const LAUNCHER_LOG: &str = "launcher";
+
+/// The most lines kept while the launcher log is not open yet.
+const MAX_PENDING_LINES: usize = 1000;
+
+/// Where the launcher's lines are written besides its output.
+enum LogFile {
+    /// Not open yet, the lines are kept until it is.
+    Pending(Vec<String>),
+    Open(PathBuf, File),
+    /// Could not be opened.
+    Closed,
+}
+
+static LOG_FILE: Mutex<LogFile> = Mutex::new(LogFile::Pending(Vec::new()));
+
+/// Open the launcher log in the log dir, rotated with the output log's limits, and write the
+/// lines logged so far to it. Everything is written to it, unless logging is off, whether or
+/// not there is a console, so that launch failures can be diagnosed afterwards.
+pub fn open_log_file() {
+    let opened = get_log_dir()
+        .and_then(|dir| Some((log_path(&dir, LAUNCHER_LOG, 0), open_log(&dir, LAUNCHER_LOG)?)));
+    let failed = match LOG_FILE.lock() {
+        Ok(mut log) => {
+            let pending = match mem::replace(&mut *log, LogFile::Closed) {
+                LogFile::Pending(lines) => lines,
+                other => {
+                    *log = other;
+                    return;
+                }
+            };
+            match opened {
+                Some((path, mut file)) => {
+                    let _ = writeln!(file, "--- {} {} launched at {}, process {}", get_app_name(),
+                                     env!("CARGO_PKG_VERSION"), date_time(), process::id());
+                    for line in pending {
+                        let _ = writeln!(file, "{}", line);
+                    }
+                    *log = LogFile::Open(path, file);
+                    false
+                }
+                None => true,
+            }
+        }
+        Err(_) => true,
+    };
+    if failed {
+        info!("launcher", "Failed to open the launcher log in {:?}", get_log_dir());
+    }
+}
+
+/// The launcher log, if it is open.
+pub fn log_file_path() -> Option<PathBuf> {
+    match &*LOG_FILE.lock().ok()? {
+        LogFile::Open(path, _) => Some(path.clone()),
+        _ => None,
+    }
+}
+
+/// Write the message if the level is logged, use [`info!`] and [`debug!`] instead.<br>
+/// Lines start with the UTC time and the category, such as `config`, `discovery` or `jni`.
+/// The launcher log gets every level, unless logging is off, and stderr the logged levels once asked for.
+pub fn write(level: Level, category: &str, args: Arguments) {
+    let logged = self::level();
+    if logged == Level::Off {
+        return;
+    }
+    let line = format!("{} [{}] {}", timestamp(), category, args);
+    if level <= logged && ECHO.load(Ordering::SeqCst) && !output_captured() {
+        eprintln!("{}", line);
+    }
+    if let Ok(mut log) = LOG_FILE.lock() {
+        match &mut *log {
+            LogFile::Pending(lines) if lines.len() < MAX_PENDING_LINES => lines.push(line),
+            LogFile::Open(_, file) => {
+                let _ = writeln!(file, "{}", line);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The time of day in UTC, to the millisecond.
+fn timestamp() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = now.as_secs() % (24 * 60 * 60);
+    format!("{:02}:{:02}:{:02}.{:03}", secs / 3600, secs / 60 % 60, secs % 60, now.subsec_millis())
+}
+
+/// The date and time in UTC, such as `2024-01-31 12:00:00 UTC`.
+fn date_time() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (year, month, day) = civil_date((secs / (24 * 60 * 60)) as i64);
+    let time = secs % (24 * 60 * 60);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC", year, month, day,
+            time / 3600, time / 60 % 60, time % 60)
+}
+
+/// The year, month and day of a number of days since 1970-01-01, in the Gregorian calendar.
+/// See Howard Hinnant's `civil_from_days`.
+fn civil_date(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Whether launch failures are also reported to the OS log, see [`set_system_log`].
+static SYSTEM_LOG: AtomicBool = AtomicBool::new(false);
+
+/// Also report launch failures to the log of the OS, the Event Log on Windows, the systemd journal on
+/// Linux and the unified log on macOS, so that admins can monitor the installs centrally.
+pub fn set_system_log(enabled: bool) {
+    SYSTEM_LOG.store(enabled, Ordering::SeqCst);
+}
+
+/// Report the failure to the OS log if enabled, see [`set_system_log`].
+pub fn report_to_system_log(msg: &str) {
+    if SYSTEM_LOG.load(Ordering::SeqCst) && !write_system_log(msg) {
+        debug!("launcher", "Failed to write to the OS log");
+    }
+}
+
+/// Report an error event from a source named after the app. Without a message file registered
+/// for the source, the Event Viewer shows the message after a note that its description is missing.
+#[cfg(windows)]
+fn write_system_log(msg: &str) -> bool {
+    use std::ffi::OsStr;
+    use std::iter::once;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr::null_mut;
+    use winapi::um::winbase::{DeregisterEventSource, RegisterEventSourceW, ReportEventW};
+    use winapi::um::winnt::EVENTLOG_ERROR_TYPE;
+    let source: Vec<u16> = OsStr::new(&get_app_name()).encode_wide().chain(once(0)).collect();
+    let wide: Vec<u16> = OsStr::new(msg).encode_wide().chain(once(0)).collect();
+    unsafe {
+        let handle = RegisterEventSourceW(null_mut(), source.as_ptr());
+        if handle.is_null() {
+            return false;
+        }
+        let mut strings = [wide.as_ptr()];
+        let reported = ReportEventW(handle, EVENTLOG_ERROR_TYPE, 0, 1, null_mut(), 1, 0,
+                                    strings.as_mut_ptr(), null_mut()) != 0;
+        DeregisterEventSource(handle);
+        reported
+    }
+}
+
+/// Send an error entry to the journal with its native protocol, each field's value is prefixed by
+/// its length so that it may contain line breaks.<br>
+/// See <https://systemd.io/JOURNAL_NATIVE_PROTOCOL/>
+#[cfg(all(unix, not(target_os = "macos")))]
+fn write_system_log(msg: &str) -> bool {
+    use std::os::unix::net::UnixDatagram;
+    const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+    // syslog's LOG_ERR
+    const PRIORITY_ERROR: &str = "3";
+
+    let app_name = get_app_name();
+    let mut entry: Vec<u8> = vec![];
+    for (field, value) in [("MESSAGE", msg), ("PRIORITY", PRIORITY_ERROR), ("SYSLOG_IDENTIFIER", &app_name)] {
+        entry.extend_from_slice(field.as_bytes());
+        entry.push(b'\n');
+        entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        entry.extend_from_slice(value.as_bytes());
+        entry.push(b'\n');
+    }
+    UnixDatagram::unbound().and_then(|s| s.send_to(&entry, JOURNAL_SOCKET)).is_ok()
+}
+
+/// Log an error through `syslog`, which macOS keeps in its unified log.
+#[cfg(target_os = "macos")]
+fn write_system_log(msg: &str) -> bool {
+    use std::ffi::CString;
+    let (ident, msg) = match (CString::new(get_app_name()), CString::new(msg.replace('\0', ""))) {
+        (Ok(ident), Ok(msg)) => (ident, msg),
+        _ => return false,
+    };
+    unsafe {
+        libc::openlog(ident.as_ptr(), libc::LOG_PID, libc::LOG_USER);
+        libc::syslog(libc::LOG_ERR, b"%s\0".as_ptr() as *const libc::c_char, msg.as_ptr());
+        libc::closelog();
+    }
+    true
+}
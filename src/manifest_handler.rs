@@ -0,0 +1,560 @@
+use std::fs;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use zip::ZipArchive;
+
+use crate::zip_handler::read_zip_entry;
+
+/// Where the manifest is kept in a jar.
+pub const MANIFEST_PATH: &str = "META-INF/MANIFEST.MF";
+
+/// The longest attribute name the JAR spec allows.
+const MAX_NAME_LEN: usize = 70;
+
+/// The UTF-8 byte order mark, which some tools write at the start of manifests.
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Whether manifests are parsed strictly, see [`set_strict_manifests`].
+static STRICT_MANIFESTS: AtomicBool = AtomicBool::new(false);
+
+/// A jar manifest, see <https://docs.oracle.com/en/java/javase/17/docs/specs/jar/jar.html#jar-manifest>
+#[derive(Debug, Default)]
+pub struct Manifest {
+    /// The main section, describing the jar itself
+    pub main_attributes: Vec<(String, String)>,
+}
+
+impl Manifest {
+    /// Get an attribute of the main section, attribute names are case-insensitive.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.main_attributes.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Whether the jar is a multi-release jar, with classes for specific Java versions
+    /// under `META-INF/versions/<version>/`.
+    pub fn is_multi_release(&self) -> bool {
+        self.get("Multi-Release").map(|v| v.trim().eq_ignore_ascii_case("true")).unwrap_or(false)
+    }
+}
+
+/// Read the manifest of the jar at the path, if it has one, or of the directory of an exploded jar,
+/// from its `META-INF` folder.
+pub fn read_jar_manifest(jar_path: &Path) -> Option<Manifest> {
+    if jar_path.is_dir() {
+        return parse_manifest_bytes(&fs::read(jar_path.join(MANIFEST_PATH)).ok()?);
+    }
+    parse_manifest_bytes(&read_zip_entry(jar_path, MANIFEST_PATH)?)
+}
+
+/// The `Class-Path` entries of a jar's manifest as paths. Per the JAR spec they are
+/// space separated URLs relative to the jar, so they are resolved against the jar's directory,
+/// or the directory itself for an exploded jar, and percent-decoded. Entries that are not `file` URLs
+/// are skipped.
+pub fn resolve_class_path(jar_path: &Path, manifest: &Manifest) -> Vec<PathBuf> {
+    let base = if jar_path.is_dir() { jar_path } else { jar_path.parent().unwrap_or(Path::new("")) };
+    manifest.get("Class-Path").unwrap_or_default()
+        .split(' ')
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let path = match entry.split_once(':') {
+                // Not a drive letter
+                Some((scheme, rest)) if scheme.len() > 1 => {
+                    if !scheme.eq_ignore_ascii_case("file") {
+                        debug!("discovery", "Skipping Class-Path entry {}", entry);
+                        return None;
+                    }
+                    // file:/C:/path has a leading slash before the drive on Windows
+                    let rest = rest.trim_start_matches("//");
+                    if cfg!(windows) && rest.get(2..3) == Some(":") {
+                        rest[1..].to_string()
+                    } else {
+                        rest.to_string()
+                    }
+                }
+                _ => entry.to_string(),
+            };
+            Some(base.join(percent_decode(&path)?))
+        })
+        .collect()
+}
+
+/// The `Class-Path` entries of a jar, followed by the entries of those jars' manifests and so on,
+/// in the order `java -jar` would search them. Jars already on the path are skipped, which also
+/// ends cycles, and nesting deeper than `max_depth` is not followed.
+pub fn resolve_class_path_transitively(jar_path: &Path, manifest: &Manifest, max_depth: usize) -> Vec<PathBuf> {
+    let mut seen: Vec<PathBuf> = vec![identity(jar_path)];
+    let mut out: Vec<PathBuf> = vec![];
+    add_class_path(jar_path, manifest, max_depth, &mut seen, &mut out);
+    out
+}
+
+fn add_class_path(jar_path: &Path, manifest: &Manifest, depth: usize,
+                  seen: &mut Vec<PathBuf>, out: &mut Vec<PathBuf>) {
+    for entry in resolve_class_path(jar_path, manifest) {
+        let id = identity(&entry);
+        if seen.contains(&id) {
+            continue;
+        }
+        seen.push(id);
+        out.push(entry.clone());
+
+        let nested = match entry.exists().then(|| read_jar_manifest(&entry)).flatten() {
+            Some(nested) if nested.get("Class-Path").is_some() => nested,
+            _ => continue,
+        };
+        if depth > 1 {
+            add_class_path(&entry, &nested, depth - 1, seen, out);
+        } else {
+            info!("discovery", "Not following the Class-Path of {:?}, it is nested too deeply", entry);
+        }
+    }
+}
+
+/// The path used to tell if two entries are the same jar.
+fn identity(path: &Path) -> PathBuf {
+    dunce::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Decode the `%XX` escapes of a URL path, `None` if it is not valid UTF-8 once decoded.
+fn percent_decode(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let mut out: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes.get(i + 1..i + 3)
+                .and_then(|h| std::str::from_utf8(h).ok())
+                .and_then(|h| u8::from_str_radix(h, 16).ok());
+            if let Some(byte) = hex {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Read the manifest of an opened jar, if it has one.
+pub fn read_manifest<R: Read + Seek>(jar: &mut ZipArchive<R>) -> Option<Manifest> {
+    parse_manifest_bytes(&read_manifest_bytes(jar)?)
+}
+
+/// Parse the raw bytes of a manifest, strictly if set with [`set_strict_manifests`], logging why
+/// it could not be.
+pub fn parse_manifest_bytes(bytes: &[u8]) -> Option<Manifest> {
+    match parse_manifest(bytes, STRICT_MANIFESTS.load(Ordering::Relaxed)) {
+        Ok(manifest) => Some(manifest),
+        Err(e) => {
+            info!("discovery", "{}", e);
+            None
+        }
+    }
+}
+
+/// Read the raw bytes of the manifest of an opened jar, if it has one.
+pub fn read_manifest_bytes<R: Read + Seek>(jar: &mut ZipArchive<R>) -> Option<Vec<u8>> {
+    let mut bytes: Vec<u8> = vec![];
+    jar.by_name(MANIFEST_PATH).ok()?.read_to_end(&mut bytes).ok()?;
+    Some(bytes)
+}
+
+/// Split a manifest into the raw bytes of its sections, the main section first.
+/// Each section includes the blank line ending it, as signature files digest them that way.
+/// Each can be read with [`parse_manifest`].
+pub fn split_sections(bytes: &[u8]) -> Vec<&[u8]> {
+    let mut sections: Vec<&[u8]> = vec![];
+    let mut start = 0;
+    let mut line_start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let terminator_len = match bytes[i] {
+            b'\r' if bytes.get(i + 1) == Some(&b'\n') => 2,
+            b'\r' | b'\n' => 1,
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        let blank = i == line_start;
+        i += terminator_len;
+        line_start = i;
+        if blank {
+            if i - terminator_len > start {
+                sections.push(&bytes[start..i]);
+            }
+            start = i;
+        }
+    }
+    if start < bytes.len() {
+        sections.push(&bytes[start..]);
+    }
+    sections
+}
+
+/// Whether manifests read from jars are parsed strictly, see [`parse_manifest`].
+pub fn set_strict_manifests(strict: bool) {
+    STRICT_MANIFESTS.store(strict, Ordering::Relaxed);
+}
+
+/// Parse the main section of a manifest, the per-entry sections after it are not read.<br>
+/// Per the JAR spec lines end in CR, LF or CRLF, and values longer than 72 bytes continue on
+/// lines starting with a single space. Continuations are joined as bytes before the value is
+/// read as UTF-8, as lines may be split within a character.<br>
+/// When `strict`, anything the spec does not allow is an error, and like `java.util.jar.Manifest`
+/// a last line that does not end in a line terminator is ignored. Otherwise a UTF-8 BOM,
+/// `Name:value` without the space, whitespace around the name and a missing final line
+/// terminator are accepted, and malformed lines are skipped, with a warning.
+pub fn parse_manifest(bytes: &[u8], strict: bool) -> Result<Manifest, String> {
+    let mut manifest = Manifest::default();
+
+    let mut bytes = bytes;
+    if let Some(rest) = bytes.strip_prefix(UTF8_BOM) {
+        lenient(strict, "Manifest starts with a byte order mark".to_string())?;
+        bytes = rest;
+    }
+
+    // The header being read, including its continuations
+    let mut header: Option<Vec<u8>> = None;
+    for line in split_lines(bytes, strict) {
+        if let Some(continuation) = line.strip_prefix(b" ") {
+            match &mut header {
+                Some(header) => header.extend_from_slice(continuation),
+                None => lenient(strict, format!("Malformed manifest line: {}", String::from_utf8_lossy(line)))?,
+            }
+            continue;
+        }
+
+        if let Some(header) = header.take() {
+            push_header(&mut manifest, &header, strict)?;
+        }
+
+        // End of the main section
+        if line.is_empty() {
+            return Ok(manifest);
+        }
+        header = Some(line.to_vec());
+    }
+
+    if let Some(header) = header.take() {
+        push_header(&mut manifest, &header, strict)?;
+    }
+
+    Ok(manifest)
+}
+
+/// Fail when strict, otherwise only warn.
+fn lenient(strict: bool, problem: String) -> Result<(), String> {
+    if strict {
+        return Err(problem);
+    }
+    info!("discovery", "Warning: {}", problem);
+    Ok(())
+}
+
+/// Parse a header and add it to the main section, skipping it if malformed when not strict.
+fn push_header(manifest: &mut Manifest, header: &[u8], strict: bool) -> Result<(), String> {
+    match parse_header(header, strict) {
+        Ok(attribute) => manifest.main_attributes.push(attribute),
+        Err(e) => lenient(strict, e)?,
+    }
+    Ok(())
+}
+
+/// Split into lines ending in CR, LF or CRLF, without the terminators.
+/// A last line without a terminator is left out when `strict`.
+fn split_lines(bytes: &[u8], strict: bool) -> Vec<&[u8]> {
+    let mut lines: Vec<&[u8]> = vec![];
+    let mut start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\r' => {
+                lines.push(&bytes[start..i]);
+                if bytes.get(i + 1) == Some(&b'\n') {
+                    i += 1;
+                }
+                start = i + 1;
+            }
+            b'\n' => {
+                lines.push(&bytes[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    if start < bytes.len() {
+        if strict {
+            info!("discovery", "Warning: ignoring the last manifest line, it has no line terminator");
+        } else {
+            lines.push(&bytes[start..]);
+        }
+    }
+    lines
+}
+
+/// Parse a `Name: value` header, the name being alphanumerics, `-` and `_`.
+/// When not strict, whitespace around the name and a missing space after the colon are accepted.
+fn parse_header(header: &[u8], strict: bool) -> Result<(String, String), String> {
+    let malformed = || format!("Malformed manifest line: {}", String::from_utf8_lossy(header));
+
+    let colon = header.iter().position(|b| *b == b':').ok_or_else(malformed)?;
+    let mut name = &header[..colon];
+    let mut value = &header[colon + 1..];
+    if strict {
+        value = value.strip_prefix(b" ").ok_or_else(malformed)?;
+    } else {
+        let start = name.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(name.len());
+        let end = name.iter().rposition(|b| !b.is_ascii_whitespace()).map_or(start, |i| i + 1);
+        name = &name[start..end];
+        value = value.strip_prefix(b" ").unwrap_or(value);
+    }
+
+    let valid_name = !name.is_empty() && name.len() <= MAX_NAME_LEN
+        && name.iter().all(|b| b.is_ascii_alphanumeric() || *b == b'-' || *b == b'_');
+    if !valid_name {
+        return Err(malformed());
+    }
+
+    let value = String::from_utf8(value.to_vec())
+        .map_err(|_| format!("Invalid UTF-8 in manifest attribute {}", String::from_utf8_lossy(name)))?;
+    Ok((String::from_utf8_lossy(name).to_string(), value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Manifest {
+        parse_manifest(text.as_bytes(), true).expect("manifest should parse")
+    }
+
+    fn parse_lenient(text: &str) -> Manifest {
+        parse_manifest(text.as_bytes(), false).expect("manifest should parse")
+    }
+
+    #[test]
+    fn maven_thin_jar() {
+        let manifest = parse("Manifest-Version: 1.0\r\n\
+        Created-By: Maven JAR Plugin 3.2.2\r\n\
+        Build-Jdk-Spec: 17\r\n\
+        Class-Path: lib/commons-lang3-3.12.0.jar lib/commons-io-2.11.0.jar lib/g\r\n \
+        uava-31.1-jre.jar lib/failureaccess-1.0.1.jar\r\n\
+        Main-Class: com.example.app.Main\r\n\
+        \r\n");
+        assert_eq!(manifest.get("Main-Class"), Some("com.example.app.Main"));
+        assert_eq!(manifest.get("Class-Path"), Some("lib/commons-lang3-3.12.0.jar lib/commons-io-2.11.0.jar \
+        lib/guava-31.1-jre.jar lib/failureaccess-1.0.1.jar"));
+        assert_eq!(manifest.main_attributes.len(), 5);
+    }
+
+    #[test]
+    fn gradle_jar_with_lf() {
+        let manifest = parse("Manifest-Version: 1.0\n\
+        Implementation-Title: AstroImageJ\n\
+        Implementation-Version: 5.1.0.00\n\
+        Main-Class: ij.ImageJ\n\
+        Multi-Release: true\n\
+        \n");
+        assert_eq!(manifest.get("Implementation-Version"), Some("5.1.0.00"));
+        assert!(manifest.is_multi_release());
+    }
+
+    #[test]
+    fn spring_boot_jar() {
+        let manifest = parse("Manifest-Version: 1.0\r\n\
+        Main-Class: org.springframework.boot.loader.JarLauncher\r\n\
+        Start-Class: com.example.demo.DemoApplication\r\n\
+        Spring-Boot-Version: 2.7.5\r\n\
+        Spring-Boot-Classes: BOOT-INF/classes/\r\n\
+        Spring-Boot-Lib: BOOT-INF/lib/\r\n\
+        Spring-Boot-Classpath-Index: BOOT-INF/classpath.idx\r\n\
+        Spring-Boot-Layers-Index: BOOT-INF/layers.idx\r\n\
+        Build-Jdk-Spec: 17\r\n\
+        \r\n");
+        assert_eq!(manifest.get("Start-Class"), Some("com.example.demo.DemoApplication"));
+        assert_eq!(manifest.get("Spring-Boot-Lib"), Some("BOOT-INF/lib/"));
+    }
+
+    #[test]
+    fn cr_terminators() {
+        let manifest = parse("Manifest-Version: 1.0\rMain-Class: a.B\r\r");
+        assert_eq!(manifest.get("Main-Class"), Some("a.B"));
+    }
+
+    #[test]
+    fn per_entry_sections_are_not_main_attributes() {
+        let manifest = parse("Manifest-Version: 1.0\n\
+        Main-Class: a.B\n\
+        \n\
+        Name: a/B.class\n\
+        SHA-256-Digest: 47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=\n\
+        \n");
+        assert_eq!(manifest.get("Name"), None);
+        assert_eq!(manifest.get("SHA-256-Digest"), None);
+        assert_eq!(manifest.main_attributes.len(), 2);
+    }
+
+    #[test]
+    fn sections_include_their_blank_line() {
+        let bytes = b"Manifest-Version: 1.0\r\n\r\nName: a/B.class\r\nSHA-256-Digest: x\r\n\r\n\
+        Name: c/D.class\nSHA-256-Digest: y\n\n";
+        let sections = split_sections(bytes);
+        assert_eq!(sections, vec![
+            &b"Manifest-Version: 1.0\r\n\r\n"[..],
+            &b"Name: a/B.class\r\nSHA-256-Digest: x\r\n\r\n"[..],
+            &b"Name: c/D.class\nSHA-256-Digest: y\n\n"[..],
+        ]);
+        let entry = parse_manifest(sections[1], true).unwrap();
+        assert_eq!(entry.get("Name"), Some("a/B.class"));
+    }
+
+    #[test]
+    fn sections_without_final_blank_line() {
+        assert_eq!(split_sections(b"A: 1\n\nName: x\nB: 2\n"), vec![&b"A: 1\n\n"[..], &b"Name: x\nB: 2\n"[..]]);
+        assert_eq!(split_sections(b"\n\nA: 1\n"), vec![&b"A: 1\n"[..]]);
+    }
+
+    #[test]
+    fn names_are_case_insensitive() {
+        let manifest = parse("main-class: a.B\n\n");
+        assert_eq!(manifest.get("Main-Class"), Some("a.B"));
+        assert_eq!(manifest.get("MAIN-CLASS"), Some("a.B"));
+    }
+
+    #[test]
+    fn value_containing_separator() {
+        let manifest = parse("Implementation-Vendor: Example: Inc.\n\
+        Implementation-Title: a\n \
+        b: c\n\
+        \n");
+        assert_eq!(manifest.get("Implementation-Vendor"), Some("Example: Inc."));
+        assert_eq!(manifest.get("Implementation-Title"), Some("ab: c"));
+    }
+
+    #[test]
+    fn continuation_split_within_character() {
+        // "é" is 0xC3 0xA9, split across the wrap
+        let bytes = b"Implementation-Title: Caf\xC3\r\n \xA9 app\r\n\r\n";
+        let manifest = parse_manifest(bytes, true).unwrap();
+        assert_eq!(manifest.get("Implementation-Title"), Some("Café app"));
+    }
+
+    #[test]
+    fn continuation_keeps_further_spaces() {
+        let manifest = parse("Class-Path: a.jar\n  b.jar\n\n");
+        assert_eq!(manifest.get("Class-Path"), Some("a.jar b.jar"));
+    }
+
+    #[test]
+    fn unterminated_last_line_is_ignored() {
+        let manifest = parse("Manifest-Version: 1.0\nMain-Class: a.B");
+        assert_eq!(manifest.get("Manifest-Version"), Some("1.0"));
+        assert_eq!(manifest.get("Main-Class"), None);
+    }
+
+    #[test]
+    fn missing_blank_line_at_end() {
+        let manifest = parse("Manifest-Version: 1.0\nMain-Class: a.B\n");
+        assert_eq!(manifest.get("Main-Class"), Some("a.B"));
+    }
+
+    #[test]
+    fn empty_manifest() {
+        assert!(parse("").main_attributes.is_empty());
+        assert!(parse("\n").main_attributes.is_empty());
+    }
+
+    #[test]
+    fn malformed_headers() {
+        assert!(parse_manifest(b"Main-Class a.B\n", true).is_err());
+        assert!(parse_manifest(b"Main-Class:a.B\n", true).is_err());
+        assert!(parse_manifest(b": a.B\n", true).is_err());
+        assert!(parse_manifest(b"Main Class: a.B\n", true).is_err());
+        assert!(parse_manifest(b" a.B\nMain-Class: a.B\n", true).is_err());
+    }
+
+    #[test]
+    fn class_path_is_relative_to_the_jar() {
+        let manifest = parse("Class-Path: lib/a.jar  lib/my%20lib.jar ../shared/ http://example.com/x.jar\n\n");
+        let jar = Path::new("app").join("main.jar");
+        assert_eq!(resolve_class_path(&jar, &manifest), vec![
+            Path::new("app").join("lib/a.jar"),
+            Path::new("app").join("lib/my lib.jar"),
+            Path::new("app").join("../shared/"),
+        ]);
+    }
+
+    #[test]
+    fn exploded_jar_has_manifest_and_class_path() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        fs::create_dir_all(dir.join("META-INF")).unwrap();
+        fs::write(dir.join(MANIFEST_PATH), "Main-Class: a.B\nClass-Path: lib/a.jar\n\n").unwrap();
+
+        let manifest = read_jar_manifest(dir).expect("the manifest should be read from META-INF");
+        assert_eq!(manifest.get("Main-Class"), Some("a.B"));
+        assert_eq!(resolve_class_path(dir, &manifest), vec![dir.join("lib/a.jar")]);
+    }
+
+    #[test]
+    fn percent_decoding() {
+        assert_eq!(percent_decode("a%20b%2Fc").as_deref(), Some("a b/c"));
+        assert_eq!(percent_decode("%C3%A9").as_deref(), Some("é"));
+        assert_eq!(percent_decode("100%").as_deref(), Some("100%"));
+        assert_eq!(percent_decode("%FF"), None);
+    }
+
+    #[test]
+    fn lenient_byte_order_mark() {
+        let text = "\u{FEFF}Manifest-Version: 1.0\nMain-Class: a.B\n\n";
+        assert!(parse_manifest(text.as_bytes(), true).is_err());
+        assert_eq!(parse_lenient(text).get("Manifest-Version"), Some("1.0"));
+    }
+
+    #[test]
+    fn lenient_missing_space() {
+        let manifest = parse_lenient("Main-Class:a.B\nClass-Path:  lib/a.jar\n\n");
+        assert_eq!(manifest.get("Main-Class"), Some("a.B"));
+        assert_eq!(manifest.get("Class-Path"), Some(" lib/a.jar"));
+    }
+
+    #[test]
+    fn lenient_whitespace_around_name() {
+        let manifest = parse_lenient("Main-Class : a.B\n\tCreated-By: Ant\n\n");
+        assert_eq!(manifest.get("Main-Class"), Some("a.B"));
+        assert_eq!(manifest.get("Created-By"), Some("Ant"));
+    }
+
+    #[test]
+    fn lenient_missing_final_newline() {
+        let manifest = parse_lenient("Manifest-Version: 1.0\r\nMain-Class: a.B");
+        assert_eq!(manifest.get("Main-Class"), Some("a.B"));
+    }
+
+    #[test]
+    fn lenient_skips_malformed_lines() {
+        let manifest = parse_lenient(" orphan\nnot a header\nMain-Class: a.B\n\n");
+        assert_eq!(manifest.get("Main-Class"), Some("a.B"));
+        assert_eq!(manifest.main_attributes.len(), 1);
+        assert!(parse_manifest(b"Implementation-Title: \xFF\nMain-Class: a.B\n", false).is_ok());
+    }
+
+    #[test]
+    fn manifest_bytes_are_parsed() {
+        let manifest = parse_manifest_bytes(b"Manifest-Version: 1.0\r\nMain-Class: a.B\r\n\r\n");
+        assert_eq!(manifest.and_then(|m| m.get("Main-Class").map(String::from)), Some("a.B".to_string()));
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        assert!(parse_manifest(b"Implementation-Title: \xFF\n", true).is_err());
+    }
+}
@@ -0,0 +1,240 @@
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::file_handler::get_log_dir;
+use crate::LauncherConfig;
+
+/// The size a log file may grow to before it is rotated, at the next launch, or for the output log
+/// while the app runs, see [`rotate_while_running`].
+const MAX_LOG_SIZE: u64 = 5 * 1024 * 1024;
+
+/// How often the size of the output log is checked while the app runs.
+const LOG_SIZE_CHECK: Duration = Duration::from_secs(10);
+
+/// How many rotated logs are kept next to the current one.
+const MAX_OLD_LOGS: u32 = 3;
+
+/// Name of the file the output of the launcher, JVM and app is captured to.
+const OUTPUT_LOG: &str = "output";
+
+/// Whether stdout and stderr go to the output log, see [`capture_output_to_log`].
+static OUTPUT_CAPTURED: AtomicBool = AtomicBool::new(false);
+
+/// Redirect the launcher's stdout and stderr to a log file in the log dir, if nothing else
+/// receives them. This also captures `System.out`/`System.err` and JVM warnings, as both the JVM
+/// and any Java process the launcher starts inherit the streams.<br>
+/// On Windows the output is captured when the launcher has no standard output, such as when
+/// started from Explorer. Elsewhere the launch environment usually keeps the output,
+/// so it is only captured when `log_dir` is set.
+pub fn capture_output(config: &LauncherConfig) {
+    if !config.capture_output || !should_capture(config) {
+        return;
+    }
+    capture_output_to_log();
+}
+
+/// Redirect the launcher's stdout and stderr to the output log in the log dir.<br>
+/// Returns whether they were redirected.
+pub fn capture_output_to_log() -> bool {
+    if let Some(log_dir) = get_log_dir() {
+        if let Some(file) = open_log(&log_dir, OUTPUT_LOG) {
+            if redirect_std_streams(file) {
+                OUTPUT_CAPTURED.store(true, Ordering::SeqCst);
+                rotate_while_running(log_dir);
+                return true;
+            }
+            info!("launcher", "Failed to capture output to {}", log_dir.display());
+        }
+    }
+    false
+}
+
+/// Whether stdout and stderr are captured to the output log, which the launcher's own lines are then
+/// kept out of, as they are in the launcher log already.
+pub fn output_captured() -> bool {
+    OUTPUT_CAPTURED.load(Ordering::SeqCst)
+}
+
+/// Rotate the output log once it grows past [`MAX_LOG_SIZE`], checked every [`LOG_SIZE_CHECK`] by a thread
+/// of its own, so that a long running app does not fill the disk. As the JVM keeps writing to the file it
+/// was given, the log is copied to `output.1.log` and emptied instead of renamed, and what is written in
+/// between is lost.
+fn rotate_while_running(log_dir: PathBuf) {
+    thread::spawn(move || loop {
+        thread::sleep(LOG_SIZE_CHECK);
+        let current = log_path(&log_dir, OUTPUT_LOG, 0);
+        if !fs::metadata(&current).map_or(false, |m| m.len() > MAX_LOG_SIZE) {
+            continue;
+        }
+        let _ = fs::remove_file(log_path(&log_dir, OUTPUT_LOG, MAX_OLD_LOGS));
+        for i in (1..MAX_OLD_LOGS).rev() {
+            let _ = fs::rename(log_path(&log_dir, OUTPUT_LOG, i), log_path(&log_dir, OUTPUT_LOG, i + 1));
+        }
+        // Appended writes continue at the new end
+        let rotated = fs::copy(&current, log_path(&log_dir, OUTPUT_LOG, 1))
+            .and_then(|_| OpenOptions::new().write(true).open(&current))
+            .and_then(|file| file.set_len(0));
+        if let Err(e) = rotated {
+            debug!("launcher", "Failed to rotate the output log {:?}: {}", current, e);
+        }
+    });
+}
+
+/// Attach to the console of the process that started the launcher, if it has one, so that output
+/// reaches the terminal when the launcher is run from cmd or PowerShell.<br>
+/// The launcher uses the GUI subsystem, so Windows does not give it a console by itself.
+/// Standard handles the parent already redirected, such as to a file, are kept.
+#[cfg(windows)]
+pub fn attach_parent_console() {
+    use winapi::um::wincon::{ATTACH_PARENT_PROCESS, AttachConsole};
+    use winapi::um::winbase::{STD_ERROR_HANDLE, STD_INPUT_HANDLE, STD_OUTPUT_HANDLE};
+    if unsafe { AttachConsole(ATTACH_PARENT_PROCESS) } == 0 {
+        return;
+    }
+
+    reopen_std_handle(STD_OUTPUT_HANDLE, "CONOUT$");
+    reopen_std_handle(STD_ERROR_HANDLE, "CONOUT$");
+    reopen_std_handle(STD_INPUT_HANDLE, "CONIN$");
+}
+
+/// Whether the launcher opened a console of its own with [`open_console`].
+#[cfg(windows)]
+static CONSOLE_OPENED: AtomicBool = AtomicBool::new(false);
+
+/// Open a console window for the launcher, as asked for with `--why-console` or by holding Shift while
+/// the app starts, so that the launcher's log and the app's `System.out`/`System.err` can be seen
+/// without a terminal. Nothing is opened when the launcher already has a console from its parent.<br>
+/// The console closes with the app, so [`wait_for_console_close`] keeps it open until the user is done.
+#[cfg(windows)]
+pub fn open_console() {
+    use winapi::um::consoleapi::AllocConsole;
+    use winapi::um::wincon::GetConsoleWindow;
+    use winapi::um::winbase::{STD_ERROR_HANDLE, STD_INPUT_HANDLE, STD_OUTPUT_HANDLE};
+    if !unsafe { GetConsoleWindow() }.is_null() || unsafe { AllocConsole() } == 0 {
+        return;
+    }
+    CONSOLE_OPENED.store(true, Ordering::SeqCst);
+
+    reopen_std_handle(STD_OUTPUT_HANDLE, "CONOUT$");
+    reopen_std_handle(STD_ERROR_HANDLE, "CONOUT$");
+    reopen_std_handle(STD_INPUT_HANDLE, "CONIN$");
+}
+
+/// Whether Shift is held down, to open a console as with `--why-console`.
+#[cfg(windows)]
+pub fn console_key_held() -> bool {
+    use winapi::um::winuser::{GetAsyncKeyState, VK_SHIFT};
+    // The most significant bit is set while the key is down
+    unsafe { GetAsyncKeyState(VK_SHIFT) as u16 & 0x8000 != 0 }
+}
+
+/// Wait for the user to press Enter before the console [`open_console`] opened closes with the launcher,
+/// so that the last lines the app printed, such as an exception, can still be read.
+#[cfg(windows)]
+pub fn wait_for_console_close() {
+    use std::io::{stdin, stdout, Write};
+    if !CONSOLE_OPENED.load(Ordering::SeqCst) {
+        return;
+    }
+    print!("{}", crate::locale_handler::text("console.close"));
+    let _ = stdout().flush();
+    let _ = stdin().read_line(&mut String::new());
+}
+
+/// Point a missing standard handle at the attached console.
+#[cfg(windows)]
+fn reopen_std_handle(std_handle: winapi::shared::minwindef::DWORD, device: &str) {
+    use std::ffi::OsStr;
+    use std::iter::once;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr::null_mut;
+    use winapi::um::fileapi::{CreateFileW, OPEN_EXISTING};
+    use winapi::um::handleapi::INVALID_HANDLE_VALUE;
+    use winapi::um::processenv::{GetStdHandle, SetStdHandle};
+    use winapi::um::winnt::{FILE_SHARE_READ, FILE_SHARE_WRITE, GENERIC_READ, GENERIC_WRITE};
+    unsafe {
+        let current = GetStdHandle(std_handle);
+        if !current.is_null() && current != INVALID_HANDLE_VALUE {
+            return;
+        }
+
+        let wide: Vec<u16> = OsStr::new(device).encode_wide().chain(once(0)).collect();
+        let handle = CreateFileW(wide.as_ptr(), GENERIC_READ | GENERIC_WRITE,
+                                 FILE_SHARE_READ | FILE_SHARE_WRITE, null_mut(),
+                                 OPEN_EXISTING, 0, null_mut());
+        if handle != INVALID_HANDLE_VALUE {
+            SetStdHandle(std_handle, handle);
+        }
+    }
+}
+
+/// Open a log in the log dir for appending, first rotating it if it has grown too large.
+pub fn open_log(log_dir: &Path, name: &str) -> Option<File> {
+    fs::create_dir_all(log_dir).ok()?;
+
+    let current = log_path(log_dir, name, 0);
+    if fs::metadata(&current).map(|m| m.len() > MAX_LOG_SIZE).unwrap_or(false) {
+        rotate_logs(log_dir, name);
+    }
+
+    OpenOptions::new().create(true).append(true).open(current).ok()
+}
+
+/// Shift `name.log` to `name.1.log` and so on, dropping the oldest log.
+fn rotate_logs(log_dir: &Path, name: &str) {
+    let _ = fs::remove_file(log_path(log_dir, name, MAX_OLD_LOGS));
+    for i in (0..MAX_OLD_LOGS).rev() {
+        let _ = fs::rename(log_path(log_dir, name, i), log_path(log_dir, name, i + 1));
+    }
+}
+
+/// The path of a log, `index` 0 being the current one.
+pub fn log_path(log_dir: &Path, name: &str, index: u32) -> PathBuf {
+    if index == 0 {
+        log_dir.join(format!("{}.log", name))
+    } else {
+        log_dir.join(format!("{}.{}.log", name, index))
+    }
+}
+
+#[cfg(windows)]
+fn should_capture(_config: &LauncherConfig) -> bool {
+    use winapi::um::handleapi::INVALID_HANDLE_VALUE;
+    use winapi::um::processenv::GetStdHandle;
+    use winapi::um::winbase::STD_OUTPUT_HANDLE;
+    let handle = unsafe { GetStdHandle(STD_OUTPUT_HANDLE) };
+    handle.is_null() || handle == INVALID_HANDLE_VALUE
+}
+
+#[cfg(not(windows))]
+fn should_capture(config: &LauncherConfig) -> bool {
+    config.log_dir.is_some()
+}
+
+/// Point the standard output and error handles at the file.
+/// Java reads them with `GetStdHandle` when its `System.out` and `System.err` are created.
+#[cfg(windows)]
+fn redirect_std_streams(file: File) -> bool {
+    use std::os::windows::io::IntoRawHandle;
+    use winapi::um::processenv::SetStdHandle;
+    use winapi::um::winbase::{STD_ERROR_HANDLE, STD_OUTPUT_HANDLE};
+    // The handle stays open for the life of the launcher
+    let handle = file.into_raw_handle();
+    unsafe {
+        SetStdHandle(STD_OUTPUT_HANDLE, handle as _) != 0 && SetStdHandle(STD_ERROR_HANDLE, handle as _) != 0
+    }
+}
+
+/// Replace the standard output and error descriptors with the file.
+#[cfg(unix)]
+fn redirect_std_streams(file: File) -> bool {
+    use std::os::unix::io::AsRawFd;
+    let fd = file.as_raw_fd();
+    unsafe {
+        libc::dup2(fd, libc::STDOUT_FILENO) != -1 && libc::dup2(fd, libc::STDERR_FILENO) != -1
+    }
+}
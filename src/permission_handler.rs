@@ -0,0 +1,220 @@
+use std::collections::HashSet;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use walkdir::WalkDir;
+
+use crate::display_handler::detailed_message;
+use crate::launch_config::{CONFIG_FILE, LauncherConfig};
+use crate::locale_handler::text;
+use crate::signature_handler::app_jar;
+
+/// How the launcher checks that other users cannot change what it runs, which on a shared machine would
+/// let them run code as whoever starts the app.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PermissionCheck {
+    /// Permissions are not checked
+    Off,
+    /// The user is warned about files others can change, but the app still starts
+    Warn,
+    /// The app is not started from files others can change
+    Enforce,
+}
+
+impl PermissionCheck {
+    /// Parse the value of the `check_permissions` key, unknown values fall back to [`PermissionCheck::Off`].
+    pub fn from_key(key: &str) -> Self {
+        match key.trim().to_lowercase().as_str() {
+            "true" | "enforce" => PermissionCheck::Enforce,
+            "warn" => PermissionCheck::Warn,
+            _ => PermissionCheck::Off,
+        }
+    }
+}
+
+/// Set once the user was told about files others can change, so that each runtime found does not tell again.
+static REPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Check that other users cannot change the app's jar or the config, nor the folders they are in,
+/// telling the user if they can.<br>
+/// Returns whether the app may be launched.
+pub fn check_app_permissions(config: &LauncherConfig) -> bool {
+    if config.check_permissions == PermissionCheck::Off {
+        return true;
+    }
+    let mut paths: Vec<PathBuf> = env::current_dir().map(|d| d.join(CONFIG_FILE)).into_iter().collect();
+    paths.extend(app_jar(config));
+    report_writable(config.check_permissions, &paths)
+}
+
+/// Check that other users cannot change the Java runtime the dynamic library at `jvm_path` belongs to,
+/// any of its files such as `lib/modules` or those in `conf`, telling the user if they can.<br>
+/// Returns whether the runtime may be used.
+pub fn check_runtime_permissions(config: &LauncherConfig, jvm_path: &Path) -> bool {
+    if config.check_permissions == PermissionCheck::Off {
+        return true;
+    }
+    report_writable(config.check_permissions, &runtime_files(jvm_path))
+}
+
+/// The dynamic library and everything in the runtime it belongs to, the folder with its `release` file,
+/// only the library if it is in none.
+fn runtime_files(jvm_path: &Path) -> Vec<PathBuf> {
+    let mut files = vec![jvm_path.to_path_buf()];
+    if let Some(home) = jvm_path.ancestors().find(|d| d.join("release").is_file()) {
+        files.extend(WalkDir::new(home).into_iter().flatten().map(|e| e.into_path()));
+    }
+    files
+}
+
+fn report_writable(check: PermissionCheck, paths: &[PathBuf]) -> bool {
+    let writable = writable_by_others(paths);
+    if writable.is_empty() {
+        return true;
+    }
+    let details = writable.iter().map(|p| p.display().to_string()).collect::<Vec<String>>().join("\n");
+    info!("signature", "Other users can change:\n{}", details);
+    // Each runtime found is checked, the user is told once
+    if REPORTED.swap(true, Ordering::SeqCst) {
+        return check != PermissionCheck::Enforce;
+    }
+    if check == PermissionCheck::Enforce {
+        detailed_message(&text("permissions.refused"), &details);
+        false
+    } else {
+        detailed_message(&text("permissions.warning"), &details);
+        true
+    }
+}
+
+/// The paths, and the folders they are in, that users other than their owner and administrators
+/// can change. Each folder up to the root counts, as replacing any of them replaces what is inside.
+fn writable_by_others(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut writable: Vec<PathBuf> = vec![];
+    let mut checked: HashSet<PathBuf> = HashSet::new();
+    for path in paths {
+        let path = match dunce::canonicalize(path) {
+            Ok(path) => path,
+            Err(_) => continue,
+        };
+        for folder in path.ancestors() {
+            // Its folders were checked with it
+            if !checked.insert(folder.to_path_buf()) {
+                break;
+            }
+            if is_writable_by_others(folder) {
+                writable.push(folder.to_path_buf());
+            }
+        }
+    }
+    writable
+}
+
+/// Whether anyone, or anyone in the path's group, may write to the path. Others may add files to a folder
+/// with the sticky bit, such as `/tmp`, but not replace those of other users. The groups of administrators,
+/// and the group of the owner alone, which has the owner's id where distributions create one for each user,
+/// do not count.
+#[cfg(unix)]
+fn is_writable_by_others(path: &Path) -> bool {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+    /// `wheel` or `root`, and `admin` on macOS
+    const ADMIN_GROUPS: &[u32] = if cfg!(target_os = "macos") { &[0, 80] } else { &[0] };
+    match path.metadata() {
+        Ok(metadata) => {
+            let mode = metadata.permissions().mode();
+            let group_only = ADMIN_GROUPS.contains(&metadata.gid()) || metadata.gid() == metadata.uid();
+            let others = if group_only { 0o002 } else { 0o022 };
+            mode & others != 0 && !(metadata.is_dir() && mode & 0o1000 != 0)
+        }
+        Err(_) => false,
+    }
+}
+
+/// Whether Everyone, Authenticated Users or Users may change the path, found from its access control list.
+/// Adding folders to a folder is allowed, as users may do to the root of a drive, only adding and removing
+/// files in it or changing it counts.
+#[cfg(windows)]
+fn is_writable_by_others(path: &Path) -> bool {
+    use std::ffi::OsStr;
+    use std::iter::once;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr::null_mut;
+    use winapi::shared::winerror::ERROR_SUCCESS;
+    use winapi::um::accctrl::{NO_MULTIPLE_TRUSTEE, SE_FILE_OBJECT, TRUSTEE_IS_SID, TRUSTEE_IS_WELL_KNOWN_GROUP,
+                              TRUSTEE_W};
+    use winapi::um::aclapi::{GetEffectiveRightsFromAclW, GetNamedSecurityInfoW};
+    use winapi::um::sddl::ConvertStringSidToSidW;
+    use winapi::um::winbase::LocalFree;
+    use winapi::um::winnt::{DACL_SECURITY_INFORMATION, DELETE, FILE_ADD_FILE, FILE_APPEND_DATA, FILE_DELETE_CHILD,
+                            FILE_WRITE_DATA, GENERIC_ALL, GENERIC_WRITE, PACL, PSECURITY_DESCRIPTOR, PSID, WRITE_DAC,
+                            WRITE_OWNER};
+
+    /// Everyone, Authenticated Users and Users
+    const OTHERS: &[&str] = &["S-1-1-0", "S-1-5-11", "S-1-5-32-545"];
+
+    let changing = WRITE_DAC | WRITE_OWNER | GENERIC_WRITE | GENERIC_ALL | if path.is_dir() {
+        FILE_ADD_FILE | FILE_DELETE_CHILD
+    } else {
+        FILE_WRITE_DATA | FILE_APPEND_DATA | DELETE
+    };
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(once(0)).collect();
+    let mut dacl: PACL = null_mut();
+    let mut descriptor: PSECURITY_DESCRIPTOR = null_mut();
+    unsafe {
+        let result = GetNamedSecurityInfoW(wide.as_ptr(), SE_FILE_OBJECT, DACL_SECURITY_INFORMATION, null_mut(),
+                                           null_mut(), &mut dacl, null_mut(), &mut descriptor);
+        if result != ERROR_SUCCESS {
+            return false;
+        }
+        // Without an access control list anyone has full access
+        let mut writable = dacl.is_null();
+        for sid_string in OTHERS.iter().take_while(|_| !writable) {
+            let sid_wide: Vec<u16> = OsStr::new(sid_string).encode_wide().chain(once(0)).collect();
+            let mut sid: PSID = null_mut();
+            if ConvertStringSidToSidW(sid_wide.as_ptr(), &mut sid) == 0 {
+                continue;
+            }
+            let mut trustee = TRUSTEE_W {
+                pMultipleTrustee: null_mut(),
+                MultipleTrusteeOperation: NO_MULTIPLE_TRUSTEE,
+                TrusteeForm: TRUSTEE_IS_SID,
+                TrusteeType: TRUSTEE_IS_WELL_KNOWN_GROUP,
+                ptstrName: sid as *mut u16,
+            };
+            let mut rights = 0;
+            if GetEffectiveRightsFromAclW(dacl, &mut trustee, &mut rights) == ERROR_SUCCESS {
+                writable = rights & changing != 0;
+            }
+            LocalFree(sid);
+        }
+        LocalFree(descriptor);
+        writable
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runtime_files_writable_by_others_are_found() {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+        let temp = tempfile::tempdir().unwrap();
+        let home = temp.path();
+        fs::create_dir_all(home.join("lib/server")).unwrap();
+        fs::write(home.join("release"), "JAVA_VERSION=\"17\"").unwrap();
+        fs::write(home.join("lib/server/libjvm.so"), "").unwrap();
+        fs::write(home.join("lib/modules"), "").unwrap();
+        let jvm_path = home.join("lib/server/libjvm.so");
+        assert!(writable_by_others(&runtime_files(&jvm_path)).is_empty());
+
+        let modules = dunce::canonicalize(home.join("lib/modules")).unwrap();
+        let mode = fs::metadata(&modules).unwrap().permissions().mode();
+        fs::set_permissions(&modules, fs::Permissions::from_mode(mode | 0o002)).unwrap();
+        let writable = writable_by_others(&runtime_files(&jvm_path));
+        assert_eq!(writable, vec![modules]);
+    }
+}
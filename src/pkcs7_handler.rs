@@ -0,0 +1,284 @@
+use p256::ecdsa::signature::hazmat::PrehashVerifier;
+use rsa::{Pkcs1v15Sign, RsaPublicKey};
+use rsa::pkcs8::DecodePublicKey;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+/// `pkcs7-signedData`, 1.2.840.113549.1.7.2
+const OID_SIGNED_DATA: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x07, 0x02];
+/// `messageDigest`, 1.2.840.113549.1.9.4
+const OID_MESSAGE_DIGEST: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x09, 0x04];
+/// 2.16.840.1.101.3.4.2.1
+const OID_SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+/// 2.16.840.1.101.3.4.2.2
+const OID_SHA384: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02];
+/// 2.16.840.1.101.3.4.2.3
+const OID_SHA512: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03];
+/// `rsaEncryption`, 1.2.840.113549.1.1.1
+const OID_RSA: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x01];
+/// `id-ecPublicKey`, 1.2.840.10045.2.1
+const OID_EC: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x02, 0x01];
+/// `prime256v1`, 1.2.840.10045.3.1.7
+const OID_P256: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x03, 0x01, 0x07];
+/// `secp384r1`, 1.3.132.0.34
+const OID_P384: &[u8] = &[0x2B, 0x81, 0x04, 0x00, 0x22];
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+/// `[0]`, constructed
+const TAG_CONTEXT_0: u8 = 0xA0;
+
+/// A DER element, its tag, content and the bytes it was read from, tag and length included.
+#[derive(Debug, Clone, Copy)]
+struct Der<'a> {
+    tag: u8,
+    content: &'a [u8],
+    raw: &'a [u8],
+}
+
+/// Read the DER element at the start of the bytes, returning it and the bytes after it.
+/// Only the single byte tags the structures read here use are supported.
+fn read_der(bytes: &[u8]) -> Option<(Der<'_>, &[u8])> {
+    let tag = *bytes.first()?;
+    let first = *bytes.get(1)? as usize;
+    let (len, header) = if first < 0x80 {
+        (first, 2)
+    } else {
+        let count = first & 0x7F;
+        if count == 0 || count > 4 {
+            return None;
+        }
+        let len = bytes.get(2..2 + count)?.iter().fold(0usize, |len, b| (len << 8) | *b as usize);
+        (len, 2 + count)
+    };
+    let end = header.checked_add(len)?;
+    let raw = bytes.get(..end)?;
+    Some((Der { tag, content: &raw[header..], raw }, &bytes[end..]))
+}
+
+/// The elements of a constructed element, `None` if one of them is malformed.
+fn children(der: Der<'_>) -> Option<Vec<Der<'_>>> {
+    let mut rest = der.content;
+    let mut children = vec![];
+    while !rest.is_empty() {
+        let (child, next) = read_der(rest)?;
+        children.push(child);
+        rest = next;
+    }
+    Some(children)
+}
+
+/// The elements of the element, which must have the tag.
+fn expect(der: Der<'_>, tag: u8) -> Result<Vec<Der<'_>>, String> {
+    if der.tag != tag {
+        return Err(format!("expected tag {:#04x}, found {:#04x}", tag, der.tag));
+    }
+    children(der).ok_or_else(|| "the signature block is malformed".to_string())
+}
+
+/// The digest algorithms a signature block may use.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Hash {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl Hash {
+    /// The algorithm of an `AlgorithmIdentifier`.
+    fn from_algorithm(algorithm: Der<'_>) -> Result<Hash, String> {
+        let oid = algorithm_oid(algorithm)?;
+        match oid {
+            OID_SHA256 => Ok(Hash::Sha256),
+            OID_SHA384 => Ok(Hash::Sha384),
+            OID_SHA512 => Ok(Hash::Sha512),
+            _ => Err("the signature block uses an unsupported digest algorithm".to_string()),
+        }
+    }
+
+    fn digest(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Hash::Sha256 => Sha256::digest(data).to_vec(),
+            Hash::Sha384 => Sha384::digest(data).to_vec(),
+            Hash::Sha512 => Sha512::digest(data).to_vec(),
+        }
+    }
+}
+
+/// The OID of an `AlgorithmIdentifier`, `SEQUENCE { OID, parameters }`.
+fn algorithm_oid(algorithm: Der<'_>) -> Result<&[u8], String> {
+    match expect(algorithm, TAG_SEQUENCE)?.first() {
+        Some(oid) if oid.tag == TAG_OID => Ok(oid.content),
+        _ => Err("the signature block has a malformed algorithm".to_string()),
+    }
+}
+
+/// The parts of an X.509 certificate identifying it and its key.
+struct Certificate<'a> {
+    /// The whole certificate, as DER
+    raw: &'a [u8],
+    issuer: &'a [u8],
+    serial: &'a [u8],
+    /// The `SubjectPublicKeyInfo`, as DER
+    public_key: Der<'a>,
+}
+
+fn read_certificate(cert: Der<'_>) -> Result<Certificate<'_>, String> {
+    let tbs = expect(cert, TAG_SEQUENCE)?;
+    let mut fields = expect(*tbs.first().ok_or("a certificate is empty")?, TAG_SEQUENCE)?.into_iter();
+    let mut serial = fields.next().ok_or("a certificate is empty")?;
+    // The version is optional
+    if serial.tag == TAG_CONTEXT_0 {
+        serial = fields.next().ok_or("a certificate has no serial number")?;
+    }
+    // The serial number is followed by the signature algorithm, issuer, validity, subject and key
+    let rest: Vec<Der> = fields.take(5).collect();
+    match rest.as_slice() {
+        [_, issuer, _, _, public_key] if serial.tag == TAG_INTEGER => Ok(Certificate {
+            raw: cert.raw,
+            issuer: issuer.raw,
+            serial: serial.content,
+            public_key: *public_key,
+        }),
+        _ => Err("a certificate is malformed".to_string()),
+    }
+}
+
+/// Check a PKCS #7 signature block, the `META-INF/*.RSA` or `*.EC` of a signed jar, against the signed
+/// data, its signature file. The signature must be made with the key of the signer's certificate in
+/// the block, over the data or over signed attributes holding its digest.<br>
+/// Whether the signer is trusted is up to the caller. Returns the signer's certificate, as DER.
+pub fn verify_signature_block<'a>(block: &'a [u8], signed: &[u8]) -> Result<&'a [u8], String> {
+    let malformed = || "the signature block is malformed".to_string();
+    let (content_info, _) = read_der(block).ok_or_else(malformed)?;
+    let content_info = expect(content_info, TAG_SEQUENCE)?;
+    match content_info.as_slice() {
+        [oid, _] if oid.tag == TAG_OID && oid.content == OID_SIGNED_DATA => {}
+        _ => return Err("the signature block is not PKCS #7 signed data".to_string()),
+    }
+    let explicit = expect(content_info[1], TAG_CONTEXT_0)?;
+    let signed_data = expect(*explicit.first().ok_or_else(malformed)?, TAG_SEQUENCE)?;
+
+    let certificates = match signed_data.iter().find(|d| d.tag == TAG_CONTEXT_0) {
+        Some(certificates) => children(*certificates).ok_or_else(malformed)?,
+        None => return Err("the signature block has no certificates".to_string()),
+    };
+    let signer_infos = expect(*signed_data.last().ok_or_else(malformed)?, TAG_SET)?;
+    let signer_info = match signer_infos.as_slice() {
+        [signer_info] => expect(*signer_info, TAG_SEQUENCE)?,
+        _ => return Err("the signature block does not have exactly one signer".to_string()),
+    };
+
+    // version, sid, digestAlgorithm, [0] signedAttrs, signatureAlgorithm, signature, [1] unsignedAttrs
+    let mut fields = signer_info.iter().skip(1);
+    let sid = *fields.next().ok_or_else(malformed)?;
+    let hash = Hash::from_algorithm(*fields.next().ok_or_else(malformed)?)?;
+    let rest: Vec<Der> = fields.copied().collect();
+    let (signed_attributes, rest) = match rest.split_first() {
+        Some((attributes, rest)) if attributes.tag == TAG_CONTEXT_0 => (Some(*attributes), rest),
+        _ => (None, rest.as_slice()),
+    };
+    // The signature algorithm follows from the signer's key and the digest algorithm
+    let signature = rest.get(1).filter(|s| s.tag == TAG_OCTET_STRING).ok_or_else(malformed)?.content;
+
+    // Identified by the issuer and serial number, as jarsigner does
+    let (issuer, serial) = match expect(sid, TAG_SEQUENCE)?.as_slice() {
+        [issuer, serial] if serial.tag == TAG_INTEGER => (issuer.raw, serial.content),
+        _ => return Err("the signer is not identified by its issuer and serial number".to_string()),
+    };
+    let signer = certificates.into_iter()
+        .map(read_certificate)
+        .collect::<Result<Vec<_>, String>>()?
+        .into_iter()
+        .find(|c| c.issuer == issuer && c.serial == serial)
+        .ok_or("the signer's certificate is not in the signature block")?;
+
+    let message = match signed_attributes {
+        Some(attributes) => {
+            check_message_digest(attributes, &hash.digest(signed))?;
+            // Signed as the DER of a SET, not of the [0] it is tagged with in the block
+            let mut message = attributes.raw.to_vec();
+            message[0] = TAG_SET;
+            message
+        }
+        None => signed.to_vec(),
+    };
+    verify_signature(signer.public_key, hash, &hash.digest(&message), signature)?;
+    Ok(signer.raw)
+}
+
+/// Check that the `messageDigest` of the signed attributes is the digest of the signed data.
+fn check_message_digest(attributes: Der<'_>, digest: &[u8]) -> Result<(), String> {
+    for attribute in children(attributes).ok_or("the signed attributes are malformed")? {
+        if let [oid, values] = expect(attribute, TAG_SEQUENCE)?.as_slice() {
+            if oid.tag == TAG_OID && oid.content == OID_MESSAGE_DIGEST {
+                return match expect(*values, TAG_SET)?.as_slice() {
+                    [value] if value.tag == TAG_OCTET_STRING && value.content == digest => Ok(()),
+                    _ => Err("the signature file was changed after signing".to_string()),
+                };
+            }
+        }
+    }
+    Err("the signed attributes have no message digest".to_string())
+}
+
+/// Check the signature of the digest with the `SubjectPublicKeyInfo`, an RSA, P-256 or P-384 key.
+fn verify_signature(public_key: Der<'_>, hash: Hash, digest: &[u8], signature: &[u8]) -> Result<(), String> {
+    fn invalid<E>(_: E) -> String {
+        "the signature block's signature is invalid".to_string()
+    }
+    fn bad_key<E>(_: E) -> String {
+        "the signer's public key is malformed".to_string()
+    }
+    let key_info = expect(public_key, TAG_SEQUENCE)?;
+    let algorithm = expect(*key_info.first().ok_or("the signer's public key is malformed")?, TAG_SEQUENCE)?;
+    let (oid, curve) = match algorithm.as_slice() {
+        [oid, parameters, ..] => (oid.content, Some(parameters.content)),
+        [oid] => (oid.content, None),
+        _ => return Err("the signer's public key is malformed".to_string()),
+    };
+    match (oid, curve) {
+        (OID_RSA, _) => {
+            let key = RsaPublicKey::from_public_key_der(public_key.raw).map_err(bad_key)?;
+            let scheme = match hash {
+                Hash::Sha256 => Pkcs1v15Sign::new::<Sha256>(),
+                Hash::Sha384 => Pkcs1v15Sign::new::<Sha384>(),
+                Hash::Sha512 => Pkcs1v15Sign::new::<Sha512>(),
+            };
+            key.verify(scheme, digest, signature).map_err(invalid)
+        }
+        (OID_EC, Some(OID_P256)) => {
+            let key = p256::ecdsa::VerifyingKey::from_public_key_der(public_key.raw).map_err(bad_key)?;
+            let signature = p256::ecdsa::Signature::from_der(signature).map_err(invalid)?;
+            key.verify_prehash(digest, &signature).map_err(invalid)
+        }
+        (OID_EC, Some(OID_P384)) => {
+            let key = p384::ecdsa::VerifyingKey::from_public_key_der(public_key.raw).map_err(bad_key)?;
+            let signature = p384::ecdsa::Signature::from_der(signature).map_err(invalid)?;
+            key.verify_prehash(digest, &signature).map_err(invalid)
+        }
+        _ => Err("the signer's key type is not supported, only RSA, P-256 and P-384 keys are".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn der_lengths_are_read() {
+        let (der, rest) = read_der(&[0x04, 0x02, 0xAA, 0xBB, 0x05, 0x00]).unwrap();
+        assert_eq!((der.tag, der.content), (0x04, &[0xAA, 0xBB][..]));
+        assert_eq!(rest, &[0x05, 0x00]);
+
+        let mut long = vec![0x04, 0x81, 0x80];
+        long.extend([7; 0x80]);
+        let (der, rest) = read_der(&long).unwrap();
+        assert_eq!((der.content.len(), der.raw.len(), rest.len()), (0x80, 0x83, 0));
+
+        assert!(read_der(&[0x04, 0x03, 0xAA]).is_none());
+        assert!(read_der(&[0x04, 0x80]).is_none());
+    }
+}
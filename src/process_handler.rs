@@ -0,0 +1,322 @@
+#[cfg(all(unix, not(target_os = "macos")))]
+use std::path::PathBuf;
+#[cfg(all(unix, not(target_os = "macos")))]
+use std::sync::Mutex;
+#[cfg(unix)]
+use std::thread;
+
+use crate::exit_handler::exit;
+use crate::java_launcher::request_jvm_exit;
+use crate::launch_config::LauncherConfig;
+
+/// Signals that ask the app to terminate.
+#[cfg(unix)]
+const TERMINATION_SIGNALS: &'static [libc::c_int] = &[libc::SIGTERM, libc::SIGINT, libc::SIGHUP];
+
+/// Handle SIGTERM, SIGINT and SIGHUP by exiting the JVM through `System.exit`,
+/// so that the app's shutdown hooks run instead of the process being killed abruptly.<br>
+/// The exit status follows the shell convention of 128 plus the signal number.
+#[cfg(unix)]
+pub fn forward_termination_signals() {
+    use signal_hook::iterator::Signals;
+
+    match Signals::new(TERMINATION_SIGNALS) {
+        Ok(mut signals) => {
+            thread::spawn(move || {
+                for sig in signals.forever() {
+                    info!("process", "Received signal {}, shutting down", sig);
+                    if !request_jvm_exit(128 + sig) {
+                        exit(128 + sig);
+                    }
+                }
+            });
+        }
+        Err(e) => info!("process", "Failed to handle termination signals: {:?}", e),
+    }
+}
+
+/// Pass SIGTERM, SIGINT and SIGHUP on to a child process, such as the Java process
+/// started in the `process` launch mode, so that it can shut down properly.
+#[cfg(unix)]
+pub fn forward_signals_to_child(pid: u32) {
+    use signal_hook::iterator::Signals;
+
+    match Signals::new(TERMINATION_SIGNALS) {
+        Ok(mut signals) => {
+            thread::spawn(move || {
+                for sig in signals.forever() {
+                    debug!("process", "Forwarding signal {} to process {}", sig, pid);
+                    unsafe {
+                        libc::kill(pid as libc::pid_t, sig);
+                    }
+                }
+            });
+        }
+        Err(e) => info!("process", "Failed to handle termination signals: {:?}", e),
+    }
+}
+
+/// Handle Ctrl+C and the console window being closed by exiting the JVM through `System.exit`,
+/// so that the app's shutdown hooks run when the launcher is started from a terminal.<br>
+/// See <https://docs.microsoft.com/en-us/windows/console/setconsolectrlhandler>
+#[cfg(windows)]
+pub fn handle_console_events() {
+    use winapi::um::consoleapi::SetConsoleCtrlHandler;
+    use winapi::shared::minwindef::TRUE;
+    unsafe {
+        if SetConsoleCtrlHandler(Some(console_ctrl_handler), TRUE) == 0 {
+            info!("process", "Failed to handle console events");
+        }
+    }
+}
+
+/// Called by Windows on a new thread. Returning from it for a close, logoff or shutdown event
+/// ends the process, as `System.exit` does not return this waits for the shutdown hooks.
+#[cfg(windows)]
+unsafe extern "system" fn console_ctrl_handler(ctrl_type: winapi::shared::minwindef::DWORD) -> winapi::shared::minwindef::BOOL {
+    use winapi::shared::minwindef::{FALSE, TRUE};
+    use winapi::um::wincon::{CTRL_C_EVENT, CTRL_CLOSE_EVENT, CTRL_SHUTDOWN_EVENT};
+
+    // Same exit status as the JVM gives these events
+    let code = match ctrl_type {
+        CTRL_C_EVENT => 130,
+        CTRL_CLOSE_EVENT | CTRL_SHUTDOWN_EVENT => 143,
+        // Ctrl+Break is left to the JVM, which prints a thread dump
+        _ => return FALSE,
+    };
+
+    info!("process", "Received console event {}, shutting down", ctrl_type);
+    if !request_jvm_exit(code) {
+        exit(code);
+    }
+    TRUE
+}
+
+/// Put the launcher in a job object that kills all processes in it once the launcher exits,
+/// so that helper processes the app starts do not outlive it.<br>
+/// Processes may still leave the job by being created with `CREATE_BREAKAWAY_FROM_JOB`.
+/// See <https://docs.microsoft.com/en-us/windows/win32/procthread/job-objects>
+#[cfg(windows)]
+pub fn kill_children_with_launcher() {
+    use std::mem::{size_of, zeroed};
+    use std::ptr::{null, null_mut};
+    use winapi::shared::minwindef::{DWORD, LPVOID};
+    use winapi::um::jobapi2::{AssignProcessToJobObject, CreateJobObjectW, SetInformationJobObject};
+    use winapi::um::processthreadsapi::GetCurrentProcess;
+    use winapi::um::winnt::{JOB_OBJECT_LIMIT_BREAKAWAY_OK, JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+                            JOBOBJECT_EXTENDED_LIMIT_INFORMATION, JobObjectExtendedLimitInformation};
+    unsafe {
+        let job = CreateJobObjectW(null_mut(), null());
+        if job.is_null() {
+            info!("process", "Failed to create a job object");
+            return;
+        }
+
+        let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = zeroed();
+        info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE | JOB_OBJECT_LIMIT_BREAKAWAY_OK;
+        let set = SetInformationJobObject(job, JobObjectExtendedLimitInformation,
+                                          &mut info as *mut JOBOBJECT_EXTENDED_LIMIT_INFORMATION as LPVOID,
+                                          size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as DWORD);
+
+        // The handle is never closed, Windows closes it when the launcher exits
+        if set == 0 || AssignProcessToJobObject(job, GetCurrentProcess()) == 0 {
+            info!("process", "Failed to assign the launcher to a job object");
+        }
+    }
+}
+
+/// Start the launcher again as an administrator, which the user is asked to allow with a UAC prompt,
+/// for apps set with `require_elevation`. Waits for it to exit.<br>
+/// Returns its exit code, `None` if the launcher already runs as an administrator.
+#[cfg(windows)]
+pub fn run_elevated() -> Option<i32> {
+    use std::env;
+    use std::iter::once;
+    use std::mem::{size_of, zeroed};
+    use std::os::windows::ffi::OsStrExt;
+    use winapi::shared::minwindef::DWORD;
+    use winapi::shared::winerror::ERROR_CANCELLED;
+    use winapi::um::errhandlingapi::GetLastError;
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::processthreadsapi::GetExitCodeProcess;
+    use winapi::um::shellapi::{SEE_MASK_NOCLOSEPROCESS, SHELLEXECUTEINFOW, ShellExecuteExW};
+    use winapi::um::synchapi::WaitForSingleObject;
+    use winapi::um::winbase::INFINITE;
+    use winapi::um::winuser::SW_SHOWNORMAL;
+    use crate::display_handler::message;
+    use crate::exit_handler::FAILURE_EXIT_CODE;
+    use crate::install_handler::is_administrator;
+    use crate::locale_handler::{text, text_with};
+
+    if is_administrator() {
+        return None;
+    }
+    let exe = match env::current_exe() {
+        Ok(exe) => exe,
+        Err(e) => {
+            message(&text_with("elevation.failed", &[&e.to_string()]));
+            return Some(FAILURE_EXIT_CODE);
+        }
+    };
+    info!("process", "Starting the launcher as an administrator");
+    let wide = |s: &std::ffi::OsStr| s.encode_wide().chain(once(0)).collect::<Vec<u16>>();
+    let verb = wide("runas".as_ref());
+    let file = wide(exe.as_os_str());
+    let args: Vec<String> = env::args().skip(1).map(|a| quote_arg(&a)).collect();
+    let params = wide(args.join(" ").as_ref());
+    // Elevated processes start in the system dir otherwise
+    let dir = env::current_dir().map(|d| wide(d.as_os_str())).ok();
+
+    unsafe {
+        let mut info: SHELLEXECUTEINFOW = zeroed();
+        info.cbSize = size_of::<SHELLEXECUTEINFOW>() as DWORD;
+        info.fMask = SEE_MASK_NOCLOSEPROCESS;
+        info.lpVerb = verb.as_ptr();
+        info.lpFile = file.as_ptr();
+        info.lpParameters = params.as_ptr();
+        info.lpDirectory = dir.as_ref().map_or(std::ptr::null(), |d| d.as_ptr());
+        info.nShow = SW_SHOWNORMAL;
+        if ShellExecuteExW(&mut info) == 0 {
+            let error = GetLastError();
+            if error == ERROR_CANCELLED {
+                info!("process", "Elevation declined by the user");
+                message(&text("elevation.declined"));
+            } else {
+                message(&text_with("elevation.failed", &[&format!("error {}", error)]));
+            }
+            return Some(FAILURE_EXIT_CODE);
+        }
+        if info.hProcess.is_null() {
+            return Some(0);
+        }
+        WaitForSingleObject(info.hProcess, INFINITE);
+        let mut code: DWORD = 0;
+        GetExitCodeProcess(info.hProcess, &mut code);
+        CloseHandle(info.hProcess);
+        Some(code as i32)
+    }
+}
+
+/// The argument quoted for a Windows command line, as programs split them with `CommandLineToArgvW`,
+/// where backslashes are only escapes before a quote.
+#[cfg(windows)]
+fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains(|c| c == ' ' || c == '\t' || c == '"') {
+        return arg.to_string();
+    }
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            c => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                quoted.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+/// Only Windows asks to run programs as an administrator.
+#[cfg(not(windows))]
+pub fn run_elevated() -> Option<i32> {
+    info!("process", "require_elevation is only supported on Windows");
+    None
+}
+
+/// The PID file of the daemon, see [`daemonize`].
+#[cfg(all(unix, not(target_os = "macos")))]
+static PID_FILE: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Detach the launcher from the terminal and session it was started from to run the app in the background,
+/// for `--why-daemon`. It forks twice so that it can never get a controlling terminal again, reads its input
+/// from `/dev/null` and writes its output to the output log. Its process id is written to `pid_file`, or else
+/// to `<app>.pid` in the data dir, which is removed again when it exits. SIGTERM shuts the app down through
+/// `System.exit`, see [`forward_termination_signals`].<br>
+/// Must be called before the launcher starts any threads, as only the calling thread survives a fork.
+/// Fails if the PID file names a process that still runs.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn daemonize(config: &LauncherConfig) -> Result<(), String> {
+    use std::{fs, io, process};
+    use crate::file_handler::{get_app_name, get_user_data_dir, resolve_path};
+    use crate::locale_handler::text_with;
+    use crate::output_handler::capture_output_to_log;
+
+    let pid_file = match &config.pid_file {
+        Some(pid_file) => Some(resolve_path(pid_file)),
+        None => get_user_data_dir().map(|d| d.join(format!("{}.pid", get_app_name()))),
+    };
+    let running = pid_file.as_ref()
+        .and_then(|f| fs::read_to_string(f).ok())
+        .and_then(|pid| pid.trim().parse::<libc::pid_t>().ok())
+        .filter(|pid| *pid > 0 && unsafe { libc::kill(*pid, 0) } == 0);
+    if let Some(pid) = running {
+        return Err(text_with("daemon.running", &[&pid.to_string()]));
+    }
+
+    let fork = || match unsafe { libc::fork() } {
+        -1 => Err(text_with("daemon.failed", &[&io::Error::last_os_error().to_string()])),
+        // The parent exits at once, without running anything the child still does
+        0 => Ok(()),
+        _ => unsafe { libc::_exit(0) },
+    };
+    fork()?;
+    unsafe {
+        libc::setsid();
+    }
+    fork()?;
+
+    if let Ok(null) = fs::OpenOptions::new().read(true).open("/dev/null") {
+        use std::os::unix::io::AsRawFd;
+        unsafe {
+            libc::dup2(null.as_raw_fd(), libc::STDIN_FILENO);
+        }
+    }
+    capture_output_to_log();
+
+    if let Some(pid_file) = pid_file {
+        let written = pid_file.parent().map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&pid_file, format!("{}\n", process::id())));
+        match written {
+            Ok(_) => {
+                info!("process", "Running as a daemon with PID file {:?}", pid_file);
+                if let Ok(mut p) = PID_FILE.lock() {
+                    *p = Some(pid_file);
+                }
+            }
+            Err(e) => info!("process", "Failed to write the PID file {:?}: {}", pid_file, e),
+        }
+    }
+    Ok(())
+}
+
+/// Remove the PID file of the daemon, unless another instance took it over, such as when restarting.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn remove_pid_file() {
+    use std::{fs, process};
+    if let Some(pid_file) = PID_FILE.lock().ok().and_then(|mut p| p.take()) {
+        let ours = fs::read_to_string(&pid_file).map_or(false, |pid| pid.trim() == process::id().to_string());
+        if ours {
+            let _ = fs::remove_file(&pid_file);
+        }
+    }
+}
+
+/// Daemons are only supported on Linux, services are run by launchd on macOS and the
+/// service manager on Windows.
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub fn daemonize(_config: &LauncherConfig) -> Result<(), String> {
+    Err(crate::locale_handler::text("daemon.unsupported"))
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub fn remove_pid_file() {}
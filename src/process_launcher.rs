@@ -0,0 +1,154 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+
+use crate::exit_handler::{ExitCode, FAILURE_EXIT_CODE, report_crash_logs};
+use crate::file_handler::{app_working_dir, get_jvm_paths, remember_launched_jvm};
+use crate::install_handler::mark_first_run_done;
+use crate::java_launcher::{LaunchOpts, set_dynamic_library_lookup_loc, show_invalid_config, show_missing_java};
+use crate::session_handler::toolkit_opts;
+use crate::timing_handler::{log_timings, Phase, time, timing_properties};
+#[cfg(feature = "updater")]
+use crate::update_handler::{confirm_update, mark_update_tried};
+#[cfg(unix)]
+use crate::process_handler::forward_signals_to_child;
+
+#[cfg(windows)]
+/// Names of the Java executables to look for, in order of preference.
+/// `javaw` does not open a console window.
+const JAVA_EXECUTABLES: &'static [&str] = &["javaw.exe", "java.exe"];
+#[cfg(not(windows))]
+/// Names of the Java executables to look for, in order of preference.
+const JAVA_EXECUTABLES: &'static [&str] = &["java"];
+
+/// How long the app's process has to run for it to have started, confirming an update
+/// and remembering the Java it runs with.
+const STARTED_AFTER: Duration = Duration::from_secs(10);
+
+/// Start the app in a separate Java process using the `java` executable of the found installation,
+/// rather than creating the JVM in the launcher's process.<br>
+/// Blocks until the process has exited, and returns its exit status.
+pub fn run_java_process(launch_opts: &LaunchOpts) -> i32 {
+    // Not enough information provided in the launcher config
+    if !launch_opts.config.validate() {
+        show_invalid_config(&launch_opts.config);
+        return ExitCode::ConfigInvalid.code();
+    }
+
+    loop {
+        if let Some(status) = try_run_java_process(launch_opts) {
+            return status;
+        }
+        // Again if the user installed Java
+        if !show_missing_java(launch_opts) {
+            return ExitCode::NoJava.code();
+        }
+    }
+}
+
+/// Start the app in a separate Java process, see [`run_java_process`].<br>
+/// Returns `None` without telling the user if no Java executable could be started.
+pub fn try_run_java_process(launch_opts: &LaunchOpts) -> Option<i32> {
+    for (i, jvm_path_f) in get_jvm_paths(launch_opts).into_iter().enumerate() {
+        if let Some(jvm_path) = time(Phase::JvmDiscovery, || (jvm_path_f)(launch_opts)) {
+            let java = match find_java_executable(&jvm_path) {
+                Some(java) => java,
+                None => {
+                    info!("discovery", "No Java executable found for {:?}", jvm_path);
+                    continue
+                }
+            };
+
+            set_dynamic_library_lookup_loc(&jvm_path, launch_opts);
+
+            // Creating the JVM and loading the main class are up to the process
+            log_timings();
+            let mut args: Vec<String> = timing_properties().iter()
+                .map(|(key, value)| format!("-D{}={}", key, value))
+                .collect();
+            args.extend(toolkit_opts(&jvm_path, &launch_opts.config, &launch_opts.all_jvm_opts()));
+            args.extend(make_process_args(launch_opts));
+
+            info!("process", "Starting {:?}", java);
+            let start_time = SystemTime::now();
+            let mut command = match app_working_dir(&launch_opts.config) {
+                // By its absolute path, which a relative one would not clearly be resolved to in another directory
+                Some(dir) => {
+                    info!("process", "Running the app in {:?}", dir);
+                    let mut command = Command::new(env::current_dir().map_or_else(|_| java.clone(), |d| d.join(&java)));
+                    command.current_dir(dir);
+                    command
+                }
+                None => Command::new(&java),
+            };
+            #[cfg(feature = "updater")]
+            mark_update_tried();
+            match command.args(args).spawn() {
+                Ok(mut child) => {
+                    #[cfg(unix)]
+                    forward_signals_to_child(child.id());
+
+                    // No code means the process was killed by a signal
+                    let status = child.wait().ok().and_then(|s| s.code());
+                    if status == Some(0) || start_time.elapsed().map_or(false, |t| t >= STARTED_AFTER) {
+                        #[cfg(feature = "updater")]
+                        confirm_update();
+                        remember_launched_jvm(&launch_opts.config, &jvm_path, i);
+                        mark_first_run_done(&launch_opts.config);
+                    }
+                    if status != Some(0) {
+                        report_crash_logs(child.id());
+                    }
+                    return Some(status.unwrap_or(FAILURE_EXIT_CODE));
+                }
+                Err(e) => {
+                    info!("process", "Failed to start {:?}: {:?}", java, e);
+                    continue
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Find the Java executable belonging to the dynamic Java library at `jvm_path`.<br>
+/// The library is in `bin/server` on Windows and `lib/server` elsewhere,
+/// so the `bin` folder is searched for starting from the installation's home.
+pub fn find_java_executable(jvm_path: &Path) -> Option<PathBuf> {
+    for home in jvm_path.ancestors().skip(2).take(2) {
+        for exe in JAVA_EXECUTABLES {
+            let java = home.join("bin").join(exe);
+            if java.is_file() {
+                return Some(java);
+            }
+        }
+    }
+    None
+}
+
+/// Build the command line arguments of the `java` executable:
+/// the JVM options, then the main class or module, then the program options.
+fn make_process_args(launch_opts: &LaunchOpts) -> Vec<String> {
+    let mut args: Vec<String> = vec![];
+    for opt in launch_opts.jvm_opts.iter().chain(launch_opts.user_jvm_opts.iter()) {
+        // The executable sets its own classpath property, so it must be given as an option
+        if let Some(classpath) = opt.strip_prefix("-Djava.class.path=") {
+            args.push("-cp".to_string());
+            args.push(classpath.to_string());
+        } else {
+            args.push(opt.to_owned());
+        }
+    }
+    if let Some(main_module) = &launch_opts.config.main_module {
+        // Given to the executable as -m instead of the property
+        args.retain(|a| !a.starts_with("-Djdk.module.main="));
+        args.push("-m".to_string());
+        args.push(main_module.to_owned());
+    } else if let Some(main_class) = &launch_opts.config.main_class {
+        args.push(main_class.to_owned());
+    }
+    args.extend(launch_opts.program_opts.iter().cloned());
+    args
+}
@@ -0,0 +1,650 @@
+#[cfg(not(target_os = "macos"))]
+use std::env;
+#[cfg(not(target_os = "macos"))]
+use std::path::PathBuf;
+
+use crate::display_handler::{is_headless, message};
+use crate::exit_handler::FAILURE_EXIT_CODE;
+#[cfg(not(target_os = "macos"))]
+use crate::file_handler::{get_app_name, process_path};
+use crate::launch_config::{FileAssociation, LauncherConfig};
+use crate::locale_handler::{text, text_with};
+#[cfg(all(unix, not(target_os = "macos")))]
+use crate::session_handler::wm_class;
+
+/// Register the launcher as the handler of links with the URI scheme, such as `myapp://open?id=1`,
+/// for the current user, as asked for with `--why-register-protocol`. The link is passed to the
+/// app as its argument. Returns the exit status of the launcher.
+pub fn register_protocol(scheme: &str, config: &LauncherConfig) -> i32 {
+    if !is_valid_scheme(scheme) {
+        message(&text_with("protocol.invalid", &[scheme]));
+        return FAILURE_EXIT_CODE;
+    }
+    let scheme = scheme.to_ascii_lowercase();
+    report(add_protocol(&scheme, config), "protocol.registered", "protocol.register_failed", &scheme)
+}
+
+/// Remove the registration of [`register_protocol`], as asked for with `--why-unregister-protocol`.
+/// Returns the exit status of the launcher.
+pub fn unregister_protocol(scheme: &str) -> i32 {
+    if !is_valid_scheme(scheme) {
+        message(&text_with("protocol.invalid", &[scheme]));
+        return FAILURE_EXIT_CODE;
+    }
+    let scheme = scheme.to_ascii_lowercase();
+    report(remove_protocol(&scheme), "protocol.unregistered", "protocol.unregister_failed", &scheme)
+}
+
+/// Register the launcher as the app opening the file types of the `[FileAssociations]` section for
+/// the current user, as asked for with `--why-register-filetypes`, such as on first run for apps
+/// installed without an installer registering them. Returns the exit status of the launcher.
+pub fn register_file_types(config: &LauncherConfig) -> i32 {
+    if config.file_associations.is_empty() {
+        message(&text("filetypes.none"));
+        return FAILURE_EXIT_CODE;
+    }
+    report(add_file_types(config), "filetypes.registered", "filetypes.register_failed",
+           &extensions(&config.file_associations))
+}
+
+/// Remove the registration of [`register_file_types`], as asked for with `--why-unregister-filetypes`.
+/// Returns the exit status of the launcher.
+pub fn unregister_file_types(config: &LauncherConfig) -> i32 {
+    if config.file_associations.is_empty() {
+        message(&text("filetypes.none"));
+        return FAILURE_EXIT_CODE;
+    }
+    report(remove_file_types(&config.file_associations), "filetypes.unregistered",
+           "filetypes.unregister_failed", &extensions(&config.file_associations))
+}
+
+/// The extensions of the file types, for messages, such as `.fits, .fit`.
+fn extensions(associations: &[FileAssociation]) -> String {
+    associations.iter().map(|a| format!(".{}", a.extension)).collect::<Vec<String>>().join(", ")
+}
+
+/// Tell the user how the registration went, and the exit status for it.
+fn report(result: Result<(), String>, done_key: &str, failed_key: &str, subject: &str) -> i32 {
+    match result {
+        Ok(()) => {
+            info!("launcher", "{}", text_with(done_key, &[subject]));
+            if !is_headless() {
+                message(&text_with(done_key, &[subject]));
+            }
+            0
+        }
+        Err(e) => {
+            message(&text_with(failed_key, &[subject, &e]));
+            FAILURE_EXIT_CODE
+        }
+    }
+}
+
+/// Whether the text is a URI scheme, a letter followed by letters, digits, `+`, `-` or `.`.
+fn is_valid_scheme(scheme: &str) -> bool {
+    let mut chars = scheme.chars();
+    chars.next().map_or(false, |c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+}
+
+/// The name the app is shown with in the OS, the config's `app_name` or that of the launcher.
+#[cfg(not(target_os = "macos"))]
+fn display_name(config: &LauncherConfig) -> String {
+    config.app_name.clone().unwrap_or_else(get_app_name)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn launcher_path() -> Result<PathBuf, String> {
+    env::current_exe().map_err(|e| e.to_string())
+}
+
+/// Handled through the scheme's key in the user's classes, as with the classes of the machine
+/// but without needing to be an admin.
+#[cfg(windows)]
+fn add_protocol(scheme: &str, config: &LauncherConfig) -> Result<(), String> {
+    let exe = launcher_path()?.display().to_string();
+    let key = format!("Software\\Classes\\{}", scheme);
+    let icon = config.icon.as_ref().map_or_else(|| format!("{},0", exe), |i| process_path(i));
+    registry::set_value(&key, None, &format!("URL:{} Protocol", display_name(config)))?;
+    registry::set_value(&key, Some("URL Protocol"), "")?;
+    registry::set_value(&format!("{}\\DefaultIcon", key), None, &icon)?;
+    registry::set_value(&format!("{}\\shell\\open\\command", key), None, &format!("\"{}\" \"%1\"", exe))
+}
+
+#[cfg(windows)]
+fn remove_protocol(scheme: &str) -> Result<(), String> {
+    registry::delete_tree(&format!("Software\\Classes\\{}", scheme))
+}
+
+/// The programmatic identifier of the app's file type in the registry, such as `AstroImageJ.fits`.
+#[cfg(windows)]
+fn prog_id(association: &FileAssociation) -> String {
+    format!("{}.{}", get_app_name().replace(' ', ""), association.extension)
+}
+
+/// Each type gets a programmatic identifier opening it with the launcher, which is made the default of
+/// the extension, and the Explorer is told the associations changed.
+#[cfg(windows)]
+fn add_file_types(config: &LauncherConfig) -> Result<(), String> {
+    let exe = launcher_path()?.display().to_string();
+    for association in &config.file_associations {
+        let prog_id = prog_id(association);
+        let key = format!("Software\\Classes\\{}", prog_id);
+        let icon = association.icon.as_ref().or(config.icon.as_ref())
+            .map_or_else(|| format!("{},0", exe), |i| process_path(i));
+        let description = association.description.clone()
+            .unwrap_or_else(|| format!("{} {}", display_name(config), association.extension));
+        registry::set_value(&key, None, &description)?;
+        registry::set_value(&format!("{}\\DefaultIcon", key), None, &icon)?;
+        registry::set_value(&format!("{}\\shell\\open\\command", key), None, &format!("\"{}\" \"%1\"", exe))?;
+
+        let extension_key = format!("Software\\Classes\\.{}", association.extension);
+        registry::set_value(&extension_key, None, &prog_id)?;
+        registry::set_value(&extension_key, Some("Content Type"), &association.mime_type)?;
+        registry::set_value(&format!("{}\\OpenWithProgids", extension_key), Some(&prog_id), "")?;
+    }
+    registry::notify_associations_changed();
+    Ok(())
+}
+
+/// The extensions stay the default of the app's identifiers only if another app did not take them over.
+#[cfg(windows)]
+fn remove_file_types(associations: &[FileAssociation]) -> Result<(), String> {
+    for association in associations {
+        let prog_id = prog_id(association);
+        let extension_key = format!("Software\\Classes\\.{}", association.extension);
+        registry::delete_tree(&format!("Software\\Classes\\{}", prog_id))?;
+        registry::delete_value(&format!("{}\\OpenWithProgids", extension_key), Some(&prog_id))?;
+        if registry::get_value(&extension_key, None).as_deref() == Some(prog_id.as_str()) {
+            registry::delete_value(&extension_key, None)?;
+        }
+    }
+    registry::notify_associations_changed();
+    Ok(())
+}
+
+/// Launch Services only passes links to apps declaring the scheme in the `CFBundleURLTypes` of their
+/// `Info.plist`, so the developers are told how when it is missing, and the app is made the default
+/// handler otherwise.
+#[cfg(target_os = "macos")]
+fn add_protocol(scheme: &str, _config: &LauncherConfig) -> Result<(), String> {
+    let declared = bundle::info_plist()
+        .map_or(false, |plist| !plist.starts_with("<?xml") || declares_url_scheme(&plist, scheme));
+    if !declared {
+        return Err(text_with("protocol.undeclared", &[scheme]));
+    }
+    bundle::set_default_url_handler(scheme)
+}
+
+/// Launch Services has no way to remove a handler, it forgets the app when the scheme is taken out
+/// of its `Info.plist` or the app is deleted, so the user is told that instead.
+#[cfg(target_os = "macos")]
+fn remove_protocol(scheme: &str) -> Result<(), String> {
+    Err(text_with("protocol.unregister_macos", &[scheme]))
+}
+
+/// As with links, Launch Services only opens the types declared in the `CFBundleDocumentTypes` of the
+/// `Info.plist` with the app, which is made their default app.
+#[cfg(target_os = "macos")]
+fn add_file_types(config: &LauncherConfig) -> Result<(), String> {
+    let plist = bundle::info_plist();
+    for association in &config.file_associations {
+        let declared = plist.as_ref()
+            .map_or(false, |p| !p.starts_with("<?xml") || declares_extension(p, &association.extension));
+        if !declared {
+            return Err(text_with("filetypes.undeclared", &[&association.extension]));
+        }
+        bundle::set_default_file_handler(&association.extension)?;
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn remove_file_types(_associations: &[FileAssociation]) -> Result<(), String> {
+    Err(text("filetypes.unregister_macos"))
+}
+
+/// Whether the XML `Info.plist` lists the extension in its `CFBundleDocumentTypes`.
+#[cfg(target_os = "macos")]
+fn declares_extension(plist: &str, extension: &str) -> bool {
+    plist.find("CFBundleDocumentTypes").map_or(false, |i| {
+        plist[i..].to_ascii_lowercase().contains(&format!("<string>{}</string>", extension))
+    })
+}
+
+/// Whether the XML `Info.plist` lists the scheme in its `CFBundleURLSchemes`.
+#[cfg(target_os = "macos")]
+fn declares_url_scheme(plist: &str, scheme: &str) -> bool {
+    plist.find("CFBundleURLSchemes").map_or(false, |i| {
+        let schemes = &plist[i..];
+        let schemes = &schemes[..schemes.find("</array>").unwrap_or(schemes.len())];
+        schemes.to_ascii_lowercase().contains(&format!("<string>{}</string>", scheme))
+    })
+}
+
+/// Handled through a hidden desktop entry for the scheme's `x-scheme-handler` type,
+/// which is made the default.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn add_protocol(scheme: &str, config: &LauncherConfig) -> Result<(), String> {
+    let mime_type = format!("x-scheme-handler/{}", scheme);
+    let icon = config.icon.as_ref().map(|i| process_path(i));
+    desktop::add_entry(&protocol_entry(scheme), &display_name(config), "%u", icon.as_deref(),
+                       wm_class(config).as_deref(), &[&mime_type])
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn remove_protocol(scheme: &str) -> Result<(), String> {
+    desktop::remove_entry(&protocol_entry(scheme))
+}
+
+/// The types are added to the user's MIME database, with their icons, and get a hidden desktop entry
+/// opening them with the launcher, which is made their default.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn add_file_types(config: &LauncherConfig) -> Result<(), String> {
+    let associations = &config.file_associations;
+    desktop::install_mime_types(&file_types_package(), associations)?;
+    for association in associations {
+        if let Some(icon) = association.icon.as_ref() {
+            desktop::install_mime_icon(&process_path(icon), &association.mime_type);
+        }
+    }
+    let mut mime_types: Vec<&str> = associations.iter().map(|a| a.mime_type.as_str()).collect();
+    mime_types.sort();
+    mime_types.dedup();
+    let icon = config.icon.as_ref().map(|i| process_path(i));
+    desktop::add_entry(&file_types_entry(), &display_name(config), "%F", icon.as_deref(),
+                       wm_class(config).as_deref(), &mime_types)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn remove_file_types(associations: &[FileAssociation]) -> Result<(), String> {
+    desktop::remove_entry(&file_types_entry())?;
+    for association in associations.iter().filter(|a| a.icon.is_some()) {
+        desktop::uninstall_mime_icon(&association.mime_type);
+    }
+    desktop::uninstall_mime_types(&file_types_package(), associations)
+}
+
+/// The file name of the desktop entry opening the app's file types.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn file_types_entry() -> String {
+    format!("{}-files.desktop", get_app_name())
+}
+
+/// The name of the app's MIME package, which must start with a vendor and a dash.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn file_types_package() -> String {
+    format!("{}-files.xml", get_app_name().to_lowercase().replace(' ', ""))
+}
+
+/// The file name of the desktop entry handling the scheme.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn protocol_entry(scheme: &str) -> String {
+    format!("{}-{}-handler.desktop", get_app_name(), scheme)
+}
+
+/// Keys in `HKEY_CURRENT_USER`.
+#[cfg(windows)]
+mod registry {
+    use std::ffi::OsStr;
+    use std::io;
+    use std::iter::once;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr::{null, null_mut};
+    use winapi::shared::minwindef::{DWORD, HKEY};
+    use winapi::shared::winerror::{ERROR_FILE_NOT_FOUND, ERROR_SUCCESS};
+    use winapi::um::shlobj::{SHCNE_ASSOCCHANGED, SHCNF_IDLIST, SHChangeNotify};
+    use winapi::um::winnt::{KEY_WRITE, REG_SZ};
+    use winapi::um::winreg::{HKEY_CURRENT_USER, RegCloseKey, RegCreateKeyExW, RegDeleteKeyValueW, RegDeleteTreeW,
+                             RegGetValueW, RegSetValueExW, RRF_RT_REG_SZ};
+
+    fn wide(text: &str) -> Vec<u16> {
+        OsStr::new(text).encode_wide().chain(once(0)).collect()
+    }
+
+    fn error(status: i32) -> String {
+        io::Error::from_raw_os_error(status).to_string()
+    }
+
+    /// Set a string value of the key, its default value without a name, creating the key if needed.
+    pub fn set_value(key: &str, name: Option<&str>, value: &str) -> Result<(), String> {
+        let (key, name, value) = (wide(key), name.map(wide), wide(value));
+        unsafe {
+            let mut handle: HKEY = null_mut();
+            let status = RegCreateKeyExW(HKEY_CURRENT_USER, key.as_ptr(), 0, null_mut(), 0, KEY_WRITE,
+                                         null_mut(), &mut handle, null_mut());
+            if status != ERROR_SUCCESS as i32 {
+                return Err(error(status));
+            }
+            let status = RegSetValueExW(handle, name.as_ref().map_or(null(), |n| n.as_ptr()), 0, REG_SZ,
+                                        value.as_ptr() as *const u8, (value.len() * 2) as u32);
+            RegCloseKey(handle);
+            if status != ERROR_SUCCESS as i32 {
+                return Err(error(status));
+            }
+        }
+        Ok(())
+    }
+
+    /// A string value of the key, its default value without a name.
+    pub fn get_value(key: &str, name: Option<&str>) -> Option<String> {
+        let (key, name) = (wide(key), name.map(wide));
+        let mut value = [0u16; 1024];
+        let mut size = (value.len() * 2) as DWORD;
+        let status = unsafe {
+            RegGetValueW(HKEY_CURRENT_USER, key.as_ptr(), name.as_ref().map_or(null(), |n| n.as_ptr()),
+                         RRF_RT_REG_SZ, null_mut(), value.as_mut_ptr() as *mut _, &mut size)
+        };
+        if status != ERROR_SUCCESS as i32 {
+            return None;
+        }
+        let len = value.iter().position(|c| *c == 0).unwrap_or(value.len());
+        Some(String::from_utf16_lossy(&value[..len]))
+    }
+
+    /// Delete a value of the key, its default value without a name, if it exists.
+    pub fn delete_value(key: &str, name: Option<&str>) -> Result<(), String> {
+        let (key, name) = (wide(key), name.map(wide));
+        let status = unsafe {
+            RegDeleteKeyValueW(HKEY_CURRENT_USER, key.as_ptr(), name.as_ref().map_or(null(), |n| n.as_ptr()))
+        };
+        if status == ERROR_SUCCESS as i32 || status == ERROR_FILE_NOT_FOUND as i32 {
+            Ok(())
+        } else {
+            Err(error(status))
+        }
+    }
+
+    /// Let the Explorer know that file associations changed, so that it shows the new icons.
+    pub fn notify_associations_changed() {
+        unsafe {
+            SHChangeNotify(SHCNE_ASSOCCHANGED, SHCNF_IDLIST, null(), null());
+        }
+    }
+
+    /// Delete the key and everything in it, if it exists.
+    pub fn delete_tree(key: &str) -> Result<(), String> {
+        let key = wide(key);
+        let status = unsafe { RegDeleteTreeW(HKEY_CURRENT_USER, key.as_ptr()) };
+        if status == ERROR_SUCCESS as i32 || status == ERROR_FILE_NOT_FOUND as i32 {
+            Ok(())
+        } else {
+            Err(error(status))
+        }
+    }
+}
+
+/// The app bundle the launcher is in.
+#[cfg(target_os = "macos")]
+mod bundle {
+    use std::env;
+    use std::ffi::{c_void, CString};
+    use std::fs;
+    use std::os::raw::c_char;
+    use std::ptr::null;
+
+    const UTF8_ENCODING: u32 = 0x08000100;
+    /// `kUTTagClassFilenameExtension`
+    const EXTENSION_TAG_CLASS: &str = "public.filename-extension";
+    /// `kLSRolesAll`
+    const ALL_ROLES: u32 = 0xFFFFFFFF;
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFStringCreateWithCString(allocator: *const c_void, text: *const c_char, encoding: u32) -> *const c_void;
+        fn CFBundleGetMainBundle() -> *const c_void;
+        fn CFBundleGetIdentifier(bundle: *const c_void) -> *const c_void;
+        fn CFRelease(object: *const c_void);
+    }
+
+    #[link(name = "CoreServices", kind = "framework")]
+    extern "C" {
+        fn LSSetDefaultHandlerForURLScheme(scheme: *const c_void, bundle_id: *const c_void) -> i32;
+        fn LSSetDefaultRoleHandlerForContentType(content_type: *const c_void, role: u32,
+                                                 bundle_id: *const c_void) -> i32;
+        fn UTTypeCreatePreferredIdentifierForTag(tag_class: *const c_void, tag: *const c_void,
+                                                 conforming_to: *const c_void) -> *const c_void;
+    }
+
+    /// The text of the bundle's `Info.plist`, which may be binary.
+    pub fn info_plist() -> Option<String> {
+        let exe = env::current_exe().ok()?;
+        let contents = exe.parent()?.parent()?;
+        Some(String::from_utf8_lossy(&fs::read(contents.join("Info.plist")).ok()?).to_string())
+    }
+
+    /// The identifier of the bundle, owned by it.
+    unsafe fn bundle_id() -> Result<*const c_void, String> {
+        let bundle = CFBundleGetMainBundle();
+        let id = if bundle.is_null() { null() } else { CFBundleGetIdentifier(bundle) };
+        if id.is_null() {
+            return Err("the launcher is not in an app bundle with a CFBundleIdentifier".to_string());
+        }
+        Ok(id)
+    }
+
+    /// The text as a `CFString`, to be released.
+    unsafe fn cf_string(text: &str) -> Result<*const c_void, String> {
+        let text = CString::new(text).map_err(|e| e.to_string())?;
+        Ok(CFStringCreateWithCString(null(), text.as_ptr(), UTF8_ENCODING))
+    }
+
+    fn check(status: i32) -> Result<(), String> {
+        match status {
+            0 => Ok(()),
+            status => Err(format!("Launch Services error {}", status)),
+        }
+    }
+
+    /// Make the bundle the default handler of links with the scheme.
+    pub fn set_default_url_handler(scheme: &str) -> Result<(), String> {
+        unsafe {
+            let id = bundle_id()?;
+            let scheme = cf_string(scheme)?;
+            let status = LSSetDefaultHandlerForURLScheme(scheme, id);
+            CFRelease(scheme);
+            check(status)
+        }
+    }
+
+    /// Make the bundle the default app of the files with the extension, for every role.
+    pub fn set_default_file_handler(extension: &str) -> Result<(), String> {
+        unsafe {
+            let id = bundle_id()?;
+            let (tag_class, extension) = (cf_string(EXTENSION_TAG_CLASS)?, cf_string(extension)?);
+            let content_type = UTTypeCreatePreferredIdentifierForTag(tag_class, extension, null());
+            CFRelease(tag_class);
+            CFRelease(extension);
+            if content_type.is_null() {
+                return Err("no type identifier for the extension".to_string());
+            }
+            let status = LSSetDefaultRoleHandlerForContentType(content_type, ALL_ROLES, id);
+            CFRelease(content_type);
+            check(status)
+        }
+    }
+}
+
+/// Desktop entries in the user's `applications` dir, see
+/// <https://specifications.freedesktop.org/desktop-entry-spec/latest/>
+#[cfg(all(unix, not(target_os = "macos")))]
+mod desktop {
+    use std::{env, fs};
+    use std::path::PathBuf;
+    use std::process::{Command, Stdio};
+
+    use crate::launch_config::FileAssociation;
+    use super::launcher_path;
+
+    /// The size icons of file types are installed as, in pixels, the icon theme scales it as needed.
+    const MIME_ICON_SIZE: &str = "64";
+
+    /// Where the user's desktop entries are, `~/.local/share/applications`.
+    fn applications_dir() -> Result<PathBuf, String> {
+        dirs::data_dir().map(|d| d.join("applications")).ok_or_else(|| "no data dir".to_string())
+    }
+
+    /// Where the user's default apps are kept, `~/.config/mimeapps.list`.
+    fn mime_apps_list() -> Option<PathBuf> {
+        Some(dirs::config_dir()?.join("mimeapps.list"))
+    }
+
+    /// Write a hidden desktop entry starting the launcher with the field code, such as `%u`,
+    /// and make it the default app of the MIME types.
+    pub fn add_entry(file_name: &str, name: &str, field_code: &str, icon: Option<&str>, wm_class: Option<&str>,
+                     mime_types: &[&str]) -> Result<(), String> {
+        let dir = applications_dir()?;
+        let mut entry = format!("[Desktop Entry]\nType=Application\nName={}\nExec={} {}\nNoDisplay=true\n\
+                                 MimeType={};\n",
+                                name, exec_quote(&launcher_path()?.display().to_string()), field_code,
+                                mime_types.join(";"));
+        if let Some(icon) = icon {
+            entry += &format!("Icon={}\n", icon);
+        }
+        if let Some(wm_class) = wm_class {
+            entry += &format!("StartupWMClass={}\n", wm_class);
+        }
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        fs::write(dir.join(file_name), entry).map_err(|e| e.to_string())?;
+
+        for mime_type in mime_types {
+            run("xdg-mime", &["default", file_name, mime_type])?;
+        }
+        update_database(&dir);
+        Ok(())
+    }
+
+    /// Add the types to the user's MIME database as a package, so that files are recognized by their
+    /// extension, see <https://specifications.freedesktop.org/shared-mime-info-spec/latest/>
+    pub fn install_mime_types(package: &str, associations: &[FileAssociation]) -> Result<(), String> {
+        with_package(package, associations, |path| run("xdg-mime", &["install", "--mode", "user", path]))
+    }
+
+    /// Remove the package of [`install_mime_types`] from the user's MIME database.
+    pub fn uninstall_mime_types(package: &str, associations: &[FileAssociation]) -> Result<(), String> {
+        with_package(package, associations, |path| run("xdg-mime", &["uninstall", "--mode", "user", path]))
+    }
+
+    /// Run the tool with the package written to a temporary file, as it is named after the file.
+    fn with_package<F>(package: &str, associations: &[FileAssociation], tool: F) -> Result<(), String>
+        where F: FnOnce(&str) -> Result<(), String> {
+        let path = env::temp_dir().join(package);
+        fs::write(&path, mime_package(associations)).map_err(|e| e.to_string())?;
+        let result = tool(&path.display().to_string());
+        let _ = fs::remove_file(&path);
+        result
+    }
+
+    /// The MIME package declaring the types.
+    fn mime_package(associations: &[FileAssociation]) -> String {
+        let mut package = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <mime-info xmlns=\"http://www.freedesktop.org/standards/shared-mime-info\">\n");
+        for association in associations {
+            package += &format!("  <mime-type type=\"{}\">\n", xml_escape(&association.mime_type));
+            if let Some(description) = &association.description {
+                package += &format!("    <comment>{}</comment>\n", xml_escape(description));
+            }
+            package += &format!("    <glob pattern=\"*.{}\"/>\n  </mime-type>\n", xml_escape(&association.extension));
+        }
+        package + "</mime-info>\n"
+    }
+
+    fn xml_escape(text: &str) -> String {
+        text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+    }
+
+    /// The name of the icon of a MIME type, such as `image-fits` for `image/fits`.
+    fn mime_icon_name(mime_type: &str) -> String {
+        mime_type.replace('/', "-")
+    }
+
+    /// Add the icon, a PNG, to the user's icon theme for the MIME type. Only logged if it fails,
+    /// as files are still opened with the app without it.
+    pub fn install_mime_icon(icon: &str, mime_type: &str) {
+        let name = mime_icon_name(mime_type);
+        if let Err(e) = run("xdg-icon-resource", &["install", "--context", "mimetypes", "--size",
+            MIME_ICON_SIZE, icon, &name]) {
+            info!("launcher", "Failed to install the icon {} for {}: {}", icon, mime_type, e);
+        }
+    }
+
+    pub fn uninstall_mime_icon(mime_type: &str) {
+        let name = mime_icon_name(mime_type);
+        if let Err(e) = run("xdg-icon-resource", &["uninstall", "--context", "mimetypes", "--size",
+            MIME_ICON_SIZE, &name]) {
+            debug!("launcher", "Did not uninstall the icon for {}: {}", mime_type, e);
+        }
+    }
+
+    /// Delete the desktop entry and take it out of the user's default apps.
+    pub fn remove_entry(file_name: &str) -> Result<(), String> {
+        let dir = applications_dir()?;
+        let path = dir.join(file_name);
+        if path.exists() {
+            fs::remove_file(&path).map_err(|e| e.to_string())?;
+        }
+        // xdg-mime cannot unset a default
+        if let Some(list) = mime_apps_list() {
+            if let Ok(text) = fs::read_to_string(&list) {
+                let kept = without_entry(&text, file_name);
+                if kept != text {
+                    fs::write(&list, kept).map_err(|e| e.to_string())?;
+                }
+            }
+        }
+        update_database(&dir);
+        Ok(())
+    }
+
+    /// The `mimeapps.list` without the desktop entry, dropping the types only it was listed for.
+    fn without_entry(text: &str, file_name: &str) -> String {
+        let mut kept = String::with_capacity(text.len());
+        for line in text.lines() {
+            match line.split_once('=') {
+                Some((mime_type, apps)) if !line.starts_with('#') && apps.split(';').any(|a| a == file_name) => {
+                    let apps: Vec<&str> = apps.split(';').filter(|a| !a.is_empty() && *a != file_name).collect();
+                    if !apps.is_empty() {
+                        kept += &format!("{}={};\n", mime_type, apps.join(";"));
+                    }
+                }
+                _ => {
+                    kept += line;
+                    kept.push('\n');
+                }
+            }
+        }
+        kept
+    }
+
+    /// Let the desktop know the entries changed, not all have the tool.
+    fn update_database(dir: &PathBuf) {
+        if let Err(e) = run("update-desktop-database", &[&dir.display().to_string()]) {
+            debug!("launcher", "Did not update the desktop database: {}", e);
+        }
+    }
+
+    fn run(tool: &str, args: &[&str]) -> Result<(), String> {
+        let status = Command::new(tool).args(args).stdin(Stdio::null()).status()
+            .map_err(|e| format!("{}: {}", tool, e))?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("{} exited with {}", tool, status))
+        }
+    }
+
+    /// The path as an argument of `Exec`, quoted as the desktop entry spec requires.
+    fn exec_quote(path: &str) -> String {
+        let mut quoted = String::from("\"");
+        for c in path.chars() {
+            match c {
+                // Escaped for the quotes, then the backslash again for the string value
+                '"' | '`' | '$' => quoted += &format!("\\\\{}", c),
+                '\\' => quoted += "\\\\\\\\",
+                c => quoted.push(c),
+            }
+        }
+        quoted.push('"');
+        quoted
+    }
+}
@@ -0,0 +1,184 @@
+use std::path::Path;
+
+use crate::launch_config::LauncherConfig;
+#[cfg(all(unix, not(target_os = "macos")))]
+use crate::launch_config::WaylandToolkit;
+
+/// The option choosing the AWT toolkit.
+#[cfg(all(unix, not(target_os = "macos")))]
+const TOOLKIT_OPT: &str = "-Dawt.toolkit.name=";
+
+/// The kind of desktop session the launcher runs in on Linux.
+#[cfg(all(unix, not(target_os = "macos")))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum SessionType {
+    Wayland,
+    X11,
+    /// No display, such as over SSH.
+    None,
+}
+
+/// The session type the desktop reports, or else the display there is.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn session_type() -> SessionType {
+    use std::env;
+    use crate::display_handler::{has_wayland_display, has_x11_display};
+    match env::var("XDG_SESSION_TYPE").as_deref() {
+        Ok("wayland") => SessionType::Wayland,
+        Ok("x11") => SessionType::X11,
+        _ if has_wayland_display() => SessionType::Wayland,
+        _ if has_x11_display() => SessionType::X11,
+        _ => SessionType::None,
+    }
+}
+
+/// The options choosing the AWT toolkit for a Wayland session, as set with `wayland_toolkit`,
+/// unless the existing options choose one. Most Java versions only have the X11 toolkit, which
+/// works through XWayland, so `WLToolkit` is only used with a Java that has it.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn toolkit_opts(jvm_path: &Path, config: &LauncherConfig, existing: &[&String]) -> Vec<String> {
+    let session = session_type();
+    debug!("display", "Session type: {:?}", session);
+    if session != SessionType::Wayland || config.wayland_toolkit == WaylandToolkit::Off
+        || existing.iter().any(|o| o.starts_with(TOOLKIT_OPT)) {
+        return vec![];
+    }
+
+    if config.wayland_toolkit == WaylandToolkit::Native {
+        if has_wayland_toolkit(jvm_path) {
+            return vec![format!("{}WLToolkit", TOOLKIT_OPT)];
+        }
+        info!("display", "The Java at {:?} has no Wayland toolkit, using XWayland", jvm_path);
+    }
+    if std::env::var_os("DISPLAY").is_none() {
+        info!("display", "No XWayland display is set, the app may fail to show its windows");
+    }
+    vec![format!("{}XToolkit", TOOLKIT_OPT)]
+}
+
+/// Whether the Java of the dynamic library at `jvm_path`, in `lib/server`, has the Wayland toolkit.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn has_wayland_toolkit(jvm_path: &Path) -> bool {
+    jvm_path.parent().and_then(|p| p.parent()).map_or(false, |lib| lib.join("libwlawt.so").is_file())
+}
+
+/// Only Linux has a choice of toolkits.
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub fn toolkit_opts(_jvm_path: &Path, _config: &LauncherConfig, _existing: &[&String]) -> Vec<String> {
+    vec![]
+}
+
+/// The option scaling the app's user interface.
+#[cfg(all(unix, not(target_os = "macos")))]
+const UI_SCALE_OPT: &str = "-Dsun.java2d.uiScale=";
+
+/// The DPI of a display at a scale of 1.
+#[cfg(all(unix, not(target_os = "macos")))]
+const BASE_DPI: f64 = 96.0;
+
+/// The option scaling the app as the desktop is scaled, unless the existing options set a scale,
+/// as Java only reads an integer `GDK_SCALE` and apps are otherwise tiny on high resolution displays.
+/// The scale is `GDK_SCALE` times `GDK_DPI_SCALE`, or else `Xft.dpi` over 96, rounded to a quarter.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn ui_scale_opts(config: &LauncherConfig, existing: &[&String]) -> Vec<String> {
+    // Java also reads options from these
+    let env_scaled = ["JAVA_TOOL_OPTIONS", "JDK_JAVA_OPTIONS", "_JAVA_OPTIONS"].iter()
+        .any(|v| std::env::var(v).map_or(false, |o| o.contains(UI_SCALE_OPT)));
+    if !config.detect_ui_scale || env_scaled || existing.iter().any(|o| o.starts_with(UI_SCALE_OPT)) {
+        return vec![];
+    }
+    match desktop_scale() {
+        Some(scale) if scale > 1.0 => {
+            let scale = (scale * 4.0).round() / 4.0;
+            info!("display", "Scaling the app by {}, as the desktop", scale);
+            vec![format!("{}{}", UI_SCALE_OPT, scale)]
+        }
+        _ => vec![],
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn desktop_scale() -> Option<f64> {
+    use std::env;
+    let var = |name: &str| env::var(name).ok().and_then(|v| v.trim().parse::<f64>().ok()).filter(|s| *s > 0.0);
+    let (scale, dpi_scale) = (var("GDK_SCALE"), var("GDK_DPI_SCALE"));
+    if scale.is_some() || dpi_scale.is_some() {
+        return Some(scale.unwrap_or(1.0) * dpi_scale.unwrap_or(1.0));
+    }
+    xft_dpi().map(|dpi| dpi / BASE_DPI)
+}
+
+/// The `Xft.dpi` X resource, which desktops set to their text scale, also under XWayland.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn xft_dpi() -> Option<f64> {
+    use std::process::{Command, Stdio};
+    use crate::display_handler::has_x11_display;
+    if !has_x11_display() {
+        return None;
+    }
+    let output = Command::new("xrdb").arg("-query").stdin(Stdio::null()).stderr(Stdio::null()).output().ok()?;
+    String::from_utf8_lossy(&output.stdout).lines()
+        .find_map(|l| l.strip_prefix("Xft.dpi:"))
+        .and_then(|dpi| dpi.trim().parse::<f64>().ok())
+        .filter(|dpi| *dpi > 0.0)
+}
+
+/// Windows and macOS tell Java their scale themselves.
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub fn ui_scale_opts(_config: &LauncherConfig, _existing: &[&String]) -> Vec<String> {
+    vec![]
+}
+
+/// The `WM_CLASS` of the app's windows on Linux, which desktops group windows by in taskbars and
+/// alt-tab, and match with the `StartupWMClass` of desktop entries: `wm_class`, or else the app's name.
+/// `None` if `wm_class` is empty.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn wm_class(config: &LauncherConfig) -> Option<String> {
+    use crate::file_handler::get_app_name;
+    let wm_class = config.wm_class.clone().or_else(|| config.app_name.clone()).unwrap_or_else(get_app_name);
+    Some(wm_class).filter(|c| !c.is_empty())
+}
+
+/// The `WM_CLASS` to give the app's windows, see [`wm_class`], unless there is no X11 display or the
+/// options, including the [`toolkit_opts`], make the app headless or choose the Wayland toolkit, as setting
+/// it starts the X11 toolkit.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn wm_class_for_launch(config: &LauncherConfig, existing: &[&String]) -> Option<String> {
+    use crate::display_handler::has_x11_display;
+    let other_toolkit = existing.iter()
+        .any(|o| o.as_str() == "-Djava.awt.headless=true" || o.as_str() == format!("{}WLToolkit", TOOLKIT_OPT));
+    if !has_x11_display() || other_toolkit {
+        return None;
+    }
+    wm_class(config)
+}
+
+/// Only X11 has a `WM_CLASS`.
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub fn wm_class_for_launch(_config: &LauncherConfig, _existing: &[&String]) -> Option<String> {
+    None
+}
+
+/// Name the launcher process after the app, as shown by `ps`, `top` and system monitors,
+/// instead of after the executable. Linux keeps at most 15 bytes of it.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn set_process_name(config: &LauncherConfig) {
+    let name = match &config.app_name {
+        Some(name) => name,
+        None => return,
+    };
+    let mut end = name.len().min(15);
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    match std::ffi::CString::new(&name[..end]) {
+        Ok(name) => unsafe {
+            libc::prctl(libc::PR_SET_NAME, name.as_ptr() as libc::c_ulong, 0, 0, 0);
+        },
+        Err(_) => info!("launcher", "Not naming the process {:?}", name),
+    }
+}
+
+/// The process is named after the app bundle on macOS, and the executable on Windows.
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub fn set_process_name(_config: &LauncherConfig) {}
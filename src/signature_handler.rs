@@ -0,0 +1,480 @@
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use crate::display_handler::detailed_message;
+use crate::file_handler::resolve_path;
+use crate::install_handler::get_app_image;
+use crate::LauncherConfig;
+use crate::locale_handler::text;
+use crate::manifest_handler::{MANIFEST_PATH, parse_manifest, read_manifest_bytes, split_sections};
+use crate::pkcs7_handler::verify_signature_block;
+use crate::zip_handler::open_zip;
+
+/// The most problems listed when a jar fails verification.
+const MAX_REPORTED_PROBLEMS: usize = 10;
+
+/// The file in the app image listing the SHA-256 of the app's files, see [`verify_integrity`].
+const INTEGRITY_FILE: &str = "integrity.sha256";
+
+/// The digest algorithms that can be checked, as named in manifests and signature files.
+const DIGEST_ALGORITHMS: &'static [&str] = &["SHA-256", "SHA-384", "SHA-512"];
+
+/// How the launcher checks the signature of the app's jar before launching.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SignatureCheck {
+    /// The jar is not checked
+    Off,
+    /// The user is warned about a jar that fails the check, but the app still starts
+    Warn,
+    /// The app is not started from a jar that fails the check
+    Enforce,
+}
+
+impl SignatureCheck {
+    /// Parse the value of the `verify_signatures` key, unknown values fall back to [`SignatureCheck::Off`].
+    pub fn from_key(key: &str) -> Self {
+        match key.trim().to_lowercase().as_str() {
+            "true" | "enforce" => SignatureCheck::Enforce,
+            "warn" => SignatureCheck::Warn,
+            _ => SignatureCheck::Off,
+        }
+    }
+}
+
+/// Check the signature of the app's jar as configured, `main_jar` or else the first jar of the
+/// classpath, telling the user if it fails.<br>
+/// Returns whether the app may be launched.
+pub fn check_app_signature(config: &LauncherConfig) -> bool {
+    if config.verify_signatures == SignatureCheck::Off {
+        return true;
+    }
+
+    let jar = match app_jar(config) {
+        Some(jar) => jar,
+        None => {
+            info!("signature", "No jar to verify the signature of");
+            return config.verify_signatures != SignatureCheck::Enforce;
+        }
+    };
+
+    info!("signature", "Verifying the signature of {:?}", jar);
+    match verify_jar(&jar, &config.trusted_signers) {
+        Ok(_) => true,
+        Err(problems) => {
+            let details = problems.join("\n");
+            info!("signature", "Signature verification failed:\n{}", details);
+            if config.verify_signatures == SignatureCheck::Enforce {
+                detailed_message(&text("signature.refused"), &details);
+                false
+            } else {
+                detailed_message(&text("signature.warning"), &details);
+                true
+            }
+        }
+    }
+}
+
+/// The app's jar, `main_jar` or else the first jar of the classpath.
+pub fn app_jar(config: &LauncherConfig) -> Option<PathBuf> {
+    match &config.main_jar {
+        Some(main_jar) => Some(resolve_path(main_jar)),
+        None => config.classpath.iter()
+            .flat_map(|c| c.split(";"))
+            .map(PathBuf::from)
+            .find(|p| p.is_file()),
+    }
+}
+
+/// Check the app's files against `integrity.sha256` in the app image if `verify_integrity` is set,
+/// telling the user which are missing or modified, such as by a broken download or an antivirus
+/// quarantining them.<br>
+/// Returns whether the app may be launched.
+pub fn check_app_integrity(config: &LauncherConfig) -> bool {
+    if !config.verify_integrity {
+        return true;
+    }
+    let manifest = match get_app_image() {
+        Some(image) => image.join(INTEGRITY_FILE),
+        None => return true,
+    };
+
+    info!("signature", "Verifying the app's files against {:?}", manifest);
+    match verify_integrity(&manifest) {
+        Ok(_) => true,
+        Err(problems) => {
+            let details = problems.join("\n");
+            info!("signature", "Integrity verification failed:\n{}", details);
+            detailed_message(&text("integrity.failed"), &details);
+            false
+        }
+    }
+}
+
+/// Check the files a hash manifest lists against their SHA-256. It has the format `sha256sum` writes,
+/// a line per file of its hex SHA-256 and its path relative to the manifest, which may start with `*`.<br>
+/// Returns the problems found, if any.
+pub fn verify_integrity(manifest: &Path) -> Result<(), Vec<String>> {
+    let listed = fs::read_to_string(manifest)
+        .map_err(|e| vec![format!("{:?} could not be read: {}", manifest, e)])?;
+    let root = manifest.parent().unwrap_or_else(|| Path::new("."));
+
+    let mut problems: Vec<String> = vec![];
+    for (i, line) in listed.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (expected, name) = match parse_hash_line(line) {
+            Some(entry) => entry,
+            None => {
+                problems.push(format!("Line {} of {} is not a SHA-256 and a path", i + 1, INTEGRITY_FILE));
+                continue;
+            }
+        };
+        match file_sha256(&root.join(name)) {
+            Ok(actual) if actual.eq_ignore_ascii_case(expected) => {}
+            Ok(_) => problems.push(format!("{} is modified", name)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => problems.push(format!("{} is missing", name)),
+            Err(e) => problems.push(format!("{} could not be read: {}", name, e)),
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems)
+    }
+}
+
+/// Split a line of a hash manifest into its SHA-256 and path.
+fn parse_hash_line(line: &str) -> Option<(&str, &str)> {
+    let (hash, name) = line.trim_end().split_once(|c: char| c.is_whitespace())?;
+    let name = name.trim_start();
+    let name = name.strip_prefix('*').unwrap_or(name);
+    if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) || name.is_empty() {
+        return None;
+    }
+    Some((hash, name))
+}
+
+/// The SHA-256 of a file, as hex.
+pub fn file_sha256(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Check that a jar was signed by one of the trusted signers and that its contents match what was signed:
+/// each signature block (`META-INF/*.RSA`, `*.EC`) must be a valid signature of its signature file by
+/// a certificate whose SHA-256 fingerprint is trusted, the signature files must match the manifest,
+/// and the manifest digests must match the files, with no files added.<br>
+/// Only SHA-2 digests and RSA, P-256 and P-384 keys are supported. The signer's certificate chain
+/// and validity are not checked, the fingerprint is what makes it trusted.<br>
+/// Returns the problems found, if any.
+pub fn verify_jar(path: &Path, trusted_signers: &[String]) -> Result<(), Vec<String>> {
+    if trusted_signers.is_empty() {
+        return Err(vec!["No trusted_signers are set to check the signature with".to_string()]);
+    }
+    let mut jar = open_zip(path).ok_or_else(|| vec!["The jar could not be opened".to_string()])?;
+    let manifest = read_manifest_bytes(&mut jar).ok_or_else(|| vec!["The jar has no manifest".to_string()])?;
+    let sections = split_sections(&manifest);
+
+    let names: Vec<String> = jar.file_names().map(String::from).collect();
+    let signature_files: Vec<&String> = names.iter().filter(|n| is_signature_file(n)).collect();
+    if signature_files.is_empty() {
+        return Err(vec!["The jar is not signed".to_string()]);
+    }
+
+    let mut problems: Vec<String> = vec![];
+
+    // The signature files must match the manifest
+    for sf_name in &signature_files {
+        let mut sf: Vec<u8> = vec![];
+        if jar.by_name(sf_name).ok().and_then(|mut f| f.read_to_end(&mut sf).ok()).is_none() {
+            problems.push(format!("{} could not be read", sf_name));
+            continue;
+        }
+
+        let base = &sf_name[..sf_name.len() - 3];
+        let block_name = ["RSA", "DSA", "EC"].iter()
+            .map(|ext| format!("{}.{}", base, ext))
+            .find(|n| names.contains(n));
+        let block_name = match block_name {
+            Some(block_name) => block_name,
+            None => {
+                problems.push(format!("{} has no signature block", sf_name));
+                continue;
+            }
+        };
+        let mut block: Vec<u8> = vec![];
+        if jar.by_name(&block_name).ok().and_then(|mut f| f.read_to_end(&mut block).ok()).is_none() {
+            problems.push(format!("{} could not be read", block_name));
+            continue;
+        }
+        match verify_signature_block(&block, &sf) {
+            Ok(signer) if is_trusted(signer, trusted_signers) => {}
+            Ok(signer) => problems.push(format!("{} is signed by {}, which is not one of trusted_signers",
+                                                block_name, fingerprint(signer))),
+            Err(e) => problems.push(format!("{}: {}", block_name, e)),
+        }
+        problems.append(&mut verify_signature_file(sf_name, &sf, &manifest, &sections));
+    }
+
+    // The manifest must match the files
+    let mut signed: Vec<String> = vec![];
+    for section in sections.iter().skip(1) {
+        let entry = match parse_manifest(section, false) {
+            Ok(entry) => entry,
+            Err(e) => {
+                problems.push(e);
+                continue;
+            }
+        };
+        let name = match entry.get("Name") {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        let expected = match find_digest(&entry.main_attributes, "-Digest") {
+            Some(expected) => expected,
+            None => continue,
+        };
+        signed.push(name.clone());
+
+        let mut contents: Vec<u8> = vec![];
+        if jar.by_name(&name).ok().and_then(|mut f| f.read_to_end(&mut contents).ok()).is_none() {
+            problems.push(format!("{} is signed but missing", name));
+            continue;
+        }
+        if !digest_matches(&expected, &contents) {
+            problems.push(format!("{} was changed after signing", name));
+        }
+    }
+
+    // Files added after signing are not covered by it
+    for name in &names {
+        if !name.ends_with('/') && !is_signature_related(name) && !signed.contains(name) {
+            problems.push(format!("{} is not signed", name));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        problems.truncate(MAX_REPORTED_PROBLEMS);
+        Err(problems)
+    }
+}
+
+/// Check a signature file against the manifest. The digest of the whole manifest may be used,
+/// otherwise the digest of the main section, which holds the `Main-Class` and `Class-Path`,
+/// and of each section it lists must match.
+fn verify_signature_file(sf_name: &str, sf: &[u8], manifest: &[u8], sections: &[&[u8]]) -> Vec<String> {
+    let mut problems: Vec<String> = vec![];
+    let sf_sections = split_sections(sf);
+    let main = match sf_sections.first().map(|s| parse_manifest(s, false)) {
+        Some(Ok(main)) => main,
+        _ => return vec![format!("{} could not be read", sf_name)],
+    };
+
+    if let Some(expected) = find_digest(&main.main_attributes, "-Digest-Manifest") {
+        if digest_matches(&expected, manifest) {
+            return problems;
+        }
+    }
+    match (find_digest(&main.main_attributes, "-Digest-Manifest-Main-Attributes"), sections.first()) {
+        (Some(expected), Some(main_section)) if digest_matches(&expected, main_section) => {}
+        (Some(_), _) => problems.push("The manifest's main attributes were changed after signing".to_string()),
+        (None, _) => problems.push(format!("{} does not sign the manifest's main attributes", sf_name)),
+    }
+
+    for sf_section in sf_sections.iter().skip(1) {
+        let entry = match parse_manifest(sf_section, false) {
+            Ok(entry) => entry,
+            Err(e) => {
+                problems.push(e);
+                continue;
+            }
+        };
+        let (name, expected) = match (entry.get("Name"), find_digest(&entry.main_attributes, "-Digest")) {
+            (Some(name), Some(expected)) => (name.to_string(), expected),
+            _ => continue,
+        };
+        let section = sections.iter().skip(1).find(|s| {
+            parse_manifest(s, false).ok().and_then(|e| e.get("Name").map(|n| n == name)).unwrap_or(false)
+        });
+        match section {
+            Some(section) if digest_matches(&expected, section) => {}
+            Some(_) => problems.push(format!("The manifest entry of {} was changed after signing", name)),
+            None => problems.push(format!("The manifest entry of {} is missing", name)),
+        }
+    }
+
+    if sf_sections.len() <= 1 {
+        problems.push(format!("{} does not match the manifest", sf_name));
+    }
+    problems
+}
+
+/// The SHA-256 fingerprint of a certificate, as hex in pairs separated by `:`, as `keytool` prints it.
+fn fingerprint(certificate: &[u8]) -> String {
+    Sha256::digest(certificate).iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(":")
+}
+
+/// Whether the certificate's SHA-256 fingerprint is one of the trusted ones, which may be
+/// written with or without `:` separators.
+fn is_trusted(certificate: &[u8], trusted_signers: &[String]) -> bool {
+    let normalize = |f: &str| f.chars().filter(|c| c.is_ascii_hexdigit()).collect::<String>().to_uppercase();
+    let actual = normalize(&fingerprint(certificate));
+    trusted_signers.iter().any(|t| normalize(t) == actual)
+}
+
+/// A signature file directly in `META-INF`.
+fn is_signature_file(name: &str) -> bool {
+    name.strip_prefix("META-INF/").map_or(false, |n| !n.contains('/') && n.to_uppercase().ends_with(".SF"))
+}
+
+/// The manifest, signature files and signature blocks, which signing does not digest.
+fn is_signature_related(name: &str) -> bool {
+    if name.eq_ignore_ascii_case(MANIFEST_PATH) {
+        return true;
+    }
+    match name.strip_prefix("META-INF/") {
+        Some(n) if !n.contains('/') => {
+            let upper = n.to_uppercase();
+            [".SF", ".RSA", ".DSA", ".EC"].iter().any(|ext| upper.ends_with(ext)) || upper.starts_with("SIG-")
+        }
+        _ => false,
+    }
+}
+
+/// Find a digest attribute such as `SHA-256-Digest`, returning the algorithm and the value.
+fn find_digest(attributes: &[(String, String)], suffix: &str) -> Option<(String, String)> {
+    DIGEST_ALGORITHMS.iter().find_map(|alg| {
+        let key = format!("{}{}", alg, suffix);
+        attributes.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(&key))
+            .map(|(_, v)| (alg.to_string(), v.trim().to_string()))
+    })
+}
+
+/// Whether the base64 digest matches the data.
+fn digest_matches((algorithm, expected): &(String, String), data: &[u8]) -> bool {
+    let actual = match algorithm.as_str() {
+        "SHA-256" => Sha256::digest(data).to_vec(),
+        "SHA-384" => Sha384::digest(data).to_vec(),
+        "SHA-512" => Sha512::digest(data).to_vec(),
+        _ => return false,
+    };
+    base64::decode(expected).map(|e| e == actual).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use zip::ZipArchive;
+
+    use super::*;
+
+    const HASH: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    #[test]
+    fn hash_lines_are_parsed() {
+        assert_eq!(parse_hash_line(&format!("{}  lib/app.jar", HASH)), Some((HASH, "lib/app.jar")));
+        assert_eq!(parse_hash_line(&format!("{} *lib/my app.jar\r", HASH)), Some((HASH, "lib/my app.jar")));
+        assert_eq!(parse_hash_line("2cf24dba  lib/app.jar"), None);
+        assert_eq!(parse_hash_line(HASH), None);
+    }
+
+    #[test]
+    fn missing_and_modified_files_are_reported() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        fs::write(root.join("good.jar"), "hello").unwrap();
+        fs::write(root.join("bad.jar"), "changed").unwrap();
+        let manifest = root.join(INTEGRITY_FILE);
+        fs::write(&manifest, format!("{0}  good.jar\n{0}  bad.jar\n{0}  gone.jar\n", HASH)).unwrap();
+
+        assert_eq!(verify_integrity(&manifest), Err(vec!["bad.jar is modified".to_string(),
+                                                          "gone.jar is missing".to_string()]));
+        fs::write(&manifest, format!("{}  good.jar\n", HASH)).unwrap();
+        assert_eq!(verify_integrity(&manifest), Ok(()));
+    }
+
+    /// A jar signed by jarsigner with an RSA key, its manifest having `Main-Class: app.Main`.
+    const SIGNED_JAR: &[u8] = include_bytes!("../tests/fixtures/signed.jar");
+
+    /// The SHA-256 fingerprint of the certificate `SIGNED_JAR` is signed with.
+    const SIGNER: &str = "D7:46:30:59:24:3E:5B:C2:13:BB:6F:E3:F2:81:FF:6F:23:24:C5:AE:6D:03:B9:11:BB:36:66:7A:7A:83:1B:91";
+
+    /// Write `SIGNED_JAR` to the file with the entries changed.
+    fn write_jar(path: &Path, change: impl Fn(&str, Vec<u8>) -> Vec<u8>) {
+        use std::io::Write;
+        use zip::ZipWriter;
+        use zip::write::FileOptions;
+        let mut jar = ZipArchive::new(Cursor::new(SIGNED_JAR)).unwrap();
+        let mut writer = ZipWriter::new(File::create(path).unwrap());
+        for i in 0..jar.len() {
+            let mut entry = jar.by_index(i).unwrap();
+            let name = entry.name().to_string();
+            let mut contents = vec![];
+            entry.read_to_end(&mut contents).unwrap();
+            writer.start_file(&name, FileOptions::default()).unwrap();
+            writer.write_all(&change(&name, contents)).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    fn replace(contents: Vec<u8>, from: &str, to: &str) -> Vec<u8> {
+        String::from_utf8(contents).unwrap().replace(from, to).into_bytes()
+    }
+
+    #[test]
+    fn jars_are_verified_against_their_signer() {
+        let temp = tempfile::tempdir().unwrap();
+        let jar = temp.path().join("signed.jar");
+        write_jar(&jar, |_, contents| contents);
+
+        assert_eq!(verify_jar(&jar, &[SIGNER.to_string()]), Ok(()));
+        assert_eq!(verify_jar(&jar, &[SIGNER.replace(':', "").to_lowercase()]), Ok(()));
+        let problems = verify_jar(&jar, &["00:11".to_string()]).unwrap_err();
+        assert_eq!(problems, vec![format!("META-INF/WHY.RSA is signed by {}, which is not one of trusted_signers", SIGNER)]);
+        assert!(verify_jar(&jar, &[]).is_err());
+    }
+
+    #[test]
+    fn tampered_manifest_fails_verification() {
+        let temp = tempfile::tempdir().unwrap();
+        let jar = temp.path().join("tampered.jar");
+        write_jar(&jar, |name, contents| match name {
+            MANIFEST_PATH => replace(contents, "Main-Class: app.Main", "Main-Class: app.Evil"),
+            _ => contents,
+        });
+
+        let problems = verify_jar(&jar, &[SIGNER.to_string()]).unwrap_err();
+        assert_eq!(problems, vec!["The manifest's main attributes were changed after signing".to_string()]);
+    }
+
+    #[test]
+    fn tampered_signature_file_fails_verification() {
+        let temp = tempfile::tempdir().unwrap();
+        let jar = temp.path().join("tampered.jar");
+        // The manifest changed, and the signature file digests of it updated to match, without signing it again
+        let manifest = replace(read_manifest_bytes(&mut ZipArchive::new(Cursor::new(SIGNED_JAR)).unwrap()).unwrap(),
+                               "Main-Class: app.Main", "Main-Class: app.Evil");
+        let sections = split_sections(&manifest);
+        let sf = format!("Signature-Version: 1.0\r\nSHA-256-Digest-Manifest: {}\r\n\
+                          SHA-256-Digest-Manifest-Main-Attributes: {}\r\n\r\n",
+                         base64::encode(Sha256::digest(&manifest)), base64::encode(Sha256::digest(sections[0])));
+        write_jar(&jar, |name, contents| match name {
+            MANIFEST_PATH => manifest.clone(),
+            "META-INF/WHY.SF" => sf.clone().into_bytes(),
+            _ => contents,
+        });
+
+        let problems = verify_jar(&jar, &[SIGNER.to_string()]).unwrap_err();
+        assert_eq!(problems, vec!["META-INF/WHY.RSA: the signature file was changed after signing".to_string()]);
+    }
+}
@@ -0,0 +1,79 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The parts of the startup that are timed, see [`time`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Phase {
+    /// Reading `launcher.ini` and the app's jars it points to.
+    Config,
+    /// Looking for a suitable Java installation.
+    JvmDiscovery,
+    /// `JNI_CreateJavaVM`, such as loading the Java runtime.
+    CreateJvm,
+    /// Loading the main class, which also runs its static initializers.
+    LoadMain,
+}
+
+impl Phase {
+    /// The system property the time is in.
+    fn property(&self) -> &'static str {
+        match self {
+            Phase::Config => "why.startup.configMs",
+            Phase::JvmDiscovery => "why.startup.jvmDiscoveryMs",
+            Phase::CreateJvm => "why.startup.createJvmMs",
+            Phase::LoadMain => "why.startup.loadMainMs",
+        }
+    }
+}
+
+/// The system property with the time from the launcher starting to the app's main method.
+const TOTAL_PROPERTY: &str = "why.startup.totalMs";
+
+/// When the launcher started, see [`start_timing`].
+static START: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// The time spent in each phase so far, in the order they were first timed.
+static TIMES: Mutex<Vec<(Phase, Duration)>> = Mutex::new(Vec::new());
+
+/// Start timing the startup, as early as possible.
+pub fn start_timing() {
+    if let Ok(mut start) = START.lock() {
+        start.get_or_insert_with(Instant::now);
+    }
+}
+
+/// Run the function, adding the time it took to the phase,
+/// so that a phase done several times is timed in total, such as creating the JVM again.
+pub fn time<T, F: FnOnce() -> T>(phase: Phase, f: F) -> T {
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    if let Ok(mut times) = TIMES.lock() {
+        match times.iter_mut().find(|(p, _)| *p == phase) {
+            Some((_, total)) => *total += elapsed,
+            None => times.push((phase, elapsed)),
+        }
+    }
+    result
+}
+
+/// The times so far as system properties for the app, in milliseconds, such as
+/// `why.startup.jvmDiscoveryMs`, with `why.startup.totalMs` the time since the launcher started.
+pub fn timing_properties() -> Vec<(String, String)> {
+    let mut properties: Vec<(String, String)> = TIMES.lock()
+        .map(|times| times.iter().map(|(p, d)| (p.property().to_string(), d.as_millis().to_string())).collect())
+        .unwrap_or_default();
+    if let Some(start) = START.lock().ok().and_then(|s| *s) {
+        properties.push((TOTAL_PROPERTY.to_string(), start.elapsed().as_millis().to_string()));
+    }
+    properties
+}
+
+/// Log how long each phase of the startup took so far.
+pub fn log_timings() {
+    let properties = timing_properties();
+    let breakdown: Vec<String> = properties.iter()
+        .map(|(property, ms)| format!("{} {}ms", property.trim_start_matches("why.startup.").trim_end_matches("Ms"), ms))
+        .collect();
+    info!("startup", "Startup took: {}", breakdown.join(", "));
+}
@@ -0,0 +1,789 @@
+use std::cmp::Ordering;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::{process, thread};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use ed25519_dalek::{Signature, VerifyingKey};
+use sha2::{Digest, Sha256};
+use sysinfo::{Pid, System, SystemExt};
+use walkdir::WalkDir;
+use zip::ZipArchive;
+
+use crate::display_handler::{ask, Choice, is_headless, notify, open_url, progress, ProgressHandle};
+use crate::download_handler::{download_to, fetch, is_https};
+use crate::file_handler::{get_app_name, get_user_data_dir};
+use crate::install_handler::get_app_image;
+use crate::launch_config::LauncherConfig;
+use crate::locale_handler::{text, text_with};
+use crate::signature_handler::{file_sha256, SignatureCheck, verify_jar};
+use crate::zip_handler::close_zips;
+
+/// The public key update packages must be signed with, as base64 of the 32 bytes of an Ed25519 key,
+/// built into the launcher with `WHY_UPDATE_PUBLIC_KEY`. It takes precedence over `update_public_key`.
+const BUILT_IN_PUBLIC_KEY: Option<&str> = option_env!("WHY_UPDATE_PUBLIC_KEY");
+
+/// Written to the staged update once it is complete, holding its version.
+const STAGED_VERSION_FILE: &str = ".version";
+
+/// Written to the backup of an applied update until its first start is confirmed, holding its version.
+const PENDING_FILE: &str = ".pending";
+
+/// Written to the backup of an applied update until Java is started with it, so that a launch ending before,
+/// such as for a setup command or into an elevated launcher, is not taken for a failed start.
+const UNTRIED_FILE: &str = ".untried";
+
+/// Lists the files of the app image an applied update replaced or added, one per line.
+const APPLIED_FILES: &str = ".files";
+
+/// Names files of the app image moved into the backup to be removed later, see [`discard`].
+const DISCARDED_PREFIX: &str = ".discarded.";
+
+/// How long the launcher waits for an update in progress before asking the user whether to keep waiting.
+const LOCK_WAIT: Duration = Duration::from_secs(5);
+
+/// How long the launcher waits for an update in progress while headless, with nobody to ask.
+const HEADLESS_LOCK_WAIT: Duration = Duration::from_secs(5 * 60);
+
+/// How often the launcher looks whether the update lock was released.
+const LOCK_POLL: Duration = Duration::from_millis(250);
+
+/// How old an update lock without a process id may be before it counts as left behind by a crashed updater.
+const STALE_LOCK_AGE: Duration = Duration::from_secs(10 * 60);
+
+/// The newest version of the app, as the update feed describes it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Release {
+    pub version: String,
+    /// The page to download it from, or about it.
+    pub url: Option<String>,
+    /// The update package, a zip of the files of the app image that changed.
+    pub download: Option<String>,
+    /// The SHA-256 of the update package, as hex.
+    pub sha256: Option<String>,
+    /// The Ed25519 signature of the update package, as base64.
+    pub signature: Option<String>,
+}
+
+/// Check for a newer version of the app on a thread of its own, so that the launch does not wait for it,
+/// if `update_url` and `app_version` are set and the last check was at least `update_check_hours` ago.
+/// The user is asked whether to open the release page of a newer version, or notified of it if the feed
+/// has none or while headless. With `auto_update` the version's update package is downloaded and staged
+/// instead, to be applied by [`apply_staged_update`] on the next start.
+pub fn start_update_check(config: &LauncherConfig) {
+    let (url, current) = match (&config.update_url, &config.app_version) {
+        (Some(url), Some(current)) if !url.is_empty() => (url.clone(), current.clone()),
+        (Some(_), None) => {
+            info!("launcher", "Not checking for updates, app_version is not set");
+            return;
+        }
+        _ => return,
+    };
+    if !is_https(&url) {
+        info!("launcher", "Not checking for updates, update_url {} is not an https URL", url);
+        return;
+    }
+    let (auto_update, signature_check) = (config.auto_update, config.verify_signatures);
+    let trusted_signers = config.trusted_signers.clone();
+    let public_key = BUILT_IN_PUBLIC_KEY.map(str::to_string).or_else(|| config.update_public_key.clone());
+    let interval = Duration::from_secs(config.update_check_hours.max(0) as u64 * 60 * 60);
+    if !is_check_due(interval) {
+        debug!("launcher", "Checked for updates less than {:?} ago", interval);
+        return;
+    }
+
+    thread::spawn(move || {
+        let release = match fetch(&url).and_then(|feed| parse_feed(&feed)) {
+            Some(release) => release,
+            None => {
+                info!("launcher", "Failed to read the update feed {}", url);
+                return;
+            }
+        };
+        record_check();
+        if compare_versions(&release.version, &current) != Ordering::Greater {
+            debug!("launcher", "The app is up to date, {} is the newest version", release.version);
+            return;
+        }
+        info!("launcher", "Version {} of the app is available, this is {}", release.version, current);
+        if auto_update && release.download.is_some() {
+            match stage_update(&release, public_key.as_deref(), signature_check, &trusted_signers) {
+                Ok(_) => {
+                    notify(&text_with("update.staged", &[&release.version]));
+                    return;
+                }
+                Err(e) => info!("launcher", "Failed to stage version {}: {}", release.version, e),
+            }
+        }
+        let msg = text_with("update.available", &[&release.version, &current]);
+        match &release.url {
+            Some(page) if !is_headless() => {
+                if ask(&msg, &[Choice::OpenReleasePage, Choice::Later]) == Choice::OpenReleasePage {
+                    open_url(page);
+                }
+            }
+            _ => notify(&msg),
+        }
+    });
+}
+
+/// The file the time of the last update check is kept in.
+fn check_file() -> Option<PathBuf> {
+    Some(get_user_data_dir()?.join(get_app_name() + ".update-check"))
+}
+
+fn is_check_due(interval: Duration) -> bool {
+    let last = check_file()
+        .and_then(|f| fs::read_to_string(f).ok())
+        .and_then(|t| t.trim().parse::<u64>().ok())
+        .map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+    match last.map(|l| SystemTime::now().duration_since(l)) {
+        Some(Ok(since)) => since >= interval,
+        // Never checked, or the clock was turned back
+        _ => true,
+    }
+}
+
+fn record_check() {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    if let Some(file) = check_file() {
+        let written = file.parent().map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&file, now.to_string()));
+        if let Err(e) = written {
+            debug!("launcher", "Failed to record the update check in {:?}: {}", file, e);
+        }
+    }
+}
+
+/// Where an update of the app image is staged, and where the files it replaces are kept until it has
+/// started, beside the app image as `.<image>.update` and `.<image>.previous`.
+struct UpdateDirs {
+    image: PathBuf,
+    staging: PathBuf,
+    backup: PathBuf,
+}
+
+fn update_dirs() -> Option<UpdateDirs> {
+    let image = get_app_image()?;
+    let name = image.file_name()?.to_string_lossy().to_string();
+    let parent = image.parent()?;
+    Some(UpdateDirs {
+        staging: parent.join(format!(".{}.update", name)),
+        backup: parent.join(format!(".{}.previous", name)),
+        image,
+    })
+}
+
+/// The trimmed content of a marker file, if it has any.
+fn read_marker(file: &Path) -> Option<String> {
+    Some(fs::read_to_string(file).ok()?.trim().to_string()).filter(|m| !m.is_empty())
+}
+
+/// Download the release's update package over https, check its signature with the public key, its SHA-256
+/// if the feed has one and, with `verify_signatures`, the signatures of its jars, and stage it beside
+/// the app image. The files are extracted from the bytes that were checked, and each is checked again
+/// once written.
+fn stage_update(release: &Release, public_key: Option<&str>, signature_check: SignatureCheck,
+                trusted_signers: &[String]) -> Result<(), String> {
+    let public_key = public_key.ok_or("no update_public_key is set to check update packages with")?;
+    let (download, signature) = match (&release.download, &release.signature) {
+        (Some(download), Some(signature)) => (download, signature),
+        _ => return Err("the feed has no signature for the update package".to_string()),
+    };
+    if !is_https(download) {
+        return Err(format!("the update package {} is not at an https URL", download));
+    }
+    let dirs = update_dirs().ok_or("the app image was not found")?;
+    let version_file = dirs.staging.join(STAGED_VERSION_FILE);
+    if read_marker(&version_file).as_deref() == Some(release.version.as_str()) {
+        debug!("launcher", "Version {} is already staged", release.version);
+        return Ok(());
+    }
+    let package = get_user_data_dir().ok_or("there is no data dir")?
+        .join(format!("{}-{}.update.zip", get_app_name(), release.version));
+
+    info!("launcher", "Downloading version {} from {}", release.version, download);
+    let staged = download_to(download, &package)
+        .and_then(|_| fs::read(&package).map_err(|e| e.to_string()))
+        .and_then(|bytes| {
+            verify_package(&bytes, public_key, signature)?;
+            if let Some(sha256) = &release.sha256 {
+                check_sha256(&bytes, sha256)?;
+            }
+            extract_package(&bytes, &dirs.staging).map_err(|e| e.to_string())
+        })
+        .and_then(|files| check_extracted(&files))
+        .and_then(|_| check_signatures(&dirs.staging, signature_check, trusted_signers))
+        // Last, as it marks the staged update as complete
+        .and_then(|_| fs::write(&version_file, &release.version).map_err(|e| e.to_string()));
+    let _ = fs::remove_file(&package);
+    match staged {
+        Ok(_) => info!("launcher", "Staged version {} in {:?}", release.version, dirs.staging),
+        Err(_) => {
+            let _ = fs::remove_dir_all(&dirs.staging);
+        }
+    }
+    staged
+}
+
+/// Check the Ed25519 signature of the update package, both as base64, against the public key.
+fn verify_package(package: &[u8], public_key: &str, signature: &str) -> Result<(), String> {
+    let key: [u8; 32] = base64::decode(public_key.trim()).ok()
+        .and_then(|k| k.try_into().ok())
+        .ok_or("update_public_key is not the base64 of an Ed25519 public key")?;
+    let key = VerifyingKey::from_bytes(&key).map_err(|e| format!("update_public_key is invalid: {}", e))?;
+    let signature: [u8; 64] = base64::decode(signature.trim()).ok()
+        .and_then(|s| s.try_into().ok())
+        .ok_or("the signature of the update package is not the base64 of an Ed25519 signature")?;
+    key.verify_strict(package, &Signature::from_bytes(&signature))
+        .map_err(|_| "the update package is not signed with update_public_key".to_string())
+}
+
+fn check_sha256(package: &[u8], expected: &str) -> Result<(), String> {
+    let actual = format!("{:x}", Sha256::digest(package));
+    if actual.eq_ignore_ascii_case(expected.trim()) {
+        Ok(())
+    } else {
+        Err(format!("the SHA-256 of the update package is {}, not {}", actual, expected))
+    }
+}
+
+/// Extract the update package into the staging dir, refusing entries that would end up outside of it.<br>
+/// Returns the files written and the SHA-256 of what was written to each.
+fn extract_package(package: &[u8], staging: &Path) -> io::Result<Vec<(PathBuf, String)>> {
+    let _ = fs::remove_dir_all(staging);
+    let mut files = vec![];
+    let mut zip = ZipArchive::new(Cursor::new(package))?;
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i)?;
+        let target = match entry.enclosed_name() {
+            Some(name) => staging.join(name),
+            None => return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                              format!("the entry {} is outside of the app image", entry.name()))),
+        };
+        if entry.is_dir() {
+            fs::create_dir_all(&target)?;
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut content = vec![];
+        io::copy(&mut entry, &mut content)?;
+        File::create(&target)?.write_all(&content)?;
+        #[cfg(unix)]
+        if let Some(mode) = entry.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&target, fs::Permissions::from_mode(mode))?;
+        }
+        files.push((target, format!("{:x}", Sha256::digest(&content))));
+    }
+    Ok(files)
+}
+
+/// Check that each file extracted from the signed package still has the content it was extracted with,
+/// so that none was changed in the staging dir while the package was being extracted.
+fn check_extracted(files: &[(PathBuf, String)]) -> Result<(), String> {
+    for (file, sha256) in files {
+        match file_sha256(file) {
+            Ok(actual) if &actual == sha256 => {}
+            Ok(_) => return Err(format!("{:?} changed after it was extracted", file)),
+            Err(e) => return Err(format!("{:?} could not be read: {}", file, e)),
+        }
+    }
+    Ok(())
+}
+
+/// With `verify_signatures` set, each jar of the update must be signed by one of `trusted_signers` and intact.
+fn check_signatures(staging: &Path, signature_check: SignatureCheck, trusted_signers: &[String]) -> Result<(), String> {
+    if signature_check == SignatureCheck::Off {
+        return Ok(());
+    }
+    let jars: Vec<PathBuf> = WalkDir::new(staging).into_iter()
+        .flatten()
+        .map(|e| e.into_path())
+        .filter(|p| p.extension().map_or(false, |e| e.eq_ignore_ascii_case("jar")))
+        .collect();
+    let checked = jars.iter().try_for_each(|jar| verify_jar(jar, trusted_signers)
+        .map_err(|problems| format!("{:?} failed verification: {}", jar, problems.join(", "))));
+    // Otherwise they would be kept open, so that they could not be moved into the app image on Windows
+    close_zips();
+    checked
+}
+
+/// Apply the update staged by an earlier launch, or roll back an applied update whose start was not
+/// confirmed with [`confirm_update`], such as when its JVM crashed.<br>
+/// Done before the config is read, as the update may change it, holding the [`UpdateLock`]. The files
+/// the update replaces are kept until it has started. Returns what to tell the user, if anything.
+pub fn apply_staged_update() -> Option<String> {
+    let dirs = update_dirs()?;
+    if !dirs.backup.exists() && !dirs.staging.join(STAGED_VERSION_FILE).exists() {
+        return None;
+    }
+    let _lock = match UpdateLock::take() {
+        Some(lock) => lock,
+        None => {
+            info!("launcher", "Another update is in progress, not applying the staged one");
+            return None;
+        }
+    };
+
+    if let Some(version) = read_marker(&dirs.backup.join(UNTRIED_FILE)) {
+        info!("launcher", "Version {} has not been started yet", version);
+        return None;
+    }
+    if let Some(version) = read_marker(&dirs.backup.join(PENDING_FILE)) {
+        info!("launcher", "Version {} did not start, rolling it back", version);
+        roll_back(&dirs);
+        return Some(text_with("update.rolled_back", &[&version]));
+    }
+    // Kept from an update that started
+    if dirs.backup.exists() {
+        let _ = fs::remove_dir_all(&dirs.backup);
+    }
+
+    let version = read_marker(&dirs.staging.join(STAGED_VERSION_FILE))?;
+    info!("launcher", "Applying version {} to {:?}", version, dirs.image);
+    let mut progress = progress(&text_with("update.applying", &[&version]));
+    let applied = apply(&dirs, &version, &mut progress);
+    progress.close();
+    let _ = fs::remove_dir_all(&dirs.staging);
+    match applied {
+        Ok(_) => None,
+        Err(e) => {
+            info!("launcher", "Failed to apply version {}: {}", version, e);
+            roll_back(&dirs);
+            Some(text_with("update.apply_failed", &[&version]))
+        }
+    }
+}
+
+/// Move the staged files into the app image, each file they replace into the backup first, showing
+/// how many are done. Stops if the user cancels, the update is then rolled back.
+fn apply(dirs: &UpdateDirs, version: &str, progress: &mut ProgressHandle) -> io::Result<()> {
+    fs::create_dir_all(&dirs.backup)?;
+    fs::write(dirs.backup.join(PENDING_FILE), version)?;
+    let mut applied = File::create(dirs.backup.join(APPLIED_FILES))?;
+
+    let staged: Vec<PathBuf> = WalkDir::new(&dirs.staging).min_depth(1).into_iter()
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter(|e| !e.file_type().is_dir())
+        .filter_map(|e| e.path().strip_prefix(&dirs.staging).ok().map(Path::to_path_buf))
+        .filter(|relative| relative != Path::new(STAGED_VERSION_FILE))
+        .collect();
+    for (i, relative) in staged.iter().enumerate() {
+        if progress.is_cancelled() {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled by the user"));
+        }
+        progress.update(Some((i * 100 / staged.len()) as u8), &relative.display().to_string());
+        let (target, backup) = (dirs.image.join(relative), dirs.backup.join(relative));
+        // Renaming works for the running launcher too, even on Windows
+        if target.symlink_metadata().is_ok() {
+            if let Some(parent) = backup.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::rename(&target, &backup)?;
+        }
+        // Once its backup is made, so that a rollback can tell the files the update added
+        writeln!(applied, "{}", relative.display())?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(dirs.staging.join(relative), &target)?;
+    }
+    fs::write(dirs.backup.join(UNTRIED_FILE), version)
+}
+
+/// Put the files an applied update replaced back into the app image, and remove those it added.
+fn roll_back(dirs: &UpdateDirs) {
+    let listed = fs::read_to_string(dirs.backup.join(APPLIED_FILES)).unwrap_or_default();
+    let added = listed.lines()
+        .filter(|l| !l.is_empty())
+        .filter(|relative| dirs.backup.join(relative).symlink_metadata().is_err());
+    for relative in added {
+        discard(&dirs.image.join(relative), &dirs.backup);
+    }
+
+    let replaced: Vec<PathBuf> = WalkDir::new(&dirs.backup).min_depth(1).into_iter()
+        .flatten()
+        .filter(|e| !e.file_type().is_dir())
+        .filter_map(|e| e.path().strip_prefix(&dirs.backup).ok().map(Path::to_path_buf))
+        .filter(|relative| ![PENDING_FILE, UNTRIED_FILE, APPLIED_FILES].iter().any(|m| relative == Path::new(m)))
+        .filter(|relative| !relative.to_string_lossy().starts_with(DISCARDED_PREFIX))
+        .collect();
+    for relative in replaced {
+        let target = dirs.image.join(&relative);
+        discard(&target, &dirs.backup);
+        if let Err(e) = fs::rename(dirs.backup.join(&relative), &target) {
+            info!("launcher", "Failed to restore {:?}: {}", target, e);
+        }
+    }
+
+    // What cannot be removed, such as the discarded running launcher on Windows, is removed by a later
+    // launch finding the backup
+    let _ = fs::remove_file(dirs.backup.join(PENDING_FILE));
+    let _ = fs::remove_dir_all(&dirs.backup);
+}
+
+/// Remove a file of the app image, or move it into the backup if it cannot be, as the running launcher
+/// cannot be removed on Windows.
+fn discard(file: &Path, backup: &Path) {
+    match fs::remove_file(file) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            let name = file.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            if let Err(e) = fs::rename(file, backup.join(format!("{}{}", DISCARDED_PREFIX, name))) {
+                info!("launcher", "Failed to remove {:?}: {}", file, e);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Mark an applied update as tried, just before Java is started with it, so that it is rolled back unless
+/// its start is confirmed with [`confirm_update`].
+pub fn mark_update_tried() {
+    if let Some(dirs) = update_dirs() {
+        let untried = dirs.backup.join(UNTRIED_FILE);
+        if untried.exists() {
+            let _ = fs::remove_file(untried);
+        }
+    }
+}
+
+/// Confirm that an applied update started, keeping it from being rolled back, see [`apply_staged_update`].
+pub fn confirm_update() {
+    if let Some(dirs) = update_dirs() {
+        let pending = dirs.backup.join(PENDING_FILE);
+        if let Some(version) = read_marker(&pending) {
+            info!("launcher", "Version {} started", version);
+            let _ = fs::remove_file(pending);
+        }
+    }
+}
+
+/// Roll back an applied update if the launch failed before it was confirmed with [`confirm_update`],
+/// so that the next start uses the previous version. One Java was not started with yet is kept.
+pub fn roll_back_failed_update() {
+    if let Some(dirs) = update_dirs() {
+        if dirs.backup.join(UNTRIED_FILE).exists() {
+            return;
+        }
+        if let Some(version) = read_marker(&dirs.backup.join(PENDING_FILE)) {
+            // Otherwise rolled back on the next start
+            let _lock = match UpdateLock::take() {
+                Some(lock) => lock,
+                None => return,
+            };
+            info!("launcher", "Version {} failed to start, rolling it back", version);
+            roll_back(&dirs);
+            notify(&text_with("update.rolled_back", &[&version]));
+        }
+    }
+}
+
+/// Held while the files of the app image change, by the launcher applying an update or by an external
+/// updater, so that the app is not started from a half updated app image, see [`wait_for_update`].<br>
+/// It is the file `<app>.update.lock` in the data dir, created exclusively and holding the process id of
+/// its holder, and is released when dropped.
+pub struct UpdateLock(PathBuf);
+
+impl UpdateLock {
+    /// Take the lock, unless it is held.
+    pub fn take() -> Option<UpdateLock> {
+        let file = lock_file()?;
+        if is_update_locked() {
+            return None;
+        }
+        if let Some(parent) = file.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let mut lock = OpenOptions::new().write(true).create_new(true).open(&file).ok()?;
+        let _ = write!(lock, "{}", process::id());
+        Some(UpdateLock(file))
+    }
+}
+
+impl Drop for UpdateLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn lock_file() -> Option<PathBuf> {
+    Some(get_user_data_dir()?.join(get_app_name() + ".update.lock"))
+}
+
+/// Whether an update holds the lock. A lock held by a process that no longer runs is removed, as is one
+/// without a process id that is older than [`STALE_LOCK_AGE`].
+fn is_update_locked() -> bool {
+    let file = match lock_file() {
+        Some(file) if file.exists() => file,
+        _ => return false,
+    };
+    let held = match fs::read_to_string(&file).ok().and_then(|pid| pid.trim().parse::<usize>().ok()) {
+        Some(pid) => System::new().refresh_process(Pid::from(pid)),
+        // Just created, or not by the launcher
+        None => fs::metadata(&file).and_then(|m| m.modified()).ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .map_or(true, |age| age < STALE_LOCK_AGE),
+    };
+    if !held {
+        info!("launcher", "Removing the stale update lock {:?}", file);
+        let _ = fs::remove_file(&file);
+    }
+    held
+}
+
+/// Wait for an update in progress to finish, asking the user whether to keep waiting each time
+/// [`LOCK_WAIT`] passes, or waiting up to [`HEADLESS_LOCK_WAIT`] while headless.<br>
+/// Returns whether the launch may go on.
+pub fn wait_for_update() -> bool {
+    if !is_update_locked() {
+        return true;
+    }
+    info!("launcher", "An update of the app is in progress, waiting for it to finish");
+    let timeout = if is_headless() { HEADLESS_LOCK_WAIT } else { LOCK_WAIT };
+    loop {
+        let start = Instant::now();
+        while start.elapsed() < timeout {
+            thread::sleep(LOCK_POLL);
+            if !is_update_locked() {
+                return true;
+            }
+        }
+        if ask(&text("update.in_progress"), &[Choice::Retry, Choice::Quit]) != Choice::Retry {
+            info!("launcher", "Not waiting for the update any longer");
+            return false;
+        }
+    }
+}
+
+/// The release an update feed describes, either JSON with its `version` and `url`, such as
+/// `{"version": "1.2.0", "url": "https://example.com/releases/1.2.0"}`, and optionally the `download`
+/// of its update package and the package's `sha256`, or a Sparkle appcast, of which the first item is taken.
+pub fn parse_feed(feed: &str) -> Option<Release> {
+    let feed = feed.trim_start();
+    if feed.starts_with('<') {
+        parse_appcast(feed)
+    } else {
+        Some(Release {
+            version: json_string_field(feed, "version")?,
+            url: json_string_field(feed, "url"),
+            download: json_string_field(feed, "download"),
+            sha256: json_string_field(feed, "sha256"),
+            signature: json_string_field(feed, "signature"),
+        })
+    }
+}
+
+/// The string value of the first field with the key in the JSON text, wherever it is nested.
+fn json_string_field(json: &str, key: &str) -> Option<String> {
+    let quoted = format!("\"{}\"", key);
+    let mut rest = json;
+    while let Some(i) = rest.find(&quoted) {
+        rest = &rest[i + quoted.len()..];
+        let value = match rest.trim_start().strip_prefix(':') {
+            Some(value) => value.trim_start(),
+            // The key was a value
+            None => continue,
+        };
+        let mut chars = value.strip_prefix('"')?.chars();
+        let mut string = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => return Some(string),
+                '\\' => match chars.next()? {
+                    'n' => string.push('\n'),
+                    't' => string.push('\t'),
+                    'u' => {
+                        let hex: String = chars.by_ref().take(4).collect();
+                        string.push(u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32).unwrap_or('?'));
+                    }
+                    escaped => string.push(escaped),
+                },
+                c => string.push(c),
+            }
+        }
+        return None;
+    }
+    None
+}
+
+/// The first item of a Sparkle appcast, its `sparkle:shortVersionString` or else `sparkle:version`,
+/// as an attribute of its enclosure or an element, and its `link`.
+fn parse_appcast(xml: &str) -> Option<Release> {
+    let start = xml.find("<item")?;
+    let item = &xml[start..xml[start..].find("</item>").map_or(xml.len(), |end| start + end)];
+    let version = ["sparkle:shortVersionString", "sparkle:version"].iter()
+        .find_map(|name| xml_element(item, name).or_else(|| xml_attribute(item, name)))?;
+    Some(Release { version, url: xml_element(item, "link"), download: None, sha256: None,
+                   signature: None })
+}
+
+fn xml_element(xml: &str, name: &str) -> Option<String> {
+    let open = format!("<{}>", name);
+    let start = xml.find(&open)? + open.len();
+    let end = xml[start..].find(&format!("</{}>", name))? + start;
+    Some(xml_unescape(xml[start..end].trim())).filter(|v| !v.is_empty())
+}
+
+fn xml_attribute(xml: &str, name: &str) -> Option<String> {
+    let prefix = format!("{}=", name);
+    let start = xml.find(&prefix)? + prefix.len();
+    let quote = xml[start..].chars().next().filter(|q| *q == '"' || *q == '\'')?;
+    let end = xml[start + 1..].find(quote)? + start + 1;
+    Some(xml_unescape(&xml[start + 1..end])).filter(|v| !v.is_empty())
+}
+
+fn xml_unescape(text: &str) -> String {
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Compare versions such as `1.10.2` and `v1.9`: by their dot separated numbers, missing ones counting
+/// as 0, then a pre-release after a `-` being older than the release, such as `2.0-beta1` before `2.0`.
+/// Build metadata after a `+` is ignored.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let split = |v: &str| {
+        let v = v.trim().trim_start_matches(|c| c == 'v' || c == 'V');
+        let v = v.split('+').next().unwrap_or(v);
+        match v.split_once('-') {
+            Some((release, pre)) => (release.to_string(), Some(pre.to_string())),
+            None => (v.to_string(), None),
+        }
+    };
+    let ((release_a, pre_a), (release_b, pre_b)) = (split(a), split(b));
+    let parts_a: Vec<&str> = release_a.split('.').collect();
+    let parts_b: Vec<&str> = release_b.split('.').collect();
+    for i in 0..parts_a.len().max(parts_b.len()) {
+        let (part_a, part_b) = (parts_a.get(i).copied().unwrap_or("0"), parts_b.get(i).copied().unwrap_or("0"));
+        let order = match (part_a.parse::<u64>(), part_b.parse::<u64>()) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            _ => part_a.cmp(part_b),
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    match (pre_a, pre_b) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => compare_versions(&a, &b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_are_compared_by_number() {
+        assert_eq!(compare_versions("1.10.0", "1.9.3"), Ordering::Greater);
+        assert_eq!(compare_versions("v2.0", "2.0.0"), Ordering::Equal);
+        assert_eq!(compare_versions("1.2", "1.2.1"), Ordering::Less);
+        assert_eq!(compare_versions("5.4.1.00", "5.4.0.99"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0+build.5", "1.0"), Ordering::Equal);
+    }
+
+    #[test]
+    fn pre_releases_are_older() {
+        assert_eq!(compare_versions("2.0-beta1", "2.0"), Ordering::Less);
+        assert_eq!(compare_versions("2.0-beta2", "2.0-beta1"), Ordering::Greater);
+        assert_eq!(compare_versions("2.0-rc.2", "2.0-rc.10"), Ordering::Less);
+        assert_eq!(compare_versions("2.1-alpha", "2.0"), Ordering::Greater);
+    }
+
+    #[test]
+    fn json_feed_is_parsed() {
+        let feed = r#"{"name": "version", "release": {"version" : "1.2.0", "url": "https:\/\/example.com\/1.2.0"}}"#;
+        assert_eq!(parse_feed(feed), Some(Release {
+            version: "1.2.0".to_string(),
+            url: Some("https://example.com/1.2.0".to_string()),
+            download: None,
+            sha256: None,
+            signature: None,
+        }));
+        assert_eq!(parse_feed(r#"{"url": "https://example.com"}"#), None);
+    }
+
+    #[test]
+    fn appcast_is_parsed() {
+        let appcast = r#"<?xml version="1.0"?>
+            <rss xmlns:sparkle="http://www.andymatuschak.org/xml-namespaces/sparkle" version="2.0">
+              <channel>
+                <title>App</title>
+                <link>https://example.com</link>
+                <item>
+                  <title>Version 3.1</title>
+                  <link>https://example.com/releases?v=3.1&amp;os=mac</link>
+                  <enclosure url="https://example.com/App-3.1.zip" sparkle:version="310"
+                             sparkle:shortVersionString="3.1"/>
+                </item>
+                <item>
+                  <sparkle:version>3.0</sparkle:version>
+                </item>
+              </channel>
+            </rss>"#;
+        assert_eq!(parse_feed(appcast), Some(Release {
+            version: "3.1".to_string(),
+            url: Some("https://example.com/releases?v=3.1&os=mac".to_string()),
+            download: None,
+            sha256: None,
+            signature: None,
+        }));
+    }
+
+    #[test]
+    fn json_feed_has_update_package() {
+        let feed = r#"{"version": "1.3", "download": "https://example.com/1.3.zip", "sha256": "ab12",
+                       "signature": "c2lnbmVk"}"#;
+        let release = parse_feed(feed).unwrap();
+        assert_eq!(release.download.as_deref(), Some("https://example.com/1.3.zip"));
+        assert_eq!(release.sha256.as_deref(), Some("ab12"));
+        assert_eq!(release.signature.as_deref(), Some("c2lnbmVk"));
+    }
+
+    #[test]
+    fn update_package_signature_is_verified() {
+        // The second test vector of RFC 8032, signing the byte 0x72
+        let public_key = "PUAXw+hDiVqStwqnTRt+vJyYLM8uxJaMwM1V8Sr0Zgw=";
+        let signature = "kqAJqfDUyrhyDoILX2QlQKKye1QWUD+Ps3YiI+vbadoIWsHkPhWZbkWPNhPQ8R2MOHsurrQwKu6wDSkWErsMAA==";
+        assert_eq!(verify_package(&[0x72], public_key, signature), Ok(()));
+        assert!(verify_package(&[0x73], public_key, signature).is_err());
+        assert!(verify_package(&[0x72], "11qYAYKxCrfVS/7TyWQHOg7hcvPapiMlrwIaaPcHURo=", signature).is_err());
+        assert!(verify_package(&[0x72], public_key, "c2lnbmVk").is_err());
+    }
+
+    #[test]
+    fn applied_update_is_rolled_back() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        let dirs = UpdateDirs {
+            image: root.join("app"),
+            staging: root.join(".app.update"),
+            backup: root.join(".app.previous"),
+        };
+        for (dir, file, content) in [(&dirs.image, "app.jar", "1.0"), (&dirs.image, "launcher.ini", "1.0"),
+                                     (&dirs.staging, "app.jar", "2.0"), (&dirs.staging, "lib/new.jar", "2.0")] {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+
+        crate::display_handler::set_headless(true);
+        apply(&dirs, "2.0", &mut progress("Installing")).unwrap();
+        assert_eq!(fs::read_to_string(dirs.image.join("app.jar")).unwrap(), "2.0");
+        assert_eq!(fs::read_to_string(dirs.image.join("lib/new.jar")).unwrap(), "2.0");
+        assert_eq!(read_marker(&dirs.backup.join(PENDING_FILE)).as_deref(), Some("2.0"));
+        assert_eq!(read_marker(&dirs.backup.join(UNTRIED_FILE)).as_deref(), Some("2.0"));
+
+        roll_back(&dirs);
+        assert_eq!(fs::read_to_string(dirs.image.join("app.jar")).unwrap(), "1.0");
+        assert_eq!(fs::read_to_string(dirs.image.join("launcher.ini")).unwrap(), "1.0");
+        assert!(!dirs.image.join("lib/new.jar").exists());
+        assert!(!dirs.backup.exists());
+    }
+}
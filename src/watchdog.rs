@@ -0,0 +1,105 @@
+use std::fs;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::display_handler::{Answer, is_headless, question};
+use crate::exit_handler::{exit, ExitCode};
+use crate::file_handler::get_user_data_dir;
+use crate::locale_handler::{text, text_with};
+use crate::message;
+
+/// Set once the main class has been loaded, ending the watch.
+static STARTUP_COMPLETE: AtomicBool = AtomicBool::new(false);
+
+/// What the launcher is currently doing, for the diagnostics.
+static STAGE: Mutex<String> = Mutex::new(String::new());
+
+/// Record what the launcher is doing, such as which JVM it is creating.
+pub fn set_stage(stage: String) {
+    info!("startup", "Startup stage: {}", stage);
+    if let Ok(mut s) = STAGE.lock() {
+        *s = stage;
+    }
+}
+
+/// Mark the startup as done, the app's main method is about to run.
+pub fn startup_complete() {
+    STARTUP_COMPLETE.store(true, Ordering::SeqCst);
+}
+
+/// End the watch without the startup being done, such as when the launch failed and the user is told why,
+/// or the app runs in a separate process instead.
+pub fn stop_watchdog() {
+    STARTUP_COMPLETE.store(true, Ordering::SeqCst);
+}
+
+/// Ends the watch when dropped, so that it ends on whichever path the launch ends, see [`start_watchdog`].
+pub struct Watchdog;
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        stop_watchdog();
+    }
+}
+
+/// Watch for the startup taking longer than `timeout`, such as when Java is stuck on a network
+/// drive or a broken font cache. Each time the timeout passes without the main class being loaded,
+/// the user is asked whether to keep waiting, write diagnostics and keep waiting, or quit.<br>
+/// The watch lasts until [`startup_complete`] or [`stop_watchdog`], or until the returned [`Watchdog`] is dropped.
+pub fn start_watchdog(timeout: Duration) -> Watchdog {
+    let start = Instant::now();
+    thread::spawn(move || {
+        loop {
+            thread::sleep(timeout);
+            if STARTUP_COMPLETE.load(Ordering::SeqCst) {
+                return;
+            }
+
+            // Nobody can answer, keep waiting
+            if is_headless() {
+                info!("startup", "Startup is taking longer than expected, at: {}",
+                         STAGE.lock().map(|s| s.clone()).unwrap_or_default());
+                continue;
+            }
+
+            let answer = question(&text("startup.slow"));
+
+            // The startup may have finished while the dialog was open
+            if STARTUP_COMPLETE.load(Ordering::SeqCst) {
+                return;
+            }
+
+            match answer {
+                Answer::Yes => {}
+                Answer::No => write_diagnostics(start.elapsed()),
+                Answer::Cancel => {
+                    info!("startup", "Startup cancelled by the user");
+                    exit(ExitCode::Cancelled.code());
+                }
+            }
+        }
+    });
+    Watchdog
+}
+
+/// Write what the launcher was doing to a file in the user data dir, and tell the user where.
+fn write_diagnostics(elapsed: Duration) {
+    let stage = STAGE.lock().map(|s| s.clone()).unwrap_or_default();
+    let report = format!("Launcher version: {}\n\
+    Stuck at: {}\n\
+    Time since startup: {}s\n",
+                         env!("CARGO_PKG_VERSION"), stage, elapsed.as_secs());
+
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    if let Some(dir) = get_user_data_dir() {
+        let path = dir.join(format!("startup-diagnostics-{}.txt", time));
+        if fs::create_dir_all(&dir).and_then(|_| fs::write(&path, report.as_bytes())).is_ok() {
+            message(&text_with("startup.diagnostics_saved", &[&path.display().to_string()]));
+            return;
+        }
+    }
+
+    message(&text_with("startup.diagnostics_failed", &[&report]));
+}
@@ -0,0 +1,816 @@
+use std::fs::File;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use flate2::read::DeflateDecoder;
+use memmap2::Mmap;
+use zip::ZipArchive;
+
+use crate::manifest_handler::read_jar_manifest;
+use crate::locale_handler::text_with;
+use crate::message;
+
+/// The class of a module's `module-info.java`.
+const MODULE_INFO: &str = "module-info.class";
+
+/// The zip archives mapped so far, see [`map_zip`], `None` for those that could not be read.
+static MAPPED_ZIPS: Mutex<Vec<(PathBuf, Option<ZipBytes>)>> = Mutex::new(Vec::new());
+
+/// The zip archives opened so far, see [`open_zip`].
+static OPENED_ZIPS: Mutex<Vec<(PathBuf, Option<Zip>)>> = Mutex::new(Vec::new());
+
+/// Signatures of the records ending a zip archive.<br>
+/// See <https://pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT>, 4.3.14 to 4.3.16
+const EOCD_SIGNATURE: u32 = 0x06054b50;
+const ZIP64_EOCD_SIGNATURE: u32 = 0x06064b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x07064b50;
+const EOCD_LEN: usize = 22;
+const ZIP64_EOCD_LEN: usize = 56;
+const ZIP64_LOCATOR_LEN: usize = 20;
+
+/// Signatures and fixed sizes of the headers of an entry.
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+const CENTRAL_HEADER_LEN: usize = 46;
+const LOCAL_HEADER_LEN: usize = 30;
+
+/// The extra field holding the 64-bit sizes and offset of an entry of a Zip64 archive.
+const ZIP64_EXTRA_FIELD: u16 = 0x0001;
+
+/// An opened zip archive, read from memory.
+pub type Zip = ZipArchive<Cursor<ZipBytes>>;
+
+/// The contents of a zip archive, mapped into memory or else read into it.
+/// Cheap to clone, so that an opened archive can be shared.
+#[derive(Clone)]
+pub enum ZipBytes {
+    Mapped(Arc<Mmap>),
+    Read(Arc<Vec<u8>>),
+}
+
+impl AsRef<[u8]> for ZipBytes {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            ZipBytes::Mapped(m) => m.as_ref(),
+            ZipBytes::Read(r) => r.as_slice(),
+        }
+    }
+}
+
+/// Where the central directory of a zip archive is, as its end records say.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct CentralDirectory {
+    pub offset: u64,
+    pub size: u64,
+    pub entries: u64,
+    /// Whether the archive needed the Zip64 records, for more than 65535 entries or 4 GB
+    pub zip64: bool,
+    /// Where the end records start, which the central directory comes before
+    pub end: u64,
+}
+
+/// Open a zip archive such as a jar, reading its whole central directory.
+/// See [`read_zip_entry`] for when only a few entries are needed.<br>
+/// Each archive is only opened once per launch, later calls share it.
+pub fn open_zip(path: &Path) -> Option<Zip> {
+    if let Ok(opened) = OPENED_ZIPS.lock() {
+        if let Some((_, zip)) = opened.iter().find(|(p, _)| p == path) {
+            return zip.clone();
+        }
+    }
+
+    let zip = match ZipArchive::new(Cursor::new(map_zip(path)?)) {
+        Ok(zip) => Some(zip),
+        Err(e) => {
+            report_unreadable(path, &e.to_string());
+            None
+        }
+    };
+    if let Ok(mut opened) = OPENED_ZIPS.lock() {
+        opened.push((path.to_path_buf(), zip.clone()));
+    }
+    zip
+}
+
+/// Read a single entry of a zip archive, such as the manifest of a jar.<br>
+/// Unlike [`open_zip`] this does not parse the whole central directory, which for large jars
+/// takes most of the time spent looking at them, it only scans it for the entry's name.
+pub fn read_zip_entry(path: &Path, name: &str) -> Option<Vec<u8>> {
+    // Already parsed
+    let opened = OPENED_ZIPS.lock().ok()
+        .and_then(|o| o.iter().find(|(p, _)| p == path).map(|(_, zip)| zip.clone()));
+    if let Some(zip) = opened {
+        let mut bytes: Vec<u8> = vec![];
+        zip?.by_name(name).ok()?.read_to_end(&mut bytes).ok()?;
+        return Some(bytes);
+    }
+
+    let bytes = map_zip(path)?;
+    match find_entry(bytes.as_ref(), name) {
+        Ok(entry) => entry,
+        Err(e) => {
+            report_unreadable(path, &e);
+            None
+        }
+    }
+}
+
+/// Whether a zip archive has the named entry, without reading it, see [`read_zip_entry`].
+pub fn zip_has_entry(path: &Path, name: &str) -> bool {
+    let opened = OPENED_ZIPS.lock().ok()
+        .and_then(|o| o.iter().find(|(p, _)| p == path).map(|(_, zip)| zip.clone()));
+    if let Some(zip) = opened {
+        return zip.map_or(false, |mut zip| zip.by_name(name).is_ok());
+    }
+
+    let bytes = match map_zip(path) {
+        Some(bytes) => bytes,
+        None => return false,
+    };
+    match locate_entry(bytes.as_ref(), name) {
+        Ok(entry) => entry.is_some(),
+        Err(e) => {
+            report_unreadable(path, &e);
+            false
+        }
+    }
+}
+
+/// Map a zip archive into memory, or else read it into memory, checking its end records.
+/// Mapped, as reading through many small reads is slow on network drives and with some virus
+/// scanners. Each archive is only mapped once per launch, later calls share it.
+fn map_zip(path: &Path) -> Option<ZipBytes> {
+    if let Ok(mapped) = MAPPED_ZIPS.lock() {
+        if let Some((_, bytes)) = mapped.iter().find(|(p, _)| p == path) {
+            return bytes.clone();
+        }
+    }
+
+    let file = File::open(path).ok()?;
+    let bytes = match read_zip_bytes(&file) {
+        Ok(bytes) => Some(bytes),
+        Err(e) => {
+            report_unreadable(path, &e);
+            None
+        }
+    };
+    if let Ok(mut mapped) = MAPPED_ZIPS.lock() {
+        mapped.push((path.to_path_buf(), bytes.clone()));
+    }
+    bytes
+}
+
+fn read_zip_bytes(file: &File) -> Result<ZipBytes, String> {
+    let len = file.metadata().map_err(|e| e.to_string())?.len();
+    if usize::try_from(len).is_err() {
+        return Err(format!("It is {} bytes, more than this launcher can address", len));
+    }
+
+    // Safety: the app's files are not expected to change while the launcher reads them
+    let bytes = match unsafe { Mmap::map(file) } {
+        Ok(map) => ZipBytes::Mapped(Arc::new(map)),
+        Err(_) => {
+            let mut bytes: Vec<u8> = vec![];
+            let mut file = file;
+            file.read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+            ZipBytes::Read(Arc::new(bytes))
+        }
+    };
+
+    // Checked first, as the zip crate's errors do not say what is wrong
+    find_central_directory(bytes.as_ref())?;
+    Ok(bytes)
+}
+
+/// Read an entry of a zip archive nested in the one at the path, see [`find_nested_entry`].
+pub fn read_nested_zip_entry(path: &Path, nested: &str, name: &str) -> Option<Vec<u8>> {
+    let bytes = map_zip(path)?;
+    match find_nested_entry(bytes.as_ref(), nested, name) {
+        Ok(entry) => entry,
+        Err(e) => {
+            report_unreadable(path, &e);
+            None
+        }
+    }
+}
+
+/// Tell the user an archive exists but cannot be read,
+/// rather than its manifest or classes seeming to be missing.
+fn report_unreadable(path: &Path, e: &str) {
+    info!("discovery", "Failed to open {:?}: {}", path, e);
+    message(&text_with("archive.unreadable", &[&path.display().to_string(), e]));
+}
+
+/// Find the named entry of a zip archive through its central directory and read it,
+/// `Ok(None)` if there is no such entry.
+pub fn find_entry(bytes: &[u8], name: &str) -> Result<Option<Vec<u8>>, String> {
+    let entry = match locate_entry(bytes, name)? {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+    match entry.method {
+        // Stored
+        0 => Ok(Some(entry.data.to_vec())),
+        // Deflated
+        8 => {
+            let mut out: Vec<u8> = Vec::with_capacity(entry.size.min(u32::MAX as u64) as usize);
+            DeflateDecoder::new(entry.data).take(entry.size).read_to_end(&mut out)
+                .map_err(|e| format!("Failed to decompress {}: {}", name, e))?;
+            Ok(Some(out))
+        }
+        method => Err(format!("{} uses the unsupported compression method {}", name, method)),
+    }
+}
+
+/// Read an entry of a zip archive nested in this one, such as a jar in `BOOT-INF/lib/` of a
+/// Spring Boot jar. Nested archives are usually stored uncompressed, so they are read in place.
+pub fn find_nested_entry(bytes: &[u8], nested: &str, name: &str) -> Result<Option<Vec<u8>>, String> {
+    let entry = match locate_entry(bytes, nested)? {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+    if entry.method == 0 {
+        return find_entry(entry.data, name).map_err(|e| format!("{}: {}", nested, e));
+    }
+    match find_entry(bytes, nested)? {
+        Some(nested_bytes) => find_entry(&nested_bytes, name).map_err(|e| format!("{}: {}", nested, e)),
+        None => Ok(None),
+    }
+}
+
+/// The still compressed data of an entry of a zip archive.
+struct LocatedEntry<'a> {
+    data: &'a [u8],
+    method: u16,
+    /// The uncompressed size
+    size: u64,
+}
+
+fn locate_entry<'a>(bytes: &'a [u8], name: &str) -> Result<Option<LocatedEntry<'a>>, String> {
+    let directory = find_central_directory(bytes)?;
+    // Offsets are from the start of the archive, which data may come before
+    let start = directory.end - directory.size - directory.offset;
+    let corrupt = || format!("The central directory entry of {} is corrupt", name);
+
+    let mut pos = (start + directory.offset) as usize;
+    let end = pos + directory.size as usize;
+    for _ in 0..directory.entries {
+        if pos + CENTRAL_HEADER_LEN > end || le_u32(bytes, pos) != Some(CENTRAL_HEADER_SIGNATURE) {
+            return Err("The central directory is corrupt".to_string());
+        }
+        let name_len = le_u16(bytes, pos + 28).unwrap_or(0) as usize;
+        let extra_len = le_u16(bytes, pos + 30).unwrap_or(0) as usize;
+        let comment_len = le_u16(bytes, pos + 32).unwrap_or(0) as usize;
+        let header = pos;
+        pos += CENTRAL_HEADER_LEN + name_len + extra_len + comment_len;
+
+        let entry_name = bytes.get(header + CENTRAL_HEADER_LEN..header + CENTRAL_HEADER_LEN + name_len);
+        if entry_name != Some(name.as_bytes()) {
+            continue;
+        }
+
+        let flags = le_u16(bytes, header + 8).unwrap_or(0);
+        if flags & 1 != 0 {
+            return Err(format!("{} is encrypted", name));
+        }
+        let method = le_u16(bytes, header + 10).unwrap_or(0);
+        let mut compressed = le_u32(bytes, header + 20).unwrap_or(0) as u64;
+        let mut size = le_u32(bytes, header + 24).unwrap_or(0) as u64;
+        let mut local = le_u32(bytes, header + 42).unwrap_or(0) as u64;
+
+        // Values too large for the header are in the Zip64 extra field, in this order
+        let extra_start = header + CENTRAL_HEADER_LEN + name_len;
+        let extra = bytes.get(extra_start..extra_start + extra_len).ok_or_else(corrupt)?;
+        let mut field = 0;
+        while field + 4 <= extra.len() {
+            let id = le_u16(extra, field).unwrap_or(0);
+            let len = le_u16(extra, field + 2).unwrap_or(0) as usize;
+            if id == ZIP64_EXTRA_FIELD {
+                let mut value = field + 4;
+                for v in [&mut size, &mut compressed, &mut local] {
+                    if *v == u32::MAX as u64 {
+                        *v = le_u64(extra, value).ok_or_else(corrupt)?;
+                        value += 8;
+                    }
+                }
+            }
+            field += 4 + len;
+        }
+
+        let local = usize::try_from(start + local).map_err(|_| corrupt())?;
+        if le_u32(bytes, local) != Some(LOCAL_HEADER_SIGNATURE) {
+            return Err(format!("The local header of {} is corrupt", name));
+        }
+        let data = local + LOCAL_HEADER_LEN
+            + le_u16(bytes, local + 26).unwrap_or(0) as usize
+            + le_u16(bytes, local + 28).unwrap_or(0) as usize;
+        let data = usize::try_from(compressed).ok()
+            .and_then(|compressed| bytes.get(data..data.checked_add(compressed)?))
+            .ok_or_else(|| format!("The data of {} lies outside the file", name))?;
+
+        return Ok(Some(LocatedEntry { data, method, size }));
+    }
+
+    Ok(None)
+}
+
+/// Find the central directory of a zip archive from its end records, including the Zip64 ones
+/// of archives with more than 65535 entries or over 4 GB.
+pub fn find_central_directory(bytes: &[u8]) -> Result<CentralDirectory, String> {
+    // The end record is last, but may be followed by a comment of up to 65535 bytes
+    let last = bytes.len().checked_sub(EOCD_LEN).ok_or("It is too short to be a zip archive")?;
+    let first = last.saturating_sub(u16::MAX as usize);
+    let eocd = (first..=last).rev()
+        .find(|&pos| le_u32(bytes, pos) == Some(EOCD_SIGNATURE)
+            && pos + EOCD_LEN + le_u16(bytes, pos + 20).unwrap_or(0) as usize <= bytes.len())
+        .ok_or("It has no end of central directory record, it is not a zip archive or was cut short")?;
+
+    let disk = le_u16(bytes, eocd + 4).unwrap_or(0);
+    let entries = le_u16(bytes, eocd + 10).unwrap_or(0);
+    let size = le_u32(bytes, eocd + 12).unwrap_or(0);
+    let offset = le_u32(bytes, eocd + 16).unwrap_or(0);
+
+    let needs_zip64 = disk == u16::MAX || entries == u16::MAX || size == u32::MAX || offset == u32::MAX;
+    let locator = eocd.checked_sub(ZIP64_LOCATOR_LEN)
+        .filter(|&pos| le_u32(bytes, pos) == Some(ZIP64_LOCATOR_SIGNATURE));
+
+    let directory = match locator {
+        Some(locator) => {
+            if le_u32(bytes, locator + 16) > Some(1) {
+                return Err("It is split over multiple files, which is not supported".to_string());
+            }
+            let zip64_eocd = le_u64(bytes, locator + 8)
+                .and_then(|pos| usize::try_from(pos).ok())
+                .filter(|&pos| pos.checked_add(ZIP64_EOCD_LEN).map_or(false, |end| end <= locator))
+                .filter(|&pos| le_u32(bytes, pos) == Some(ZIP64_EOCD_SIGNATURE))
+                .ok_or("Its Zip64 end of central directory record is missing or corrupt")?;
+            if le_u32(bytes, zip64_eocd + 16) != Some(0) {
+                return Err("It is split over multiple files, which is not supported".to_string());
+            }
+            CentralDirectory {
+                entries: le_u64(bytes, zip64_eocd + 32).unwrap_or(0),
+                size: le_u64(bytes, zip64_eocd + 40).unwrap_or(0),
+                offset: le_u64(bytes, zip64_eocd + 48).unwrap_or(0),
+                zip64: true,
+                end: zip64_eocd as u64,
+            }
+        }
+        None if needs_zip64 => {
+            return Err("It needs Zip64 records, which are missing or corrupt".to_string());
+        }
+        None => {
+            if disk != 0 {
+                return Err("It is split over multiple files, which is not supported".to_string());
+            }
+            CentralDirectory {
+                entries: entries as u64,
+                size: size as u64,
+                offset: offset as u64,
+                zip64: false,
+                end: eocd as u64,
+            }
+        }
+    };
+
+    // Data may come before the archive, as with self-extracting archives, but not after
+    if directory.offset.checked_add(directory.size).map_or(true, |end| end > directory.end) {
+        return Err(format!("Its central directory lies outside the file, at {} of {} bytes",
+                           directory.offset, bytes.len()));
+    }
+    // Each entry takes at least the fixed part of its header
+    if directory.entries.checked_mul(46).map_or(true, |min| min > directory.size) {
+        return Err(format!("It claims {} entries, more than its central directory holds",
+                           directory.entries));
+    }
+
+    Ok(directory)
+}
+
+fn le_u16(bytes: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(pos..pos.checked_add(2)?)?.try_into().ok()?))
+}
+
+fn le_u32(bytes: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(pos..pos.checked_add(4)?)?.try_into().ok()?))
+}
+
+fn le_u64(bytes: &[u8], pos: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(bytes.get(pos..pos.checked_add(8)?)?.try_into().ok()?))
+}
+
+/// Close the archives opened by [`open_zip`] and [`read_zip_entry`], so that they are not kept open while the app runs,
+/// which on Windows would stop it from replacing them, such as when updating.
+pub fn close_zips() {
+    if let Ok(mut opened) = OPENED_ZIPS.lock() {
+        opened.clear();
+    }
+    if let Ok(mut mapped) = MAPPED_ZIPS.lock() {
+        mapped.clear();
+    }
+}
+
+/// A module found on the module path.
+#[derive(Debug, Clone)]
+pub struct ModuleInfo {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// Find the modules of the module path entries, in the order Java would use them.
+/// An entry is a modular or automatic jar, an exploded module, or a directory of those.<br>
+/// Java only uses the first module of a name, later ones are reported and left out.
+pub fn find_modules(module_path: &[PathBuf]) -> Vec<ModuleInfo> {
+    let mut modules: Vec<ModuleInfo> = vec![];
+    for entry in module_path {
+        let candidates: Vec<PathBuf> = if entry.is_dir() && !entry.join(MODULE_INFO).is_file() {
+            let mut children: Vec<PathBuf> = entry.read_dir()
+                .map(|d| d.flatten().map(|e| e.path()).collect())
+                .unwrap_or_default();
+            children.sort();
+            children.into_iter()
+                .filter(|p| p.is_dir() || p.extension().map_or(false, |e| e == "jar"))
+                .collect()
+        } else {
+            vec![entry.clone()]
+        };
+
+        for path in candidates {
+            let name = match read_module_name(&path) {
+                Some(name) => name,
+                None => continue,
+            };
+            if let Some(existing) = modules.iter().find(|m| m.name == name) {
+                info!("discovery", "Module {} is in both {:?} and {:?}, only the first is used",
+                         name, existing.path, path);
+                continue;
+            }
+            modules.push(ModuleInfo { name, path });
+        }
+    }
+    modules
+}
+
+/// The name of the module in a jar or exploded module directory: from its `module-info.class`,
+/// else the `Automatic-Module-Name` of its manifest, else derived from the file name as Java
+/// does for automatic modules.
+pub fn read_module_name(path: &Path) -> Option<String> {
+    if path.is_dir() {
+        let mut bytes: Vec<u8> = vec![];
+        File::open(path.join(MODULE_INFO)).ok()?.read_to_end(&mut bytes).ok()?;
+        return parse_module_info_name(&bytes);
+    }
+
+    // Modular jars
+    if let Some(bytes) = read_zip_entry(path, MODULE_INFO) {
+        return parse_module_info_name(&bytes);
+    }
+
+    // Multi-release jars may only have a versioned descriptor
+    let manifest = read_jar_manifest(path);
+    let mut descriptors: Vec<String> = vec![];
+    if manifest.as_ref().map_or(false, |m| m.is_multi_release()) {
+        let jar = open_zip(path)?;
+        let mut versioned: Vec<(u16, String)> = jar.file_names()
+            .filter_map(|n| {
+                let release = n.strip_prefix("META-INF/versions/")?.strip_suffix(MODULE_INFO)?;
+                Some((release.strip_suffix('/')?.parse().ok()?, n.to_string()))
+            })
+            .collect();
+        versioned.sort();
+        descriptors.extend(versioned.into_iter().map(|(_, n)| n));
+    }
+    if let Some(descriptor) = descriptors.first() {
+        return parse_module_info_name(&read_zip_entry(path, descriptor)?);
+    }
+
+    // Automatic modules
+    if let Some(name) = manifest.as_ref().and_then(|m| m.get("Automatic-Module-Name")) {
+        return Some(name.trim().to_string());
+    }
+    automatic_module_name(path.file_stem()?.to_str()?)
+}
+
+/// Derive the name of an automatic module from a jar's file name, as `ModuleFinder` does:
+/// a version after a `-` followed by a digit is dropped, and non-alphanumerics become dots.
+fn automatic_module_name(file_stem: &str) -> Option<String> {
+    let bytes = file_stem.as_bytes();
+    let version_start = (0..bytes.len())
+        .find(|i| bytes[*i] == b'-' && bytes.get(i + 1).map_or(false, |b| b.is_ascii_digit()));
+    let base = &file_stem[..version_start.unwrap_or(file_stem.len())];
+
+    let mut name = String::new();
+    for c in base.chars() {
+        if c.is_alphanumeric() {
+            name.push(c);
+        } else if !name.is_empty() && !name.ends_with('.') {
+            name.push('.');
+        }
+    }
+    let name = name.trim_end_matches('.').to_string();
+    if name.is_empty() { None } else { Some(name) }
+}
+
+/// Read the module name from the `Module` attribute of a `module-info.class`.<br>
+/// See <https://docs.oracle.com/javase/specs/jvms/se17/html/jvms-4.html#jvms-4.7.25>
+fn parse_module_info_name(class: &[u8]) -> Option<String> {
+    let mut r = ClassReader { bytes: class, pos: 0 };
+    if r.u32()? != 0xCAFEBABE {
+        return None;
+    }
+    r.skip(4)?; // Minor and major version
+
+    // The constant pool, keeping the UTF-8 and module entries
+    let pool_count = r.u16()? as usize;
+    let mut utf8: Vec<Option<String>> = vec![None; pool_count];
+    let mut module_names: Vec<Option<u16>> = vec![None; pool_count];
+    let mut i = 1;
+    while i < pool_count {
+        match r.u8()? {
+            1 => {
+                let len = r.u16()? as usize;
+                // Modified UTF-8, which matches UTF-8 for the characters module names use
+                utf8[i] = Some(String::from_utf8_lossy(r.take(len)?).to_string());
+            }
+            19 => module_names[i] = Some(r.u16()?),
+            7 | 8 | 16 | 20 => r.skip(2)?,
+            15 => r.skip(3)?,
+            3 | 4 | 9 | 10 | 11 | 12 | 17 | 18 => r.skip(4)?,
+            // Long and double take two entries
+            5 | 6 => {
+                r.skip(8)?;
+                i += 1;
+            }
+            _ => return None,
+        }
+        i += 1;
+    }
+
+    // Access flags, this and super class
+    r.skip(6)?;
+    let interfaces = r.u16()? as usize;
+    r.skip(interfaces * 2)?;
+
+    // Fields and methods, which a module descriptor should not have
+    for _ in 0..2 {
+        let members = r.u16()?;
+        for _ in 0..members {
+            r.skip(6)?;
+            skip_attributes(&mut r)?;
+        }
+    }
+
+    let attributes = r.u16()?;
+    for _ in 0..attributes {
+        let name_index = r.u16()? as usize;
+        let len = r.u32()? as usize;
+        if utf8.get(name_index)?.as_deref() == Some("Module") {
+            let module_index = r.u16()? as usize;
+            let name_index = (*module_names.get(module_index)?)? as usize;
+            return utf8.get(name_index)?.clone();
+        }
+        r.skip(len)?;
+    }
+
+    None
+}
+
+fn skip_attributes(r: &mut ClassReader) -> Option<()> {
+    let attributes = r.u16()?;
+    for _ in 0..attributes {
+        r.skip(2)?;
+        let len = r.u32()? as usize;
+        r.skip(len)?;
+    }
+    Some(())
+}
+
+/// Reads the big-endian values of a class file.
+struct ClassReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ClassReader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let slice = self.bytes.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(slice)
+    }
+
+    fn skip(&mut self, len: usize) -> Option<()> {
+        self.take(len).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_be_bytes(self.take(2)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.take(4)?.try_into().ok()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use zip::{CompressionMethod, ZipWriter};
+    use zip::write::FileOptions;
+
+    use super::*;
+
+    /// A central directory of the given entries, only their fixed-size part.
+    fn central_directory(entries: usize) -> Vec<u8> {
+        vec![0; entries * 46]
+    }
+
+    fn eocd(entries: u16, size: u32, offset: u32, comment: &[u8]) -> Vec<u8> {
+        let mut bytes = EOCD_SIGNATURE.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&[0; 4]); // Disk numbers
+        bytes.extend_from_slice(&entries.to_le_bytes());
+        bytes.extend_from_slice(&entries.to_le_bytes());
+        bytes.extend_from_slice(&size.to_le_bytes());
+        bytes.extend_from_slice(&offset.to_le_bytes());
+        bytes.extend_from_slice(&(comment.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(comment);
+        bytes
+    }
+
+    /// The Zip64 end record and its locator, for a Zip64 end record at `pos`.
+    fn zip64_records(entries: u64, size: u64, offset: u64, pos: u64) -> Vec<u8> {
+        let mut bytes = ZIP64_EOCD_SIGNATURE.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&(ZIP64_EOCD_LEN as u64 - 12).to_le_bytes());
+        bytes.extend_from_slice(&[45, 0, 45, 0]); // Versions
+        bytes.extend_from_slice(&[0; 8]); // Disk numbers
+        bytes.extend_from_slice(&entries.to_le_bytes());
+        bytes.extend_from_slice(&entries.to_le_bytes());
+        bytes.extend_from_slice(&size.to_le_bytes());
+        bytes.extend_from_slice(&offset.to_le_bytes());
+
+        bytes.extend_from_slice(&ZIP64_LOCATOR_SIGNATURE.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&pos.to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn finds_central_directory() {
+        let mut bytes = central_directory(3);
+        bytes.extend(eocd(3, 3 * 46, 0, b""));
+        let directory = find_central_directory(&bytes).unwrap();
+        assert_eq!(directory.entries, 3);
+        assert_eq!(directory.size, 3 * 46);
+        assert!(!directory.zip64);
+    }
+
+    #[test]
+    fn finds_central_directory_before_comment() {
+        let mut bytes = central_directory(1);
+        // A comment that looks like the start of an end record
+        bytes.extend(eocd(1, 46, 0, &EOCD_SIGNATURE.to_le_bytes()));
+        assert_eq!(find_central_directory(&bytes).unwrap().entries, 1);
+    }
+
+    #[test]
+    fn allows_data_before_archive() {
+        let mut bytes = b"#!/bin/sh\n".to_vec();
+        bytes.extend(central_directory(1));
+        bytes.extend(eocd(1, 46, 0, b""));
+        assert!(find_central_directory(&bytes).is_ok());
+    }
+
+    #[test]
+    fn finds_zip64_central_directory() {
+        // More entries than the end record can count
+        let entries = 70_000;
+        let mut bytes = central_directory(entries);
+        let pos = bytes.len() as u64;
+        bytes.extend(zip64_records(entries as u64, pos, 0, pos));
+        bytes.extend(eocd(u16::MAX, u32::MAX, u32::MAX, b""));
+
+        let directory = find_central_directory(&bytes).unwrap();
+        assert_eq!(directory.entries, entries as u64);
+        assert_eq!(directory.size, pos);
+        assert!(directory.zip64);
+    }
+
+    #[test]
+    fn rejects_missing_zip64_records() {
+        let mut bytes = central_directory(1);
+        bytes.extend(eocd(u16::MAX, u32::MAX, u32::MAX, b""));
+        let e = find_central_directory(&bytes).unwrap_err();
+        assert!(e.contains("Zip64"), "{}", e);
+    }
+
+    #[test]
+    fn rejects_corrupt_zip64_records() {
+        let mut bytes = central_directory(1);
+        // Points past the locator
+        bytes.extend(zip64_records(1, 46, 0, 1_000_000));
+        bytes.extend(eocd(u16::MAX, u32::MAX, u32::MAX, b""));
+        let e = find_central_directory(&bytes).unwrap_err();
+        assert!(e.contains("Zip64"), "{}", e);
+    }
+
+    #[test]
+    fn rejects_central_directory_outside_file() {
+        let mut bytes = central_directory(1);
+        // As when a file over 4 GB was cut short
+        bytes.extend(eocd(1, 46, u32::MAX - 46, b""));
+        let e = find_central_directory(&bytes).unwrap_err();
+        assert!(e.contains("outside"), "{}", e);
+    }
+
+    #[test]
+    fn rejects_too_many_entries() {
+        let mut bytes = central_directory(1);
+        bytes.extend(eocd(2, 46, 0, b""));
+        assert!(find_central_directory(&bytes).is_err());
+    }
+
+    #[test]
+    fn rejects_split_archives() {
+        let mut bytes = central_directory(1);
+        let mut end = eocd(1, 46, 0, b"");
+        end[4] = 1;
+        bytes.extend(end);
+        let e = find_central_directory(&bytes).unwrap_err();
+        assert!(e.contains("split"), "{}", e);
+    }
+
+    #[test]
+    fn rejects_truncated_archives() {
+        let mut bytes = central_directory(1);
+        bytes.extend(eocd(1, 46, 0, b""));
+        bytes.truncate(bytes.len() - 4);
+        assert!(find_central_directory(&bytes).is_err());
+        assert!(find_central_directory(b"PK").is_err());
+        assert!(find_central_directory(b"").is_err());
+    }
+
+    fn write_zip(files: &[(&str, &[u8], CompressionMethod)]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(vec![]));
+        for (name, content, method) in files {
+            writer.start_file(*name, FileOptions::default().compression_method(*method)).unwrap();
+            writer.write_all(content).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn finds_entries() {
+        let bytes = write_zip(&[
+            ("META-INF/MANIFEST.MF", b"Manifest-Version: 1.0\r\n", CompressionMethod::Deflated),
+            ("a/Main.class", &[0xCA, 0xFE, 0xBA, 0xBE], CompressionMethod::Stored),
+        ]);
+        assert_eq!(find_entry(&bytes, "META-INF/MANIFEST.MF").unwrap().unwrap(),
+                   b"Manifest-Version: 1.0\r\n");
+        assert_eq!(find_entry(&bytes, "a/Main.class").unwrap().unwrap(), [0xCA, 0xFE, 0xBA, 0xBE]);
+        assert_eq!(find_entry(&bytes, "a/Other.class").unwrap(), None);
+        assert_eq!(find_entry(&bytes, "a/").unwrap(), None);
+    }
+
+    #[test]
+    fn finds_entries_after_data_before_archive() {
+        let mut bytes = b"#!/bin/sh\n".to_vec();
+        bytes.extend(write_zip(&[("a.txt", b"a", CompressionMethod::Deflated)]));
+        assert_eq!(find_entry(&bytes, "a.txt").unwrap().unwrap(), b"a");
+    }
+
+    #[test]
+    fn reports_corrupt_entries() {
+        let mut bytes = write_zip(&[("a.txt", b"a", CompressionMethod::Stored)]);
+        // The local header
+        bytes[0] = 0;
+        assert!(find_entry(&bytes, "a.txt").is_err());
+    }
+
+    #[test]
+    fn reads_archive_with_many_entries() {
+        let entries = u16::MAX as usize + 10;
+        let mut writer = ZipWriter::new(Cursor::new(vec![]));
+        for i in 0..entries {
+            writer.start_file(format!("{}.txt", i), FileOptions::default()).unwrap();
+        }
+        writer.start_file("last.txt", FileOptions::default()).unwrap();
+        writer.write_all(b"found").unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let directory = find_central_directory(&bytes).unwrap();
+        assert_eq!(directory.entries, entries as u64 + 1);
+        assert!(directory.zip64);
+
+        assert_eq!(find_entry(&bytes, "last.txt").unwrap().unwrap(), b"found");
+
+        let mut zip = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let mut content = String::new();
+        zip.by_name("last.txt").unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, "found");
+    }
+}
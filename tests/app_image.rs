@@ -0,0 +1,135 @@
+//! The launcher's config, manifest and Java discovery working together on app images of each layout.
+
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use why::{get_jvm_candidates, get_jvm_paths, LaunchOpts, LaunchOptsBuilder, LauncherConfig};
+
+mod support;
+
+use support::{AppImage, LAYOUTS};
+
+fn read_config(path: &Path) -> LauncherConfig {
+    LauncherConfig::parse_config_from(BufReader::new(File::open(path).unwrap())).unwrap()
+}
+
+/// The Java the launcher would start the app with from the configured runtime.
+fn found_jvm(opts: &LaunchOpts) -> Option<PathBuf> {
+    let first = get_jvm_paths(opts).into_iter().next()?;
+    first(opts)
+}
+
+#[test]
+fn app_is_launched_from_bundled_runtime() {
+    for layout in LAYOUTS {
+        let image = AppImage::new("bundled", layout);
+        let jar = image.add_jar("app.jar", "Manifest-Version: 1.0\nMain-Class: a.Main\n", &[("a.Main", 61)]);
+        let library = image.add_runtime("17.0.2");
+        let ini = image.write_config("main_jar=$APP$/app.jar\njvm_install=$RUNTIME$\n");
+
+        let opts = LaunchOptsBuilder::from_config(read_config(&ini)).build().unwrap();
+        assert_eq!(opts.config.main_class.as_deref(), Some("a.Main"), "{:?}", layout);
+        // Raised for the main class
+        assert_eq!(opts.config.min_java, Some(17), "{:?}", layout);
+        let classpath = opts.jvm_opts.iter().find_map(|o| o.strip_prefix("-Djava.class.path="));
+        assert_eq!(classpath.map(Path::new), Some(jar.as_path()), "{:?}", layout);
+
+        assert_eq!(found_jvm(&opts), Some(dunce::canonicalize(&library).unwrap()), "{:?}", layout);
+        let candidate = &get_jvm_candidates(&opts.config)[0];
+        assert_eq!(candidate.source, "jvm_install");
+        assert_eq!(candidate.version.as_deref(), Some("17.0.2"));
+        assert_eq!(candidate.rejection, None, "{:?}", layout);
+    }
+}
+
+#[test]
+fn runtime_older_than_main_class_is_refused() {
+    for layout in LAYOUTS {
+        let image = AppImage::new("older", layout);
+        image.add_jar("app.jar", "Manifest-Version: 1.0\nMain-Class: a.Main\n", &[("a.Main", 61)]);
+        image.add_runtime("11.0.20");
+        let ini = image.write_config("main_jar=$APP$/app.jar\njvm_install=$RUNTIME$\n");
+
+        let opts = LaunchOptsBuilder::from_config(read_config(&ini)).build().unwrap();
+        assert_eq!(found_jvm(&opts), None, "{:?}", layout);
+        let candidate = &get_jvm_candidates(&opts.config)[0];
+        assert_eq!(candidate.rejection.as_deref(), Some("Java 11 is older than min_java 17"), "{:?}", layout);
+    }
+}
+
+#[test]
+fn class_path_of_manifest_is_resolved_in_app_folder() {
+    for layout in LAYOUTS {
+        let image = AppImage::new("class-path", layout);
+        image.add_jar("app.jar", "Manifest-Version: 1.0\nMain-Class: a.Main\nClass-Path: lib/dep.jar\n",
+                      &[("a.Main", 55)]);
+        let dep = image.add_jar("lib/dep.jar", "Manifest-Version: 1.0\n", &[("b.Dep", 55)]);
+        image.add_runtime("11.0.20");
+        let ini = image.write_config("main_jar=$APP$/app.jar\njvm_install=$RUNTIME$\nmin_java=8\n");
+
+        let opts = LaunchOptsBuilder::from_config(read_config(&ini)).build().unwrap();
+        let classpath = opts.config.classpath.clone().unwrap();
+        assert_eq!(classpath.split(';').map(Path::new).last(), Some(dep.as_path()), "{:?}", layout);
+        assert_eq!(opts.config.min_java, Some(11), "{:?}", layout);
+        assert!(found_jvm(&opts).is_some(), "{:?}", layout);
+    }
+}
+
+#[test]
+fn missing_runtime_is_reported() {
+    for layout in LAYOUTS {
+        let image = AppImage::new("missing", layout);
+        image.add_jar("app.jar", "Manifest-Version: 1.0\nMain-Class: a.Main\n", &[("a.Main", 52)]);
+        let ini = image.write_config("main_jar=$APP$/app.jar\njvm_install=$RUNTIME$\n");
+
+        let opts = LaunchOptsBuilder::from_config(read_config(&ini)).build().unwrap();
+        assert_eq!(found_jvm(&opts), None, "{:?}", layout);
+        let rejection = get_jvm_candidates(&opts.config)[0].rejection.clone().unwrap();
+        assert!(rejection.starts_with("no ") && rejection.ends_with(" found"), "{:?}: {}", layout, rejection);
+    }
+}
+
+#[test]
+fn exploded_main_jar_is_launched() {
+    for layout in LAYOUTS {
+        let image = AppImage::new("exploded", layout);
+        let classes = image.add_exploded_jar("classes", "Manifest-Version: 1.0\nMain-Class: a.Main\nClass-Path: lib/dep.jar\n",
+                                             &[("a.Main", 61)]);
+        let dep = image.add_jar("classes/lib/dep.jar", "Manifest-Version: 1.0\n", &[("b.Dep", 61)]);
+        image.add_runtime("17.0.2");
+        let ini = image.write_config("main_jar=$APP$/classes\njvm_install=$RUNTIME$\n");
+
+        let opts = LaunchOptsBuilder::from_config(read_config(&ini)).build().unwrap();
+        assert_eq!(opts.config.main_class.as_deref(), Some("a.Main"), "{:?}", layout);
+        // Raised for the main class on disk
+        assert_eq!(opts.config.min_java, Some(17), "{:?}", layout);
+        let classpath = opts.config.classpath.clone().unwrap();
+        let entries: Vec<&Path> = classpath.split(';').map(Path::new).collect();
+        assert_eq!(entries, vec![classes.as_path(), dep.as_path()], "{:?}", layout);
+        assert!(found_jvm(&opts).is_some(), "{:?}", layout);
+    }
+}
+
+#[test]
+fn launcher_diagnoses_app_image() {
+    for layout in LAYOUTS {
+        let image = AppImage::new("launcher", layout);
+        image.add_jar("app.jar", "Manifest-Version: 1.0\nMain-Class: a.Main\n", &[("a.Main", 61)]);
+        image.add_runtime("17.0.2");
+        image.write_config("main_jar=$APP$/app.jar\njvm_install=$RUNTIME$\n");
+        let launcher = image.add_launcher();
+
+        let out = Command::new(&launcher).args(["--why-headless", "--why-diagnose"]).output().unwrap();
+        let summary = String::from_utf8_lossy(&out.stdout);
+        assert!(out.status.success(), "{:?}: {}{}", layout, summary, String::from_utf8_lossy(&out.stderr));
+        let runtime = summary.lines().find(|l| l.contains("(jvm_install)"));
+        assert!(runtime.map_or(false, |r| r.contains("Java 17.0.2") && r.ends_with("usable")),
+                "{:?}: {}", layout, summary);
+        // Kept in the portable app image
+        let written = fs::read_dir(image.launcher_dir().join("data")).unwrap().flatten()
+            .any(|f| f.file_name().to_string_lossy().starts_with("diagnosis-"));
+        assert!(written, "{:?}", layout);
+    }
+}
@@ -0,0 +1,190 @@
+//! App images for the integration tests, laid out as jpackage lays them out on each platform, with the
+//! launcher's `launcher.ini`, jars with manifests and a Java runtime of which only the dynamic library and
+//! the `release` file are there. Each layout can be built on any platform, using the name this platform
+//! gives the Java dynamic library, so that all of them are tested everywhere. The launcher keeps its files
+//! in a data dir of the tests' own meanwhile, rather than in the user's profile.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::env;
+
+use tempfile::TempDir;
+use why::set_user_data_dir;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+/// Where jpackage puts the launcher, the app's jars and the runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// `App.exe`, `app/` and `runtime/` in the app image.
+    Windows,
+    /// `bin/App`, `lib/app/` and `lib/runtime/` in the app image.
+    Linux,
+    /// `Contents/MacOS/App`, `Contents/app/` and `Contents/runtime/Contents/Home/` in `App.app`.
+    MacOs,
+}
+
+pub const LAYOUTS: [Layout; 3] = [Layout::Windows, Layout::Linux, Layout::MacOs];
+
+#[cfg(windows)]
+const DYN_JAVA_LIB: &str = "jvm.dll";
+#[cfg(target_os = "macos")]
+const DYN_JAVA_LIB: &str = "libjvm.dylib";
+#[cfg(all(unix, not(target_os = "macos")))]
+const DYN_JAVA_LIB: &str = "libjvm.so";
+
+/// Where the launcher keeps its files, such as its cache, while there are app images, and how many there are.
+/// It is removed with the last of them.
+static DATA_DIR: Mutex<Option<(TempDir, usize)>> = Mutex::new(None);
+
+/// An app image in a temporary folder of its own, removed again when dropped.
+pub struct AppImage {
+    pub root: PathBuf,
+    pub layout: Layout,
+    _dir: TempDir,
+}
+
+impl AppImage {
+    /// An empty app image, named after the test so that tests running at the same time do not share one.
+    pub fn new(test: &str, layout: Layout) -> Self {
+        let dir = tempfile::Builder::new().prefix(&format!("why-app-image-{}-{:?}-", test, layout)).tempdir().unwrap();
+        if let Ok(mut data_dir) = DATA_DIR.lock() {
+            match data_dir.as_mut() {
+                Some((_, images)) => *images += 1,
+                None => {
+                    let data = tempfile::Builder::new().prefix("why-app-image-data-").tempdir().unwrap();
+                    set_user_data_dir(Some(data.path().to_path_buf()));
+                    *data_dir = Some((data, 1));
+                }
+            }
+        }
+        let image = AppImage { root: dir.path().to_path_buf(), layout, _dir: dir };
+        for dir in [image.launcher_dir(), image.app_dir(), image.runtime_dir()] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        image
+    }
+
+    fn contents(&self) -> PathBuf {
+        self.root.join("App.app").join("Contents")
+    }
+
+    /// The folder of the launcher executable, where `launcher.ini` is.
+    pub fn launcher_dir(&self) -> PathBuf {
+        match self.layout {
+            Layout::Windows => self.root.clone(),
+            Layout::Linux => self.root.join("bin"),
+            Layout::MacOs => self.contents().join("MacOS"),
+        }
+    }
+
+    /// The folder of the app's jars.
+    pub fn app_dir(&self) -> PathBuf {
+        match self.layout {
+            Layout::Windows => self.root.join("app"),
+            Layout::Linux => self.root.join("lib").join("app"),
+            Layout::MacOs => self.contents().join("app"),
+        }
+    }
+
+    /// The home of the bundled Java runtime, with its `release` file.
+    pub fn runtime_dir(&self) -> PathBuf {
+        match self.layout {
+            Layout::Windows => self.root.join("runtime"),
+            Layout::Linux => self.root.join("lib").join("runtime"),
+            Layout::MacOs => self.contents().join("runtime").join("Contents").join("Home"),
+        }
+    }
+
+    /// Write `launcher.ini`, in which `$APP$` and `$RUNTIME$` are replaced by the app and runtime folders.
+    pub fn write_config(&self, ini: &str) -> PathBuf {
+        let ini = ini.replace("$APP$", &config_path(&self.app_dir()))
+            .replace("$RUNTIME$", &config_path(&self.runtime_dir()));
+        let path = self.launcher_dir().join("launcher.ini");
+        fs::write(&path, ini).unwrap();
+        path
+    }
+
+    /// Add a jar to the app folder with the manifest and the classes, each given by its name and
+    /// major class file version, such as 61 for Java 17.
+    pub fn add_jar(&self, name: &str, manifest: &str, classes: &[(&str, u16)]) -> PathBuf {
+        let path = self.app_dir().join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let mut writer = ZipWriter::new(File::create(&path).unwrap());
+        writer.start_file("META-INF/MANIFEST.MF", FileOptions::default()).unwrap();
+        writer.write_all(manifest.replace('\n', "\r\n").as_bytes()).unwrap();
+        for (class, major) in classes {
+            writer.start_file(class.replace('.', "/") + ".class", FileOptions::default()).unwrap();
+            writer.write_all(&class_file(*major)).unwrap();
+        }
+        writer.finish().unwrap();
+        path
+    }
+
+    /// Add the directory of an exploded jar to the app folder, with its manifest in `META-INF`,
+    /// as [`AppImage::add_jar`] adds a jar.
+    pub fn add_exploded_jar(&self, name: &str, manifest: &str, classes: &[(&str, u16)]) -> PathBuf {
+        let dir = self.app_dir().join(name);
+        fs::create_dir_all(dir.join("META-INF")).unwrap();
+        fs::write(dir.join("META-INF").join("MANIFEST.MF"), manifest.replace('\n', "\r\n")).unwrap();
+        for (class, major) in classes {
+            let path = dir.join(class.replace('.', "/") + ".class");
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, class_file(*major)).unwrap();
+        }
+        dir
+    }
+
+    /// Copy the launcher executable into the app image as `App`, beside a `portable` file so that it keeps
+    /// its files in the app image. Returns the path of the copy.
+    pub fn add_launcher(&self) -> PathBuf {
+        let launcher = self.launcher_dir().join(format!("App{}", env::consts::EXE_SUFFIX));
+        fs::copy(env!("CARGO_BIN_EXE_JavaLauncher"), &launcher).unwrap();
+        fs::write(self.launcher_dir().join("portable"), "").unwrap();
+        launcher
+    }
+
+    /// Add the runtime's dynamic library where jpackage puts it, and its `release` file with the
+    /// Java version and this platform's architecture. Returns the path of the library.
+    pub fn add_runtime(&self, java_version: &str) -> PathBuf {
+        let runtime = self.runtime_dir();
+        let lib_dir = match self.layout {
+            Layout::Windows => runtime.join("bin").join("server"),
+            Layout::Linux | Layout::MacOs => runtime.join("lib").join("server"),
+        };
+        fs::create_dir_all(&lib_dir).unwrap();
+        let library = lib_dir.join(DYN_JAVA_LIB);
+        fs::write(&library, b"").unwrap();
+        fs::write(runtime.join("release"), format!("JAVA_VERSION=\"{}\"\nOS_ARCH=\"{}\"\nMODULES=\"java.base\"\n",
+                                                   java_version, env::consts::ARCH)).unwrap();
+        library
+    }
+}
+
+impl Drop for AppImage {
+    fn drop(&mut self) {
+        if let Ok(mut data_dir) = DATA_DIR.lock() {
+            if let Some((_, images)) = data_dir.as_mut() {
+                *images -= 1;
+                if *images == 0 {
+                    *data_dir = None;
+                }
+            }
+        }
+    }
+}
+
+/// The start of a class file, as far as the launcher reads it, for the major class file version.
+fn class_file(major: u16) -> Vec<u8> {
+    let mut header = vec![0xCA, 0xFE, 0xBA, 0xBE, 0, 0];
+    header.extend(major.to_be_bytes());
+    header
+}
+
+/// The path as written in `launcher.ini`, with forward slashes, which Windows takes too, as backslashes
+/// would be read as escapes.
+fn config_path(path: &Path) -> String {
+    path.display().to_string().replace('\\', "/")
+}